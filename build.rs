@@ -8,8 +8,8 @@ fn main() {
     let out_dir = env::var("OUT_DIR").unwrap();
     let out_dir = Path::new(&out_dir);
     println!("Writing tests to: {:?}", out_dir);
-    write_tag_tests(&out_dir);
-    write_database_tests(&out_dir);
+    write_tag_tests(out_dir);
+    write_database_tests(out_dir);
 }
 
 fn write_tag_tests(path: &Path) {
@@ -66,6 +66,7 @@ fn write_tag_test(test_file: &mut File, filepath: &Path, tag_name: &str) {
         "beatgrid" => "Beatgrid",
         "markers" => "Markers",
         "markers2" => "Markers2",
+        "offsets" => "Offsets",
         "overview" => "Overview",
         "vidassoc" => "VidAssoc",
         "relvolad" => "RelVolAd",
@@ -83,6 +84,46 @@ fn write_tag_test(test_file: &mut File, filepath: &Path, tag_name: &str) {
         tag_type = tag_type,
     )
     .unwrap();
+
+    // Check that the `TagContainer` roundtrip is byte-exact.
+    let tag_format = match tag_type {
+        "id3" => "ID3",
+        "flac" => "FLAC",
+        "mp4" => "MP4",
+        "ogg" => "Ogg",
+        _ => {
+            panic!("Unknown tag type!")
+        }
+    };
+
+    write!(
+        test_file,
+        include_str!("./tests/test_container_roundtrip.rs.in"),
+        name = test_name,
+        filepath = filepath.to_str().unwrap(),
+        tag_name = tag_name,
+        tag_format = tag_format,
+    )
+    .unwrap();
+
+    // Check that the tag format is detected from the data alone. FLAC and MP4 tags use the same
+    // envelope, so only the `Serato Markers_` tag (which doesn't exist in FLAC files) is detected
+    // as MP4.
+    let detected_format = match (tag_format, tag_name) {
+        ("MP4", "markers") => "MP4",
+        ("MP4", _) => "FLAC",
+        (tag_format, _) => tag_format,
+    };
+
+    write!(
+        test_file,
+        include_str!("./tests/test_detect.rs.in"),
+        name = test_name,
+        filepath = filepath.to_str().unwrap(),
+        parser = parser,
+        detected_format = detected_format,
+    )
+    .unwrap();
 }
 
 fn write_database_tests(path: &Path) {
@@ -0,0 +1,64 @@
+//! Compares owned and borrowed parsing of a large synthetic `database V2` file.
+//!
+//! Run with `cargo run --release --example databasebench [TRACK_COUNT]`.
+
+use std::env;
+use std::path::PathBuf;
+use std::string::String;
+use std::time::Instant;
+use triseratops::library::database::{self, Database, Field};
+
+fn synthetic_database(track_count: usize) -> Result<Vec<u8>, triseratops::error::Error> {
+    let mut fields = vec![Field::Version(
+        "2.0/Serato Scratch LIVE Database".to_string(),
+    )];
+    for i in 0..track_count {
+        fields.push(Field::Track(vec![
+            Field::FileType("mp3".to_string()),
+            Field::FilePath(PathBuf::from(format!(
+                "Music/Artist {}/Track {}.mp3",
+                i % 100,
+                i
+            ))),
+            Field::SongTitle(format!("Track {}", i)),
+            Field::Artist(format!("Artist {}", i % 100)),
+            Field::Album(format!("Album {}", i % 1000)),
+            Field::Genre("House".to_string()),
+            Field::BPM("126.00".to_string()),
+            Field::Key("Am".to_string()),
+            Field::Length("06:12.34".to_string()),
+            Field::Missing(i % 10 == 0),
+        ]));
+    }
+    let mut data = vec![];
+    database::write(&mut data, &fields)?;
+    Ok(data)
+}
+
+fn main() -> Result<(), triseratops::error::Error> {
+    let mut args: Vec<String> = env::args().collect();
+    let _prog = args.remove(0);
+    let track_count = match args.first() {
+        Some(arg) => arg.parse().expect("Expected a track count!"),
+        None => 50_000,
+    };
+
+    let data = synthetic_database(track_count)?;
+    println!("Database: {} tracks, {} bytes", track_count, data.len());
+
+    let start = Instant::now();
+    let database = Database::parse(&data)?;
+    let missing = database.tracks().filter(|track| track.missing()).count();
+    println!("Owned:    {:?} ({} missing)", start.elapsed(), missing);
+
+    let start = Instant::now();
+    let mut missing = 0;
+    for track in database::track_record_refs(&data) {
+        if track?.missing() {
+            missing += 1;
+        }
+    }
+    println!("Borrowed: {:?} ({} missing)", start.elapsed(), missing);
+
+    Ok(())
+}
@@ -5,136 +5,114 @@ use std::string::String;
 use triseratops::tag::format::{flac::FLACTag, id3::ID3Tag, mp4::MP4Tag, ogg::OggTag};
 
 fn parse_file(data: &[u8]) -> bool {
-    let res = triseratops::tag::Analysis::parse_id3(&data);
-    if res.is_ok() {
+    if let Ok(tag) = triseratops::tag::Analysis::parse_id3(data) {
         println!("Tag: Analysis (ID3)");
-        println!("{:#?}", res.unwrap());
+        println!("{:#?}", tag);
         return true;
     }
-    let res = triseratops::tag::Autotags::parse_id3(&data);
-    if res.is_ok() {
+    if let Ok(tag) = triseratops::tag::Autotags::parse_id3(data) {
         println!("Tag: Autotags (ID3)");
-        println!("{:#?}", res.unwrap());
+        println!("{:#?}", tag);
         return true;
     }
-    let res = triseratops::tag::Beatgrid::parse_id3(&data);
-    if res.is_ok() {
+    if let Ok(tag) = triseratops::tag::Beatgrid::parse_id3(data) {
         println!("Tag: Beatgrid (ID3)");
-        println!("{:#?}", res.unwrap());
+        println!("{:#?}", tag);
         return true;
     }
-    let res = triseratops::tag::Markers::parse_id3(&data);
-    if res.is_ok() {
+    if let Ok(tag) = triseratops::tag::Markers::parse_id3(data) {
         println!("Tag: Markers (ID3)");
-        println!("{:#?}", res.unwrap());
+        println!("{:#?}", tag);
         return true;
     }
-    let res = triseratops::tag::Markers2::parse_id3(&data);
-    if res.is_ok() {
+    if let Ok(tag) = triseratops::tag::Markers2::parse_id3(data) {
         println!("Tag: Markers2 (ID3)");
-        println!("{:#?}", res.unwrap());
+        println!("{:#?}", tag);
         return true;
     }
-    let res = triseratops::tag::Overview::parse_id3(&data);
-    if res.is_ok() {
+    if let Ok(tag) = triseratops::tag::Overview::parse_id3(data) {
         println!("Tag: Overview (ID3)");
-        println!("{:#?}", res.unwrap());
+        println!("{:#?}", tag);
         return true;
     }
-    let res = triseratops::tag::Analysis::parse_flac(&data);
-    if res.is_ok() {
+    if let Ok(tag) = triseratops::tag::Analysis::parse_flac(data) {
         println!("Tag: Analysis (FLAC)");
-        println!("{:#?}", res.unwrap());
+        println!("{:#?}", tag);
         return true;
     }
-    let res = triseratops::tag::Autotags::parse_flac(&data);
-    if res.is_ok() {
+    if let Ok(tag) = triseratops::tag::Autotags::parse_flac(data) {
         println!("Tag: Autotags (FLAC)");
-        println!("{:#?}", res.unwrap());
+        println!("{:#?}", tag);
         return true;
     }
-    let res = triseratops::tag::Beatgrid::parse_flac(&data);
-    if res.is_ok() {
+    if let Ok(tag) = triseratops::tag::Beatgrid::parse_flac(data) {
         println!("Tag: Beatgrid (FLAC)");
-        println!("{:#?}", res.unwrap());
+        println!("{:#?}", tag);
         return true;
     }
-    let res = triseratops::tag::Markers2::parse_flac(&data);
-    if res.is_ok() {
+    if let Ok(tag) = triseratops::tag::Markers2::parse_flac(data) {
         println!("Tag: Markers2 (FLAC)");
-        println!("{:#?}", res.unwrap());
+        println!("{:#?}", tag);
         return true;
     }
-    let res = triseratops::tag::Overview::parse_flac(&data);
-    if res.is_ok() {
+    if let Ok(tag) = triseratops::tag::Overview::parse_flac(data) {
         println!("Tag: Overview (FLAC)");
-        println!("{:#?}", res.unwrap());
+        println!("{:#?}", tag);
         return true;
     }
-    let res = triseratops::tag::RelVolAd::parse_flac(&data);
-    if res.is_ok() {
+    if let Ok(tag) = triseratops::tag::RelVolAd::parse_flac(data) {
         println!("Tag: RelVolAd (FLAC)");
-        println!("{:#?}", res.unwrap());
+        println!("{:#?}", tag);
         return true;
     }
-    let res = triseratops::tag::Analysis::parse_mp4(&data);
-    if res.is_ok() {
+    if let Ok(tag) = triseratops::tag::Analysis::parse_mp4(data) {
         println!("Tag: Analysis (MP4)");
-        println!("{:#?}", res.unwrap());
+        println!("{:#?}", tag);
         return true;
     }
-    let res = triseratops::tag::Autotags::parse_mp4(&data);
-    if res.is_ok() {
+    if let Ok(tag) = triseratops::tag::Autotags::parse_mp4(data) {
         println!("Tag: Autotags (MP4)");
-        println!("{:#?}", res.unwrap());
+        println!("{:#?}", tag);
         return true;
     }
-    let res = triseratops::tag::Beatgrid::parse_mp4(&data);
-    if res.is_ok() {
+    if let Ok(tag) = triseratops::tag::Beatgrid::parse_mp4(data) {
         println!("Tag: Beatgrid (MP4)");
-        println!("{:#?}", res.unwrap());
+        println!("{:#?}", tag);
         return true;
     }
-    let res = triseratops::tag::Markers::parse_mp4(&data);
-    if res.is_ok() {
+    if let Ok(tag) = triseratops::tag::Markers::parse_mp4(data) {
         println!("Tag: Markers (MP4)");
-        println!("{:#?}", res.unwrap());
+        println!("{:#?}", tag);
         return true;
     }
-    let res = triseratops::tag::Markers2::parse_mp4(&data);
-    if res.is_ok() {
+    if let Ok(tag) = triseratops::tag::Markers2::parse_mp4(data) {
         println!("Tag: Markers2 (MP4)");
-        println!("{:#?}", res.unwrap());
+        println!("{:#?}", tag);
         return true;
     }
-    let res = triseratops::tag::Overview::parse_mp4(&data);
-    if res.is_ok() {
+    if let Ok(tag) = triseratops::tag::Overview::parse_mp4(data) {
         println!("Tag: Overview (MP4)");
-        println!("{:#?}", res.unwrap());
+        println!("{:#?}", tag);
         return true;
     }
-    let res = triseratops::tag::RelVolAd::parse_mp4(&data);
-    if res.is_ok() {
+    if let Ok(tag) = triseratops::tag::RelVolAd::parse_mp4(data) {
         println!("Tag: RelVolAd (MP4)");
-        println!("{:#?}", res.unwrap());
+        println!("{:#?}", tag);
         return true;
     }
-    let res = triseratops::tag::VidAssoc::parse_mp4(&data);
-    if res.is_ok() {
+    if let Ok(tag) = triseratops::tag::VidAssoc::parse_mp4(data) {
         println!("Tag: VidAssoc (MP4)");
-        println!("{:#?}", res.unwrap());
+        println!("{:#?}", tag);
         return true;
     }
-    let res = triseratops::tag::Analysis::parse_ogg(&data);
-    if res.is_ok() {
+    if let Ok(tag) = triseratops::tag::Analysis::parse_ogg(data) {
         println!("Tag: Analysis (Ogg)");
-        println!("{:#?}", res.unwrap());
+        println!("{:#?}", tag);
         return true;
     }
-    let res = triseratops::tag::Markers2::parse_ogg(&data);
-    if res.is_ok() {
+    if let Ok(tag) = triseratops::tag::Markers2::parse_ogg(data) {
         println!("Tag: Markers2 (Ogg)");
-        println!("{:#?}", res.unwrap());
+        println!("{:#?}", tag);
         return true;
     }
     false
@@ -12,7 +12,7 @@ fn main() -> Result<(), triseratops::error::Error> {
     let path = args.remove(0);
 
     let library = Library::read_from_path(path)?;
-    let tracks: Vec<&Track> = library.tracks().collect();
+    let tracks: Vec<&Track> = library.tracks()?.collect();
     println!("Library ({} tracks)", tracks.len());
     println!("{:#?}", tracks);
     let subcrates = library.subcrates();
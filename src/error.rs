@@ -40,13 +40,138 @@ pub enum Error {
     #[error("Envelope name mismatch")]
     EnvelopeNameMismatch { expected: String, actual: String },
 
+    /// Happens when tag data is padded with null bytes, but strict parsing was requested.
+    #[error("Unexpected padding ({length} bytes)")]
+    UnexpectedPadding { length: usize },
+
     /// Happens when trying to write tag data even though no data is available.
     #[error("No tag data available")]
     NoTagDataAvailable,
 
+    /// Happens when trying to write a beatgrid marker that Serato can't handle.
+    ///
+    /// The index counts all beatgrid markers, i.e. the terminal marker has the index of the last
+    /// non-terminal marker plus one.
+    #[error("Invalid beatgrid marker at index {index}: {reason}")]
+    InvalidBeatgridMarker { index: usize, reason: &'static str },
+
+    /// Happens when trying to store a cue or loop in a slot that doesn't exist.
+    #[error("Invalid marker index {index}")]
+    InvalidMarkerIndex { index: u8 },
+
+    /// Happens when passing several cues (or several loops) with the same index.
+    #[error("Duplicate marker index {index}")]
+    DuplicateMarkerIndex { index: u8 },
+
+    /// Happens when trying to add a cue to a slot that is already occupied without allowing it to
+    /// be overwritten.
+    #[error("Marker slot {index} is already occupied")]
+    MarkerSlotOccupied { index: u8 },
+
+    /// Happens when trying to store a saved loop that doesn't end after its start position.
+    #[error("Invalid loop at index {index}: end position {end_position_millis} ms is not after start position {start_position_millis} ms")]
+    InvalidLoopPositions {
+        index: u8,
+        start_position_millis: u32,
+        end_position_millis: u32,
+    },
+
+    /// Happens when trying to write an overview chunk that doesn't have the expected size of 16
+    /// bytes.
+    #[error("Invalid overview chunk at index {index} ({length} bytes)")]
+    InvalidOverviewChunk { index: usize, length: usize },
+
+    /// Happens when trying to encode a value that doesn't fit into the `serato32` format.
+    #[error("Invalid serato32 value {value:#X}")]
+    InvalidSerato32Value { value: u32 },
+
+    /// Happens when trying to write a `Serato Markers_` tag with a version whose entry layout is
+    /// unknown, or whose data doesn't match the version (see
+    /// [`Markers::legacy_data`](crate::tag::Markers::legacy_data)).
+    #[error("Unsupported Serato Markers_ version {major}.{minor}")]
+    UnsupportedMarkersVersion { major: u8, minor: u8 },
+
+    /// Happens when trying to write a number that Serato can't represent (e.g. `NaN`).
+    #[error("Invalid number {value}")]
+    InvalidNumber { value: f64 },
+
+    /// Happens when trying to write a date that doesn't fit into a 32-bit timestamp field.
+    #[error("Timestamp {timestamp} is out of range")]
+    TimestampOutOfRange { timestamp: u64 },
+
+    /// Happens when trying to write a date before the Unix epoch, which timestamps can't represent.
+    #[error("Time is {duration:?} before the Unix epoch")]
+    TimeBeforeEpoch { duration: std::time::Duration },
+
+    /// Happens when a path isn't located on the volume of the library.
+    #[error("Path {path:?} is not located on the library volume")]
+    PathOutsideVolume { path: std::path::PathBuf },
+
+    /// Happens when trying to add a track to a crate that already contains it.
+    #[error("Duplicate track {path:?}")]
+    DuplicateTrack { path: std::path::PathBuf },
+
+    /// Happens when a crate name is empty or contains a path separator or the subcrate separator
+    /// `%%`.
+    #[error("Invalid crate name {name:?}")]
+    InvalidCrateName { name: String },
+
+    /// Happens when trying to save a crate that hasn't been read from a file.
+    #[error("Crate has not been read from a file")]
+    NoCratePath,
+
+    /// Happens when trying to access a crate track at a position that doesn't exist.
+    #[error("Invalid track index {index}")]
+    InvalidTrackIndex { index: usize },
+
+    /// Happens when a raw ID3 frame can't be parsed (see
+    /// [`GeobFrame`](crate::tag::format::id3::GeobFrame)).
+    #[error("Invalid ID3 frame: {reason}")]
+    InvalidID3Frame { reason: &'static str },
+
+    /// Happens when tag data that is read from a reader exceeds
+    /// [`MAX_TAG_SIZE`](crate::tag::format::MAX_TAG_SIZE).
+    #[error("Tag data exceeds the maximum size of {max_size} bytes")]
+    TagTooLarge { max_size: u64 },
+
+    /// Happens when trying to read or write Serato tags in a tag type that Serato doesn't use
+    /// (e.g. APE tags or the Vorbis comments of Opus files).
+    #[error("Serato tags are not supported in {tag_type} tags of {file_type} files")]
+    UnsupportedTagType { tag_type: String, file_type: String },
+
+    /// Happens when an audio file that embeds the tag in a chunk (e.g. AIFF or WAV) is malformed.
+    #[error("Invalid container: {reason}")]
+    InvalidContainer { reason: &'static str },
+
     /// Represents all other cases of `std::io::Error`.
+    ///
+    /// When parsing from a reader, this variant is only returned if reading failed, so the
+    /// operation may be retried (see [`is_io_error`](Error::is_io_error)). Data that ends
+    /// prematurely is a parse error.
     #[error(transparent)]
-    IOError(#[from] std::io::Error),
+    IOError(std::io::Error),
+}
+
+impl Error {
+    /// Returns `true` if the error was caused by an I/O failure instead of malformed data.
+    pub fn is_io_error(&self) -> bool {
+        matches!(self, Error::IOError(_))
+    }
+}
+
+impl From<std::io::Error> for Error {
+    /// Convert an I/O error, restoring parse errors that have been passed through an
+    /// [`io::Read`](std::io::Read) implementation of this library (e.g. when decoding base64 data
+    /// from a reader).
+    fn from(err: std::io::Error) -> Self {
+        if err.get_ref().is_some_and(|inner| inner.is::<Error>()) {
+            let inner = err
+                .into_inner()
+                .and_then(|inner| inner.downcast::<Error>().ok());
+            return *inner.expect("inner error is an Error");
+        }
+        Error::IOError(err)
+    }
 }
 
 fn convert_err(
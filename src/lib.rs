@@ -43,6 +43,20 @@
 //! use other libraries (e.g. [`id3`](https://lib.rs/crates/id3) to do that. You can check the
 //! `examples/` directory for some toy examples.
 //!
+//! If the `id3` feature is enabled, [`TagContainer::read_from_id3_tag`](tag::TagContainer) and
+//! `TagContainer::write_into_id3_tag` can be used to read and write the `GEOB` frames of an
+//! [`id3::Tag`](https://docs.rs/id3/0.6/id3/struct.Tag.html) directly.
+//! The same is possible for the `VORBIS_COMMENT` block of a
+//! [`metaflac::Tag`](https://docs.rs/metaflac/0.2/metaflac/struct.Tag.html) using
+//! `TagContainer::read_from_flac_tag` and `TagContainer::write_into_flac_tag` if the `metaflac`
+//! feature is enabled, and for the freeform atoms of an
+//! [`mp4ameta::Tag`](https://docs.rs/mp4ameta/0.13/mp4ameta/struct.Tag.html) using
+//! `TagContainer::read_from_mp4_tag` and `TagContainer::write_into_mp4_tag` if the `mp4ameta`
+//! feature is enabled. With the `lofty` feature, `TagContainer::read_from_lofty` and
+//! `TagContainer::write_into_lofty` read and write the Serato tags of a
+//! [`lofty::file::TaggedFile`](https://docs.rs/lofty/0.22/lofty/file/struct.TaggedFile.html) in
+//! all file types that Serato stores tags in.
+//!
 //! ## Serialization
 //!
 //! This library aims to provide support for lossless data roundtripping, i.e. parsing and then
@@ -70,7 +84,7 @@
 //! | `BeatGrid`   | **Yes** | **Yes** | **Yes** | No         | No             | Beatgrid Markers
 //! | `Markers_`   | **Yes** | *n/a*   | **Yes** | No         | No             | Hotcues, Saved Loops, etc.
 //! | `Markers2`   | **Yes** | **Yes** | **Yes** | **Yes**    | No             | Hotcues, Saved Loops, etc.
-//! | `Offsets_`   | No      | No      | No      | No         | No             | ?
+//! | `Offsets_`   | Partial | *n/a*   | *n/a*   | *n/a*      | No             | Timing offsets (?)
 //! | `Overview`   | **Yes** | **Yes** | **Yes** | No         | No             | Overview Waveform data
 //! | `RelVol`     | *n/a*   | Partial | Partial | *n/a*      | No             | Relative Volume Adjument data (?)
 //! | `VideoAssoc` | *n/a*   | Partial | Partial | *n/a*      | No             | Video Association data (?)
@@ -78,13 +92,19 @@
 //! ## Caveats
 //!
 //! - Most Ogg tags are currently not supported. Their format is completely different from
-//!   the other tag types and need to be reverse-engineered first.
-//! - The `Serato Offsets_` tag haven't been reverse engineed yet, and no support has been
-//!   implemented.
+//!   the other tag types and need to be reverse-engineered first. Only the `Serato Analysis` and
+//!   `Serato Markers2` tags can be read and written, because those are the only ones that sample
+//!   files are available for.
+//! - The layout of the `Serato Offsets_` tag (sample rate and offset table) hasn't been confirmed
+//!   with files from all Serato versions yet. Data that doesn't match it is preserved as a byte
+//!   vector when rewriting tags.
+//! - Version 1 `Serato Markers_` tags written by very old Serato versions (e.g. ITCH) use a
+//!   different entry layout that hasn't been reverse-engineered yet. Their data is preserved when
+//!   rewriting tags, but no cues, loops or track color are decoded from them.
 //! - The `Serato RelVolAd` and the `Serato VidAssoc` tags haven't been reverse engineed yet, but
 //!   preliminary support has been added. For now, they just return a tag version and a byte vector.
 //! - AAC files (among others) do not store metadata in tags, and use XML files in the
-//! `_Serato_/Metadata` directory instead. No support has been added yet.
+//!   `_Serato_/Metadata` directory instead. No support has been added yet.
 //! - The cue colors stored in the metadata are *not* the same as displayed in Serato DJ Pro.
 //!   Instead, they uses the color palette from Serato DJ Into. Serato then maps them to a new
 //!   color palette. Support for converting between the two is currently missing.
@@ -95,6 +115,9 @@
 //!   when writing tags from Serato DJ even if no actual changes were made (possibly an
 //!   out-of-bounds read or uninitialized data in Serato DJ). For parsing and using this in Serato,
 //!   that doesn't make a different, but the roundtrip tests will ignore those last two bytes.
+//!   If unmodified tags need to be written back byte by byte, enable
+//!   [`ParseOptions::preserve_raw`](tag::ParseOptions::preserve_raw) when creating the
+//!   [`TagContainer`](tag::TagContainer).
 //!
 //! # Library
 //!
@@ -105,4 +128,4 @@
 pub mod error;
 pub mod library;
 pub mod tag;
-pub(in crate) mod util;
+pub(crate) mod util;
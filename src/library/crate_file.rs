@@ -0,0 +1,744 @@
+//! Parser for Serato crate files (`_Serato_/Subcrates/*.crate`).
+//!
+//! ```
+//! use triseratops::library::crate_file::Crate;
+//!
+//! fn print_crate(path: &std::path::Path) {
+//!     let serato_crate = Crate::read_from_path(path).unwrap();
+//!     let volume_root = Crate::volume_root(path).unwrap();
+//!     for track_path in serato_crate.resolve_track_paths(volume_root) {
+//!         println!("{}", track_path.display());
+//!     }
+//! }
+//! ```
+
+use super::database::{self, Database, Field, TrackRecord};
+use super::path::PathResolver;
+use crate::error::Error;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// File extension of the crate files
+const CRATE_EXTENSION: &str = "crate";
+
+/// The crate version that Serato DJ writes.
+pub const CRATE_VERSION: &str = "1.0/Serato ScratchLive Crate";
+
+/// Separates the names of parent and child crates in the file name of nested crates (e.g.
+/// `House%%Deep.crate`).
+pub const SUBCRATE_SEPARATOR: &str = "%%";
+
+/// The columns that Serato DJ shows for new crates (name and width).
+const DEFAULT_COLUMNS: [(&str, u32); 7] = [
+    ("song", 450),
+    ("artist", 0),
+    ("bpm", 0),
+    ("key", 0),
+    ("album", 0),
+    ("length", 0),
+    ("comment", 0),
+];
+
+/// The column that the tracks of a crate are sorted by.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sorting {
+    /// The name of the column (e.g. `#` for the crate order, or `bpm`).
+    pub column: String,
+    /// Whether the tracks are sorted in descending order.
+    pub reverse: bool,
+}
+
+impl Default for Sorting {
+    /// Sort by crate order (i.e. the `#` column) in ascending order.
+    fn default() -> Self {
+        Self {
+            column: String::from("#"),
+            reverse: false,
+        }
+    }
+}
+
+/// The name of a column in the track table.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ColumnName {
+    Added,
+    Album,
+    Art,
+    Artist,
+    Bitrate,
+    BPM,
+    Comment,
+    Composer,
+    Filename,
+    Genre,
+    Grouping,
+    Key,
+    Label,
+    Length,
+    Remixer,
+    Song,
+    Year,
+    /// Any other column, which is written back as it is.
+    Other(String),
+}
+
+impl ColumnName {
+    /// Returns the name that Serato stores (e.g. `bpm`).
+    pub fn as_str(&self) -> &str {
+        match self {
+            ColumnName::Added => "added",
+            ColumnName::Album => "album",
+            ColumnName::Art => "art",
+            ColumnName::Artist => "artist",
+            ColumnName::Bitrate => "bitrate",
+            ColumnName::BPM => "bpm",
+            ColumnName::Comment => "comment",
+            ColumnName::Composer => "composer",
+            ColumnName::Filename => "filename",
+            ColumnName::Genre => "genre",
+            ColumnName::Grouping => "grouping",
+            ColumnName::Key => "key",
+            ColumnName::Label => "label",
+            ColumnName::Length => "length",
+            ColumnName::Remixer => "remixer",
+            ColumnName::Song => "song",
+            ColumnName::Year => "year",
+            ColumnName::Other(name) => name,
+        }
+    }
+}
+
+impl From<&str> for ColumnName {
+    fn from(name: &str) -> Self {
+        match name {
+            "added" => ColumnName::Added,
+            "album" => ColumnName::Album,
+            "art" => ColumnName::Art,
+            "artist" => ColumnName::Artist,
+            "bitrate" => ColumnName::Bitrate,
+            "bpm" => ColumnName::BPM,
+            "comment" => ColumnName::Comment,
+            "composer" => ColumnName::Composer,
+            "filename" => ColumnName::Filename,
+            "genre" => ColumnName::Genre,
+            "grouping" => ColumnName::Grouping,
+            "key" => ColumnName::Key,
+            "label" => ColumnName::Label,
+            "length" => ColumnName::Length,
+            "remixer" => ColumnName::Remixer,
+            "song" => ColumnName::Song,
+            "year" => ColumnName::Year,
+            name => ColumnName::Other(String::from(name)),
+        }
+    }
+}
+
+/// The configuration of a column that is shown in the track table of the crate (`ovct`).
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColumnConfig {
+    /// The name of the column (`tvcn`).
+    pub name: ColumnName,
+    /// The width of the column in pixels (`tvcw`), where `0` means default width.
+    ///
+    /// This is `None` if the width is missing or not a number. In the latter case, the original
+    /// value is kept in `other_fields`.
+    pub width: Option<u32>,
+    /// All other fields of the column record, which are written back verbatim.
+    pub other_fields: Vec<Field>,
+}
+
+impl ColumnConfig {
+    /// Create a column configuration with the given name and width.
+    pub fn new(name: impl Into<ColumnName>, width: u32) -> Self {
+        Self {
+            name: name.into(),
+            width: Some(width),
+            other_fields: vec![],
+        }
+    }
+
+    fn from_fields(fields: Vec<Field>) -> Self {
+        let mut column = Self {
+            name: ColumnName::Other(String::new()),
+            width: None,
+            other_fields: vec![],
+        };
+        for field in fields {
+            match field {
+                Field::ColumnName(name) => column.name = ColumnName::from(name.as_str()),
+                Field::ColumnWidth(width) => match width.parse::<u32>() {
+                    Ok(value) if value.to_string() == width => column.width = Some(value),
+                    _ => column.other_fields.push(Field::ColumnWidth(width)),
+                },
+                field => column.other_fields.push(field),
+            }
+        }
+        column
+    }
+
+    fn to_field(&self) -> Field {
+        let mut fields = vec![Field::ColumnName(self.name.as_str().to_string())];
+        if let Some(width) = self.width {
+            fields.push(Field::ColumnWidth(width.to_string()));
+        }
+        fields.extend(self.other_fields.iter().cloned());
+        Field::ColumnTitle(fields)
+    }
+}
+
+/// The reason why a playlist entry wasn't imported (see [`Crate::from_m3u`]).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SkipReason {
+    /// The file doesn't exist.
+    NotFound,
+    /// The file isn't located on the volume of the library.
+    OtherVolume,
+    /// The track is already contained in the playlist.
+    Duplicate,
+}
+
+/// A playlist entry that wasn't imported.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SkippedEntry {
+    /// The line number in the playlist (starting at 1).
+    pub line: usize,
+    /// The path as written in the playlist.
+    pub path: String,
+    /// The reason why the entry was skipped.
+    pub reason: SkipReason,
+}
+
+/// Represents a Serato crate file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Crate {
+    /// The crate version (e.g. `1.0/Serato ScratchLive Crate`).
+    pub version: Option<String>,
+    /// The sort settings of the crate.
+    pub sorting: Option<Sorting>,
+    /// The columns of the track table.
+    pub columns: Vec<ColumnConfig>,
+    /// The paths of the tracks in the crate, in crate order.
+    ///
+    /// Serato stores the paths relative to the volume root (see [`Crate::volume_root`]) without
+    /// a leading separator, so they can be joined with the volume root directly (see
+    /// [`Crate::resolve_track_paths`]).
+    pub tracks: Vec<PathBuf>,
+    /// All other fields of the crate file, which are written back verbatim.
+    pub other_fields: Vec<Field>,
+    /// The number of other fields that precede the tracks in the crate file.
+    tracks_position: Option<usize>,
+    /// Other fields of the sort settings.
+    sorting_fields: Vec<Field>,
+    /// All fields of the track records, so that other fields are preserved.
+    track_fields: HashMap<PathBuf, Vec<Field>>,
+    /// The path that the crate was read from (see [`Crate::save`]).
+    path: Option<PathBuf>,
+}
+
+impl Crate {
+    /// Create a new crate with the given sort settings and tracks, and the same version and
+    /// columns that Serato DJ uses for new crates.
+    pub fn new(sorting: Sorting, tracks: Vec<PathBuf>) -> Self {
+        let columns = DEFAULT_COLUMNS
+            .iter()
+            .map(|&(name, width)| ColumnConfig::new(name, width))
+            .collect();
+        Self {
+            version: Some(String::from(CRATE_VERSION)),
+            sorting: Some(sorting),
+            columns,
+            tracks,
+            other_fields: vec![],
+            tracks_position: None,
+            sorting_fields: vec![],
+            track_fields: HashMap::new(),
+            path: None,
+        }
+    }
+
+    /// Parse the data of a crate file.
+    pub fn parse(input: &[u8]) -> Result<Self, Error> {
+        let mut serato_crate = Crate {
+            version: None,
+            sorting: None,
+            columns: vec![],
+            tracks: vec![],
+            other_fields: vec![],
+            tracks_position: None,
+            sorting_fields: vec![],
+            track_fields: HashMap::new(),
+            path: None,
+        };
+        for field in database::parse(input)? {
+            match field {
+                database::Field::Version(version) => {
+                    serato_crate.version = Some(version);
+                }
+                database::Field::Sorting(fields) => {
+                    let (sorting, other_fields) = sorting_from_fields(fields);
+                    serato_crate.sorting = Some(sorting);
+                    serato_crate.sorting_fields = other_fields;
+                }
+                database::Field::ColumnTitle(fields) => {
+                    serato_crate.columns.push(ColumnConfig::from_fields(fields));
+                }
+                database::Field::Track(fields) => {
+                    serato_crate
+                        .tracks_position
+                        .get_or_insert(serato_crate.other_fields.len());
+                    let paths: Vec<_> = fields
+                        .iter()
+                        .filter_map(|field| match field {
+                            database::Field::TrackPath(path) => Some(relative_path(path.clone())),
+                            _ => None,
+                        })
+                        .collect();
+                    if let [path] = paths.as_slice() {
+                        serato_crate.track_fields.insert(path.clone(), fields);
+                    }
+                    serato_crate.tracks.extend(paths);
+                }
+                field => serato_crate.other_fields.push(field),
+            }
+        }
+        Ok(serato_crate)
+    }
+
+    /// Read and parse the crate file at the given path.
+    pub fn read_from_path(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let data = fs::read(path)?;
+        let mut serato_crate = Self::parse(&data)?;
+        serato_crate.path = Some(path.to_path_buf());
+        Ok(serato_crate)
+    }
+
+    /// Returns the path that the crate was read from, if any.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Returns `true` if the crate contains the track with the given path.
+    ///
+    /// Leading separators are ignored when comparing paths.
+    pub fn contains_track(&self, path: impl AsRef<Path>) -> bool {
+        let path = relative_path(path.as_ref().to_path_buf());
+        self.tracks.contains(&path)
+    }
+
+    /// Append a track to the crate.
+    ///
+    /// Fails if the crate already contains the track, because Serato doesn't allow duplicates.
+    pub fn add_track(&mut self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = relative_path(path.as_ref().to_path_buf());
+        if self.tracks.contains(&path) {
+            return Err(Error::DuplicateTrack { path });
+        }
+        self.tracks.push(path);
+        Ok(())
+    }
+
+    /// Remove a track from the crate.
+    ///
+    /// Returns `false` if the crate doesn't contain the track.
+    pub fn remove_track(&mut self, path: impl AsRef<Path>) -> bool {
+        let path = relative_path(path.as_ref().to_path_buf());
+        let length = self.tracks.len();
+        self.tracks.retain(|track| *track != path);
+        self.tracks.len() != length
+    }
+
+    /// Replace the path of a track, keeping its position and the other fields of its record.
+    ///
+    /// Returns `false` if the crate doesn't contain the track. Fails if the crate already
+    /// contains a track with the new path.
+    pub fn rename_track(
+        &mut self,
+        path: impl AsRef<Path>,
+        new_path: impl AsRef<Path>,
+    ) -> Result<bool, Error> {
+        let path = relative_path(path.as_ref().to_path_buf());
+        let new_path = relative_path(new_path.as_ref().to_path_buf());
+        if path == new_path {
+            return Ok(self.tracks.contains(&path));
+        }
+        if self.tracks.contains(&new_path) {
+            return Err(Error::DuplicateTrack { path: new_path });
+        }
+        let track = match self.tracks.iter_mut().find(|track| **track == path) {
+            Some(track) => track,
+            None => return Ok(false),
+        };
+        *track = new_path.clone();
+        if let Some(fields) = self.track_fields.remove(&path) {
+            self.track_fields.insert(new_path, fields);
+        }
+        Ok(true)
+    }
+
+    /// Move the track at position `from` to position `to`, shifting the tracks in between.
+    pub fn move_track(&mut self, from: usize, to: usize) -> Result<(), Error> {
+        if let Some(&index) = [from, to].iter().find(|&&i| i >= self.tracks.len()) {
+            return Err(Error::InvalidTrackIndex { index });
+        }
+        let track = self.tracks.remove(from);
+        self.tracks.insert(to, track);
+        Ok(())
+    }
+
+    /// Write the crate back to the file that it was read from.
+    ///
+    /// Returns a [`NoCratePath`](Error::NoCratePath) error if the crate wasn't read from a file
+    /// (see [`Crate::read_from_path`]).
+    pub fn save(&self) -> Result<(), Error> {
+        let path = self.path.as_ref().ok_or(Error::NoCratePath)?;
+        let mut data = vec![];
+        self.write(&mut data)?;
+        fs::write(path, data)?;
+        Ok(())
+    }
+
+    /// Serialize the crate in the same format as Serato DJ.
+    ///
+    /// The fields are written in the order that Serato uses: version, sort settings, columns and
+    /// tracks. Leading separators are stripped from the track paths.
+    ///
+    /// Fields that aren't known (e.g. because they were added by a newer Serato version) are
+    /// written back verbatim: Other fields are written before or after the tracks, depending on
+    /// their position in the parsed file. Unknown fields of the track records keep their
+    /// positions, and unknown fields of the sort settings are written after the known ones.
+    pub fn write(&self, writer: impl io::Write) -> Result<usize, Error> {
+        let mut fields = vec![];
+        if let Some(version) = &self.version {
+            fields.push(Field::Version(version.clone()));
+        }
+        if let Some(sorting) = &self.sorting {
+            let mut sorting_fields = vec![
+                Field::ColumnName(sorting.column.clone()),
+                Field::ReverseOrder(sorting.reverse),
+            ];
+            sorting_fields.extend(self.sorting_fields.iter().cloned());
+            fields.push(Field::Sorting(sorting_fields));
+        }
+        fields.extend(self.columns.iter().map(ColumnConfig::to_field));
+        let tracks_position = self
+            .tracks_position
+            .unwrap_or(self.other_fields.len())
+            .min(self.other_fields.len());
+        let (leading_fields, trailing_fields) = self.other_fields.split_at(tracks_position);
+        fields.extend(leading_fields.iter().cloned());
+        fields.extend(self.tracks.iter().map(|path| self.track_field(path)));
+        fields.extend(trailing_fields.iter().cloned());
+        database::write(writer, &fields)
+    }
+
+    /// Returns the track record for the given path, with the other fields of the parsed record
+    /// in their original positions.
+    fn track_field(&self, path: &Path) -> Field {
+        let path = relative_path(path.to_path_buf());
+        let fields = match self.track_fields.get(&path) {
+            Some(fields) => fields
+                .iter()
+                .map(|field| match field {
+                    Field::TrackPath(_) => Field::TrackPath(path.clone()),
+                    field => field.clone(),
+                })
+                .collect(),
+            None => vec![Field::TrackPath(path)],
+        };
+        Field::Track(fields)
+    }
+
+    /// Returns the file name of the crate with the given name.
+    ///
+    /// For nested crates, pass the names of all parent crates first (e.g. `["House", "Deep"]`
+    /// results in `House%%Deep.crate`). Returns an [`InvalidCrateName`](Error::InvalidCrateName)
+    /// error if a name is empty or contains a path separator or the `%%` separator.
+    pub fn file_name(names: &[&str]) -> Result<String, Error> {
+        let is_valid = |name: &&str| {
+            !name.is_empty() && !name.contains(['/', '\\']) && !name.contains(SUBCRATE_SEPARATOR)
+        };
+        if let Some(name) = names.iter().find(|name| !is_valid(name)) {
+            return Err(Error::InvalidCrateName {
+                name: (*name).to_string(),
+            });
+        }
+        if names.is_empty() {
+            return Err(Error::InvalidCrateName {
+                name: String::new(),
+            });
+        }
+        Ok(format!(
+            "{}.{}",
+            names.join(SUBCRATE_SEPARATOR),
+            CRATE_EXTENSION
+        ))
+    }
+
+    /// Write the crate into the given `Subcrates` directory and return the path of the file.
+    ///
+    /// See [`Crate::file_name`] for how the crate names are mapped to the file name. An existing
+    /// crate file with the same name is replaced.
+    pub fn write_to_dir(&self, subcrates_dir: &Path, names: &[&str]) -> Result<PathBuf, Error> {
+        let path = subcrates_dir.join(Self::file_name(names)?);
+        let mut data = vec![];
+        self.write(&mut data)?;
+        fs::write(&path, data)?;
+        Ok(path)
+    }
+
+    /// Returns the volume root for the crate file at the given path (see
+    /// [`path::volume_root`](super::path::volume_root)).
+    ///
+    /// Returns `None` if the crate file isn't located inside a `_Serato_` directory.
+    pub fn volume_root(crate_path: &Path) -> Option<&Path> {
+        super::path::volume_root(crate_path)
+    }
+
+    /// Returns the track paths joined with the given volume root.
+    ///
+    /// Paths that were written on Windows are converted to the current platform (see
+    /// [`PathResolver::resolve`]).
+    pub fn resolve_track_paths(&self, volume_root: &Path) -> Vec<PathBuf> {
+        let resolver = PathResolver::new(volume_root);
+        self.tracks
+            .iter()
+            .map(|path| resolver.resolve(path))
+            .collect()
+    }
+
+    /// Export the crate as M3U8 playlist (i.e. an extended M3U playlist encoded as UTF-8).
+    ///
+    /// The track paths are resolved to absolute paths with the given resolver. Use
+    /// [`Crate::to_m3u8_with_database`] to add the length, artist and title of the tracks.
+    pub fn to_m3u8(&self, resolver: &PathResolver) -> String {
+        self.m3u8(resolver, None)
+    }
+
+    /// Export the crate as M3U8 playlist with an `#EXTINF` line for each track that is found in
+    /// the database.
+    ///
+    /// The `#EXTINF` line contains the length in seconds (or `-1` if unknown) and `Artist - Title`.
+    /// If the title is unknown, the file name is used instead.
+    pub fn to_m3u8_with_database(&self, resolver: &PathResolver, database: &Database) -> String {
+        self.m3u8(resolver, Some(database))
+    }
+
+    /// Create a crate from the entries of an M3U or M3U8 playlist, in playlist order.
+    ///
+    /// Relative entries are resolved against `playlist_dir` and all paths are converted into the
+    /// form that Serato stores with the given resolver. Comments and extended M3U metadata (e.g.
+    /// `#EXTINF`) are ignored. Entries whose files don't exist, which aren't located on the
+    /// volume of the resolver, or which occur more than once are skipped and returned
+    /// separately.
+    pub fn from_m3u(
+        playlist: &str,
+        playlist_dir: &Path,
+        resolver: &PathResolver,
+    ) -> (Self, Vec<SkippedEntry>) {
+        let mut serato_crate = Self::new(Sorting::default(), vec![]);
+        let mut skipped = vec![];
+        let playlist = playlist.strip_prefix('\u{FEFF}').unwrap_or(playlist);
+        for (index, line) in playlist.lines().enumerate() {
+            let entry = line.trim();
+            if entry.is_empty() || entry.starts_with('#') {
+                continue;
+            }
+            let path = playlist_dir.join(entry);
+            let reason = if !path.is_file() {
+                Some(SkipReason::NotFound)
+            } else {
+                match resolver.to_stored(&path) {
+                    Some(stored) => serato_crate
+                        .add_track(stored)
+                        .err()
+                        .map(|_| SkipReason::Duplicate),
+                    None => Some(SkipReason::OtherVolume),
+                }
+            };
+            if let Some(reason) = reason {
+                skipped.push(SkippedEntry {
+                    line: index + 1,
+                    path: entry.to_string(),
+                    reason,
+                });
+            }
+        }
+        (serato_crate, skipped)
+    }
+
+    /// Read an M3U or M3U8 playlist and create a crate from its entries (see
+    /// [`Crate::from_m3u`]).
+    ///
+    /// Playlists that aren't valid UTF-8 are decoded as Latin-1, which was common for M3U files.
+    pub fn read_m3u(
+        path: impl AsRef<Path>,
+        resolver: &PathResolver,
+    ) -> Result<(Self, Vec<SkippedEntry>), Error> {
+        let path = path.as_ref();
+        let data = fs::read(path)?;
+        let playlist = match String::from_utf8(data) {
+            Ok(playlist) => playlist,
+            Err(err) => err.into_bytes().into_iter().map(char::from).collect(),
+        };
+        let playlist_dir = path.parent().unwrap_or_else(|| Path::new(""));
+        Ok(Self::from_m3u(&playlist, playlist_dir, resolver))
+    }
+
+    fn m3u8(&self, resolver: &PathResolver, database: Option<&Database>) -> String {
+        let records: HashMap<&str, &TrackRecord> = database
+            .into_iter()
+            .flat_map(Database::tracks)
+            .map(|track| (track.path(), track))
+            .collect();
+        let mut playlist = String::from("#EXTM3U\n");
+        for path in &self.tracks {
+            let resolved = resolver.resolve(path);
+            let record = path.to_str().and_then(|path| records.get(path));
+            if let Some(record) = record {
+                playlist.push_str(&extinf(record, &resolved));
+            }
+            playlist.push_str(&resolved.to_string_lossy());
+            playlist.push('\n');
+        }
+        playlist
+    }
+}
+
+/// Returns the `#EXTINF` line for a track.
+fn extinf(record: &TrackRecord, path: &Path) -> String {
+    let seconds = record
+        .length()
+        .map_or(-1, |length| length.as_secs_f64().round() as i64);
+    let title = match (record.artist(), record.title()) {
+        (Some(artist), Some(title)) if !artist.is_empty() => format!("{} - {}", artist, title),
+        (_, Some(title)) => title.to_string(),
+        _ => path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+    };
+    // Line breaks would end the entry
+    let title = title.replace(['\r', '\n'], " ");
+    format!("#EXTINF:{},{}\n", seconds, title)
+}
+
+/// Returns the sort settings and all other fields of the sort settings.
+fn sorting_from_fields(fields: Vec<database::Field>) -> (Sorting, Vec<Field>) {
+    let mut sorting = Sorting {
+        column: String::new(),
+        reverse: false,
+    };
+    let mut other_fields = vec![];
+    for field in fields {
+        match field {
+            database::Field::ColumnName(column) => sorting.column = column,
+            database::Field::ReverseOrder(reverse) => sorting.reverse = reverse,
+            field => other_fields.push(field),
+        }
+    }
+    (sorting, other_fields)
+}
+
+/// Strip leading separators, so that joining the path with the volume root doesn't replace it.
+fn relative_path(path: PathBuf) -> PathBuf {
+    match path.to_str() {
+        Some(text) => PathBuf::from(text.trim_start_matches(['/', '\\'])),
+        None => path,
+    }
+}
+
+#[test]
+fn test_volume_root() {
+    assert_eq!(
+        Crate::volume_root(Path::new("/media/usb/_Serato_/Subcrates/House.crate")),
+        Some(Path::new("/media/usb"))
+    );
+    assert_eq!(
+        Crate::volume_root(Path::new("/media/usb/Subcrates/House.crate")),
+        None
+    );
+    assert_eq!(
+        relative_path(PathBuf::from("/Users/dj/Music/a.mp3")),
+        PathBuf::from("Users/dj/Music/a.mp3")
+    );
+}
+
+#[test]
+fn test_file_name() {
+    assert_eq!(Crate::file_name(&["House"]).unwrap(), "House.crate");
+    assert_eq!(
+        Crate::file_name(&["House", "Deep"]).unwrap(),
+        "House%%Deep.crate"
+    );
+    assert!(matches!(
+        Crate::file_name(&[]),
+        Err(Error::InvalidCrateName { name }) if name.is_empty()
+    ));
+    assert!(Crate::file_name(&["House", ""]).is_err());
+    assert!(matches!(
+        Crate::file_name(&["House%%Deep"]),
+        Err(Error::InvalidCrateName { name }) if name == "House%%Deep"
+    ));
+    assert!(matches!(
+        Crate::file_name(&["House", "../Deep"]),
+        Err(Error::InvalidCrateName { name }) if name == "../Deep"
+    ));
+}
+
+#[test]
+fn test_column_config() {
+    let fields = vec![
+        Field::ColumnName(String::from("mystery")),
+        Field::ColumnWidth(String::from("wide")),
+        Field::UnknownBoolean {
+            name: b"xyz".to_vec(),
+            value: true,
+        },
+    ];
+    let column = ColumnConfig::from_fields(fields.clone());
+    assert_eq!(column.name, ColumnName::Other(String::from("mystery")));
+    assert_eq!(column.width, None);
+    assert_eq!(column.to_field(), Field::ColumnTitle(fields));
+
+    let column = ColumnConfig::from_fields(vec![
+        Field::ColumnName(String::from("bpm")),
+        Field::ColumnWidth(String::from("120")),
+    ]);
+    assert_eq!(column, ColumnConfig::new(ColumnName::BPM, 120));
+}
+
+#[test]
+fn test_m3u8() {
+    let record = |fields| database::DatabaseEntry::Track(TrackRecord::new(fields));
+    let database = Database {
+        entries: vec![
+            record(vec![
+                Field::FilePath(PathBuf::from("Music/a.mp3")),
+                Field::Artist(String::from("Artist")),
+                Field::SongTitle(String::from("Line\nBreak")),
+                Field::Length(String::from("06:12.60")),
+            ]),
+            record(vec![Field::FilePath(PathBuf::from("Music/b.mp3"))]),
+        ],
+    };
+    let serato_crate = Crate::new(
+        Sorting::default(),
+        vec![
+            PathBuf::from("Music/a.mp3"),
+            PathBuf::from("Music/b.mp3"),
+            PathBuf::from("Music/c.mp3"),
+        ],
+    );
+    assert_eq!(
+        serato_crate.to_m3u8_with_database(&PathResolver::new("/Volumes/USB"), &database),
+        "#EXTM3U\n\
+         #EXTINF:373,Artist - Line Break\n\
+         /Volumes/USB/Music/a.mp3\n\
+         #EXTINF:-1,b.mp3\n\
+         /Volumes/USB/Music/b.mp3\n\
+         /Volumes/USB/Music/c.mp3\n"
+    );
+}
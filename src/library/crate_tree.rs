@@ -0,0 +1,169 @@
+//! The crate hierarchy that Serato shows in the sidebar.
+//!
+//! Nested crates are stored as flat files in the `Subcrates` directory, with the names of all
+//! parent crates encoded in the file name (e.g. `House%%Deep.crate`, see [`SUBCRATE_SEPARATOR`]).
+//! Use [`Crate::file_name`](super::crate_file::Crate::file_name) to compute the file name for a
+//! crate in the tree.
+//!
+//! ```
+//! use triseratops::library::crate_tree::{read_crate_tree, CrateNode};
+//!
+//! fn print_tree(nodes: &[CrateNode], depth: usize) {
+//!     for node in nodes {
+//!         println!("{:indent$}{}", "", node.name, indent = depth * 2);
+//!         print_tree(&node.children, depth + 1);
+//!     }
+//! }
+//!
+//! fn print_sidebar(subcrates_dir: &std::path::Path) {
+//!     print_tree(&read_crate_tree(subcrates_dir).unwrap(), 0);
+//! }
+//! ```
+
+use super::crate_file::SUBCRATE_SEPARATOR;
+use super::neworder;
+use crate::error::Error;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File extension of the crate files
+const CRATE_EXTENSION: &str = "crate";
+
+/// A crate in the crate hierarchy.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CrateNode {
+    /// The name of the crate (without the names of its parents).
+    pub name: String,
+    /// The nested crates.
+    pub children: Vec<CrateNode>,
+    /// The path of the crate file.
+    ///
+    /// This is `None` if a nested crate exists, but the file of its parent crate doesn't.
+    pub crate_file: Option<PathBuf>,
+}
+
+impl CrateNode {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            children: vec![],
+            crate_file: None,
+        }
+    }
+}
+
+/// Scan the given `Subcrates` directory and return the top-level crates.
+///
+/// The crates are ordered like in the [`neworder.pref`](super::neworder) file in the parent
+/// directory, if present. All other crates are sorted by name (case-insensitively) after them,
+/// like Serato does.
+pub fn read_crate_tree(subcrates_dir: &Path) -> Result<Vec<CrateNode>, Error> {
+    let mut stems = BTreeSet::new();
+    for entry in fs::read_dir(subcrates_dir)? {
+        let path = entry?.path();
+        if !path.is_file() || path.extension() != Some(CRATE_EXTENSION.as_ref()) {
+            continue;
+        }
+        if let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) {
+            stems.insert(stem.to_string());
+        }
+    }
+
+    let order = match subcrates_dir.parent() {
+        Some(serato_dir) => neworder::read_from_dir(serato_dir)?.unwrap_or_default(),
+        None => vec![],
+    };
+
+    let mut nodes = vec![];
+    for stem in &stems {
+        let names = split_crate_names(stem, &stems);
+        let crate_file = subcrates_dir.join(format!("{}.{}", stem, CRATE_EXTENSION));
+        insert_node(&mut nodes, &names, crate_file);
+    }
+    sort_nodes(&mut nodes, "", &order);
+    Ok(nodes)
+}
+
+/// Insert the crate with the given names (including the names of its parents) into the tree.
+fn insert_node(nodes: &mut Vec<CrateNode>, names: &[String], crate_file: PathBuf) {
+    let (name, names) = match names.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+    let index = match nodes.iter().position(|node| node.name == *name) {
+        Some(index) => index,
+        None => {
+            nodes.push(CrateNode::new(name.clone()));
+            nodes.len() - 1
+        }
+    };
+    let node = &mut nodes[index];
+    if names.is_empty() {
+        node.crate_file = Some(crate_file);
+    } else {
+        insert_node(&mut node.children, names, crate_file);
+    }
+}
+
+/// Split the file name of a crate (without extension) into the names of the crate and its
+/// parents.
+///
+/// Crate names may contain single `%` characters, so a run of three `%` characters is ambiguous:
+/// the `%` either belongs to the end of the parent name or to the start of the child name. If
+/// only one of both parent crate files exists, that one is used. Otherwise, the `%` is assumed to
+/// belong to the parent name (e.g. `100%%%Deep` is `100%` and `Deep`).
+fn split_crate_names(stem: &str, stems: &BTreeSet<String>) -> Vec<String> {
+    let bytes = stem.as_bytes();
+    let mut names = vec![];
+    let mut name_start = 0;
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] != b'%' {
+            index += 1;
+            continue;
+        }
+        let run_start = index;
+        while index < bytes.len() && bytes[index] == b'%' {
+            index += 1;
+        }
+        let separator = match index - run_start {
+            0 | 1 => continue,
+            3 if stems.contains(&stem[..run_start]) && !stems.contains(&stem[..=run_start]) => {
+                run_start
+            }
+            run_length => run_start + (run_length - 2).min(1),
+        };
+        names.push(stem[name_start..separator].to_string());
+        name_start = separator + SUBCRATE_SEPARATOR.len();
+    }
+    names.push(stem[name_start..].to_string());
+    names
+}
+
+/// Sort the nodes with the given parent file name prefix recursively.
+fn sort_nodes(nodes: &mut [CrateNode], prefix: &str, order: &[String]) {
+    let stem = |node: &CrateNode| format!("{}{}", prefix, node.name);
+    nodes.sort_by_cached_key(|node| {
+        let stem = stem(node);
+        let position = order.iter().position(|entry| *entry == stem);
+        (position.unwrap_or(order.len()), node.name.to_lowercase())
+    });
+    for node in nodes {
+        let prefix = format!("{}{}", stem(node), SUBCRATE_SEPARATOR);
+        sort_nodes(&mut node.children, &prefix, order);
+    }
+}
+
+#[test]
+fn test_split_crate_names() {
+    let stems: BTreeSet<String> = ["A", "A%", "%B"].iter().map(|s| s.to_string()).collect();
+    let split = |stem| split_crate_names(stem, &stems);
+    assert_eq!(split("House"), vec!["House"]);
+    assert_eq!(split("House%%Deep%%Dub"), vec!["House", "Deep", "Dub"]);
+    assert_eq!(split("100% House"), vec!["100% House"]);
+    assert_eq!(split("100%%%Deep"), vec!["100%", "Deep"]);
+    assert_eq!(split("100%%%%Deep"), vec!["100%", "%Deep"]);
+    assert_eq!(split("%B%%%C"), vec!["%B", "%C"]);
+    assert_eq!(split("A%%%C"), vec!["A%", "C"]);
+}
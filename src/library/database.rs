@@ -1,8 +1,15 @@
 extern crate nom;
 
+use super::parser::{parse_bitrate, parse_bpm, parse_length, parse_sample_rate};
+use super::timestamp;
+use super::Track;
 use crate::error::Error;
 use crate::util::Res;
+use std::fs;
+use std::io;
+use std::io::Read;
 use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
 
 pub type Path = String;
 
@@ -14,7 +21,16 @@ const FIELD_TEXT: u8 = b't';
 const FIELD_U16: u8 = b's';
 const FIELD_U32: u8 = b'u';
 
-#[derive(Debug)]
+/// Name of the flag that marks a track file as missing
+pub(super) const FLAG_MISSING: &[u8] = b"mis";
+/// Name of the flag that marks a track file as corrupt
+const FLAG_CORRUPT: &[u8] = b"crt";
+/// Name of the flag that marks a beatgrid as locked
+pub(super) const FLAG_BEATGRID_LOCKED: &[u8] = b"bgl";
+/// Name of the flag that marks a track as played in the current session
+const FLAG_PLAYED: &[u8] = b"ply";
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum Field {
     Unknown {
         field_type: u8,
@@ -81,6 +97,30 @@ pub enum Field {
     ColumnName(String),
     ColumnWidth(String),
     TrackPath(PathBuf),
+    // Smart crates
+    SmartCrateRule(Vec<Field>),
+    SmartCrateMatchAll(Vec<Field>),
+    SmartCrateLiveUpdate(Vec<Field>),
+    RuleComparison(String),
+    RuleField(u32),
+    RuleValueDate(String),
+    RuleValueInteger(u32),
+    RuleValueText(String),
+}
+
+impl Field {
+    /// Returns the field name as stored in the file, including the type prefix (e.g. `tsng`).
+    pub fn name(&self) -> String {
+        match field_parts(self) {
+            Ok((_, name, _)) if name.len() == 4 => String::from_utf8_lossy(name).into_owned(),
+            Ok((field_type, name, _)) => {
+                let mut desc = vec![field_type];
+                desc.extend_from_slice(name);
+                String::from_utf8_lossy(&desc).into_owned()
+            }
+            Err(_) => String::new(),
+        }
+    }
 }
 
 fn take_field_type(input: &[u8]) -> Res<&[u8], u8> {
@@ -103,16 +143,52 @@ fn take_field_content(input: &[u8]) -> Res<&[u8], &[u8]> {
     nom::multi::length_data(take_field_length)(input)
 }
 
-fn take_u16_bytes(input: &[u8]) -> Res<&[u8], Vec<u16>> {
-    nom::multi::many0(nom::number::complete::be_u16)(input)
+/// Decode UTF-16 text, which is big-endian unless it starts with a little-endian BOM. A leading
+/// BOM is removed.
+///
+/// Unpaired surrogates and a trailing byte (if the number of bytes is odd) are replaced with
+/// `U+FFFD`, or make decoding fail if `strict` is set.
+pub(super) fn decode_u16_text(input: &[u8], strict: bool) -> Option<String> {
+    let (input, is_little_endian) = match input {
+        [0xFE, 0xFF, input @ ..] => (input, false),
+        [0xFF, 0xFE, input @ ..] => (input, true),
+        _ => (input, false),
+    };
+    let chunks = input.chunks_exact(2);
+    let is_truncated = !chunks.remainder().is_empty();
+    if strict && is_truncated {
+        return None;
+    }
+    let units = chunks.map(|chunk| {
+        let bytes = [chunk[0], chunk[1]];
+        if is_little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        }
+    });
+    let mut text = String::with_capacity(input.len() / 2);
+    for c in std::char::decode_utf16(units) {
+        match c {
+            Ok(c) => text.push(c),
+            Err(_) if strict => return None,
+            Err(_) => text.push(std::char::REPLACEMENT_CHARACTER),
+        }
+    }
+    if is_truncated {
+        text.push(std::char::REPLACEMENT_CHARACTER);
+    }
+    Some(text)
 }
 
-fn parse_u16_text(input: &[u8]) -> Res<&[u8], String> {
-    let (input, bytes) = nom::combinator::all_consuming(take_u16_bytes)(input)?;
-    let text = std::char::decode_utf16(bytes.iter().cloned())
-        .map(|r| r.unwrap_or(std::char::REPLACEMENT_CHARACTER))
-        .collect::<String>();
-    Ok((input, text))
+fn parse_u16_text(input: &[u8], strict: bool) -> Res<&[u8], String> {
+    match decode_u16_text(input, strict) {
+        Some(text) => Ok((&input[input.len()..], text)),
+        None => Err(nom::Err::Error(nom::error::make_error(
+            input,
+            nom::error::ErrorKind::Verify,
+        ))),
+    }
 }
 
 fn parse_bool(input: &[u8]) -> Res<&[u8], bool> {
@@ -121,7 +197,12 @@ fn parse_bool(input: &[u8]) -> Res<&[u8], bool> {
     Ok((input, value))
 }
 
-fn parse_field<'a, 'b>(input: &'a [u8], name: &'b [u8], field_type: u8) -> Res<&'a [u8], Field> {
+fn parse_field<'a>(
+    input: &'a [u8],
+    name: &[u8],
+    field_type: u8,
+    strict: bool,
+) -> Res<&'a [u8], Field> {
     match field_type {
         FIELD_BOOL => {
             let (input, value) = nom::combinator::all_consuming(parse_bool)(input)?;
@@ -161,6 +242,8 @@ fn parse_field<'a, 'b>(input: &'a [u8], name: &'b [u8], field_type: u8) -> Res<&
                 nom::combinator::all_consuming(nom::number::complete::be_u32)(input)?;
             let field = match name {
                 b"add" => Field::DateAdded(value),
+                b"rkt" => Field::RuleField(value),
+                b"rpt" => Field::RuleValueInteger(value),
                 b"tme" => Field::FileTime(value),
                 //b"lbl" => ???
                 //b"fsb" => ???
@@ -174,7 +257,7 @@ fn parse_field<'a, 'b>(input: &'a [u8], name: &'b [u8], field_type: u8) -> Res<&
             Ok((input, field))
         }
         FIELD_PATH => {
-            let (input, path) = parse_u16_text(input)?;
+            let (input, path) = parse_u16_text(input, strict)?;
             let path = PathBuf::from(path);
             let field = match name {
                 b"fil" => Field::FilePath(path),
@@ -187,7 +270,7 @@ fn parse_field<'a, 'b>(input: &'a [u8], name: &'b [u8], field_type: u8) -> Res<&
             Ok((input, field))
         }
         FIELD_TEXT => {
-            let (input, text) = parse_u16_text(input)?;
+            let (input, text) = parse_u16_text(input, strict)?;
             let field = match name {
                 b"add" => Field::DateAddedStr(text),
                 b"alb" => Field::Album(text),
@@ -201,6 +284,9 @@ fn parse_field<'a, 'b>(input: &'a [u8], name: &'b [u8], field_type: u8) -> Res<&
                 b"key" => Field::Key(text),
                 b"lbl" => Field::Label(text),
                 b"len" => Field::Length(text),
+                b"rft" => Field::RuleComparison(text),
+                b"rpt" => Field::RuleValueText(text),
+                b"rtt" => Field::RuleValueDate(text),
                 b"siz" => Field::FileSize(text),
                 b"smp" => Field::SampleRate(text),
                 b"sng" => Field::SongTitle(text),
@@ -217,7 +303,8 @@ fn parse_field<'a, 'b>(input: &'a [u8], name: &'b [u8], field_type: u8) -> Res<&
             Ok((input, field))
         }
         FIELD_CONTAINER => {
-            let (input, fields) = nom::combinator::all_consuming(take_fields)(input)?;
+            let (input, fields) =
+                nom::combinator::all_consuming(|i| take_fields(i, strict))(input)?;
             let field = match name {
                 b"srt" => Field::Sorting(fields),
                 b"trk" => Field::Track(fields),
@@ -230,10 +317,16 @@ fn parse_field<'a, 'b>(input: &'a [u8], name: &'b [u8], field_type: u8) -> Res<&
             Ok((input, field))
         }
         FIELD_CONTAINER_R => {
-            let (input, fields) = nom::combinator::all_consuming(take_fields)(input)?;
-            let field = Field::UnknownContainerRField {
-                name: name.to_vec(),
-                fields,
+            let (input, fields) =
+                nom::combinator::all_consuming(|i| take_fields(i, strict))(input)?;
+            let field = match name {
+                b"art" => Field::SmartCrateMatchAll(fields),
+                b"lut" => Field::SmartCrateLiveUpdate(fields),
+                b"urt" => Field::SmartCrateRule(fields),
+                _ => Field::UnknownContainerRField {
+                    name: name.to_vec(),
+                    fields,
+                },
             };
             Ok((input, field))
         }
@@ -252,26 +345,1576 @@ fn parse_field<'a, 'b>(input: &'a [u8], name: &'b [u8], field_type: u8) -> Res<&
     }
 }
 
-fn take_field(input: &[u8]) -> Res<&[u8], Field> {
+fn take_field(input: &[u8], strict: bool) -> Res<&[u8], Field> {
     let (input, desc) = take_field_desc(input)?;
     let (input, content) = take_field_content(input)?;
-    let (_, field) = match desc {
+    let (name, field_type) = match desc {
         // Special case: `vrsn` is a text field but begins with `v`
-        b"vrsn" => parse_field(content, desc, FIELD_TEXT)?,
+        b"vrsn" => (desc, FIELD_TEXT),
         _ => {
             let (desc, typ) = take_field_type(desc)?;
             let (_, name) = take_field_name(desc)?;
-            parse_field(content, name, typ)?
+            (name, typ)
+        }
+    };
+
+    // Unless parsing strictly, fields with malformed content (e.g. a boolean with more than one
+    // byte) are kept as unknown fields instead of failing to parse the whole database.
+    // Unknown fields whose content would change when writing them back (e.g. text with an odd
+    // number of bytes) are kept as raw content, so that fields added by newer Serato versions
+    // survive a round trip.
+    let field = match parse_field(content, name, field_type, strict) {
+        Ok((_, field)) if is_unknown_value(&field) && !encodes_to(&field, content) => {
+            raw_field(field_type, name, content)
         }
+        Ok((_, field)) => field,
+        Err(err) if strict => return Err(err),
+        Err(_) => raw_field(field_type, name, content),
     };
     Ok((input, field))
 }
 
-fn take_fields(input: &[u8]) -> Res<&[u8], Vec<Field>> {
-    nom::multi::many1(take_field)(input)
+fn raw_field(field_type: u8, name: &[u8], content: &[u8]) -> Field {
+    Field::Unknown {
+        field_type,
+        name: name.to_vec(),
+        content: content.to_vec(),
+    }
+}
+
+/// Returns `true` for fields with an unknown name that aren't containers.
+fn is_unknown_value(field: &Field) -> bool {
+    matches!(
+        field,
+        Field::UnknownBoolean { .. }
+            | Field::UnknownPathField { .. }
+            | Field::UnknownU16Field { .. }
+            | Field::UnknownU32Field { .. }
+            | Field::UnknownTextField { .. }
+    )
+}
+
+/// Returns `true` if the field is serialized to the given content.
+fn encodes_to(field: &Field, content: &[u8]) -> bool {
+    field_parts(field).is_ok_and(|(_, _, encoded)| encoded == content)
 }
 
+fn take_fields(input: &[u8], strict: bool) -> Res<&[u8], Vec<Field>> {
+    nom::multi::many1(|i| take_field(i, strict))(input)
+}
+
+/// Parse the fields of a `database V2` or crate file.
+///
+/// Text is decoded leniently (see [`parse_strict`]): Unpaired surrogates and the trailing byte of
+/// text with an odd number of bytes are replaced with `U+FFFD`, so the text might differ when it
+/// is written back. Other fields with malformed content are kept as [`Field::Unknown`].
+///
+/// Use [`Database::parse`] to preserve the original content of such text, so that writing an
+/// unmodified database produces identical data, and to detect truncated text (see
+/// [`TrackRecord::truncated_fields`]).
 pub fn parse(input: &[u8]) -> Result<Vec<Field>, Error> {
-    let (_, fields) = nom::combinator::all_consuming(take_fields)(input)?;
+    let (_, fields) = nom::combinator::all_consuming(|i| take_fields(i, false))(input)?;
+    Ok(fields)
+}
+
+/// Parse the fields like [`parse`], but fail on malformed field content (e.g. text with unpaired
+/// surrogates) instead of replacing it.
+pub fn parse_strict(input: &[u8]) -> Result<Vec<Field>, Error> {
+    let (_, fields) = nom::combinator::all_consuming(|i| take_fields(i, true))(input)?;
     Ok(fields)
 }
+
+/// Returns the field type, the field name and the serialized content of a field.
+fn field_parts(field: &Field) -> Result<(u8, &[u8], Vec<u8>), Error> {
+    let text = |name, text: &str| (FIELD_TEXT, name, encode_u16_text(text));
+    let path = |name, path: &PathBuf| (FIELD_PATH, name, encode_u16_text(&path.to_string_lossy()));
+    let boolean = |name, value: bool| (FIELD_BOOL, name, vec![u8::from(value)]);
+    let number = |name, value: u32| (FIELD_U32, name, value.to_be_bytes().to_vec());
+    let parts = match field {
+        Field::Unknown {
+            field_type,
+            name,
+            content,
+        } => (*field_type, name.as_slice(), content.clone()),
+        Field::UnknownBoolean { name, value } => boolean(name.as_slice(), *value),
+        Field::UnknownContainerField { name, fields } => {
+            (FIELD_CONTAINER, name.as_slice(), encode_fields(fields)?)
+        }
+        Field::UnknownContainerRField { name, fields } => {
+            (FIELD_CONTAINER_R, name.as_slice(), encode_fields(fields)?)
+        }
+        Field::UnknownPathField { name, path: p } => path(name.as_slice(), p),
+        Field::UnknownU16Field { name, value } => {
+            (FIELD_U16, name.as_slice(), value.to_be_bytes().to_vec())
+        }
+        Field::UnknownU32Field { name, value } => number(name.as_slice(), *value),
+        Field::UnknownTextField { name, text: t } => text(name.as_slice(), t),
+        Field::Album(t) => text(b"alb", t),
+        Field::Artist(t) => text(b"art", t),
+        Field::BPM(t) => text(b"bpm", t),
+        Field::BeatgridLocked(value) => boolean(b"bgl", *value),
+        Field::Bitrate(t) => text(b"bit", t),
+        Field::Comment(t) => text(b"com", t),
+        Field::Composer(t) => text(b"cmp", t),
+        Field::DateAdded(value) => number(b"add", *value),
+        Field::DateAddedStr(t) => text(b"add", t),
+        Field::FilePath(p) => path(b"fil", p),
+        Field::FileSize(t) => text(b"siz", t),
+        Field::FileTime(value) => number(b"tme", *value),
+        Field::FileType(t) => text(b"typ", t),
+        Field::Genre(t) => text(b"gen", t),
+        Field::Grouping(t) => text(b"grp", t),
+        Field::Key(t) => text(b"key", t),
+        Field::Label(t) => text(b"lbl", t),
+        Field::Length(t) => text(b"len", t),
+        Field::Missing(value) => boolean(b"mis", *value),
+        Field::SampleRate(t) => text(b"smp", t),
+        Field::SongTitle(t) => text(b"sng", t),
+        Field::Track(fields) => (FIELD_CONTAINER, &b"trk"[..], encode_fields(fields)?),
+        Field::Version(t) => text(b"vrsn", t),
+        Field::Year(t) => text(b"tyr", t),
+        Field::Sorting(fields) => (FIELD_CONTAINER, &b"srt"[..], encode_fields(fields)?),
+        Field::ReverseOrder(value) => boolean(b"rev", *value),
+        Field::ColumnTitle(fields) => (FIELD_CONTAINER, &b"vct"[..], encode_fields(fields)?),
+        Field::ColumnName(t) => text(b"vcn", t),
+        Field::ColumnWidth(t) => text(b"vcw", t),
+        Field::TrackPath(p) => path(b"trk", p),
+        Field::SmartCrateRule(fields) => (FIELD_CONTAINER_R, &b"urt"[..], encode_fields(fields)?),
+        Field::SmartCrateMatchAll(fields) => {
+            (FIELD_CONTAINER_R, &b"art"[..], encode_fields(fields)?)
+        }
+        Field::SmartCrateLiveUpdate(fields) => {
+            (FIELD_CONTAINER_R, &b"lut"[..], encode_fields(fields)?)
+        }
+        Field::RuleComparison(t) => text(b"rft", t),
+        Field::RuleField(value) => number(b"rkt", *value),
+        Field::RuleValueDate(t) => text(b"rtt", t),
+        Field::RuleValueInteger(value) => number(b"rpt", *value),
+        Field::RuleValueText(t) => text(b"rpt", t),
+    };
+    Ok(parts)
+}
+
+/// Encode text as UTF-16 (big-endian) without BOM.
+fn encode_u16_text(text: &str) -> Vec<u8> {
+    text.encode_utf16().flat_map(|c| c.to_be_bytes()).collect()
+}
+
+fn encode_fields(fields: &[Field]) -> Result<Vec<u8>, Error> {
+    let mut data = vec![];
+    write(&mut data, fields)?;
+    Ok(data)
+}
+
+fn write_field(writer: impl io::Write, field: &Field) -> Result<usize, Error> {
+    let (field_type, name, content) = field_parts(field)?;
+    write_field_content(writer, field_type, name, &content)
+}
+
+fn write_field_content(
+    mut writer: impl io::Write,
+    field_type: u8,
+    name: &[u8],
+    content: &[u8],
+) -> Result<usize, Error> {
+    // Special case: `vrsn` is a text field but begins with `v`
+    let mut bytes_written = if name.len() == 4 {
+        writer.write(name)?
+    } else {
+        writer.write(&[field_type])? + writer.write(name)?
+    };
+    let length = content.len() as u32;
+    bytes_written += writer.write(&length.to_be_bytes())?;
+    bytes_written += writer.write(content)?;
+    Ok(bytes_written)
+}
+
+/// Serialize the fields in the format of the `database V2` and crate files.
+///
+/// Unknown fields are written back verbatim.
+pub fn write(mut writer: impl io::Write, fields: &[Field]) -> Result<usize, Error> {
+    let mut bytes_written = 0;
+    for field in fields {
+        bytes_written += write_field(&mut writer, field)?;
+    }
+    Ok(bytes_written)
+}
+
+/// Parse the `database V2` data and return all track records.
+///
+/// Fields that are unknown or can't be parsed are skipped, so that a single record which isn't
+/// fully understood doesn't prevent reading the rest of the library.
+pub fn parse_tracks(input: &[u8]) -> Result<Vec<Track>, Error> {
+    parse(input)?
+        .into_iter()
+        .filter_map(|field| match field {
+            Field::Track(fields) => Some(Track::from_fields(fields)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// File types of video files that can be added to the library with Serato Video.
+const VIDEO_FILE_TYPES: [&str; 10] = [
+    "3gp", "avi", "divx", "flv", "m4v", "mkv", "mov", "mp4", "mpg", "wmv",
+];
+
+/// The kind of media file that a track record refers to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrackKind {
+    Audio,
+    /// A video file, which can only be played with Serato Video.
+    Video,
+}
+
+/// A track record (`otrk`) of the `database V2` file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrackRecord {
+    /// The fields of the record in their original order.
+    pub fields: Vec<Field>,
+    /// The decoded fields that don't encode to their original content (e.g. text with an odd
+    /// number of bytes) together with that content, which is written instead while the field
+    /// is unchanged.
+    original: Vec<(Field, Vec<u8>)>,
+}
+
+impl TrackRecord {
+    /// Create a track record with the given fields.
+    pub fn new(fields: Vec<Field>) -> Self {
+        Self {
+            fields,
+            original: vec![],
+        }
+    }
+
+    /// Create a track record from fields that have been decoded from the given content and keep
+    /// the original content of lossily decoded fields.
+    fn with_original(fields: Vec<Field>, content: &[u8]) -> Self {
+        let original = fields
+            .iter()
+            .zip(raw_fields(content))
+            .filter(|(field, (_, content))| !encodes_to(field, content))
+            .map(|(field, (_, content))| (field.clone(), content.to_vec()))
+            .collect();
+        Self { fields, original }
+    }
+
+    /// Serialize the fields of the record.
+    ///
+    /// Unchanged fields that have been decoded lossily are written with their original content.
+    fn encode(&self) -> Result<Vec<u8>, Error> {
+        let mut unused: Vec<_> = self.original.iter().collect();
+        let mut data = vec![];
+        for field in &self.fields {
+            let (field_type, name, content) = field_parts(field)?;
+            match unused.iter().position(|(original, _)| original == field) {
+                Some(index) => {
+                    let (_, original) = unused.remove(index);
+                    write_field_content(&mut data, field_type, name, original)?;
+                }
+                None => {
+                    write_field_content(&mut data, field_type, name, &content)?;
+                }
+            }
+        }
+        Ok(data)
+    }
+
+    fn find<'a, T>(&'a self, f: impl Fn(&'a Field) -> Option<T>) -> Option<T> {
+        self.fields.iter().find_map(f)
+    }
+
+    /// Returns the file path of the track as stored in the database (see
+    /// [`PathResolver`](super::path::PathResolver)), or an empty string if it's missing.
+    pub fn path(&self) -> &str {
+        self.file_path()
+            .and_then(std::path::Path::to_str)
+            .unwrap_or_default()
+    }
+
+    /// Returns the file type (e.g. `mp3`).
+    pub fn file_type(&self) -> Option<&str> {
+        self.find(|field| match field {
+            Field::FileType(text) => Some(text.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Returns whether the track is an audio or a video file.
+    ///
+    /// The kind is derived from the file type, or from the file extension if the record doesn't
+    /// contain the file type. Additional fields in records of video files haven't been
+    /// reverse-engineered yet, but are kept as [unknown fields](TrackRecord::unknown_fields).
+    pub fn kind(&self) -> TrackKind {
+        let file_type = self.file_type().map(str::to_lowercase).or_else(|| {
+            self.file_path()
+                .and_then(std::path::Path::extension)
+                .map(|extension| extension.to_string_lossy().to_lowercase())
+        });
+        match file_type {
+            Some(file_type) if VIDEO_FILE_TYPES.contains(&file_type.as_str()) => TrackKind::Video,
+            _ => TrackKind::Audio,
+        }
+    }
+
+    /// Returns the song title.
+    pub fn title(&self) -> Option<&str> {
+        self.find(|field| match field {
+            Field::SongTitle(text) => Some(text.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn artist(&self) -> Option<&str> {
+        self.find(|field| match field {
+            Field::Artist(text) => Some(text.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn album(&self) -> Option<&str> {
+        self.find(|field| match field {
+            Field::Album(text) => Some(text.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn genre(&self) -> Option<&str> {
+        self.find(|field| match field {
+            Field::Genre(text) => Some(text.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn comment(&self) -> Option<&str> {
+        self.find(|field| match field {
+            Field::Comment(text) => Some(text.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn composer(&self) -> Option<&str> {
+        self.find(|field| match field {
+            Field::Composer(text) => Some(text.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn grouping(&self) -> Option<&str> {
+        self.find(|field| match field {
+            Field::Grouping(text) => Some(text.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn label(&self) -> Option<&str> {
+        self.find(|field| match field {
+            Field::Label(text) => Some(text.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Returns the musical key as shown in Serato (e.g. `Bb` or `C#m`).
+    pub fn key(&self) -> Option<&str> {
+        self.find(|field| match field {
+            Field::Key(text) => Some(text.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Returns the BPM value (e.g. `126.00` in the database).
+    pub fn bpm(&self) -> Option<f64> {
+        self.find(|field| match field {
+            Field::BPM(text) => parse_bpm(text),
+            _ => None,
+        })
+    }
+
+    /// Returns the release year.
+    pub fn year(&self) -> Option<u16> {
+        self.find(|field| match field {
+            Field::Year(text) => text.trim().parse().ok(),
+            _ => None,
+        })
+    }
+
+    /// Returns the bitrate in kbps (e.g. `320.0kbps` in the database).
+    pub fn bitrate_kbps(&self) -> Option<f64> {
+        self.find(|field| match field {
+            Field::Bitrate(text) => parse_bitrate(text),
+            _ => None,
+        })
+    }
+
+    /// Returns the sample rate in Hz (e.g. `44.1k` in the database).
+    pub fn sample_rate_hz(&self) -> Option<u32> {
+        self.find(|field| match field {
+            Field::SampleRate(text) => parse_sample_rate(text),
+            _ => None,
+        })
+    }
+
+    /// Returns the track length (e.g. `06:22.93` in the database).
+    pub fn length(&self) -> Option<Duration> {
+        self.find(|field| match field {
+            Field::Length(text) => parse_length(text),
+            _ => None,
+        })
+    }
+
+    /// Returns the file size as shown in Serato (e.g. `14.6MB`).
+    pub fn file_size(&self) -> Option<&str> {
+        self.find(|field| match field {
+            Field::FileSize(text) => Some(text.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Returns the time the track was added to the library as Unix timestamp.
+    ///
+    /// If the numeric field is missing or `0`, the text field is used instead.
+    pub fn date_added(&self) -> Option<u64> {
+        self.find(|field| match field {
+            Field::DateAdded(timestamp) if *timestamp != 0 => Some(u64::from(*timestamp)),
+            _ => None,
+        })
+        .or_else(|| {
+            self.find(|field| match field {
+                Field::DateAddedStr(text) => text.trim().parse().ok().filter(|&t: &u64| t != 0),
+                _ => None,
+            })
+        })
+    }
+
+    /// Returns the time the track was added to the library.
+    pub fn date_added_system_time(&self) -> Option<SystemTime> {
+        self.date_added().and_then(timestamp::to_system_time)
+    }
+
+    /// Returns the modification time of the file as Unix timestamp.
+    pub fn file_time(&self) -> Option<u64> {
+        self.find(|field| match field {
+            Field::FileTime(timestamp) if *timestamp != 0 => Some(u64::from(*timestamp)),
+            _ => None,
+        })
+    }
+
+    /// Returns the modification time of the file.
+    pub fn file_system_time(&self) -> Option<SystemTime> {
+        self.file_time().and_then(timestamp::to_system_time)
+    }
+
+    /// Returns `true` if Serato couldn't find the track file.
+    pub fn missing(&self) -> bool {
+        self.flag(FLAG_MISSING)
+    }
+
+    /// Returns `true` if Serato marked the track file as corrupt.
+    pub fn corrupt(&self) -> bool {
+        self.flag(FLAG_CORRUPT)
+    }
+
+    /// Returns `true` if the beatgrid is locked.
+    pub fn beatgrid_locked(&self) -> bool {
+        self.flag(FLAG_BEATGRID_LOCKED)
+    }
+
+    /// Returns `true` if the track was played in the current session.
+    pub fn played(&self) -> bool {
+        self.flag(FLAG_PLAYED)
+    }
+
+    fn flag(&self, name: &[u8]) -> bool {
+        self.find(|field| flag_value(field).filter(|&(n, _)| n == name))
+            .is_some_and(|(_, value)| value)
+    }
+
+    /// Returns the names and values of all boolean fields.
+    pub(super) fn flags(&self) -> impl Iterator<Item = (&[u8], bool)> {
+        self.fields.iter().filter_map(flag_value)
+    }
+
+    /// Returns the text fields that have been read with an odd number of bytes.
+    ///
+    /// Serato never writes such fields, so they indicate a corrupted database. Their trailing byte
+    /// has been replaced with `U+FFFD`, but the original content is written back unless the field
+    /// is modified.
+    pub fn truncated_fields(&self) -> impl Iterator<Item = &Field> {
+        self.original
+            .iter()
+            .filter(|(_, content)| content.len() % 2 != 0)
+            .map(|(field, _)| field)
+    }
+
+    /// Returns all fields whose meaning is unknown.
+    pub fn unknown_fields(&self) -> impl Iterator<Item = &Field> {
+        self.fields.iter().filter(|field| {
+            matches!(
+                field,
+                Field::Unknown { .. }
+                    | Field::UnknownBoolean { .. }
+                    | Field::UnknownContainerField { .. }
+                    | Field::UnknownContainerRField { .. }
+                    | Field::UnknownPathField { .. }
+                    | Field::UnknownU16Field { .. }
+                    | Field::UnknownU32Field { .. }
+                    | Field::UnknownTextField { .. }
+            )
+        })
+    }
+
+    /// Returns the file path of the track.
+    pub fn file_path(&self) -> Option<&std::path::Path> {
+        self.fields.iter().find_map(|field| match field {
+            Field::FilePath(path) => Some(path.as_path()),
+            _ => None,
+        })
+    }
+
+    /// Set the file path of the track.
+    pub fn set_file_path(&mut self, path: impl Into<PathBuf>) {
+        self.set_field(
+            |field| matches!(field, Field::FilePath(_)),
+            Some(Field::FilePath(path.into())),
+        );
+    }
+
+    /// Set whether the track file is missing.
+    pub fn set_missing(&mut self, missing: bool) {
+        self.set_flag(FLAG_MISSING, missing);
+    }
+
+    /// Set whether the track file is corrupt.
+    pub fn set_corrupt(&mut self, corrupt: bool) {
+        self.set_flag(FLAG_CORRUPT, corrupt);
+    }
+
+    /// Set whether the beatgrid is locked.
+    pub fn set_beatgrid_locked(&mut self, locked: bool) {
+        self.set_flag(FLAG_BEATGRID_LOCKED, locked);
+    }
+
+    /// Set whether the track was played in the current session.
+    pub fn set_played(&mut self, played: bool) {
+        self.set_flag(FLAG_PLAYED, played);
+    }
+
+    /// Replace the value of the flag in place, or append the flag if there is none.
+    ///
+    /// Flags with more than one byte keep their size, so that Serato reads them like before.
+    fn set_flag(&mut self, name: &[u8], value: bool) {
+        let existing = self
+            .fields
+            .iter_mut()
+            .find(|field| flag_value(field).is_some_and(|(n, _)| n == name));
+        if let Some(Field::Unknown { content, .. }) = existing {
+            let length = content.len().max(1);
+            *content = vec![0; length];
+            content[length - 1] = u8::from(value);
+            return;
+        }
+        let field = match name {
+            FLAG_MISSING => Field::Missing(value),
+            FLAG_BEATGRID_LOCKED => Field::BeatgridLocked(value),
+            _ => Field::UnknownBoolean {
+                name: name.to_vec(),
+                value,
+            },
+        };
+        self.set_field(
+            |f| flag_value(f).is_some_and(|(n, _)| n == name),
+            Some(field),
+        );
+    }
+
+    /// Set the time the track was added to the library.
+    ///
+    /// Both the numeric and the text field are updated, if present. Fails if the time is before
+    /// the Unix epoch or doesn't fit into the 32-bit field (see [`timestamp::to_u32`]).
+    pub fn set_date_added(&mut self, time: SystemTime) -> Result<(), Error> {
+        let timestamp = timestamp::try_from_system_time(time)?;
+        let value = timestamp::to_u32(timestamp)?;
+        self.set_field(
+            |field| matches!(field, Field::DateAdded(_)),
+            Some(Field::DateAdded(value)),
+        );
+        if let Some(text) = self.fields.iter_mut().find_map(|field| match field {
+            Field::DateAddedStr(text) => Some(text),
+            _ => None,
+        }) {
+            *text = timestamp.to_string();
+        }
+        Ok(())
+    }
+
+    /// Set or remove the grouping of the track.
+    pub fn set_grouping(&mut self, grouping: Option<String>) {
+        self.set_field(
+            |field| matches!(field, Field::Grouping(_)),
+            grouping.map(Field::Grouping),
+        );
+    }
+
+    /// Replace the first field that matches in place (so that the field order is preserved),
+    /// or append the field if there is none. If `field` is `None`, all matching fields are
+    /// removed.
+    fn set_field(&mut self, matches: impl Fn(&Field) -> bool, field: Option<Field>) {
+        match field {
+            Some(field) => match self.fields.iter_mut().find(|f| matches(f)) {
+                Some(existing) => *existing = field,
+                None => self.fields.push(field),
+            },
+            None => self.fields.retain(|f| !matches(f)),
+        }
+    }
+}
+
+/// Returns the name and the value of a boolean field.
+///
+/// Serato writes flags as a single byte. Flags with more bytes (e.g. a 4-byte integer) are kept as
+/// raw fields by the parser and are `true` if any byte is non-zero.
+fn flag_value(field: &Field) -> Option<(&[u8], bool)> {
+    match field {
+        Field::Missing(value) => Some((FLAG_MISSING, *value)),
+        Field::BeatgridLocked(value) => Some((FLAG_BEATGRID_LOCKED, *value)),
+        Field::UnknownBoolean { name, value } => Some((name, *value)),
+        Field::Unknown {
+            field_type: FIELD_BOOL,
+            name,
+            content,
+        } => Some((name, decode_bool(content))),
+        _ => None,
+    }
+}
+
+/// Decode the content of a boolean field, which might be longer than a single byte.
+fn decode_bool(content: &[u8]) -> bool {
+    content.iter().any(|&byte| byte != 0)
+}
+
+/// Decode the content of a text field leniently like [`parse`] does.
+fn decode_text(content: &[u8]) -> Option<String> {
+    decode_u16_text(content, false)
+}
+
+/// A top-level entry of the `database V2` file.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DatabaseEntry {
+    Track(TrackRecord),
+    /// Any other field (e.g. the version), which is written back verbatim.
+    Other(Field),
+}
+
+/// The contents of a `database V2` file, which can be modified and written back.
+///
+/// **Back up the database before overwriting it!** Serato might refuse to load a database
+/// that it doesn't understand, and all crates and history entries reference the tracks by file
+/// path. Unknown fields and the order of records and fields are preserved, so that writing an
+/// unmodified database results in the same data.
+///
+/// ```
+/// use triseratops::library::database::Database;
+///
+/// fn relocate(path: &std::path::Path) -> Result<(), triseratops::error::Error> {
+///     let mut database = Database::read_from_path(path)?;
+///     for track in database.tracks_mut() {
+///         let new_path = track
+///             .file_path()
+///             .and_then(|path| path.strip_prefix("Music/Old").ok())
+///             .map(|path| std::path::Path::new("Music/New").join(path));
+///         if let Some(new_path) = new_path {
+///             track.set_file_path(new_path);
+///             track.set_missing(false);
+///         }
+///     }
+///     database.write_to_path(path)
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Database {
+    pub entries: Vec<DatabaseEntry>,
+}
+
+impl Database {
+    /// Parse the data of a `database V2` file.
+    pub fn parse(input: &[u8]) -> Result<Self, Error> {
+        Ok(Self::from_fields(parse(input)?, input))
+    }
+
+    /// Parse the data like [`Database::parse`], but fail on malformed field content (see
+    /// [`parse_strict`]).
+    pub fn parse_strict(input: &[u8]) -> Result<Self, Error> {
+        Ok(Self::from_fields(parse_strict(input)?, input))
+    }
+
+    fn from_fields(fields: Vec<Field>, input: &[u8]) -> Self {
+        let entries = fields
+            .into_iter()
+            .zip(raw_fields(input))
+            .map(|(field, (_, content))| match field {
+                Field::Track(fields) => {
+                    DatabaseEntry::Track(TrackRecord::with_original(fields, content))
+                }
+                field => DatabaseEntry::Other(field),
+            })
+            .collect();
+        Self { entries }
+    }
+
+    /// Read and parse the `database V2` file at the given path.
+    pub fn read_from_path(path: impl AsRef<std::path::Path>) -> Result<Self, Error> {
+        let data = fs::read(path)?;
+        Self::parse(&data)
+    }
+
+    /// Returns the database version (e.g. `2.0/Serato Scratch LIVE Database`).
+    pub fn version(&self) -> Option<&str> {
+        self.entries.iter().find_map(|entry| match entry {
+            DatabaseEntry::Other(Field::Version(version)) => Some(version.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Returns the track records.
+    pub fn tracks(&self) -> impl Iterator<Item = &TrackRecord> {
+        self.entries.iter().filter_map(|entry| match entry {
+            DatabaseEntry::Track(track) => Some(track),
+            DatabaseEntry::Other(_) => None,
+        })
+    }
+
+    /// Returns the track records for modification.
+    pub fn tracks_mut(&mut self) -> impl Iterator<Item = &mut TrackRecord> {
+        self.entries.iter_mut().filter_map(|entry| match entry {
+            DatabaseEntry::Track(track) => Some(track),
+            DatabaseEntry::Other(_) => None,
+        })
+    }
+
+    /// Returns the track record with the given file path for modification.
+    pub fn track_mut(
+        &mut self,
+        file_path: impl AsRef<std::path::Path>,
+    ) -> Option<&mut TrackRecord> {
+        let file_path = file_path.as_ref();
+        self.tracks_mut()
+            .find(|track| track.file_path() == Some(file_path))
+    }
+
+    /// Serialize the database in the same format as Serato DJ.
+    ///
+    /// Text that has been decoded lossily is written with its original content unless it has
+    /// been modified.
+    pub fn write(&self, mut writer: impl io::Write) -> Result<usize, Error> {
+        let mut bytes_written = 0;
+        for entry in &self.entries {
+            bytes_written += match entry {
+                DatabaseEntry::Track(track) => {
+                    write_field_content(&mut writer, FIELD_CONTAINER, b"trk", &track.encode()?)?
+                }
+                DatabaseEntry::Other(field) => write_field(&mut writer, field)?,
+            };
+        }
+        Ok(bytes_written)
+    }
+
+    /// Write the database to the file at the given path, replacing its contents.
+    ///
+    /// The data is serialized before the file is opened, so that the file isn't truncated if
+    /// serialization fails.
+    pub fn write_to_path(&self, path: impl AsRef<std::path::Path>) -> Result<(), Error> {
+        let mut data = vec![];
+        self.write(&mut data)?;
+        fs::write(path, data)?;
+        Ok(())
+    }
+}
+
+/// Splits off the next field and returns its descriptor (e.g. `tsng`) and its raw content.
+fn take_raw_field(input: &[u8]) -> Res<&[u8], (&[u8], &[u8])> {
+    let (input, desc) = take_field_desc(input)?;
+    let (input, content) = take_field_content(input)?;
+    Ok((input, (desc, content)))
+}
+
+/// Returns the descriptors and raw contents of the fields, stopping at the first malformed one.
+fn raw_fields(mut input: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    std::iter::from_fn(move || {
+        let (remaining, field) = take_raw_field(input).ok()?;
+        input = remaining;
+        Some(field)
+    })
+}
+
+/// A borrowed view of a track record (`otrk`) that doesn't decode or copy any data until it is
+/// accessed.
+///
+/// Use [`track_record_refs`] to filter the records of a large database before converting the
+/// interesting ones into owned records (see [`TrackRecordRef::to_owned`]).
+///
+/// ```
+/// use triseratops::library::database::{track_record_refs, TrackRecord};
+///
+/// fn missing_tracks(data: &[u8]) -> Vec<TrackRecord> {
+///     track_record_refs(data)
+///         .filter_map(Result::ok)
+///         .filter(|track| track.missing())
+///         .filter_map(|track| track.to_owned().ok())
+///         .collect()
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TrackRecordRef<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> TrackRecordRef<'a> {
+    /// Create a view of the content of a track record.
+    ///
+    /// Only the field boundaries are checked, the field contents aren't decoded.
+    pub fn new(data: &'a [u8]) -> Result<Self, Error> {
+        nom::combinator::all_consuming(nom::multi::many0(take_raw_field))(data)?;
+        Ok(Self { data })
+    }
+
+    /// Returns the raw content of the record.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Returns the descriptors (e.g. `tsng`) and raw contents of all fields.
+    pub fn raw_fields(&self) -> impl Iterator<Item = (&'a [u8], &'a [u8])> {
+        raw_fields(self.data)
+    }
+
+    /// Returns the raw content of the first field with the given descriptor (e.g. `tsng`).
+    pub fn raw_field(&self, desc: &[u8]) -> Option<&'a [u8]> {
+        self.raw_fields()
+            .find(|(field_desc, _)| *field_desc == desc)
+            .map(|(_, content)| content)
+    }
+
+    /// Returns the descriptors of the text fields with an odd number of bytes (see
+    /// [`TrackRecord::truncated_fields`]).
+    pub fn truncated_fields(&self) -> impl Iterator<Item = &'a [u8]> {
+        self.raw_fields()
+            .filter(|(desc, content)| {
+                matches!(desc.first(), Some(&FIELD_TEXT) | Some(&FIELD_PATH))
+                    && content.len() % 2 != 0
+            })
+            .map(|(desc, _)| desc)
+    }
+
+    /// Decode the text field with the given descriptor (e.g. `tsng`).
+    ///
+    /// Malformed text is decoded leniently like [`parse`] does.
+    pub fn text(&self, desc: &[u8]) -> Option<String> {
+        self.raw_field(desc).and_then(decode_text)
+    }
+
+    fn boolean(&self, desc: &[u8]) -> bool {
+        self.raw_field(desc).is_some_and(decode_bool)
+    }
+
+    /// Returns the file path of the track.
+    pub fn file_path(&self) -> Option<PathBuf> {
+        self.text(b"pfil").map(PathBuf::from)
+    }
+
+    /// Returns `true` if the track has the given file path, without decoding the stored path.
+    pub fn has_file_path(&self, path: &str) -> bool {
+        self.raw_field(b"pfil").is_some_and(|content| {
+            let mut units = path.encode_utf16();
+            content.chunks(2).all(|chunk| {
+                units.next().map(u16::to_be_bytes).as_ref().map(|b| &b[..]) == Some(chunk)
+            }) && units.next().is_none()
+        })
+    }
+
+    pub fn title(&self) -> Option<String> {
+        self.text(b"tsng")
+    }
+
+    pub fn artist(&self) -> Option<String> {
+        self.text(b"tart")
+    }
+
+    pub fn album(&self) -> Option<String> {
+        self.text(b"talb")
+    }
+
+    pub fn genre(&self) -> Option<String> {
+        self.text(b"tgen")
+    }
+
+    pub fn key(&self) -> Option<String> {
+        self.text(b"tkey")
+    }
+
+    /// Returns the BPM value (e.g. `126.00` in the database).
+    pub fn bpm(&self) -> Option<f64> {
+        parse_bpm(&self.text(b"tbpm")?)
+    }
+
+    /// Returns `true` if Serato couldn't find the track file.
+    pub fn missing(&self) -> bool {
+        self.boolean(b"bmis")
+    }
+
+    /// Returns `true` if Serato marked the track file as corrupt.
+    pub fn corrupt(&self) -> bool {
+        self.boolean(b"bcrt")
+    }
+
+    /// Returns `true` if the beatgrid is locked.
+    pub fn beatgrid_locked(&self) -> bool {
+        self.boolean(b"bbgl")
+    }
+
+    /// Returns `true` if the track was played in the current session.
+    pub fn played(&self) -> bool {
+        self.boolean(b"bply")
+    }
+
+    /// Decode all fields into an owned record.
+    pub fn to_owned(&self) -> Result<TrackRecord, Error> {
+        let (_, fields) =
+            nom::combinator::all_consuming(nom::multi::many0(|i| take_field(i, false)))(self.data)?;
+        Ok(TrackRecord::with_original(fields, self.data))
+    }
+}
+
+/// Returns borrowed views of the track records in the `database V2` data.
+///
+/// Other top-level fields are skipped. If the data is truncated, an error is returned and the
+/// iteration stops.
+pub fn track_record_refs(input: &[u8]) -> impl Iterator<Item = Result<TrackRecordRef<'_>, Error>> {
+    let mut input = input;
+    std::iter::from_fn(move || {
+        while !input.is_empty() {
+            match take_raw_field(input) {
+                Ok((remaining, (desc, content))) => {
+                    input = remaining;
+                    if desc == b"otrk" {
+                        return Some(TrackRecordRef::new(content));
+                    }
+                }
+                Err(err) => {
+                    input = &[];
+                    return Some(Err(err.into()));
+                }
+            }
+        }
+        None
+    })
+}
+
+/// A top-level field (name and content) as read by the [`DatabaseReader`].
+type RawField = ([u8; 4], Vec<u8>);
+
+/// Reads the track records of a `database V2` file one by one.
+///
+/// In contrast to [`parse_tracks`], the data is read lazily from the reader, so that large
+/// databases don't need to be loaded into memory at once, and lookups can stop early.
+///
+/// ```
+/// use std::fs::File;
+/// use std::io::BufReader;
+/// use triseratops::library::database::DatabaseReader;
+///
+/// fn find_title(path: &str, file_path: &str) -> Option<String> {
+///     let file = File::open(path).ok()?;
+///     let mut reader = DatabaseReader::new(BufReader::new(file));
+///     let track = reader
+///         .tracks()
+///         .filter_map(Result::ok)
+///         .find(|track| track.file_path.to_str() == Some(file_path))?;
+///     track.title
+/// }
+/// ```
+pub struct DatabaseReader<R> {
+    reader: R,
+    version: Option<String>,
+    stop_on_error: bool,
+    is_finished: bool,
+}
+
+impl<R: io::Read> DatabaseReader<R> {
+    /// Create a new reader.
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            version: None,
+            stop_on_error: false,
+            is_finished: false,
+        }
+    }
+
+    /// Set whether iterating stops after the first record that can't be parsed.
+    ///
+    /// By default, the error is yielded and iteration continues with the next record. Errors that
+    /// occur when reading the data (e.g. because it is truncated) always stop the iteration.
+    pub fn set_stop_on_error(&mut self, stop_on_error: bool) {
+        self.stop_on_error = stop_on_error;
+    }
+
+    /// Returns the database version (once the `vrsn` field has been read).
+    pub fn version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
+    /// Returns an iterator over the track records.
+    pub fn tracks(&mut self) -> impl Iterator<Item = Result<Track, Error>> + '_ {
+        std::iter::from_fn(move || self.next_track())
+    }
+
+    fn next_track(&mut self) -> Option<Result<Track, Error>> {
+        while !self.is_finished {
+            let (desc, content) = match self.read_field() {
+                Ok(Some(field)) => field,
+                Ok(None) => {
+                    self.is_finished = true;
+                    return None;
+                }
+                Err(err) => {
+                    self.is_finished = true;
+                    return Some(Err(err));
+                }
+            };
+            match &desc {
+                b"otrk" => {
+                    let track = parse(&content).and_then(Track::from_fields);
+                    if track.is_err() && self.stop_on_error {
+                        self.is_finished = true;
+                    }
+                    return Some(track);
+                }
+                b"vrsn" => {
+                    if let Some(version) = decode_u16_text(&content, false) {
+                        self.version = Some(version);
+                    }
+                }
+                _ => (),
+            }
+        }
+        None
+    }
+
+    /// Read the next top-level field, or return `None` at the end of the data.
+    fn read_field(&mut self) -> Result<Option<RawField>, Error> {
+        let mut header = [0; 8];
+        let mut length = 0;
+        while length < header.len() {
+            match self.reader.read(&mut header[length..]) {
+                Ok(0) if length == 0 => return Ok(None),
+                Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+                Ok(n) => length += n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+                Err(err) => return Err(err.into()),
+            }
+        }
+        let mut desc = [0; 4];
+        desc.copy_from_slice(&header[..4]);
+        let length = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+        let mut content = vec![];
+        (&mut self.reader)
+            .take(length.into())
+            .read_to_end(&mut content)?;
+        if content.len() != length as usize {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        Ok(Some((desc, content)))
+    }
+}
+
+/// Serialize a raw field with the given descriptor (type and name) and content.
+#[cfg(test)]
+pub(super) fn encode_field(desc: &[u8], content: &[u8]) -> Vec<u8> {
+    let mut data = desc.to_vec();
+    data.extend_from_slice(&(content.len() as u32).to_be_bytes());
+    data.extend_from_slice(content);
+    data
+}
+
+#[test]
+fn test_database_reader() {
+    let mut input = encode_field(b"vrsn", b"\x002\x00.\x000");
+    input.extend(encode_field(b"otrk", &encode_field(b"pfil", b"\x00a")));
+    // The length of the inner field exceeds the record
+    input.extend(encode_field(b"otrk", b"pfil\x00\x00\x00\xFF\x00b"));
+    input.extend(encode_field(b"otrk", &encode_field(b"pfil", b"\x00c")));
+
+    let mut reader = DatabaseReader::new(io::Cursor::new(&input));
+    let tracks: Vec<_> = reader.tracks().collect();
+    assert_eq!(reader.version(), Some("2.0"));
+    assert_eq!(tracks.len(), 3);
+    assert_eq!(tracks[0].as_ref().unwrap().file_path, PathBuf::from("a"));
+    assert!(tracks[1].is_err());
+    assert_eq!(tracks[2].as_ref().unwrap().file_path, PathBuf::from("c"));
+
+    let mut reader = DatabaseReader::new(io::Cursor::new(&input));
+    reader.set_stop_on_error(true);
+    assert_eq!(reader.tracks().count(), 2);
+
+    // Truncated data ends the iteration with an error.
+    let mut reader = DatabaseReader::new(&input[..input.len() - 1]);
+    let tracks: Vec<_> = reader.tracks().collect();
+    assert_eq!(tracks.len(), 3);
+    assert!(tracks[2].is_err());
+}
+
+#[test]
+fn test_parse_malformed_field() {
+    let mut track = encode_field(b"pfil", b"\x00a\x00.\x00m\x00p\x003");
+    // Text with an odd number of bytes and a boolean with two bytes
+    track.extend(encode_field(b"tsng", b"\x00a\x00"));
+    track.extend(encode_field(b"bbgl", b"\x01\x01"));
+    track.extend(encode_field(b"tbpm", b"\x001\x002\x008"));
+    let mut input = encode_field(b"vrsn", b"\x002\x00.\x000");
+    input.extend(encode_field(b"otrk", &track));
+
+    let fields = parse(&input).unwrap();
+    assert_eq!(fields.len(), 2);
+    match &fields[1] {
+        Field::Track(fields) => {
+            assert_eq!(fields.len(), 4);
+            assert_eq!(fields[1], Field::SongTitle(String::from("a\u{FFFD}")));
+            assert!(matches!(
+                &fields[2],
+                Field::Unknown {
+                    field_type: FIELD_BOOL,
+                    ..
+                }
+            ));
+        }
+        _ => unreachable!(),
+    }
+
+    let tracks = parse_tracks(&input).unwrap();
+    assert_eq!(tracks.len(), 1);
+    assert_eq!(tracks[0].file_path, PathBuf::from("a.mp3"));
+    assert_eq!(tracks[0].title.as_deref(), Some("a\u{FFFD}"));
+    assert_eq!(tracks[0].bpm, Some(128.0));
+    assert!(parse_strict(&input).is_err());
+}
+
+#[test]
+fn test_database_edit() {
+    let mut track = encode_field(b"pfil", &encode_u16_text("Music/Old/a.mp3"));
+    track.extend(encode_field(b"tgrp", &encode_u16_text("Warmup")));
+    track.extend(encode_field(b"bxyz", b"\x01"));
+    let mut input = encode_field(
+        b"vrsn",
+        &encode_u16_text("2.0/Serato Scratch LIVE Database"),
+    );
+    input.extend(encode_field(b"otrk", &track));
+    input.extend(encode_field(
+        b"otrk",
+        &encode_field(b"pfil", &encode_u16_text("b.mp3")),
+    ));
+
+    let mut database = Database::parse(&input).unwrap();
+    assert_eq!(database.version(), Some("2.0/Serato Scratch LIVE Database"));
+    let mut output = vec![];
+    database.write(&mut output).unwrap();
+    assert_eq!(output, input);
+
+    let track = database.track_mut("Music/Old/a.mp3").unwrap();
+    track.set_file_path("Music/New/a.mp3");
+    track.set_grouping(None);
+    track.set_missing(true);
+    assert!(database.track_mut("Music/Old/a.mp3").is_none());
+    database
+        .track_mut("b.mp3")
+        .unwrap()
+        .set_grouping(Some(String::from("Peak")));
+
+    let mut output = vec![];
+    database.write(&mut output).unwrap();
+    let database = Database::parse(&output).unwrap();
+    let tracks: Vec<_> = database.tracks().collect();
+    assert_eq!(
+        tracks[0].fields,
+        vec![
+            Field::FilePath(PathBuf::from("Music/New/a.mp3")),
+            Field::UnknownBoolean {
+                name: b"xyz".to_vec(),
+                value: true,
+            },
+            Field::Missing(true),
+        ]
+    );
+    assert_eq!(
+        tracks[1].fields,
+        vec![
+            Field::FilePath(PathBuf::from("b.mp3")),
+            Field::Grouping(String::from("Peak")),
+        ]
+    );
+}
+
+#[test]
+fn test_track_record_values() {
+    let track = TrackRecord::new(vec![
+        Field::FilePath(PathBuf::from("a.mp3")),
+        Field::Year(String::from("1999")),
+        Field::DateAddedStr(String::from("1580992490")),
+        Field::BeatgridLocked(true),
+        Field::UnknownU16Field {
+            name: b"bav".to_vec(),
+            value: 0x0201,
+        },
+    ]);
+    assert_eq!(track.path(), "a.mp3");
+    assert_eq!(track.year(), Some(1999));
+    assert_eq!(track.date_added(), Some(1580992490));
+    assert!(track.beatgrid_locked());
+    assert!(!track.missing());
+    assert_eq!(track.unknown_fields().count(), 1);
+    assert_eq!(TrackRecord::new(vec![]).path(), "");
+}
+
+#[test]
+fn test_track_record_dates() {
+    use std::time::UNIX_EPOCH;
+
+    let mut track = TrackRecord::new(vec![
+        Field::DateAdded(0),
+        Field::DateAddedStr(String::from("1580992490")),
+        Field::FileTime(0),
+    ]);
+    // Zero means unknown, so the text field is used instead
+    assert_eq!(track.date_added(), Some(1580992490));
+    assert_eq!(
+        track.date_added_system_time(),
+        Some(UNIX_EPOCH + Duration::from_secs(1580992490))
+    );
+    assert_eq!(track.file_time(), None);
+    assert_eq!(track.file_system_time(), None);
+
+    let time = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+    track.set_date_added(time).unwrap();
+    assert_eq!(
+        track.fields[..2],
+        [
+            Field::DateAdded(1_600_000_000),
+            Field::DateAddedStr(String::from("1600000000")),
+        ]
+    );
+    assert_eq!(track.date_added_system_time(), Some(time));
+
+    let after_2106 = UNIX_EPOCH + Duration::from_secs(1 << 32);
+    assert!(matches!(
+        track.set_date_added(after_2106),
+        Err(Error::TimestampOutOfRange { .. })
+    ));
+    assert!(track.set_date_added(UNIX_EPOCH).is_err());
+    assert!(matches!(
+        track.set_date_added(UNIX_EPOCH - Duration::from_secs(60)),
+        Err(Error::TimeBeforeEpoch { duration }) if duration == Duration::from_secs(60)
+    ));
+    assert_eq!(track.date_added_system_time(), Some(time));
+}
+
+#[test]
+fn test_decode_u16_text() {
+    let text = "Caf\u{e9} \u{1F3A7} \u{6771}\u{4EAC}";
+    let be: Vec<u8> = text.encode_utf16().flat_map(|c| c.to_be_bytes()).collect();
+    let le: Vec<u8> = text.encode_utf16().flat_map(|c| c.to_le_bytes()).collect();
+    assert_eq!(decode_u16_text(&be, true).as_deref(), Some(text));
+    assert_eq!(
+        decode_u16_text(&[&[0xFE, 0xFF][..], &be].concat(), true).as_deref(),
+        Some(text)
+    );
+    assert_eq!(
+        decode_u16_text(&[&[0xFF, 0xFE][..], &le].concat(), true).as_deref(),
+        Some(text)
+    );
+
+    // The emoji is encoded as surrogate pair, so dropping its low surrogate leaves an unpaired
+    // high surrogate.
+    let mut corrupted = be.clone();
+    corrupted.drain(12..14);
+    assert_eq!(
+        decode_u16_text(&corrupted, false).as_deref(),
+        Some("Caf\u{e9} \u{FFFD} \u{6771}\u{4EAC}")
+    );
+    assert_eq!(decode_u16_text(&corrupted, true), None);
+
+    // A trailing byte is replaced as well.
+    assert_eq!(
+        decode_u16_text(&be[..be.len() - 1], false).as_deref(),
+        Some("Caf\u{e9} \u{1F3A7} \u{6771}\u{FFFD}")
+    );
+    assert_eq!(decode_u16_text(&be[..be.len() - 1], true), None);
+
+    let mut input = b"tsng".to_vec();
+    input.extend_from_slice(&(corrupted.len() as u32).to_be_bytes());
+    input.extend_from_slice(&corrupted);
+    assert_eq!(
+        parse(&input).unwrap(),
+        vec![Field::SongTitle(String::from(
+            "Caf\u{e9} \u{FFFD} \u{6771}\u{4EAC}"
+        ))]
+    );
+    assert!(parse_strict(&input).is_err());
+
+    // Lenient decoding also applies to track records
+    let mut track = encode_field(b"pfil", &encode_u16_text("a.mp3"));
+    track.extend(encode_field(b"tsng", &corrupted));
+    track.extend(encode_field(b"tart", &[&[0xFF, 0xFE][..], &le].concat()));
+    track.extend(encode_field(b"talb", &be[..be.len() - 1]));
+    let input = encode_field(b"otrk", &track);
+    let database = Database::parse(&input).unwrap();
+    let record = database.tracks().next().unwrap();
+    assert_eq!(record.title(), Some("Caf\u{e9} \u{FFFD} \u{6771}\u{4EAC}"));
+    assert_eq!(record.artist(), Some(text));
+    assert_eq!(record.album(), Some("Caf\u{e9} \u{1F3A7} \u{6771}\u{FFFD}"));
+    assert_eq!(
+        record.truncated_fields().collect::<Vec<_>>(),
+        vec![&Field::Album(String::from(
+            "Caf\u{e9} \u{1F3A7} \u{6771}\u{FFFD}"
+        ))]
+    );
+    let track_ref = TrackRecordRef::new(&track).unwrap();
+    assert_eq!(track_ref.title().as_deref(), record.title());
+    assert_eq!(
+        track_ref.truncated_fields().collect::<Vec<_>>(),
+        vec![b"talb"]
+    );
+
+    let mut output = vec![];
+    database.write(&mut output).unwrap();
+    assert_eq!(output, input);
+}
+
+#[test]
+fn test_track_record_kind() {
+    let record = |fields| TrackRecord::new(fields);
+    assert_eq!(
+        record(vec![
+            Field::FileType(String::from("mp3")),
+            Field::FilePath(PathBuf::from("a.mp3")),
+        ])
+        .kind(),
+        TrackKind::Audio
+    );
+    assert_eq!(
+        record(vec![
+            Field::FileType(String::from("mov")),
+            Field::FilePath(PathBuf::from("a.mov")),
+            Field::UnknownTextField {
+                name: b"vfx".to_vec(),
+                text: String::from("?"),
+            },
+        ])
+        .kind(),
+        TrackKind::Video
+    );
+    assert_eq!(
+        record(vec![Field::FilePath(PathBuf::from("Videos/a.MP4"))]).kind(),
+        TrackKind::Video
+    );
+    assert_eq!(record(vec![]).kind(), TrackKind::Audio);
+}
+
+#[test]
+fn test_track_record_refs() {
+    let mut track = encode_field(b"pfil", &encode_u16_text("Music/a.mp3"));
+    track.extend(encode_field(b"tsng", &encode_u16_text("\u{6771}\u{4EAC}")));
+    track.extend(encode_field(b"tbpm", &encode_u16_text("126.00")));
+    track.extend(encode_field(b"bmis", b"\x01"));
+    let mut input = encode_field(
+        b"vrsn",
+        &encode_u16_text("2.0/Serato Scratch LIVE Database"),
+    );
+    input.extend(encode_field(b"otrk", &track));
+    input.extend(encode_field(
+        b"otrk",
+        &encode_field(b"pfil", &encode_u16_text("b.mp3")),
+    ));
+
+    let tracks: Vec<_> = track_record_refs(&input).collect::<Result<_, _>>().unwrap();
+    assert_eq!(tracks.len(), 2);
+    let track = tracks[0];
+    assert!(track.has_file_path("Music/a.mp3"));
+    assert!(!track.has_file_path("Music/a.mp"));
+    assert!(!track.has_file_path("Music/a.mp3x"));
+    assert_eq!(track.file_path(), Some(PathBuf::from("Music/a.mp3")));
+    assert_eq!(track.title().as_deref(), Some("\u{6771}\u{4EAC}"));
+    assert_eq!(track.artist(), None);
+    assert_eq!(track.bpm(), Some(126.0));
+    assert!(track.missing());
+    assert!(!tracks[1].missing());
+    assert_eq!(track.raw_fields().count(), 4);
+
+    let owned = track.to_owned().unwrap();
+    assert_eq!(owned.path(), "Music/a.mp3");
+    assert_eq!(owned.fields.len(), 4);
+    assert_eq!(
+        Database::parse(&input).unwrap().tracks().next(),
+        Some(&owned)
+    );
+
+    // Truncated data yields an error
+    let results: Vec<_> = track_record_refs(&input[..input.len() - 1]).collect();
+    assert_eq!(results.len(), 2);
+    assert!(results[1].is_err());
+}
+
+#[test]
+fn test_track_models() {
+    let mut input = encode_field(b"pfil", &encode_u16_text("Music/a.mp3"));
+    input.extend(encode_field(b"tsng", b"\x00a\x00"));
+    input.extend(encode_field(b"tart", b"\xFE\xFF\x00a"));
+    input.extend(encode_field(b"talb", &encode_u16_text("Album")));
+    input.extend(encode_field(b"tbpm", &encode_u16_text(" 126.00")));
+    input.extend(encode_field(b"bmis", b"\x00\x01"));
+    input.extend(encode_field(b"bply", b"\x01"));
+
+    let track_ref = TrackRecordRef::new(&input).unwrap();
+    let record = track_ref.to_owned().unwrap();
+    let track = Track::from(&record);
+    assert_eq!(track_ref.title().as_deref(), Some("a\u{FFFD}"));
+    assert_eq!(record.title(), Some("a\u{FFFD}"));
+    assert_eq!(track.title.as_deref(), Some("a\u{FFFD}"));
+    assert_eq!(track_ref.artist().as_deref(), Some("a"));
+    assert_eq!(record.artist(), Some("a"));
+    assert_eq!(track.artist.as_deref(), Some("a"));
+    assert_eq!(track_ref.album().as_deref(), Some("Album"));
+    assert_eq!(record.album(), Some("Album"));
+    assert_eq!(track.album.as_deref(), Some("Album"));
+    assert_eq!(track_ref.bpm(), Some(126.0));
+    assert_eq!(record.bpm(), Some(126.0));
+    assert_eq!(track.bpm, Some(126.0));
+    assert!(track_ref.missing());
+    assert!(record.missing());
+    assert!(track.missing);
+    assert!(track_ref.played());
+    assert!(record.played());
+    assert_eq!(track.flags.get("ply"), Some(&true));
+    assert!(!track.flags.contains_key("mis"));
+    assert_eq!(track.file_path, PathBuf::from("Music/a.mp3"));
+}
+
+#[test]
+fn test_parse_unknown_fields() {
+    let mut input = encode_field(b"tzzz", &encode_u16_text("abc"));
+    input.extend(encode_field(b"tzzy", b"\x00a\x00"));
+    input.extend(encode_field(b"bzzz", b"\x02"));
+    input.extend(encode_field(b"otrk", &encode_field(b"pzzz", b"\xD8\x00")));
+    input.extend(encode_field(b"tsng", b"\x00a\x00"));
+
+    let fields = parse(&input).unwrap();
+    assert_eq!(
+        fields[..3],
+        [
+            Field::UnknownTextField {
+                name: b"zzz".to_vec(),
+                text: String::from("abc"),
+            },
+            raw_field(FIELD_TEXT, b"zzy", b"\x00a\x00"),
+            raw_field(FIELD_BOOL, b"zzz", b"\x02"),
+        ]
+    );
+    assert_eq!(
+        fields[3],
+        Field::Track(vec![raw_field(FIELD_PATH, b"zzz", b"\xD8\x00")])
+    );
+    // Known fields are still decoded leniently
+    assert_eq!(fields[4], Field::SongTitle(String::from("a\u{FFFD}")));
+
+    let mut output = vec![];
+    write(&mut output, &fields[..4]).unwrap();
+    assert_eq!(output, input[..output.len()]);
+}
+
+#[test]
+fn test_roundtrip_malformed_known_fields() {
+    let mut track = encode_field(b"pfil", &encode_u16_text("a.mp3"));
+    // Odd number of bytes, leading BOM, unpaired surrogate
+    track.extend(encode_field(b"tsng", b"\x00a\x00b\x00"));
+    track.extend(encode_field(b"tart", b"\xFE\xFF\x00a"));
+    track.extend(encode_field(b"talb", b"\xD8\x00\x00a"));
+    track.extend(encode_field(b"tgen", &encode_u16_text("House")));
+    let mut input = encode_field(
+        b"vrsn",
+        &encode_u16_text("2.0/Serato Scratch LIVE Database"),
+    );
+    input.extend(encode_field(b"otrk", &track));
+
+    let mut database = Database::parse(&input).unwrap();
+    let record = database.tracks().next().unwrap();
+    assert_eq!(record.path(), "a.mp3");
+    assert_eq!(record.title(), Some("ab\u{FFFD}"));
+    assert_eq!(record.artist(), Some("a"));
+    assert_eq!(record.album(), Some("\u{FFFD}a"));
+    assert_eq!(record.genre(), Some("House"));
+    assert_eq!(record.unknown_fields().count(), 0);
+
+    let mut output = vec![];
+    database.write(&mut output).unwrap();
+    assert_eq!(output, input);
+
+    let track_ref = track_record_refs(&input).next().unwrap().unwrap();
+    assert_eq!(&track_ref.to_owned().unwrap(), record);
+
+    // Modified fields are written with their new content
+    let record = database.tracks_mut().next().unwrap();
+    record.fields[1] = Field::SongTitle(String::from("b"));
+    let mut output = vec![];
+    database.write(&mut output).unwrap();
+    let database = Database::parse(&output).unwrap();
+    let record = database.tracks().next().unwrap();
+    assert_eq!(record.title(), Some("b"));
+    assert_eq!(record.artist(), Some("a"));
+    assert_eq!(output.len(), input.len() - 3);
+}
+
+#[test]
+fn test_track_record_flags() {
+    let mut track = TrackRecord::new(vec![
+        Field::Missing(false),
+        Field::UnknownBoolean {
+            name: b"crt".to_vec(),
+            value: true,
+        },
+        raw_field(FIELD_BOOL, b"ply", b"\x00\x00\x00\x01"),
+    ]);
+    assert!(!track.missing());
+    assert!(track.corrupt());
+    assert!(track.played());
+    assert!(!track.beatgrid_locked());
+
+    track.set_missing(true);
+    track.set_corrupt(false);
+    track.set_played(false);
+    track.set_beatgrid_locked(true);
+    assert_eq!(
+        track.fields,
+        vec![
+            Field::Missing(true),
+            Field::UnknownBoolean {
+                name: b"crt".to_vec(),
+                value: false,
+            },
+            raw_field(FIELD_BOOL, b"ply", b"\x00\x00\x00\x00"),
+            Field::BeatgridLocked(true),
+        ]
+    );
+    assert!(track.missing());
+    assert!(!track.corrupt());
+    assert!(!track.played());
+    assert!(track.beatgrid_locked());
+
+    // The 4-byte flag keeps its size
+    let mut data = vec![];
+    write(&mut data, &track.fields).unwrap();
+    let fields = parse(&data).unwrap();
+    assert_eq!(fields, track.fields);
+    track.set_played(true);
+    assert_eq!(
+        track.fields[2],
+        raw_field(FIELD_BOOL, b"ply", b"\x00\x00\x00\x01")
+    );
+}
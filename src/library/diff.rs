@@ -0,0 +1,346 @@
+//! Comparison of two versions of the `database V2` file.
+//!
+//! ```
+//! use triseratops::library::database::Database;
+//! use triseratops::library::diff::{diff, DiffOptions, TrackChange};
+//!
+//! fn print_changes(old: &Database, new: &Database) {
+//!     let options = DiffOptions {
+//!         detect_relocations: true,
+//!     };
+//!     for change in diff(old.tracks(), new.tracks(), &options) {
+//!         if let TrackChange::Relocated { old_path, new_path, .. } = change {
+//!             println!("{} -> {}", old_path.display(), new_path.display());
+//!         }
+//!     }
+//! }
+//! ```
+
+use super::database::{Field, TrackRecord};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+
+/// Options for [`diff`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DiffOptions {
+    /// Match removed and added tracks with the same title, artist and length, and report them as
+    /// [`TrackChange::Relocated`].
+    pub detect_relocations: bool,
+}
+
+/// A field of a track record that has been changed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldChange {
+    /// The field name (e.g. `tsng` for the song title).
+    pub name: String,
+    /// The old value, or `None` if the field has been added.
+    pub before: Option<Field>,
+    /// The new value, or `None` if the field has been removed.
+    pub after: Option<Field>,
+}
+
+/// A change of a track between two versions of the database.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TrackChange {
+    Added {
+        path: PathBuf,
+    },
+    Removed {
+        path: PathBuf,
+    },
+    /// Fields other than the file path have been changed.
+    Modified {
+        path: PathBuf,
+        fields: Vec<FieldChange>,
+    },
+    /// The file path has been changed (see [`DiffOptions::detect_relocations`]). The changes of
+    /// other fields are listed in `fields`.
+    Relocated {
+        old_path: PathBuf,
+        new_path: PathBuf,
+        fields: Vec<FieldChange>,
+    },
+}
+
+/// Compare the track records of two versions of the database, keyed on the file path.
+///
+/// Records with the same file path (i.e. duplicate records or records without a path) are
+/// matched in order.
+///
+/// Removed and modified tracks are returned in the order of the old records, followed by the
+/// relocated and added tracks in the order of the new records.
+pub fn diff<'a>(
+    old: impl IntoIterator<Item = &'a TrackRecord>,
+    new: impl IntoIterator<Item = &'a TrackRecord>,
+    options: &DiffOptions,
+) -> Vec<TrackChange> {
+    let old: Vec<&TrackRecord> = old.into_iter().collect();
+    let new: Vec<&TrackRecord> = new.into_iter().collect();
+    let new_keys = record_keys(&new);
+    let mut new_by_key: HashMap<(&Path, usize), &TrackRecord> =
+        new_keys.iter().copied().zip(new.iter().copied()).collect();
+
+    let mut changes = vec![];
+    let mut removed = vec![];
+    for (key, track) in record_keys(&old).into_iter().zip(old) {
+        match new_by_key.remove(&key) {
+            Some(new_track) => {
+                let fields = field_changes(track, new_track);
+                if !fields.is_empty() {
+                    changes.push(TrackChange::Modified {
+                        path: key.0.to_path_buf(),
+                        fields,
+                    });
+                }
+            }
+            None => removed.push(track),
+        }
+    }
+    let added: Vec<&TrackRecord> = new_keys
+        .into_iter()
+        .zip(new)
+        .filter(|(key, _)| new_by_key.contains_key(key))
+        .map(|(_, track)| track)
+        .collect();
+
+    // Tracks that have been removed and added with the same identity are paired in order.
+    let mut relocations: HashMap<usize, usize> = HashMap::new();
+    if options.detect_relocations {
+        let mut removed_by_identity: HashMap<_, VecDeque<usize>> = HashMap::new();
+        for (index, track) in removed.iter().enumerate() {
+            if let Some(identity) = identity(track) {
+                removed_by_identity
+                    .entry(identity)
+                    .or_default()
+                    .push_back(index);
+            }
+        }
+        for (index, track) in added.iter().enumerate() {
+            let removed_index = identity(track)
+                .and_then(|identity| removed_by_identity.get_mut(&identity))
+                .and_then(VecDeque::pop_front);
+            if let Some(removed_index) = removed_index {
+                relocations.insert(index, removed_index);
+            }
+        }
+    }
+
+    let relocated: HashSet<usize> = relocations.values().copied().collect();
+    changes.extend(
+        removed
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !relocated.contains(index))
+            .map(|(_, track)| TrackChange::Removed {
+                path: record_path(track).to_path_buf(),
+            }),
+    );
+    changes.extend(added.iter().enumerate().map(|(index, track)| {
+        let path = record_path(track).to_path_buf();
+        match relocations.get(&index) {
+            Some(&removed_index) => {
+                let old_track = removed[removed_index];
+                TrackChange::Relocated {
+                    old_path: record_path(old_track).to_path_buf(),
+                    new_path: path,
+                    fields: field_changes(old_track, track)
+                        .into_iter()
+                        .filter(|change| !matches!(change.after, Some(Field::FilePath(_))))
+                        .collect(),
+                }
+            }
+            None => TrackChange::Added { path },
+        }
+    }));
+    changes
+}
+
+fn record_path(track: &TrackRecord) -> &Path {
+    track.file_path().unwrap_or_else(|| Path::new(""))
+}
+
+/// Returns the file path of each record together with the number of preceding records with the
+/// same path.
+fn record_keys<'a>(tracks: &[&'a TrackRecord]) -> Vec<(&'a Path, usize)> {
+    let mut occurrences: HashMap<&Path, usize> = HashMap::new();
+    tracks
+        .iter()
+        .map(|track| {
+            let path = record_path(track);
+            let occurrence = occurrences.entry(path).or_default();
+            *occurrence += 1;
+            (path, *occurrence - 1)
+        })
+        .collect()
+}
+
+/// Returns the title, artist and length of the track, if the track has a title.
+fn identity(track: &TrackRecord) -> Option<(&str, Option<&str>, Option<&str>)> {
+    let length = track.fields.iter().find_map(|field| match field {
+        Field::Length(length) => Some(length.as_str()),
+        _ => None,
+    });
+    Some((track.title()?, track.artist(), length))
+}
+
+/// Compare the fields of two records by field name and return the changes sorted by name.
+fn field_changes(old: &TrackRecord, new: &TrackRecord) -> Vec<FieldChange> {
+    let old_fields = fields_by_name(old);
+    let mut new_fields = fields_by_name(new);
+    let mut changes = vec![];
+    for (name, old_values) in old_fields {
+        let new_values = new_fields.remove(&name).unwrap_or_default();
+        push_changes(&mut changes, &name, old_values, new_values);
+    }
+    for (name, new_values) in new_fields {
+        push_changes(&mut changes, &name, vec![], new_values);
+    }
+    changes.sort_by(|a, b| a.name.cmp(&b.name));
+    changes
+}
+
+fn push_changes(changes: &mut Vec<FieldChange>, name: &str, old: Vec<&Field>, new: Vec<&Field>) {
+    for index in 0..old.len().max(new.len()) {
+        let before = old.get(index).copied();
+        let after = new.get(index).copied();
+        if before != after {
+            changes.push(FieldChange {
+                name: name.to_string(),
+                before: before.cloned(),
+                after: after.cloned(),
+            });
+        }
+    }
+}
+
+fn fields_by_name(track: &TrackRecord) -> BTreeMap<String, Vec<&Field>> {
+    let mut fields: BTreeMap<String, Vec<&Field>> = BTreeMap::new();
+    for field in &track.fields {
+        fields.entry(field.name()).or_default().push(field);
+    }
+    fields
+}
+
+#[test]
+fn test_diff() {
+    let track = |path: &str, title: &str, key: &str| {
+        TrackRecord::new(vec![
+            Field::FilePath(PathBuf::from(path)),
+            Field::SongTitle(String::from(title)),
+            Field::Length(String::from("06:22.93")),
+            Field::Key(String::from(key)),
+        ])
+    };
+    let old = vec![
+        track("a.mp3", "A", "Am"),
+        track("b.mp3", "B", "Bm"),
+        track("c.mp3", "C", "Cm"),
+        track("Old/d.mp3", "D", "Dm"),
+    ];
+    let mut modified = track("b.mp3", "B", "F#m");
+    modified.fields.push(Field::Grouping(String::from("Peak")));
+    let new = vec![
+        track("a.mp3", "A", "Am"),
+        modified,
+        track("New/d.mp3", "D", "Em"),
+        track("e.mp3", "E", "Em"),
+    ];
+
+    let changes = diff(&old, &new, &DiffOptions::default());
+    assert_eq!(
+        changes,
+        vec![
+            TrackChange::Modified {
+                path: PathBuf::from("b.mp3"),
+                fields: vec![
+                    FieldChange {
+                        name: String::from("tgrp"),
+                        before: None,
+                        after: Some(Field::Grouping(String::from("Peak"))),
+                    },
+                    FieldChange {
+                        name: String::from("tkey"),
+                        before: Some(Field::Key(String::from("Bm"))),
+                        after: Some(Field::Key(String::from("F#m"))),
+                    },
+                ],
+            },
+            TrackChange::Removed {
+                path: PathBuf::from("c.mp3"),
+            },
+            TrackChange::Removed {
+                path: PathBuf::from("Old/d.mp3"),
+            },
+            TrackChange::Added {
+                path: PathBuf::from("New/d.mp3"),
+            },
+            TrackChange::Added {
+                path: PathBuf::from("e.mp3"),
+            },
+        ]
+    );
+
+    let options = DiffOptions {
+        detect_relocations: true,
+    };
+    let changes = diff(&old, &new, &options);
+    assert_eq!(changes.len(), 4);
+    assert_eq!(
+        changes[2],
+        TrackChange::Relocated {
+            old_path: PathBuf::from("Old/d.mp3"),
+            new_path: PathBuf::from("New/d.mp3"),
+            fields: vec![FieldChange {
+                name: String::from("tkey"),
+                before: Some(Field::Key(String::from("Dm"))),
+                after: Some(Field::Key(String::from("Em"))),
+            }],
+        }
+    );
+    assert!(diff(&old, &old, &options).is_empty());
+}
+
+#[test]
+fn test_diff_duplicate_paths() {
+    let track = |path: Option<&str>, title: &str| {
+        let mut fields = vec![Field::SongTitle(String::from(title))];
+        if let Some(path) = path {
+            fields.insert(0, Field::FilePath(PathBuf::from(path)));
+        }
+        TrackRecord::new(fields)
+    };
+    let old = vec![
+        track(Some("a.mp3"), "A"),
+        track(Some("a.mp3"), "B"),
+        track(None, "C"),
+        track(None, "D"),
+    ];
+    let new = vec![
+        track(Some("a.mp3"), "A"),
+        track(Some("a.mp3"), "E"),
+        track(Some("a.mp3"), "F"),
+        track(None, "C"),
+    ];
+
+    let changes = diff(&old, &new, &DiffOptions::default());
+    assert_eq!(
+        changes,
+        vec![
+            TrackChange::Modified {
+                path: PathBuf::from("a.mp3"),
+                fields: vec![FieldChange {
+                    name: String::from("tsng"),
+                    before: Some(Field::SongTitle(String::from("B"))),
+                    after: Some(Field::SongTitle(String::from("E"))),
+                }],
+            },
+            TrackChange::Removed {
+                path: PathBuf::new(),
+            },
+            TrackChange::Added {
+                path: PathBuf::from("a.mp3"),
+            },
+        ]
+    );
+    assert!(diff(&old, &old, &DiffOptions::default()).is_empty());
+}
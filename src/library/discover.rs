@@ -0,0 +1,138 @@
+//! Discovery of Serato library directories.
+//!
+//! Serato keeps a `_Serato_` directory in the user's music directory (the primary library) and in
+//! the root directory of every external drive that has been used with it. Serato Scratch Live
+//! used a `ScratchLIVE` directory instead.
+//!
+//! ```
+//! use triseratops::library::discover::{discover, music_dir};
+//!
+//! for location in discover(music_dir().as_deref(), &["/Volumes/USB"]) {
+//!     println!("{:?} library at {}", location.kind, location.path.display());
+//! }
+//! ```
+
+use std::path::{Path, PathBuf};
+
+/// Name of the Serato directory
+const SERATO_DIR: &str = "_Serato_";
+/// Name of the Serato Scratch Live directory
+const LEGACY_DIR: &str = "ScratchLIVE";
+/// Name of the music directory inside the user's home directory
+const MUSIC_DIR: &str = "Music";
+
+/// Where a library is located.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LibraryKind {
+    /// The library in the user's music directory.
+    Primary,
+    /// A library in the root directory of an external drive.
+    External,
+}
+
+/// A library directory that was found by [`discover`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LibraryLocation {
+    /// The path of the `_Serato_` (or `ScratchLIVE`) directory, which can be passed to
+    /// [`Library::open`](super::Library::open).
+    pub path: PathBuf,
+    pub kind: LibraryKind,
+    /// Whether the directory is a legacy `ScratchLIVE` directory.
+    pub is_legacy: bool,
+}
+
+/// Returns the library directories in the given music directory and in the root directories of
+/// the given mount points.
+///
+/// Mount points that don't exist or that don't contain a library are skipped.
+pub fn discover(
+    music_dir: Option<&Path>,
+    mount_points: &[impl AsRef<Path>],
+) -> Vec<LibraryLocation> {
+    let mut locations = vec![];
+    if let Some(music_dir) = music_dir {
+        locations.extend(find_libraries(music_dir, LibraryKind::Primary));
+    }
+    for mount_point in mount_points {
+        locations.extend(find_libraries(mount_point.as_ref(), LibraryKind::External));
+    }
+    locations
+}
+
+fn find_libraries(dir: &Path, kind: LibraryKind) -> impl Iterator<Item = LibraryLocation> + '_ {
+    [(SERATO_DIR, false), (LEGACY_DIR, true)]
+        .iter()
+        .map(move |&(name, is_legacy)| LibraryLocation {
+            path: dir.join(name),
+            kind,
+            is_legacy,
+        })
+        .filter(|location| location.path.is_dir())
+}
+
+/// Returns the user's music directory (`~/Music`), based on the `HOME` or `USERPROFILE`
+/// environment variable.
+pub fn music_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(|home| PathBuf::from(home).join(MUSIC_DIR))
+}
+
+/// Returns the mount points of the external drives.
+///
+/// These are the directories in `/Volumes` on macOS (except the system drive), the directories in
+/// `/media`, `/media/$USER`, `/run/media/$USER` and `/mnt` on other Unix systems, and the drive
+/// roots on Windows.
+#[cfg(feature = "mount-points")]
+pub fn mount_points() -> Vec<PathBuf> {
+    if cfg!(windows) {
+        return (b'A'..=b'Z')
+            .map(|letter| PathBuf::from(format!("{}:\\", char::from(letter))))
+            .filter(|path| path.is_dir())
+            .collect();
+    }
+
+    let parents: Vec<PathBuf> = if cfg!(target_os = "macos") {
+        vec![PathBuf::from("/Volumes")]
+    } else {
+        let user = std::env::var_os("USER").unwrap_or_default();
+        vec![
+            PathBuf::from("/media"),
+            Path::new("/media").join(&user),
+            Path::new("/run/media").join(&user),
+            PathBuf::from("/mnt"),
+        ]
+    };
+    let root = Path::new("/");
+    let mut mount_points = vec![];
+    for parent in parents {
+        let entries = match std::fs::read_dir(&parent) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries.filter_map(Result::ok) {
+            let path = entry.path();
+            // The system drive is linked into `/Volumes` on macOS.
+            let is_system_drive = std::fs::canonicalize(&path).is_ok_and(|path| path == root);
+            if path.is_dir() && !is_system_drive && !mount_points.contains(&path) {
+                mount_points.push(path);
+            }
+        }
+    }
+    mount_points
+}
+
+/// Returns the library directories in the user's music directory and on all external drives
+/// (see [`music_dir`] and [`mount_points`]).
+#[cfg(feature = "mount-points")]
+pub fn discover_all() -> Vec<LibraryLocation> {
+    discover(music_dir().as_deref(), &mount_points())
+}
+
+#[cfg(feature = "mount-points")]
+#[test]
+fn test_mount_points() {
+    let mount_points = mount_points();
+    assert!(mount_points.iter().all(|path| path.is_dir()));
+    assert!(!mount_points.contains(&PathBuf::from("/")));
+}
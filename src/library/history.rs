@@ -0,0 +1,436 @@
+//! Parser for Serato history session files (`_Serato_/History/Sessions/*.session`).
+//!
+//! Session files use the same field format as the [database](super::database), but the session
+//! information (`oses`) and each entry (`oent`) contain an `adat` field with numbered attributes.
+//!
+//! **Note:** No sample files are available yet, so the attribute numbers are based on the findings
+//! of other reverse-engineering projects. Unknown attributes are kept as raw data.
+//!
+//! ```
+//! use triseratops::library::history::Session;
+//!
+//! fn print_setlist(path: &std::path::Path) {
+//!     let session = Session::read_from_path(path).unwrap();
+//!     for entry in session.entries.iter().filter(|entry| entry.played == Some(true)) {
+//!         println!("{:?} - {:?}", entry.artist, entry.title);
+//!     }
+//! }
+//! ```
+
+use super::database::{self, Field};
+use super::timestamp;
+use crate::error::Error;
+use crate::util::Res;
+use std::convert::TryInto;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+const ATTR_ROW: u32 = 1;
+const ATTR_FILE_PATH: u32 = 2;
+const ATTR_TITLE: u32 = 6;
+const ATTR_ARTIST: u32 = 7;
+const ATTR_ALBUM: u32 = 8;
+const ATTR_GENRE: u32 = 9;
+const ATTR_BPM: u32 = 15;
+const ATTR_START_TIME: u32 = 28;
+const ATTR_END_TIME: u32 = 29;
+const ATTR_DECK: u32 = 31;
+const ATTR_PLAY_TIME: u32 = 45;
+const ATTR_PLAYED: u32 = 50;
+const ATTR_KEY: u32 = 51;
+
+/// A track that was loaded into a deck during the session.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PlayedTrack {
+    /// The row number of the entry in the history.
+    pub row: Option<u32>,
+    pub file_path: Option<PathBuf>,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub genre: Option<String>,
+    pub key: Option<String>,
+    pub bpm: Option<u32>,
+    /// The time the track was loaded as Unix timestamp.
+    pub start_time: Option<u64>,
+    /// The time the track was unloaded as Unix timestamp.
+    pub end_time: Option<u64>,
+    /// The number of seconds that the track was played.
+    pub play_time: Option<u32>,
+    /// The deck number (starting at 1).
+    pub deck: Option<u32>,
+    /// Whether the track was actually played, or just loaded into a deck.
+    pub played: Option<bool>,
+    /// All other attributes (number and raw data).
+    pub other_attributes: Vec<(u32, Vec<u8>)>,
+}
+
+impl PlayedTrack {
+    fn from_attributes(attributes: Vec<(u32, Vec<u8>)>) -> Self {
+        let mut track = Self::default();
+        for (id, data) in attributes {
+            match id {
+                ATTR_ROW => track.row = decode_u32(&data),
+                ATTR_FILE_PATH => track.file_path = Some(PathBuf::from(decode_text(&data))),
+                ATTR_TITLE => track.title = Some(decode_text(&data)),
+                ATTR_ARTIST => track.artist = Some(decode_text(&data)),
+                ATTR_ALBUM => track.album = Some(decode_text(&data)),
+                ATTR_GENRE => track.genre = Some(decode_text(&data)),
+                ATTR_KEY => track.key = Some(decode_text(&data)),
+                ATTR_BPM => track.bpm = decode_u32(&data),
+                ATTR_START_TIME => track.start_time = decode_timestamp(&data),
+                ATTR_END_TIME => track.end_time = decode_timestamp(&data),
+                ATTR_PLAY_TIME => track.play_time = decode_u32(&data),
+                ATTR_DECK => track.deck = decode_u32(&data),
+                ATTR_PLAYED => track.played = data.first().map(|&byte| byte != 0),
+                _ => track.other_attributes.push((id, data)),
+            }
+        }
+        track
+    }
+
+    /// Returns the time the track was loaded.
+    pub fn start_system_time(&self) -> Option<SystemTime> {
+        self.start_time.and_then(timestamp::to_system_time)
+    }
+
+    /// Returns the time the track was unloaded.
+    pub fn end_system_time(&self) -> Option<SystemTime> {
+        self.end_time.and_then(timestamp::to_system_time)
+    }
+}
+
+/// A column of the CSV export (see [`Session::write_csv`]).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CsvColumn {
+    /// The time the track was loaded (UTC, RFC 3339).
+    StartTime,
+    /// The time the track was unloaded (UTC, RFC 3339).
+    EndTime,
+    Artist,
+    Title,
+    Bpm,
+    Deck,
+    /// `played` or `skipped` (i.e. loaded but not played).
+    Status,
+}
+
+impl CsvColumn {
+    /// All columns in the default order.
+    pub const ALL: [CsvColumn; 7] = [
+        CsvColumn::StartTime,
+        CsvColumn::EndTime,
+        CsvColumn::Artist,
+        CsvColumn::Title,
+        CsvColumn::Bpm,
+        CsvColumn::Deck,
+        CsvColumn::Status,
+    ];
+
+    /// Returns the column name in the header row.
+    pub fn header(self) -> &'static str {
+        match self {
+            CsvColumn::StartTime => "Start Time",
+            CsvColumn::EndTime => "End Time",
+            CsvColumn::Artist => "Artist",
+            CsvColumn::Title => "Title",
+            CsvColumn::Bpm => "BPM",
+            CsvColumn::Deck => "Deck",
+            CsvColumn::Status => "Status",
+        }
+    }
+
+    fn value(self, entry: &PlayedTrack) -> String {
+        match self {
+            CsvColumn::StartTime => entry.start_time.map(timestamp::to_rfc3339),
+            CsvColumn::EndTime => entry.end_time.map(timestamp::to_rfc3339),
+            CsvColumn::Artist => entry.artist.clone(),
+            CsvColumn::Title => entry.title.clone(),
+            CsvColumn::Bpm => entry.bpm.map(|bpm| bpm.to_string()),
+            CsvColumn::Deck => entry.deck.map(|deck| deck.to_string()),
+            CsvColumn::Status => entry
+                .played
+                .map(|played| String::from(if played { "played" } else { "skipped" })),
+        }
+        .unwrap_or_default()
+    }
+}
+
+/// Options for the CSV export (see [`Session::write_csv`]).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CsvOptions {
+    /// The columns to write, in order.
+    pub columns: Vec<CsvColumn>,
+    /// Whether to write a header row with the column names.
+    pub header: bool,
+}
+
+impl Default for CsvOptions {
+    /// All columns with header row.
+    fn default() -> Self {
+        Self {
+            columns: CsvColumn::ALL.to_vec(),
+            header: true,
+        }
+    }
+}
+
+/// Represents a Serato history session file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Session {
+    /// The start time of the session as Unix timestamp.
+    ///
+    /// If the session information doesn't contain it, the earliest start time of all entries is
+    /// used instead.
+    pub date: Option<u64>,
+    /// The tracks in the order that they were loaded.
+    pub entries: Vec<PlayedTrack>,
+}
+
+impl Session {
+    /// Parse the data of a session file.
+    pub fn parse(input: &[u8]) -> Result<Self, Error> {
+        let mut date = None;
+        let mut entries = vec![];
+        for field in database::parse(input)? {
+            if let Field::UnknownContainerField { name, fields } = field {
+                match name.as_slice() {
+                    b"ses" => {
+                        date = PlayedTrack::from_attributes(attributes(&fields)?).start_time;
+                    }
+                    b"ent" => entries.push(PlayedTrack::from_attributes(attributes(&fields)?)),
+                    _ => (),
+                }
+            }
+        }
+        let date = date.or_else(|| entries.iter().filter_map(|entry| entry.start_time).min());
+        Ok(Self { date, entries })
+    }
+
+    /// Read and parse the session file at the given path.
+    pub fn read_from_path(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let data = fs::read(path)?;
+        Self::parse(&data)
+    }
+
+    /// Returns the start time of the session.
+    pub fn system_time(&self) -> Option<SystemTime> {
+        self.date.and_then(timestamp::to_system_time)
+    }
+
+    /// Export the session as CSV with all columns (see [`Session::write_csv`]).
+    pub fn to_csv(&self) -> String {
+        let mut data = vec![];
+        self.write_csv(&mut data, &CsvOptions::default())
+            .expect("writing into a vector never fails");
+        String::from_utf8(data).expect("CSV is valid UTF-8")
+    }
+
+    /// Write the session as CSV (RFC 4180) with one row per entry, in the order that the tracks
+    /// were loaded.
+    ///
+    /// Values that contain commas, quotes or line breaks are quoted. Unknown values are empty.
+    pub fn write_csv(
+        &self,
+        mut writer: impl io::Write,
+        options: &CsvOptions,
+    ) -> Result<usize, Error> {
+        let mut csv = String::new();
+        if options.header {
+            let header: Vec<_> = options
+                .columns
+                .iter()
+                .map(|column| column.header())
+                .collect();
+            push_csv_row(&mut csv, &header);
+        }
+        for entry in &self.entries {
+            let row: Vec<_> = options
+                .columns
+                .iter()
+                .map(|column| column.value(entry))
+                .collect();
+            push_csv_row(&mut csv, &row);
+        }
+        writer.write_all(csv.as_bytes())?;
+        Ok(csv.len())
+    }
+}
+
+fn push_csv_row(csv: &mut String, values: &[impl AsRef<str>]) {
+    for (index, value) in values.iter().enumerate() {
+        if index > 0 {
+            csv.push(',');
+        }
+        let value = value.as_ref();
+        if value.contains([',', '"', '\r', '\n']) {
+            csv.push('"');
+            csv.push_str(&value.replace('"', "\"\""));
+            csv.push('"');
+        } else {
+            csv.push_str(value);
+        }
+    }
+    csv.push_str("\r\n");
+}
+
+/// Returns the attributes of the `adat` field in the given fields.
+fn attributes(fields: &[Field]) -> Result<Vec<(u32, Vec<u8>)>, Error> {
+    for field in fields {
+        if let Field::Unknown {
+            field_type: b'a',
+            name,
+            content,
+        } = field
+        {
+            if name == b"dat" {
+                let (_, attributes) = nom::combinator::all_consuming(nom::multi::many0(
+                    take_attribute,
+                ))(content.as_slice())?;
+                return Ok(attributes);
+            }
+        }
+    }
+    Ok(vec![])
+}
+
+fn take_attribute(input: &[u8]) -> Res<&[u8], (u32, Vec<u8>)> {
+    let (input, id) = nom::number::complete::be_u32(input)?;
+    let (input, data) = nom::multi::length_data(nom::number::complete::be_u32)(input)?;
+    Ok((input, (id, data.to_vec())))
+}
+
+fn decode_u32(data: &[u8]) -> Option<u32> {
+    let bytes: [u8; 4] = data.try_into().ok()?;
+    Some(u32::from_be_bytes(bytes))
+}
+
+/// Decode a timestamp with 4 or 8 bytes, where `0` means that the time is unknown.
+fn decode_timestamp(data: &[u8]) -> Option<u64> {
+    timestamp::decode(data).filter(|&timestamp| timestamp != 0)
+}
+
+/// Decode null-terminated UTF-16 (big-endian) text.
+fn decode_text(data: &[u8]) -> String {
+    database::decode_u16_text(data, false)
+        .unwrap_or_default()
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+#[test]
+fn test_parse_session() {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn attribute(id: u32, data: &[u8]) -> Vec<u8> {
+        database::encode_field(&id.to_be_bytes(), data)
+    }
+    fn text(value: &str) -> Vec<u8> {
+        value
+            .encode_utf16()
+            .chain(std::iter::once(0))
+            .flat_map(|c| c.to_be_bytes())
+            .collect()
+    }
+    fn entry(row: u32, title: &str, start_time: u32, played: bool) -> Vec<u8> {
+        let mut attributes = attribute(ATTR_ROW, &row.to_be_bytes());
+        attributes.extend(attribute(ATTR_FILE_PATH, &text("Music/a.mp3")));
+        attributes.extend(attribute(ATTR_TITLE, &text(title)));
+        attributes.extend(attribute(ATTR_ARTIST, &text("Artist")));
+        attributes.extend(attribute(ATTR_BPM, &126u32.to_be_bytes()));
+        attributes.extend(attribute(ATTR_START_TIME, &start_time.to_be_bytes()));
+        attributes.extend(attribute(ATTR_END_TIME, &(start_time + 300).to_be_bytes()));
+        attributes.extend(attribute(ATTR_DECK, &2u32.to_be_bytes()));
+        attributes.extend(attribute(ATTR_PLAYED, &[u8::from(played)]));
+        attributes.extend(attribute(99, &[0xAB]));
+        database::encode_field(b"oent", &database::encode_field(b"adat", &attributes))
+    }
+
+    let version: Vec<u8> = "1.0/Serato Scratch LIVE Review"
+        .encode_utf16()
+        .flat_map(|c| c.to_be_bytes())
+        .collect();
+    let mut input = database::encode_field(b"vrsn", &version);
+    input.extend(entry(2, "Second", 1_600_000_400, false));
+    input.extend(entry(1, "First", 1_600_000_000, true));
+
+    let session = Session::parse(&input).unwrap();
+    assert_eq!(session.date, Some(1_600_000_000));
+    assert_eq!(
+        session.system_time(),
+        Some(UNIX_EPOCH + Duration::from_secs(1_600_000_000))
+    );
+    assert_eq!(session.entries.len(), 2);
+
+    let entry = &session.entries[0];
+    assert_eq!(entry.row, Some(2));
+    assert_eq!(entry.file_path, Some(PathBuf::from("Music/a.mp3")));
+    assert_eq!(entry.title.as_deref(), Some("Second"));
+    assert_eq!(entry.artist.as_deref(), Some("Artist"));
+    assert_eq!(entry.bpm, Some(126));
+    assert_eq!(entry.start_time, Some(1_600_000_400));
+    assert_eq!(entry.end_time, Some(1_600_000_700));
+    assert_eq!(entry.deck, Some(2));
+    assert_eq!(entry.played, Some(false));
+    assert_eq!(entry.other_attributes, vec![(99, vec![0xAB])]);
+    assert_eq!(session.entries[1].played, Some(true));
+
+    // 64-bit timestamps are supported and `0` means unknown
+    let mut attributes = attribute(ATTR_START_TIME, &(1u64 << 32).to_be_bytes());
+    attributes.extend(attribute(ATTR_END_TIME, &0u32.to_be_bytes()));
+    let session = Session::parse(&database::encode_field(
+        b"oent",
+        &database::encode_field(b"adat", &attributes),
+    ))
+    .unwrap();
+    assert_eq!(session.date, Some(1 << 32));
+    assert_eq!(session.entries[0].end_time, None);
+    assert_eq!(session.entries[0].end_system_time(), None);
+}
+
+#[test]
+fn test_session_csv() {
+    let session = Session {
+        date: Some(1_600_000_000),
+        entries: vec![
+            PlayedTrack {
+                title: Some(String::from("Intro")),
+                artist: Some(String::from("Artist")),
+                bpm: Some(126),
+                start_time: Some(1_600_000_000),
+                end_time: Some(1_600_000_300),
+                deck: Some(1),
+                played: Some(true),
+                ..PlayedTrack::default()
+            },
+            PlayedTrack {
+                title: Some(String::from("The \"Best\" Song, Remix")),
+                artist: Some(String::from("Artist")),
+                start_time: Some(1_600_000_200),
+                deck: Some(2),
+                played: Some(false),
+                ..PlayedTrack::default()
+            },
+        ],
+    };
+    assert_eq!(
+        session.to_csv(),
+        "Start Time,End Time,Artist,Title,BPM,Deck,Status\r\n\
+         2020-09-13T12:26:40Z,2020-09-13T12:31:40Z,Artist,Intro,126,1,played\r\n\
+         2020-09-13T12:30:00Z,,Artist,\"The \"\"Best\"\" Song, Remix\",,2,skipped\r\n"
+    );
+
+    let options = CsvOptions {
+        columns: vec![CsvColumn::Deck, CsvColumn::Title],
+        header: false,
+    };
+    let mut data = vec![];
+    let bytes_written = session.write_csv(&mut data, &options).unwrap();
+    assert_eq!(bytes_written, data.len());
+    assert_eq!(
+        String::from_utf8(data).unwrap(),
+        "1,Intro\r\n2,\"The \"\"Best\"\" Song, Remix\"\r\n"
+    );
+}
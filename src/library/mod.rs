@@ -1,5 +1,15 @@
 //! Parsers for the Serato library database and crates
+pub mod crate_file;
+pub mod crate_tree;
 pub mod database;
-pub(self) mod parser;
+pub mod diff;
+pub mod discover;
+pub mod history;
+pub mod neworder;
+mod parser;
+pub mod path;
+pub mod smart_crate;
+pub mod timestamp;
 
-pub use parser::{Library, Track};
+pub use diff::diff;
+pub use parser::{Library, PathChange, Track};
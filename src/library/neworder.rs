@@ -0,0 +1,105 @@
+//! Parser and writer for the crate order file (`_Serato_/neworder.pref`).
+//!
+//! Serato shows the crates in the sidebar in the order of this file, which contains one
+//! `[crate]` line per crate file name (without extension):
+//!
+//! ```text
+//! [begin record]
+//! [crate]Techno
+//! [crate]House
+//! [crate]House%%Deep
+//! [end record]
+//! ```
+//!
+//! The file is encoded as UTF-16 (big-endian). Crates that aren't listed are shown after the
+//! listed ones in alphabetical order (see [`read_crate_tree`](super::crate_tree::read_crate_tree)).
+//!
+//! **Note:** No sample files are available yet, so the format is based on the findings of other
+//! reverse-engineering projects. The parser also accepts little-endian UTF-16 with BOM and UTF-8.
+
+use super::database::decode_u16_text;
+use crate::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// File name of the crate order file inside the Serato directory
+const NEWORDER_FILENAME: &str = "neworder.pref";
+/// Prefix of the crate entries
+const CRATE_PREFIX: &str = "[crate]";
+/// First line of the file
+const BEGIN_RECORD: &str = "[begin record]";
+/// Last line of the file
+const END_RECORD: &str = "[end record]";
+
+/// Parse the data of the crate order file and return the crate file names (without extension)
+/// in the order that they are shown in Serato.
+pub fn parse(input: &[u8]) -> Vec<String> {
+    let text = match input {
+        [0xFE, 0xFF, ..] | [0xFF, 0xFE, ..] => decode_u16_text(input, false).unwrap_or_default(),
+        data if data.contains(&0) => decode_u16_text(data, false).unwrap_or_default(),
+        data => String::from_utf8_lossy(data).into_owned(),
+    };
+    text.lines()
+        .filter_map(|line| line.trim().strip_prefix(CRATE_PREFIX))
+        .map(String::from)
+        .collect()
+}
+
+/// Read and parse the crate order file in the given `_Serato_` directory.
+///
+/// Returns `None` if the file doesn't exist.
+pub fn read_from_dir(serato_dir: &Path) -> Result<Option<Vec<String>>, Error> {
+    match fs::read(serato_dir.join(NEWORDER_FILENAME)) {
+        Ok(data) => Ok(Some(parse(&data))),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Serialize the crate order with the given crate file names (without extension).
+pub fn write(mut writer: impl io::Write, crates: &[impl AsRef<str>]) -> Result<usize, Error> {
+    let mut text = format!("{}\n", BEGIN_RECORD);
+    for name in crates {
+        text.push_str(CRATE_PREFIX);
+        text.push_str(name.as_ref());
+        text.push('\n');
+    }
+    text.push_str(END_RECORD);
+    text.push('\n');
+    let data: Vec<u8> = text.encode_utf16().flat_map(|c| c.to_be_bytes()).collect();
+    writer.write_all(&data)?;
+    Ok(data.len())
+}
+
+/// Write the crate order file into the given `_Serato_` directory and return its path.
+pub fn write_to_dir(serato_dir: &Path, crates: &[impl AsRef<str>]) -> Result<PathBuf, Error> {
+    let path = serato_dir.join(NEWORDER_FILENAME);
+    let mut data = vec![];
+    write(&mut data, crates)?;
+    fs::write(&path, data)?;
+    Ok(path)
+}
+
+#[test]
+fn test_parse() {
+    let text = "[begin record]\n[crate]House\n[crate]House%%Deep\n[end record]\n";
+    let mut data = vec![0xFE, 0xFF];
+    data.extend(text.encode_utf16().flat_map(|c| c.to_be_bytes()));
+    assert_eq!(parse(&data), vec!["House", "House%%Deep"]);
+
+    let data: Vec<u8> = text.encode_utf16().flat_map(|c| c.to_be_bytes()).collect();
+    assert_eq!(parse(&data), vec!["House", "House%%Deep"]);
+    assert_eq!(parse(text.as_bytes()), vec!["House", "House%%Deep"]);
+    assert_eq!(parse(b"[crate]A\r\n[crate]B\r\n"), vec!["A", "B"]);
+}
+
+#[test]
+fn test_write() {
+    let crates = ["Techno", "House", "House%%Deep", "100% \u{1F3A7}"];
+    let mut data = vec![];
+    let bytes_written = write(&mut data, &crates).unwrap();
+    assert_eq!(bytes_written, data.len());
+    assert_eq!(&data[..4], b"\x00[\x00b");
+    assert_eq!(parse(&data), crates);
+}
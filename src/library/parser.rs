@@ -4,23 +4,31 @@
 //! use std::path::PathBuf;
 //! use triseratops::library::{Library, Track};
 //!
-//! fn read_library(music_dir: &PathBuf) {
-//!     let library = Library::read_from_path(music_dir).unwrap();
-//!     for subcrate_name in library.subcrates() {
-//!         let tracks: Vec<&Track> = library.subcrate(&subcrate_name).unwrap().collect();
-//!         println!("Subcrate '{}': {} tracks", subcrate_name, tracks.len());
+//! fn read_library(serato_dir: &PathBuf) {
+//!     let library = Library::open(serato_dir).unwrap();
+//!     for crate_name in library.crates().unwrap() {
+//!         let tracks: Vec<&Track> = library.crate_tracks(&crate_name).unwrap();
+//!         println!("Crate '{}': {} tracks", crate_name, tracks.len());
 //!     }
 //! }
 //! ```
 
+use super::crate_file::Crate;
+use super::crate_tree::{read_crate_tree, CrateNode};
 use super::database;
+use super::history::Session;
+use super::path::{LibraryPath, PathResolver, PathStyle};
+use super::smart_crate::SmartCrate;
+use super::timestamp;
 use crate::error::Error;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
 use std::fs::File;
 use std::io;
-use std::io::{BufReader, Read};
+use std::io::BufReader;
 use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime};
 
 /// File name of the main database file
 const DATABASE_FILENAME: &str = "database V2";
@@ -30,6 +38,16 @@ const CRATE_EXTENSION: &str = "crate";
 const SERATO_DIR: &str = "_Serato_";
 /// Name of the directory containing subcrates inside the Serato directory
 const SUBCRATE_DIR: &str = "Subcrates";
+/// Name of the directory containing smart crates inside the Serato directory
+const SMART_CRATE_DIR: &str = "SmartCrates";
+/// File extension of the smart crate files
+const SMART_CRATE_EXTENSION: &str = "scrate";
+/// Name of the history directory inside the Serato directory
+const HISTORY_DIR: &str = "History";
+/// Name of the directory containing the session files inside the history directory
+const SESSIONS_DIR: &str = "Sessions";
+/// File extension of the session files
+const SESSION_EXTENSION: &str = "session";
 
 #[derive(Clone, Debug)]
 pub struct Track {
@@ -44,8 +62,23 @@ pub struct Track {
     pub grouping: Option<String>,
     pub label: Option<String>,
     pub key: Option<String>,
+    /// The BPM value (e.g. `126.00` in the database).
+    pub bpm: Option<f64>,
+    /// The bitrate in kbps (e.g. `320.0kbps` in the database).
+    pub bitrate_kbps: Option<f64>,
+    /// The sample rate in Hz (e.g. `44.1k` in the database).
+    pub sample_rate_hz: Option<u32>,
+    /// The track length (e.g. `06:22.93` in the database).
+    pub length: Option<Duration>,
+    /// The time the track was added to the library as Unix timestamp (see
+    /// [`Track::date_added_system_time`]).
+    pub date_added: Option<u64>,
     pub missing: bool,
     pub beatgrid_locked: bool,
+    /// All other boolean fields of the track record, keyed by their field name (e.g. `ply`).
+    ///
+    /// The meaning of these flags hasn't been reverse-engineered yet.
+    pub flags: BTreeMap<String, bool>,
 }
 
 impl Track {
@@ -63,55 +96,122 @@ impl Track {
             grouping: None,
             label: None,
             key: None,
+            bpm: None,
+            bitrate_kbps: None,
+            sample_rate_hz: None,
+            length: None,
+            date_added: None,
             missing: false,
             beatgrid_locked: false,
+            flags: BTreeMap::new(),
         }
     }
 
     /// Creates a new Track object from a list of database fields.
     pub fn from_fields(fields: Vec<database::Field>) -> Result<Self, Error> {
-        let mut track = Self::new();
-        for field in fields {
-            match field {
-                database::Field::FilePath(file_path) => {
-                    track.file_path = file_path;
-                }
-                database::Field::FileType(file_type) => {
-                    track.file_type = Some(file_type);
-                }
-                database::Field::Album(album) => {
-                    track.album = Some(album);
-                }
-                database::Field::Artist(artist) => {
-                    track.artist = Some(artist);
-                }
-                database::Field::Comment(comment) => {
-                    track.comment = Some(comment);
-                }
-                database::Field::Composer(composer) => {
-                    track.composer = Some(composer);
-                }
-                database::Field::Grouping(grouping) => {
-                    track.grouping = Some(grouping);
-                }
-                database::Field::Label(label) => {
-                    track.label = Some(label);
-                }
-                database::Field::Key(key) => {
-                    track.key = Some(key);
-                }
-                database::Field::Missing(missing) => {
-                    track.missing = missing;
-                }
-                database::Field::BeatgridLocked(beatgrid_lock) => {
-                    track.beatgrid_locked = beatgrid_lock;
-                }
-                _ => (),
-            }
+        Ok(Self::from(&database::TrackRecord::new(fields)))
+    }
+
+    /// Returns the time the track was added to the library.
+    pub fn date_added_system_time(&self) -> Option<SystemTime> {
+        self.date_added.and_then(timestamp::to_system_time)
+    }
+}
+
+impl From<&database::TrackRecord> for Track {
+    fn from(record: &database::TrackRecord) -> Self {
+        let owned = |value: Option<&str>| value.map(str::to_owned);
+        Self {
+            file_path: record
+                .file_path()
+                .map(Path::to_path_buf)
+                .unwrap_or_default(),
+            file_type: owned(record.file_type()),
+            title: owned(record.title()),
+            artist: owned(record.artist()),
+            album: owned(record.album()),
+            genre: owned(record.genre()),
+            comment: owned(record.comment()),
+            composer: owned(record.composer()),
+            grouping: owned(record.grouping()),
+            label: owned(record.label()),
+            key: owned(record.key()),
+            bpm: record.bpm(),
+            bitrate_kbps: record.bitrate_kbps(),
+            sample_rate_hz: record.sample_rate_hz(),
+            length: record.length(),
+            date_added: record.date_added(),
+            missing: record.missing(),
+            beatgrid_locked: record.beatgrid_locked(),
+            flags: record
+                .flags()
+                .filter(|(name, _)| {
+                    ![database::FLAG_MISSING, database::FLAG_BEATGRID_LOCKED].contains(name)
+                })
+                .map(|(name, value)| (String::from_utf8_lossy(name).into_owned(), value))
+                .collect(),
         }
+    }
+}
+
+/// Parse a BPM string (e.g. `126.00`).
+pub(super) fn parse_bpm(value: &str) -> Option<f64> {
+    value.trim().parse().ok()
+}
+
+/// Parse a bitrate string (e.g. `320.0kbps`) into kbps.
+pub(super) fn parse_bitrate(value: &str) -> Option<f64> {
+    value.trim().trim_end_matches("kbps").trim().parse().ok()
+}
+
+/// Parse a sample rate string (e.g. `44.1k`) into Hz.
+pub(super) fn parse_sample_rate(value: &str) -> Option<u32> {
+    let value = value.trim();
+    match value.strip_suffix('k') {
+        Some(khz) => khz
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .map(|khz| (khz * 1000.0).round() as u32),
+        None => value.parse().ok(),
+    }
+}
 
-        Ok(track)
+/// Parse a length string (e.g. `06:22.93` or `1:02:03.45`) into a duration.
+pub(super) fn parse_length(value: &str) -> Option<Duration> {
+    let mut parts = value.trim().rsplit(':');
+    let seconds: f64 = parts.next()?.parse().ok()?;
+    let mut multiplier = 60.0;
+    let mut total = seconds;
+    for part in parts {
+        let value: u32 = part.parse().ok()?;
+        total += f64::from(value) * multiplier;
+        multiplier *= 60.0;
     }
+    if !total.is_finite() || total < 0.0 {
+        return None;
+    }
+    Some(Duration::from_secs_f64(total))
+}
+
+#[test]
+fn test_parse_track_values() {
+    assert_eq!(parse_bpm(" 126.00"), Some(126.0));
+    assert_eq!(parse_bpm(""), None);
+    assert_eq!(parse_bitrate("320.0kbps"), Some(320.0));
+    assert_eq!(parse_bitrate("foo"), None);
+    assert_eq!(parse_sample_rate("44.1k"), Some(44100));
+    assert_eq!(parse_sample_rate("48.0k"), Some(48000));
+    assert_eq!(parse_sample_rate("96000"), Some(96000));
+    assert_eq!(
+        parse_length("06:22.93"),
+        Some(Duration::from_millis(382_930))
+    );
+    assert_eq!(
+        parse_length("1:02:03.5"),
+        Some(Duration::from_millis(3_723_500))
+    );
+    assert_eq!(parse_length("abc"), None);
 }
 
 impl Default for Track {
@@ -120,55 +220,130 @@ impl Default for Track {
     }
 }
 
+/// A track path that is changed by [`Library::relocate`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathChange {
+    /// The library file that contains the path (i.e. the database or a crate file).
+    pub file: PathBuf,
+    /// The stored path before the change.
+    pub old_path: PathBuf,
+    /// The stored path after the change.
+    pub new_path: PathBuf,
+}
+
 /// DAO that reads Serato libraries from the file system.
+///
+/// The library is loaded lazily: The database is read on first access (see
+/// [`Library::tracks`]), and crate, smart crate and session files are read when they are
+/// requested. Missing components (e.g. a library without history) are treated as empty.
+///
+/// Track records that can't be parsed are skipped, their errors are available from
+/// [`Library::track_errors`].
 pub struct Library {
     path: PathBuf,
+    tracks: OnceLock<LoadedTracks>,
+}
+
+/// The tracks of the database and the errors of the skipped track records.
+struct LoadedTracks {
     tracks: HashMap<PathBuf, Track>,
+    errors: Vec<Error>,
 }
 
 impl Library {
-    /// Read the library in the given path.
+    /// Open the library in the given `_Serato_` directory without reading any files yet.
+    pub fn open(serato_path: impl AsRef<Path>) -> Result<Self, Error> {
+        let serato_path = fs::canonicalize(serato_path)?;
+        if !serato_path.is_dir() {
+            return Err(Error::IOError(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "library path is not a directory",
+            )));
+        }
+        Ok(Library {
+            path: serato_path,
+            tracks: OnceLock::new(),
+        })
+    }
+
+    /// Read the library in the given path (i.e. the directory that contains the `_Serato_`
+    /// directory).
+    ///
+    /// In contrast to [`Library::open`], the database is read immediately.
     pub fn read_from_path(path: impl AsRef<Path>) -> Result<Self, Error> {
-        let path = fs::canonicalize(path)?;
-        let tracks = HashMap::new();
-        let mut library = Library { path, tracks };
+        let mut library = Self::open(path.as_ref().join(SERATO_DIR))?;
         library.reload()?;
 
         Ok(library)
     }
 
-    fn serato_path(&self) -> PathBuf {
-        self.path.join(SERATO_DIR)
+    fn serato_path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the resolver for the track paths stored in the library.
+    pub fn path_resolver(&self) -> PathResolver {
+        let database_path = self.serato_path().join(DATABASE_FILENAME);
+        PathResolver::from_library_file(&database_path).unwrap_or_else(|| {
+            let serato_path = self.serato_path();
+            PathResolver::new(serato_path.parent().unwrap_or(serato_path))
+        })
     }
 
     /// Reload the library database from the hard disk.
     pub fn reload(&mut self) -> Result<(), Error> {
+        self.tracks = OnceLock::new();
+        self.load_tracks()?;
+
+        Ok(())
+    }
+
+    /// Returns the tracks of the database, which is read on first access.
+    fn load(&self) -> Result<&LoadedTracks, Error> {
+        if let Some(loaded) = self.tracks.get() {
+            return Ok(loaded);
+        }
         let database_path = self.serato_path().join(DATABASE_FILENAME);
-        let mut file = BufReader::new(File::open(database_path)?);
-        let mut data = vec![];
-        file.read_to_end(&mut data)?;
-
-        let mut tracks = HashMap::new();
-        let fields = database::parse(&data)?;
-        for field in fields {
-            if let database::Field::Track(t) = field {
-                let track = Track::from_fields(t)?;
-                tracks.insert(track.file_path.clone(), track);
+        let mut loaded = LoadedTracks {
+            tracks: HashMap::new(),
+            errors: vec![],
+        };
+        match File::open(database_path) {
+            Ok(file) => {
+                for track in database::DatabaseReader::new(BufReader::new(file)).tracks() {
+                    match track {
+                        Ok(track) => {
+                            loaded.tracks.insert(track.file_path.clone(), track);
+                        }
+                        Err(err) => loaded.errors.push(err),
+                    }
+                }
             }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+            Err(err) => return Err(err.into()),
         }
-        self.tracks = tracks;
+        Ok(self.tracks.get_or_init(|| loaded))
+    }
 
-        Ok(())
+    fn load_tracks(&self) -> Result<&HashMap<PathBuf, Track>, Error> {
+        Ok(&self.load()?.tracks)
+    }
+
+    /// Get the errors of the track records that were skipped when reading the database.
+    ///
+    /// If the database is truncated, the last error is the one that ended reading it.
+    pub fn track_errors(&self) -> Result<&[Error], Error> {
+        Ok(&self.load()?.errors)
     }
 
     /// Get all tracks in the library.
-    pub fn tracks(&self) -> impl Iterator<Item = &Track> {
-        self.tracks.values()
+    pub fn tracks(&self) -> Result<impl Iterator<Item = &Track>, Error> {
+        Ok(self.load_tracks()?.values())
     }
 
     /// Get the track struct for the given path.
-    pub fn track(&self, file_path: &PathBuf) -> Option<&Track> {
-        self.tracks.get(file_path)
+    pub fn track(&self, file_path: &Path) -> Result<Option<&Track>, Error> {
+        Ok(self.load_tracks()?.get(file_path))
     }
 
     /// Get a list of subcrate names.
@@ -183,31 +358,238 @@ impl Library {
             .filter_map(|x| crate_name_from_path(&x).ok())
     }
 
-    /// Get a list of tracks from the subcrate with the given name.
-    pub fn subcrate(&self, name: &str) -> Result<impl Iterator<Item = &Track>, Error> {
+    /// Get the names of all crates (including nested crates, e.g. `House%%Deep`) in the order
+    /// that Serato shows them in the sidebar.
+    pub fn crates(&self) -> Result<Vec<String>, Error> {
+        fn collect_names(nodes: &[CrateNode], names: &mut Vec<String>) {
+            for node in nodes {
+                let name = node
+                    .crate_file
+                    .as_deref()
+                    .and_then(Path::file_stem)
+                    .and_then(|stem| stem.to_str());
+                if let Some(name) = name {
+                    names.push(name.to_string());
+                }
+                collect_names(&node.children, names);
+            }
+        }
+
+        let mut names = vec![];
+        collect_names(&self.crate_tree()?, &mut names);
+        Ok(names)
+    }
+
+    /// Get the crate hierarchy as shown in the Serato sidebar.
+    pub fn crate_tree(&self) -> Result<Vec<CrateNode>, Error> {
+        match read_crate_tree(&self.serato_path().join(SUBCRATE_DIR)) {
+            Err(Error::IOError(err)) if err.kind() == io::ErrorKind::NotFound => Ok(vec![]),
+            result => result,
+        }
+    }
+
+    /// Get the tracks of the crate with the given name (see [`Library::crates`]).
+    ///
+    /// Tracks that aren't part of the database are skipped.
+    pub fn crate_tracks(&self, name: &str) -> Result<Vec<&Track>, Error> {
         let filename = format!("{}.{}", name, CRATE_EXTENSION);
         let crate_path = self.serato_path().join(SUBCRATE_DIR).join(filename);
-        let mut file = BufReader::new(File::open(crate_path)?);
-        let mut data = vec![];
-        file.read_to_end(&mut data)?;
-
-        let fields = database::parse(&data)?;
-        let tracks = fields.into_iter().filter_map(move |field| {
-            if let database::Field::Track(track_fields) = field {
-                for track_field in track_fields {
-                    if let database::Field::TrackPath(path) = track_field {
-                        return self.track(&path);
-                    }
+        let serato_crate = Crate::read_from_path(crate_path)?;
+        let tracks = self.load_tracks()?;
+        Ok(serato_crate
+            .tracks
+            .iter()
+            .filter_map(|path| tracks.get(path))
+            .collect())
+    }
+
+    /// Get a list of tracks from the subcrate with the given name.
+    pub fn subcrate(&self, name: &str) -> Result<impl Iterator<Item = &Track>, Error> {
+        Ok(self.crate_tracks(name)?.into_iter())
+    }
+
+    /// Get all smart crates, sorted by name.
+    pub fn smart_crates(&self) -> Result<Vec<SmartCrate>, Error> {
+        let smart_crates_path = self.serato_path().join(SMART_CRATE_DIR);
+        files_with_extension(&smart_crates_path, SMART_CRATE_EXTENSION)?
+            .iter()
+            .map(SmartCrate::read_from_path)
+            .collect()
+    }
+
+    /// Get the crate entries whose track isn't part of the database, as pairs of crate name (see
+    /// [`Library::crates`]) and stored track path.
+    ///
+    /// Paths are compared like Serato does (see [`LibraryPath::comparison_key`]).
+    pub fn orphaned_crate_entries(&self) -> Result<Vec<(String, PathBuf)>, Error> {
+        let keys: HashSet<String> = self
+            .load_tracks()?
+            .keys()
+            .map(|path| LibraryPath::from(path.as_path()).comparison_key())
+            .collect();
+        let subcrates_path = self.serato_path().join(SUBCRATE_DIR);
+        let mut entries = vec![];
+        for name in self.crates()? {
+            let crate_path = subcrates_path.join(format!("{}.{}", name, CRATE_EXTENSION));
+            for path in Crate::read_from_path(crate_path)?.tracks {
+                if !keys.contains(&LibraryPath::from(path.as_path()).comparison_key()) {
+                    entries.push((name.clone(), path));
                 }
             }
+        }
+        Ok(entries)
+    }
 
-            None
-        });
+    /// Get the tracks of the database whose files are missing, sorted by path.
+    ///
+    /// Without `check_files`, only the tracks that Serato marked as missing are returned and the
+    /// file system isn't accessed. Otherwise, all tracks whose resolved path (see
+    /// [`Library::path_resolver`]) doesn't exist are returned as well.
+    pub fn missing_files(&self, check_files: bool) -> Result<Vec<&Track>, Error> {
+        let resolver = self.path_resolver();
+        let mut tracks: Vec<&Track> = self
+            .tracks()?
+            .filter(|track| {
+                track.missing || (check_files && !resolver.resolve(&track.file_path).is_file())
+            })
+            .collect();
+        tracks.sort_by(|a, b| a.file_path.cmp(&b.file_path));
         Ok(tracks)
     }
+
+    /// Replace the path prefix `from` with `to` in all track paths of the database (`pfil`) and
+    /// of the crate files (`ptrk`), and return the changes.
+    ///
+    /// Absolute prefixes (e.g. `/Volumes/USB/Music`) are converted into the stored form that is
+    /// relative to the volume root (see [`Library::path_resolver`]) and have to be located on the
+    /// volume of the library. Relative prefixes are taken as stored paths. Only whole path
+    /// components match, case-insensitively (see [`LibraryPath::replace_prefix`]).
+    ///
+    /// With `dry_run`, no files are written. Otherwise, all files are updated and the database is
+    /// reloaded. All other data of the files is preserved. **Back up the library first!**
+    pub fn relocate(
+        &mut self,
+        from: impl AsRef<Path>,
+        to: impl AsRef<Path>,
+        dry_run: bool,
+    ) -> Result<Vec<PathChange>, Error> {
+        let resolver = self.path_resolver();
+        let stored_prefix = |path: &Path| {
+            if path.is_absolute() {
+                resolver
+                    .to_library_path(path)
+                    .ok_or_else(|| Error::PathOutsideVolume {
+                        path: path.to_path_buf(),
+                    })
+            } else {
+                Ok(LibraryPath::from(path))
+            }
+        };
+        let from = stored_prefix(from.as_ref())?;
+        let to = stored_prefix(to.as_ref())?;
+        let relocate = |path: &Path| {
+            let new_path = LibraryPath::from(path).replace_prefix(&from, &to)?;
+            let new_path = PathBuf::from(new_path.to_string_with_style(PathStyle::Unix));
+            Some(new_path).filter(|new_path| new_path != path)
+        };
+
+        // Apply all changes in memory first, so that nothing is written if a file fails.
+        let mut changes = vec![];
+        let database_path = self.serato_path().join(DATABASE_FILENAME);
+        let mut database = match database::Database::read_from_path(&database_path) {
+            Ok(database) => Some(database),
+            Err(Error::IOError(err)) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => return Err(err),
+        };
+        let mut database_changed = false;
+        for track in database.iter_mut().flat_map(database::Database::tracks_mut) {
+            let old_path = PathBuf::from(track.path());
+            if let Some(new_path) = relocate(&old_path) {
+                track.set_file_path(new_path.clone());
+                database_changed = true;
+                changes.push(PathChange {
+                    file: database_path.clone(),
+                    old_path,
+                    new_path,
+                });
+            }
+        }
+
+        let mut crates = vec![];
+        let subcrates_path = self.serato_path().join(SUBCRATE_DIR);
+        for name in self.crates()? {
+            let crate_path = subcrates_path.join(format!("{}.{}", name, CRATE_EXTENSION));
+            let mut serato_crate = Crate::read_from_path(&crate_path)?;
+            let mut crate_changed = false;
+            for old_path in serato_crate.tracks.clone() {
+                if let Some(new_path) = relocate(&old_path) {
+                    serato_crate.rename_track(&old_path, &new_path)?;
+                    crate_changed = true;
+                    changes.push(PathChange {
+                        file: crate_path.clone(),
+                        old_path,
+                        new_path,
+                    });
+                }
+            }
+            if crate_changed {
+                crates.push(serato_crate);
+            }
+        }
+
+        if !dry_run {
+            if let Some(database) = database.filter(|_| database_changed) {
+                database.write_to_path(&database_path)?;
+            }
+            for serato_crate in &crates {
+                serato_crate.save()?;
+            }
+            self.reload()?;
+        }
+        Ok(changes)
+    }
+
+    /// Get the history sessions in chronological order.
+    ///
+    /// The session files are read one by one while iterating.
+    pub fn sessions(&self) -> Result<impl Iterator<Item = Result<Session, Error>>, Error> {
+        let sessions_path = self.serato_path().join(HISTORY_DIR).join(SESSIONS_DIR);
+        let paths = files_with_extension(&sessions_path, SESSION_EXTENSION)?;
+        Ok(paths.into_iter().map(Session::read_from_path))
+    }
+}
+
+/// Returns the paths of all files with the given extension in the directory, sorted by name.
+///
+/// Returns an empty list if the directory doesn't exist.
+fn files_with_extension(dir: &Path, extension: &str) -> Result<Vec<PathBuf>, Error> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err.into()),
+    };
+    let mut paths = vec![];
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_file() && path.extension() == Some(extension.as_ref()) {
+            paths.push(path);
+        }
+    }
+    paths.sort_by_cached_key(|path| file_sort_key(path));
+    Ok(paths)
+}
+
+/// Returns the sort key for a file, so that numbered files (like session files) are sorted
+/// numerically and all other files by name.
+fn file_sort_key(path: &Path) -> (Option<u64>, PathBuf) {
+    let number = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .and_then(|stem| stem.parse().ok());
+    (number, path.to_path_buf())
 }
 
-fn crate_name_from_path(path: &PathBuf) -> Result<String, Error> {
+fn crate_name_from_path(path: &Path) -> Result<String, Error> {
     if !path.is_file() {
         return Err(Error::IOError(io::Error::new(
             io::ErrorKind::Other,
@@ -0,0 +1,360 @@
+//! Conversion between the track paths stored in library files and file system paths.
+//!
+//! Serato stores track paths relative to the root directory of the volume that the `_Serato_`
+//! directory is located on, without a leading separator:
+//!
+//! | `_Serato_` directory       | Volume root    | Track path                 | Stored path            |
+//! | -------------------------- | -------------- | -------------------------- | ---------------------- |
+//! | `/Users/dj/Music/_Serato_` | `/`            | `/Users/dj/Music/a.mp3`    | `Users/dj/Music/a.mp3` |
+//! | `/Volumes/USB/_Serato_`    | `/Volumes/USB` | `/Volumes/USB/Music/a.mp3` | `Music/a.mp3`          |
+//!
+//! ```
+//! use std::path::Path;
+//! use triseratops::library::path::PathResolver;
+//!
+//! let resolver = PathResolver::from_library_file(Path::new("/Volumes/USB/_Serato_/database V2"))
+//!     .unwrap();
+//! assert_eq!(resolver.volume_root(), Path::new("/Volumes/USB"));
+//! assert_eq!(
+//!     resolver.resolve(Path::new("Music/a.mp3")),
+//!     Path::new("/Volumes/USB/Music/a.mp3")
+//! );
+//! ```
+//!
+//! Libraries that were created on Windows may contain drive letters and backslashes instead. Use
+//! [`LibraryPath`] to parse paths of both conventions and to write them back in the convention
+//! of the target OS (see [`PathStyle`]).
+
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+
+/// Name of the Serato directory inside the library directory
+const SERATO_DIR: &str = "_Serato_";
+
+/// Name of the directory that contains the user home directories on macOS and Windows.
+const USERS_DIR: &str = "Users";
+
+/// Returns the volume root for a library file (e.g. the `database V2` file or a crate file).
+///
+/// If the `_Serato_` directory is located inside a user home directory (e.g.
+/// `/Users/dj/Music/_Serato_`), the library is on the system drive and the volume root is the root
+/// directory of the file system (or the drive on Windows). Otherwise, the library is on an
+/// external drive and the directory that contains the `_Serato_` directory is the volume root
+/// (e.g. `/Volumes/USB`).
+///
+/// Returns `None` if the file isn't located inside a `_Serato_` directory.
+pub fn volume_root(library_file: &Path) -> Option<&Path> {
+    let library_dir = library_file
+        .ancestors()
+        .find(|path| path.file_name().is_some_and(|name| name == SERATO_DIR))
+        .and_then(Path::parent)?;
+    let mut components = library_dir
+        .components()
+        .skip_while(|component| matches!(component, Component::Prefix(_)));
+    let is_system_drive = components.next() == Some(Component::RootDir)
+        && components.next() == Some(Component::Normal(USERS_DIR.as_ref()));
+    if is_system_drive {
+        library_dir.ancestors().last()
+    } else {
+        Some(library_dir)
+    }
+}
+
+/// The path convention of an operating system.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PathStyle {
+    /// Components are separated by `/` (macOS).
+    Unix,
+    /// Components are separated by `\` and paths may start with a drive letter.
+    Windows,
+}
+
+impl PathStyle {
+    /// Returns the component separator.
+    pub fn separator(self) -> char {
+        match self {
+            PathStyle::Unix => '/',
+            PathStyle::Windows => '\\',
+        }
+    }
+}
+
+impl Default for PathStyle {
+    /// Returns the path convention of the current platform.
+    fn default() -> Self {
+        if cfg!(windows) {
+            PathStyle::Windows
+        } else {
+            PathStyle::Unix
+        }
+    }
+}
+
+/// A track path as stored in library files, independent of the path convention.
+///
+/// Both `/` and `\` are accepted as separators. Empty and `.` components are removed and `..`
+/// components are resolved.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct LibraryPath {
+    drive: Option<char>,
+    components: Vec<String>,
+}
+
+impl LibraryPath {
+    /// Parse a stored path of either convention.
+    pub fn parse(path: &str) -> Self {
+        let mut chars = path.chars();
+        let (drive, path) = match (chars.next(), chars.next()) {
+            (Some(letter), Some(':')) if letter.is_ascii_alphabetic() => {
+                (Some(letter.to_ascii_uppercase()), &path[2..])
+            }
+            _ => (None, path),
+        };
+        let mut components: Vec<String> = vec![];
+        for component in path.split(['/', '\\']) {
+            match component {
+                "" | "." => (),
+                ".." => {
+                    components.pop();
+                }
+                _ => components.push(component.to_string()),
+            }
+        }
+        Self { drive, components }
+    }
+
+    /// Returns the uppercase drive letter, if any.
+    pub fn drive(&self) -> Option<char> {
+        self.drive
+    }
+
+    /// Returns the path components without the drive.
+    pub fn components(&self) -> impl Iterator<Item = &str> {
+        self.components.iter().map(String::as_str)
+    }
+
+    /// Returns the path in the given convention.
+    ///
+    /// The drive letter is only written for [`PathStyle::Windows`].
+    pub fn to_string_with_style(&self, style: PathStyle) -> String {
+        let separator = style.separator().to_string();
+        let path = self.components.join(&separator);
+        match (style, self.drive) {
+            (PathStyle::Windows, Some(drive)) => format!("{}:{}{}", drive, separator, path),
+            _ => path,
+        }
+    }
+
+    /// Returns the path as relative path of the current platform (without the drive).
+    pub fn to_path_buf(&self) -> PathBuf {
+        self.components.iter().collect()
+    }
+
+    /// Returns a key for comparing paths like Serato does.
+    ///
+    /// Separators are unified, the drive letter is ignored (like in [`PathResolver::resolve`])
+    /// and the path is converted to lowercase, because the default file systems of macOS and
+    /// Windows are case-insensitive. Unicode normalization forms are not unified.
+    pub fn comparison_key(&self) -> String {
+        self.components.join("/").to_lowercase()
+    }
+
+    /// Replace the leading components that match `prefix` with the components of `replacement`.
+    ///
+    /// Only whole components match (i.e. `Music` is not a prefix of `Music2/a.mp3`), and they are
+    /// compared like in [`LibraryPath::comparison_key`]. The drive letter is kept. Returns `None`
+    /// if the path doesn't start with the prefix.
+    pub fn replace_prefix(&self, prefix: &LibraryPath, replacement: &LibraryPath) -> Option<Self> {
+        if self.components.len() < prefix.components.len() {
+            return None;
+        }
+        let (head, tail) = self.components.split_at(prefix.components.len());
+        let matches = head
+            .iter()
+            .zip(&prefix.components)
+            .all(|(a, b)| a.to_lowercase() == b.to_lowercase());
+        if !matches {
+            return None;
+        }
+        Some(Self {
+            drive: self.drive,
+            components: replacement.components.iter().chain(tail).cloned().collect(),
+        })
+    }
+
+    /// Compare two paths case-insensitively, as Windows does.
+    pub fn eq_ignore_case(&self, other: &Self) -> bool {
+        self.drive == other.drive
+            && self.components.len() == other.components.len()
+            && self
+                .components
+                .iter()
+                .zip(&other.components)
+                .all(|(a, b)| a.to_lowercase() == b.to_lowercase())
+    }
+}
+
+impl From<&Path> for LibraryPath {
+    fn from(path: &Path) -> Self {
+        Self::parse(&path.to_string_lossy())
+    }
+}
+
+impl fmt::Display for LibraryPath {
+    /// Formats the path in the convention of the current platform.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_string_with_style(PathStyle::default()))
+    }
+}
+
+/// Converts between stored track paths and file system paths for a specific volume.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PathResolver {
+    volume_root: PathBuf,
+}
+
+impl PathResolver {
+    /// Create a resolver for the given volume root.
+    pub fn new(volume_root: impl Into<PathBuf>) -> Self {
+        Self {
+            volume_root: volume_root.into(),
+        }
+    }
+
+    /// Create a resolver for the volume of a library file (see [`volume_root`]).
+    pub fn from_library_file(library_file: &Path) -> Option<Self> {
+        volume_root(library_file).map(Self::new)
+    }
+
+    /// Returns the volume root.
+    pub fn volume_root(&self) -> &Path {
+        &self.volume_root
+    }
+
+    /// Convert a stored track path of either convention into a file system path.
+    ///
+    /// The drive letter of Windows paths is ignored, because it's replaced by the volume root.
+    pub fn resolve(&self, stored_path: &Path) -> PathBuf {
+        self.volume_root
+            .join(LibraryPath::from(stored_path).to_path_buf())
+    }
+
+    /// Convert a file system path into the form that Serato stores (i.e. relative to the volume
+    /// root, separated by `/` and without a leading separator).
+    ///
+    /// Returns `None` if the path isn't located on the volume.
+    pub fn to_stored(&self, path: &Path) -> Option<PathBuf> {
+        self.to_library_path(path)
+            .map(|path| PathBuf::from(path.to_string_with_style(PathStyle::Unix)))
+    }
+
+    /// Convert a file system path into a [`LibraryPath`] relative to the volume root.
+    ///
+    /// Returns `None` if the path isn't located on the volume.
+    pub fn to_library_path(&self, path: &Path) -> Option<LibraryPath> {
+        let relative = path.strip_prefix(&self.volume_root).ok()?;
+        let components = relative
+            .components()
+            .map(|component| match component {
+                Component::Normal(part) => Some(part.to_string_lossy().into_owned()),
+                _ => None,
+            })
+            .collect::<Option<_>>()?;
+        Some(LibraryPath {
+            drive: None,
+            components,
+        })
+    }
+}
+
+#[test]
+fn test_system_drive() {
+    let database = Path::new("/Users/dj/Music/_Serato_/database V2");
+    assert_eq!(volume_root(database), Some(Path::new("/")));
+
+    let resolver = PathResolver::from_library_file(database).unwrap();
+    let path = resolver.resolve(Path::new("Users/dj/Music/Artist/a.mp3"));
+    assert_eq!(path, Path::new("/Users/dj/Music/Artist/a.mp3"));
+    assert_eq!(
+        resolver.to_stored(&path),
+        Some(PathBuf::from("Users/dj/Music/Artist/a.mp3"))
+    );
+}
+
+#[test]
+fn test_external_drive() {
+    let crate_file = Path::new("/Volumes/USB/_Serato_/Subcrates/House.crate");
+    assert_eq!(volume_root(crate_file), Some(Path::new("/Volumes/USB")));
+
+    let resolver = PathResolver::from_library_file(crate_file).unwrap();
+    let path = resolver.resolve(Path::new("Music/a.mp3"));
+    assert_eq!(path, Path::new("/Volumes/USB/Music/a.mp3"));
+    assert_eq!(
+        resolver.to_stored(&path),
+        Some(PathBuf::from("Music/a.mp3"))
+    );
+    // Leading separators in stored paths don't replace the volume root.
+    assert_eq!(resolver.resolve(Path::new("/Music/a.mp3")), path);
+    // Tracks on other volumes can't be stored.
+    assert_eq!(resolver.to_stored(Path::new("/Volumes/Other/a.mp3")), None);
+    assert_eq!(resolver.to_stored(Path::new("/Users/dj/a.mp3")), None);
+
+    assert_eq!(volume_root(Path::new("/Volumes/USB/database V2")), None);
+}
+
+#[test]
+fn test_windows_paths() {
+    let path = LibraryPath::parse("c:\\Users\\dj/Music\\.\\Artist\\a.mp3");
+    assert_eq!(path.drive(), Some('C'));
+    assert_eq!(
+        path.components().collect::<Vec<_>>(),
+        vec!["Users", "dj", "Music", "Artist", "a.mp3"]
+    );
+    assert_eq!(
+        path.to_string_with_style(PathStyle::Windows),
+        "C:\\Users\\dj\\Music\\Artist\\a.mp3"
+    );
+    assert_eq!(
+        path.to_string_with_style(PathStyle::Unix),
+        "Users/dj/Music/Artist/a.mp3"
+    );
+    assert_eq!(
+        LibraryPath::parse("Music/Sub/../a.mp3").to_string_with_style(PathStyle::Windows),
+        "Music\\a.mp3"
+    );
+
+    let other = LibraryPath::parse("C:/users/DJ/music/artist/A.MP3");
+    assert_ne!(path, other);
+    assert!(path.eq_ignore_case(&other));
+    assert!(!path.eq_ignore_case(&LibraryPath::parse("D:/Users/dj/Music/Artist/a.mp3")));
+    assert_eq!(path.comparison_key(), "users/dj/music/artist/a.mp3");
+    assert_eq!(path.comparison_key(), other.comparison_key());
+
+    let prefix = LibraryPath::parse("users/DJ");
+    let replacement = LibraryPath::parse("Volumes/USB");
+    assert_eq!(
+        path.replace_prefix(&prefix, &replacement),
+        Some(LibraryPath::parse("C:/Volumes/USB/Music/Artist/a.mp3"))
+    );
+    assert_eq!(
+        path.replace_prefix(&LibraryPath::parse("Users/d"), &replacement),
+        None
+    );
+    assert_eq!(
+        LibraryPath::parse("Users").replace_prefix(&prefix, &replacement),
+        None
+    );
+
+    let resolver = PathResolver::new("/Volumes/USB");
+    assert_eq!(
+        resolver.resolve(Path::new("E:\\Music\\a.mp3")),
+        Path::new("/Volumes/USB/Music/a.mp3")
+    );
+    assert_eq!(
+        resolver
+            .to_library_path(Path::new("/Volumes/USB/Music/a.mp3"))
+            .unwrap()
+            .to_string_with_style(PathStyle::Windows),
+        "Music\\a.mp3"
+    );
+}
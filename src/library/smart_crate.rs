@@ -0,0 +1,324 @@
+//! Parser for Serato smart crate files (`_Serato_/SmartCrates/*.scrate`).
+//!
+//! Smart crates use the same field format as regular [crates](super::crate_file), but instead of
+//! a list of tracks they contain filter rules.
+//!
+//! **Note:** No sample files are available yet, so the field names and rule values are based on
+//! the findings of other reverse-engineering projects and might be incomplete.
+
+use super::database::{self, Field};
+use crate::error::Error;
+use std::fs;
+use std::path::Path;
+
+/// Whether a track needs to match all or any of the rules.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MatchMode {
+    /// The track needs to match all rules.
+    All,
+    /// The track needs to match at least one rule.
+    Any,
+}
+
+/// The track field that a rule is applied to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RuleField {
+    Added,
+    Album,
+    Artist,
+    BPM,
+    Comment,
+    Composer,
+    Filename,
+    Genre,
+    Grouping,
+    Key,
+    Label,
+    Plays,
+    Remixer,
+    Song,
+    Year,
+}
+
+impl RuleField {
+    fn from_id(id: u32) -> Option<Self> {
+        let field = match id {
+            4 => RuleField::Filename,
+            6 => RuleField::Song,
+            7 => RuleField::Artist,
+            8 => RuleField::Album,
+            9 => RuleField::Genre,
+            15 => RuleField::BPM,
+            17 => RuleField::Comment,
+            19 => RuleField::Grouping,
+            20 => RuleField::Remixer,
+            21 => RuleField::Label,
+            22 => RuleField::Composer,
+            23 => RuleField::Year,
+            25 => RuleField::Added,
+            51 => RuleField::Key,
+            79 => RuleField::Plays,
+            _ => return None,
+        };
+        Some(field)
+    }
+}
+
+/// The comparison operator of a rule.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    /// The text is equal to the value (`cond_is_str`).
+    TextIs,
+    /// The text isn't equal to the value (`cond_isn_str`).
+    TextIsNot,
+    /// The text contains the value (`cond_con_str`).
+    TextContains,
+    /// The text doesn't contain the value (`cond_dnc_str`).
+    TextDoesNotContain,
+    /// The text starts with the value (`cond_sta_str`).
+    TextStartsWith,
+    /// The text ends with the value (`cond_end_str`).
+    TextEndsWith,
+    /// The number is equal to the value (`cond_is_int`).
+    NumberIs,
+    /// The number isn't equal to the value (`cond_isn_int`).
+    NumberIsNot,
+    /// The number is less than the value (`cond_lt_int`).
+    NumberLessThan,
+    /// The number is greater than the value (`cond_gt_int`).
+    NumberGreaterThan,
+    /// The date is equal to the value (`cond_is_date`).
+    DateIs,
+    /// The date isn't equal to the value (`cond_isn_date`).
+    DateIsNot,
+    /// The date is before the value (`cond_bef_date`).
+    DateBefore,
+    /// The date is after the value (`cond_aft_date`).
+    DateAfter,
+}
+
+impl Comparison {
+    fn from_name(name: &str) -> Option<Self> {
+        let comparison = match name {
+            "cond_is_str" => Comparison::TextIs,
+            "cond_isn_str" => Comparison::TextIsNot,
+            "cond_con_str" => Comparison::TextContains,
+            "cond_dnc_str" => Comparison::TextDoesNotContain,
+            "cond_sta_str" => Comparison::TextStartsWith,
+            "cond_end_str" => Comparison::TextEndsWith,
+            "cond_is_int" => Comparison::NumberIs,
+            "cond_isn_int" => Comparison::NumberIsNot,
+            "cond_lt_int" => Comparison::NumberLessThan,
+            "cond_gt_int" => Comparison::NumberGreaterThan,
+            "cond_is_date" => Comparison::DateIs,
+            "cond_isn_date" => Comparison::DateIsNot,
+            "cond_bef_date" => Comparison::DateBefore,
+            "cond_aft_date" => Comparison::DateAfter,
+            _ => return None,
+        };
+        Some(comparison)
+    }
+}
+
+/// The value that a rule compares the track field with.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RuleValue {
+    Text(String),
+    Integer(u32),
+    /// A date as stored by Serato.
+    Date(String),
+}
+
+/// A filter rule of a smart crate.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Rule {
+    Filter {
+        field: RuleField,
+        comparison: Comparison,
+        value: RuleValue,
+    },
+    /// A rule that references an unknown field or comparison operator, or that lacks a value.
+    ///
+    /// The fields of the rule record are kept as they are.
+    Raw(Vec<Field>),
+}
+
+impl Rule {
+    fn from_fields(fields: Vec<Field>) -> Self {
+        let mut field = None;
+        let mut comparison = None;
+        let mut value = None;
+        for f in &fields {
+            match f {
+                Field::RuleField(id) => field = RuleField::from_id(*id),
+                Field::RuleComparison(name) => comparison = Comparison::from_name(name),
+                Field::RuleValueText(text) => value = Some(RuleValue::Text(text.clone())),
+                Field::RuleValueInteger(number) => value = Some(RuleValue::Integer(*number)),
+                Field::RuleValueDate(date) => value = Some(RuleValue::Date(date.clone())),
+                _ => (),
+            }
+        }
+        match (field, comparison, value) {
+            (Some(field), Some(comparison), Some(value)) => Rule::Filter {
+                field,
+                comparison,
+                value,
+            },
+            _ => Rule::Raw(fields),
+        }
+    }
+}
+
+/// Represents a Serato smart crate file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SmartCrate {
+    /// The name of the smart crate (i.e. the file name without extension).
+    pub name: String,
+    /// The smart crate version.
+    pub version: Option<String>,
+    /// The filter rules in the order that they are shown in Serato.
+    pub rules: Vec<Rule>,
+    /// Whether a track needs to match all or any of the rules.
+    pub match_mode: MatchMode,
+    /// Whether the track list is updated automatically when the library changes.
+    pub live_update: bool,
+}
+
+impl SmartCrate {
+    /// Parse the data of a smart crate file with the given name.
+    pub fn parse(name: impl Into<String>, input: &[u8]) -> Result<Self, Error> {
+        let mut smart_crate = SmartCrate {
+            name: name.into(),
+            version: None,
+            rules: vec![],
+            match_mode: MatchMode::All,
+            live_update: false,
+        };
+        for field in database::parse(input)? {
+            match field {
+                Field::Version(version) => smart_crate.version = Some(version),
+                Field::SmartCrateRule(fields) => smart_crate.rules.push(Rule::from_fields(fields)),
+                Field::SmartCrateMatchAll(fields) => {
+                    if let Some(false) = first_bool(&fields) {
+                        smart_crate.match_mode = MatchMode::Any;
+                    }
+                }
+                Field::SmartCrateLiveUpdate(fields) => {
+                    smart_crate.live_update = first_bool(&fields).unwrap_or(false);
+                }
+                _ => (),
+            }
+        }
+        Ok(smart_crate)
+    }
+
+    /// Read and parse the smart crate file at the given path.
+    ///
+    /// The name of the smart crate is derived from the file name.
+    pub fn read_from_path(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let name = path
+            .file_stem()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let data = fs::read(path)?;
+        Self::parse(name, &data)
+    }
+}
+
+/// Returns the value of the first boolean field (the flag containers contain a single `brut`
+/// field).
+fn first_bool(fields: &[Field]) -> Option<bool> {
+    fields.iter().find_map(|field| match field {
+        Field::UnknownBoolean { value, .. } => Some(*value),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+fn encode_text(text: &str) -> Vec<u8> {
+    text.encode_utf16().flat_map(|c| c.to_be_bytes()).collect()
+}
+
+#[test]
+fn test_parse_smart_crate() {
+    let rule = |comparison: &str, field: u32, value: Vec<u8>| -> Vec<u8> {
+        let mut data = database::encode_field(b"trft", &encode_text(comparison));
+        data.extend(database::encode_field(b"urkt", &field.to_be_bytes()));
+        data.extend(value);
+        database::encode_field(b"rurt", &data)
+    };
+
+    let mut input =
+        database::encode_field(b"vrsn", &encode_text("1.0/Serato ScratchLive Smart Crate"));
+    input.extend(database::encode_field(
+        b"rart",
+        &database::encode_field(b"brut", &[0]),
+    ));
+    input.extend(database::encode_field(
+        b"rlut",
+        &database::encode_field(b"brut", &[1]),
+    ));
+    input.extend(rule(
+        "cond_con_str",
+        9,
+        database::encode_field(b"trpt", &encode_text("House")),
+    ));
+    input.extend(rule(
+        "cond_gt_int",
+        15,
+        database::encode_field(b"urpt", &120u32.to_be_bytes()),
+    ));
+    input.extend(rule(
+        "cond_aft_date",
+        25,
+        database::encode_field(b"trtt", &encode_text("2020-01-01")),
+    ));
+    input.extend(rule(
+        "cond_is_str",
+        1234,
+        database::encode_field(b"trpt", &encode_text("?")),
+    ));
+
+    let smart_crate = SmartCrate::parse("House", &input).unwrap();
+    assert_eq!(smart_crate.name, "House");
+    assert_eq!(
+        smart_crate.version.as_deref(),
+        Some("1.0/Serato ScratchLive Smart Crate")
+    );
+    assert_eq!(smart_crate.match_mode, MatchMode::Any);
+    assert!(smart_crate.live_update);
+    assert_eq!(smart_crate.rules.len(), 4);
+    assert_eq!(
+        smart_crate.rules[0],
+        Rule::Filter {
+            field: RuleField::Genre,
+            comparison: Comparison::TextContains,
+            value: RuleValue::Text(String::from("House")),
+        }
+    );
+    assert_eq!(
+        smart_crate.rules[1],
+        Rule::Filter {
+            field: RuleField::BPM,
+            comparison: Comparison::NumberGreaterThan,
+            value: RuleValue::Integer(120),
+        }
+    );
+    assert_eq!(
+        smart_crate.rules[2],
+        Rule::Filter {
+            field: RuleField::Added,
+            comparison: Comparison::DateAfter,
+            value: RuleValue::Date(String::from("2020-01-01")),
+        }
+    );
+    assert_eq!(
+        smart_crate.rules[3],
+        Rule::Raw(vec![
+            Field::RuleComparison(String::from("cond_is_str")),
+            Field::RuleField(1234),
+            Field::RuleValueText(String::from("?")),
+        ])
+    );
+}
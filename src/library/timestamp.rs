@@ -0,0 +1,152 @@
+//! Conversion of the dates in library files.
+//!
+//! Serato stores dates as Unix timestamps (seconds since 1970-01-01 00:00:00 UTC). Most fields
+//! are 32-bit unsigned integers (e.g. `uadd` and `utme` in the `database V2` file and the start
+//! and end times in history sessions), which will overflow on 2106-02-07. To be prepared for
+//! this, timestamps are passed around as `u64` values and only checked for the 32-bit range when
+//! they are written (see [`to_u32`]).
+//!
+//! Serato writes `0` if a date is unknown, so that value is treated like a missing field.
+//!
+//! ```
+//! use std::time::{Duration, UNIX_EPOCH};
+//! use triseratops::library::timestamp;
+//!
+//! let time = UNIX_EPOCH + Duration::from_secs(1_580_992_490);
+//! assert_eq!(timestamp::to_system_time(1_580_992_490), Some(time));
+//! assert_eq!(timestamp::from_system_time(time), Some(1_580_992_490));
+//! assert_eq!(timestamp::to_system_time(0), None);
+//! ```
+
+use crate::error::Error;
+use std::convert::TryFrom;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Convert a timestamp into a [`SystemTime`].
+///
+/// Returns `None` for `0`, which Serato uses for unknown dates.
+pub fn to_system_time(timestamp: u64) -> Option<SystemTime> {
+    if timestamp == 0 {
+        return None;
+    }
+    UNIX_EPOCH.checked_add(Duration::from_secs(timestamp))
+}
+
+/// Convert a [`SystemTime`] into a timestamp, truncating fractional seconds.
+///
+/// Returns `None` if the time isn't after the Unix epoch, because it can't be distinguished from
+/// an unknown date.
+pub fn from_system_time(time: SystemTime) -> Option<u64> {
+    let timestamp = time.duration_since(UNIX_EPOCH).ok()?.as_secs();
+    if timestamp == 0 {
+        None
+    } else {
+        Some(timestamp)
+    }
+}
+
+/// Convert a [`SystemTime`] into a timestamp like [`from_system_time`], but fail with an error
+/// that tells why the time can't be stored.
+pub(super) fn try_from_system_time(time: SystemTime) -> Result<u64, Error> {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) if duration.as_secs() > 0 => Ok(duration.as_secs()),
+        Ok(_) => Err(Error::TimestampOutOfRange { timestamp: 0 }),
+        Err(err) => Err(Error::TimeBeforeEpoch {
+            duration: err.duration(),
+        }),
+    }
+}
+
+/// Convert a timestamp into the value of a 32-bit field.
+///
+/// Fails for dates after 2106-02-07 06:28:15 UTC instead of silently wrapping around.
+pub fn to_u32(timestamp: u64) -> Result<u32, Error> {
+    u32::try_from(timestamp).map_err(|_| Error::TimestampOutOfRange { timestamp })
+}
+
+/// Format a timestamp as UTC date and time in RFC 3339 format (e.g. `2020-09-13T12:26:40Z`).
+pub fn to_rfc3339(timestamp: u64) -> String {
+    let days = timestamp / 86400;
+    let seconds = timestamp % 86400;
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// Convert the number of days since the Unix epoch into year, month and day of the proleptic
+/// Gregorian calendar (see <https://howardhinnant.github.io/date_algorithms.html>).
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Decode a big-endian timestamp with 4 or 8 bytes.
+pub(super) fn decode(data: &[u8]) -> Option<u64> {
+    match *data {
+        [a, b, c, d] => Some(u64::from(u32::from_be_bytes([a, b, c, d]))),
+        [a, b, c, d, e, f, g, h] => Some(u64::from_be_bytes([a, b, c, d, e, f, g, h])),
+        _ => None,
+    }
+}
+
+#[test]
+fn test_timestamp() {
+    assert_eq!(to_system_time(0), None);
+    assert_eq!(from_system_time(UNIX_EPOCH), None);
+    assert_eq!(from_system_time(UNIX_EPOCH - Duration::from_secs(1)), None);
+    assert!(matches!(
+        try_from_system_time(UNIX_EPOCH - Duration::from_millis(1500)),
+        Err(Error::TimeBeforeEpoch { duration }) if duration == Duration::from_millis(1500)
+    ));
+    assert!(matches!(
+        try_from_system_time(UNIX_EPOCH),
+        Err(Error::TimestampOutOfRange { timestamp: 0 })
+    ));
+    assert_eq!(
+        try_from_system_time(UNIX_EPOCH + Duration::from_secs(1)).unwrap(),
+        1
+    );
+    let time = UNIX_EPOCH + Duration::from_millis(1_600_000_000_999);
+    assert_eq!(from_system_time(time), Some(1_600_000_000));
+
+    // The 32-bit fields overflow in 2106
+    let last = u64::from(u32::MAX);
+    assert_eq!(to_u32(last).unwrap(), u32::MAX);
+    assert!(matches!(
+        to_u32(last + 1),
+        Err(Error::TimestampOutOfRange { timestamp }) if timestamp == last + 1
+    ));
+    assert_eq!(
+        to_system_time(last + 1),
+        Some(UNIX_EPOCH + Duration::from_secs(last + 1))
+    );
+
+    assert_eq!(decode(&[0x5F, 0x5E, 0x10, 0x00]), Some(1_600_000_000));
+    assert_eq!(decode(&[0, 0, 0, 1, 0, 0, 0, 0]), Some(last + 1));
+    assert_eq!(decode(&[0, 1]), None);
+
+    assert_eq!(to_rfc3339(0), "1970-01-01T00:00:00Z");
+    assert_eq!(to_rfc3339(951_782_400), "2000-02-29T00:00:00Z");
+    assert_eq!(to_rfc3339(1_600_000_000), "2020-09-13T12:26:40Z");
+    assert_eq!(to_rfc3339(last), "2106-02-07T06:28:15Z");
+}
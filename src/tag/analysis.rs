@@ -2,11 +2,21 @@
 use super::format::{enveloped, flac, id3, mp4, ogg, Tag};
 use super::generic::Version;
 use super::util::{take_version, write_version};
+use super::TagFormat;
 use crate::error::Error;
 use crate::util::{parse_utf8, Res};
 use nom::error::ParseError;
 use std::io;
 
+/// The most recent known analysis version written by Serato DJ.
+pub const LATEST_ANALYSIS_VERSION: Version = Version { major: 2, minor: 1 };
+
+/// The oldest analysis version that Serato DJ accepts without re-analyzing the track.
+///
+/// Files analyzed by earlier versions (e.g. the `0.0` version found in some Ogg files) are
+/// treated as outdated.
+pub const MIN_ANALYSIS_VERSION: Version = Version { major: 2, minor: 0 };
+
 /// Represents the  `Serato Analysis` tag.
 ///
 /// This is probably the Serato Version number that performed the analysis.
@@ -23,10 +33,57 @@ use std::io;
 ///     println!("{:?}", markers);
 /// }
 /// ```
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Analysis {
     /// The analysis version.
     pub version: Version,
+    /// Trailing bytes after the version.
+    ///
+    /// Serato doesn't write these, but some third-party tools do. They are kept so that they are
+    /// written back unchanged. Ogg tags store the version as text and never contain extra bytes.
+    pub extra: Vec<u8>,
+}
+
+impl Analysis {
+    /// Creates a new `Serato Analysis` tag with the given version.
+    ///
+    /// Writing this tag to a file that hasn't been analyzed by Serato yet prevents Serato from
+    /// treating the file as unanalyzed (and overwriting the other tags). Use
+    /// [`LATEST_ANALYSIS_VERSION`] unless you need to mimic a specific Serato version.
+    ///
+    /// ```
+    /// use triseratops::tag::{Analysis, analysis::LATEST_ANALYSIS_VERSION, format::id3::ID3Tag};
+    ///
+    /// let analysis = Analysis::new(LATEST_ANALYSIS_VERSION);
+    /// let mut data = vec![];
+    /// analysis.write_id3(&mut data).expect("Failed to serialize data!");
+    /// assert_eq!(data, &[0x02, 0x01]);
+    /// ```
+    pub fn new(version: Version) -> Self {
+        Self {
+            version,
+            extra: vec![],
+        }
+    }
+
+    /// Parse the tag data from a tag of the given format, but reject trailing bytes after the
+    /// version instead of keeping them in [`extra`](Self::extra).
+    pub fn parse_strict(input: &[u8], tag_format: TagFormat) -> Result<Self, Error> {
+        match tag_format {
+            TagFormat::ID3 => {
+                let (_, version) = nom::combinator::all_consuming(nom::error::context(
+                    "take version",
+                    take_version,
+                ))(input)?;
+                Ok(Self::new(version))
+            }
+            TagFormat::FLAC | TagFormat::MP4 => {
+                let content = enveloped::decode_enveloped(input, Self::NAME)?;
+                Self::parse_strict(&content, TagFormat::ID3)
+            }
+            TagFormat::Ogg => parse_analysis_ogg(input),
+        }
+    }
 }
 
 impl Tag for Analysis {
@@ -38,7 +95,7 @@ impl Tag for Analysis {
     }
 
     fn write(&self, writer: impl io::Write) -> Result<usize, Error> {
-        write_analysis(writer, &self)
+        write_analysis(writer, self)
     }
 }
 
@@ -60,7 +117,7 @@ impl ogg::OggTag for Analysis {
     }
 
     fn write_ogg(&self, writer: impl io::Write) -> Result<usize, Error> {
-        write_analysis_ogg(writer, &self)
+        write_analysis_ogg(writer, self)
     }
 }
 
@@ -82,7 +139,11 @@ fn take_ascii_u8(input: &[u8]) -> Res<&[u8], u8> {
 /// Returns an [`Analysis` struct](Analysis) parsed from the input slice.
 fn take_analysis(input: &[u8]) -> Res<&[u8], Analysis> {
     let (input, version) = nom::error::context("take version", take_version)(input)?;
-    let analysis = Analysis { version };
+    let (input, extra) = nom::combinator::rest(input)?;
+    let analysis = Analysis {
+        version,
+        extra: extra.to_vec(),
+    };
 
     Ok((input, analysis))
 }
@@ -95,7 +156,7 @@ fn take_analysis_ogg(input: &[u8]) -> Res<&[u8], Analysis> {
     let (input, minor) = nom::error::context("take major version", take_ascii_u8)(input)?;
     let version = Version { major, minor };
 
-    let analysis = Analysis { version };
+    let analysis = Analysis::new(version);
     Ok((input, analysis))
 }
 
@@ -110,17 +171,16 @@ pub fn parse_analysis_ogg(input: &[u8]) -> Result<Analysis, Error> {
 }
 
 /// Serialize [`Analysis` struct](Analysis) to bytes.
-pub fn write_analysis(writer: impl io::Write, analysis: &Analysis) -> Result<usize, Error> {
-    write_version(writer, &analysis.version)
+pub fn write_analysis(mut writer: impl io::Write, analysis: &Analysis) -> Result<usize, Error> {
+    let mut bytes_written = write_version(&mut writer, &analysis.version)?;
+    bytes_written += writer.write(&analysis.extra)?;
+    Ok(bytes_written)
 }
 
 /// Serialize [`Analysis` struct](Analysis) to bytes ([Ogg](super::format::ogg) version).
 pub fn write_analysis_ogg(mut writer: impl io::Write, analysis: &Analysis) -> Result<usize, Error> {
-    Ok(writer.write(&[
-        analysis.version.major + 0x30,
-        b'.',
-        analysis.version.minor + 0x30,
-    ])?)
+    let version = format!("{}.{}", analysis.version.major, analysis.version.minor);
+    Ok(writer.write(version.as_bytes())?)
 }
 
 #[test]
@@ -128,13 +188,76 @@ fn test_write_analysis() {
     use std::io::Cursor;
 
     let mut writer = Cursor::new(vec![0; 15]);
-    let bytes_written = write_analysis(
-        &mut writer,
-        &Analysis {
-            version: Version { major: 2, minor: 4 },
-        },
-    )
-    .unwrap();
+    let bytes_written =
+        write_analysis(&mut writer, &Analysis::new(Version { major: 2, minor: 4 })).unwrap();
     assert_eq!(bytes_written, 2);
     assert_eq!(&writer.get_ref()[..2], &[2, 4]);
 }
+
+#[test]
+fn test_new_analysis_roundtrip() {
+    use super::format::{flac::FLACTag, id3::ID3Tag, mp4::MP4Tag};
+    use std::io::Cursor;
+
+    let analysis = Analysis::new(LATEST_ANALYSIS_VERSION);
+    assert_eq!(analysis.version, Version { major: 2, minor: 1 });
+
+    let mut writer = Cursor::new(vec![]);
+    analysis.write_id3(&mut writer).unwrap();
+    assert_eq!(Analysis::parse_id3(writer.get_ref()).unwrap(), analysis);
+
+    let mut writer = Cursor::new(vec![]);
+    analysis.write_flac(&mut writer).unwrap();
+    assert_eq!(Analysis::parse_flac(writer.get_ref()).unwrap(), analysis);
+
+    let mut writer = Cursor::new(vec![]);
+    analysis.write_mp4(&mut writer).unwrap();
+    assert_eq!(Analysis::parse_mp4(writer.get_ref()).unwrap(), analysis);
+}
+
+#[test]
+fn test_write_analysis_ogg() {
+    let mut writer = vec![];
+    let analysis = Analysis::new(Version {
+        major: 2,
+        minor: 10,
+    });
+    let bytes_written = write_analysis_ogg(&mut writer, &analysis).unwrap();
+    assert_eq!(bytes_written, 4);
+    assert_eq!(&writer[..], b"2.10");
+    assert_eq!(parse_analysis_ogg(&writer).unwrap(), analysis);
+}
+
+#[test]
+fn test_analysis_extra_bytes() {
+    use super::format::{flac::FLACTag, id3::ID3Tag};
+    use std::io::Cursor;
+
+    let payloads: [&[u8]; 3] = [
+        &[0x02, 0x01],
+        &[0x02, 0x01, 0x00],
+        &[0x02, 0x01, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08],
+    ];
+    for payload in &payloads {
+        let analysis = Analysis::parse_id3(payload).unwrap();
+        assert_eq!(analysis.version, Version { major: 2, minor: 1 });
+        assert_eq!(analysis.extra, &payload[2..]);
+
+        let mut writer = Cursor::new(vec![]);
+        let bytes_written = analysis.write_id3(&mut writer).unwrap();
+        assert_eq!(bytes_written, payload.len());
+        assert_eq!(writer.get_ref().as_slice(), *payload);
+
+        let mut writer = Cursor::new(vec![]);
+        analysis.write_flac(&mut writer).unwrap();
+        assert_eq!(Analysis::parse_flac(writer.get_ref()).unwrap(), analysis);
+
+        let strict = Analysis::parse_strict(writer.get_ref(), TagFormat::FLAC);
+        if payload.len() == 2 {
+            assert_eq!(strict.unwrap(), analysis);
+        } else {
+            assert!(strict.is_err());
+            assert!(Analysis::parse_strict(payload, TagFormat::ID3).is_err());
+        }
+    }
+}
@@ -6,7 +6,7 @@ use super::format::id3;
 use super::format::mp4;
 use super::format::Tag;
 use super::generic::Version;
-use super::util::{take_version, write_version};
+use super::util::{take_padding, take_version, write_padding, write_version};
 use crate::error::Error;
 use crate::util::{take_until_nullbyte, Res};
 use std::io;
@@ -27,7 +27,7 @@ use std::io;
 ///     println!("{:?}", content);
 /// }
 /// ```
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Autotags {
     /// The tag version.
     pub version: Version,
@@ -37,6 +37,8 @@ pub struct Autotags {
     pub auto_gain: f64,
     /// The track's gain value (manual?).
     pub gain_db: f64,
+    /// The number of null bytes that the tag data was padded with (written back unchanged).
+    pub padding: usize,
 }
 
 impl Tag for Autotags {
@@ -48,7 +50,7 @@ impl Tag for Autotags {
     }
 
     fn write(&self, writer: impl io::Write) -> Result<usize, Error> {
-        write_autotags(writer, &self)
+        write_autotags(writer, self)
     }
 }
 
@@ -88,30 +90,102 @@ fn take_autotags(input: &[u8]) -> Res<&[u8], Autotags> {
     let (input, bpm) = take_double_str(input)?;
     let (input, auto_gain) = take_double_str(input)?;
     let (input, gain_db) = take_double_str(input)?;
+    let (input, padding) = take_padding(input)?;
 
     let autotags = Autotags {
         version,
         bpm,
         auto_gain,
         gain_db,
+        padding,
     };
 
     Ok((input, autotags))
 }
 
+/// Writes an `f64` as zero-terminated ASCII chars with a fixed number of decimals.
+///
+/// Serato never uses exponent notation and writes values that round to zero without a sign (i.e.
+/// `0.000` instead of `-0.000`). Non-finite values can't be represented and result in an
+/// [`InvalidNumber`](Error::InvalidNumber) error.
 pub fn write_double_str(
     mut writer: impl io::Write,
     number: f64,
     width: usize,
 ) -> Result<usize, Error> {
-    let number_str = format!("{:.*}\0", width, number);
+    if !number.is_finite() {
+        return Err(Error::InvalidNumber { value: number });
+    }
+    let mut number_str = format!("{:.*}", width, number);
+    if number_str.starts_with('-') && number_str.bytes().all(|b| matches!(b, b'-' | b'0' | b'.')) {
+        number_str.remove(0);
+    }
+    number_str.push('\0');
     Ok(writer.write(number_str.as_bytes())?)
 }
 
+#[test]
+fn test_write_double_str() {
+    use std::io::Cursor;
+
+    let mut writer = Cursor::new(vec![]);
+    assert_eq!(write_double_str(&mut writer, -3.3579, 3).unwrap(), 7);
+    assert_eq!(writer.get_ref().as_slice(), b"-3.358\0");
+
+    let mut writer = Cursor::new(vec![]);
+    write_double_str(&mut writer, 115.0, 2).unwrap();
+    assert_eq!(writer.get_ref().as_slice(), b"115.00\0");
+
+    let mut writer = Cursor::new(vec![]);
+    write_double_str(&mut writer, 1e20, 3).unwrap();
+    assert_eq!(writer.get_ref().as_slice(), b"100000000000000000000.000\0");
+
+    let mut writer = Cursor::new(vec![]);
+    write_double_str(&mut writer, -0.0001, 3).unwrap();
+    assert_eq!(writer.get_ref().as_slice(), b"0.000\0");
+
+    assert!(write_double_str(Cursor::new(vec![]), f64::NAN, 3).is_err());
+    assert!(write_double_str(Cursor::new(vec![]), f64::NEG_INFINITY, 3).is_err());
+}
+
+/// Serialize the [`Autotags` struct](Autotags) to bytes.
+///
+/// The BPM value is written with 2 decimals, the gain values are written with 3 decimals.
 pub fn write_autotags(mut writer: impl io::Write, autotags: &Autotags) -> Result<usize, Error> {
     let mut bytes_written = write_version(&mut writer, &autotags.version)?;
     bytes_written += write_double_str(&mut writer, autotags.bpm, 2)?;
     bytes_written += write_double_str(&mut writer, autotags.auto_gain, 3)?;
     bytes_written += write_double_str(&mut writer, autotags.gain_db, 3)?;
+    bytes_written += write_padding(&mut writer, autotags.padding)?;
     Ok(bytes_written)
 }
+
+#[test]
+fn test_write_autotags() {
+    use std::io::Cursor;
+
+    let autotags = Autotags {
+        version: Version { major: 1, minor: 1 },
+        bpm: 115.0,
+        auto_gain: -3.2571,
+        gain_db: 7.44,
+        padding: 0,
+    };
+    let mut writer = Cursor::new(vec![]);
+    let bytes_written = write_autotags(&mut writer, &autotags).unwrap();
+    let output = writer.get_ref().as_slice();
+    assert_eq!(bytes_written, output.len());
+    assert_eq!(
+        output,
+        &[
+            0x01, 0x01, 0x31, 0x31, 0x35, 0x2E, 0x30, 0x30, 0x00, 0x2D, 0x33, 0x2E, 0x32, 0x35,
+            0x37, 0x00, 0x37, 0x2E, 0x34, 0x34, 0x30, 0x00
+        ][..]
+    );
+
+    let reparsed = Autotags::parse(output).unwrap();
+    assert_eq!(reparsed.version, autotags.version);
+    assert!((reparsed.bpm - autotags.bpm).abs() < 0.005);
+    assert!((reparsed.auto_gain - autotags.auto_gain).abs() < 0.0005);
+    assert!((reparsed.gain_db - autotags.gain_db).abs() < 0.0005);
+}
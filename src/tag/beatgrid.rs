@@ -2,16 +2,19 @@
 
 use super::format::{enveloped, flac, id3, mp4, Tag};
 use super::generic::Version;
-use super::util::{take_version, write_version};
+use super::util::{take_padding, take_version, write_padding, write_version};
+use super::validation::{Finding, Problem};
+use super::TagKind;
 use crate::error::Error;
 use crate::util::Res;
 use std::io;
+use std::time::Duration;
 
 /// Represents the terminal beatgrid marker in the `Serato BeatGrid` tag.
 ///
 /// The last beatgrid marker always has to be a terminal one. This is also the case if the tag only
 /// contains a single beatgrid marker.
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct TerminalMarker {
     /// The position in seconds.
     pub position: f32,
@@ -22,7 +25,7 @@ pub struct TerminalMarker {
 /// Represents a non-terminal beatgrid marker in the `Serato BeatGrid` tag.
 ///
 /// All beatgrid markers before the last one are non-terminal beatgrid markers.
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct NonTerminalMarker {
     /// The position in seconds.
     pub position: f32,
@@ -30,6 +33,26 @@ pub struct NonTerminalMarker {
     pub beats_till_next_marker: u32,
 }
 
+/// The lowest tempo that is considered plausible by [`Beatgrid::validate()`].
+pub const MIN_BPM: f32 = 20.0;
+
+/// The highest tempo that is considered plausible by [`Beatgrid::validate()`].
+pub const MAX_BPM: f32 = 500.0;
+
+/// A section of the beatgrid with constant tempo (see [`Beatgrid::segments()`]).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Segment {
+    /// The start position in milliseconds.
+    pub start_position_millis: f64,
+    /// The end position in milliseconds, or `None` for the last segment that lasts until the end
+    /// of the track.
+    pub end_position_millis: Option<f64>,
+    /// The number of beats in the segment, or `None` for the last segment.
+    pub beats: Option<u32>,
+    /// The beats per minute (BPM) of the segment.
+    pub bpm: f64,
+}
+
 /// Represents the `Serato BeatGrid` tag.
 ///
 /// It stores the Beatgrid as a sequence of zero or more [non-terminal beatgrid markers](NonTerminalMarker) and
@@ -47,7 +70,7 @@ pub struct NonTerminalMarker {
 ///     println!("{:?}", content);
 /// }
 /// ```
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Beatgrid {
     /// The analysis version.
     pub version: Version,
@@ -57,6 +80,431 @@ pub struct Beatgrid {
     pub terminal_marker: TerminalMarker,
     /// A single footer byte that is apparently random (?).
     pub footer: u8,
+    /// The number of null bytes that the tag data was padded with (written back unchanged).
+    pub padding: usize,
+}
+
+impl Beatgrid {
+    /// Create a `Serato BeatGrid` tag from the given markers.
+    ///
+    /// The markers are validated using [`validate_markers()`], so that the tag is guaranteed to
+    /// be writable and consistent.
+    pub fn new(
+        non_terminal_markers: Vec<NonTerminalMarker>,
+        terminal_marker: TerminalMarker,
+    ) -> Result<Self, Error> {
+        validate_markers(&non_terminal_markers, &terminal_marker)?;
+        Ok(Self {
+            version: Version { major: 1, minor: 0 },
+            non_terminal_markers,
+            terminal_marker,
+            footer: 0x00,
+            padding: 0,
+        })
+    }
+
+    /// Create a beatgrid with constant tempo, i.e. a single terminal marker at the first beat.
+    ///
+    /// Returns an [`InvalidBeatgridMarker`](Error::InvalidBeatgridMarker) error if the BPM isn't
+    /// finite and positive or the position is negative (see [`validate_markers()`]).
+    ///
+    /// ```
+    /// use triseratops::tag::Beatgrid;
+    ///
+    /// let beatgrid = Beatgrid::constant(128.0, 250.0).unwrap();
+    /// assert_eq!(beatgrid.terminal_marker.position, 0.25);
+    /// assert_eq!(beatgrid.constant_bpm(0.01), Some(128.0));
+    /// assert!(Beatgrid::constant(0.0, 250.0).is_err());
+    /// ```
+    pub fn constant(bpm: f32, first_beat_millis: f64) -> Result<Self, Error> {
+        let terminal_marker = TerminalMarker {
+            position: (first_beat_millis / 1000.0) as f32,
+            bpm,
+        };
+        Self::new(vec![], terminal_marker)
+    }
+
+    /// Create a beatgrid with constant tempo like [`constant()`](Self::constant), with the
+    /// position of the first beat given as [`Duration`].
+    pub fn constant_from_duration(bpm: f32, first_beat: Duration) -> Result<Self, Error> {
+        Self::constant(bpm, first_beat.as_secs_f64() * 1000.0)
+    }
+
+    /// Returns the BPM of the track if the tempo of all [segments](Self::segments) differs by at
+    /// most `bpm_tolerance` from the BPM of the terminal marker, or `None` if the tempo varies.
+    ///
+    /// Grids with non-terminal markers are constant as well if the markers are placed on the
+    /// beats of the terminal marker's tempo.
+    pub fn constant_bpm(&self, bpm_tolerance: f32) -> Option<f32> {
+        let bpm = self.terminal_marker.bpm;
+        if !bpm.is_finite() {
+            return None;
+        }
+        let bpm_tolerance = f64::from(bpm_tolerance);
+        if self
+            .segments()
+            .all(|segment| (segment.bpm - f64::from(bpm)).abs() <= bpm_tolerance)
+        {
+            Some(bpm)
+        } else {
+            None
+        }
+    }
+
+    /// Returns `true` if the tempo of the track is constant within `bpm_tolerance` (see
+    /// [`constant_bpm()`](Self::constant_bpm)).
+    pub fn is_constant(&self, bpm_tolerance: f32) -> bool {
+        self.constant_bpm(bpm_tolerance).is_some()
+    }
+
+    /// Returns the position of the beat that is closest to the given position (in milliseconds).
+    ///
+    /// This is the same as [`quantize()`](Self::quantize) with a subdivision of 1.
+    pub fn nearest_beat(&self, position_millis: f64) -> f64 {
+        self.quantize(position_millis, 1)
+    }
+
+    /// Snap the given position (in milliseconds) to the closest fraction of a beat, e.g. to half
+    /// beats for a `subdivision` of 2 or quarter beats for 4. A `subdivision` of 0 is treated like
+    /// 1.
+    ///
+    /// The beats are the same as the ones returned by [`beats()`](Self::beats), i.e. positions
+    /// before the first marker are snapped using the tempo of the first segment, and positions
+    /// after the terminal marker using its BPM. The result is never negative. Positions in
+    /// segments without valid tempo are returned unchanged.
+    ///
+    /// ```
+    /// use triseratops::tag::Beatgrid;
+    ///
+    /// let beatgrid = Beatgrid::constant(120.0, 250.0).unwrap();
+    /// assert_eq!(beatgrid.nearest_beat(1340.0), 1250.0);
+    /// assert_eq!(beatgrid.quantize(1340.0, 4), 1375.0);
+    /// assert_eq!(beatgrid.quantize(100.0, 2), 0.0);
+    /// ```
+    pub fn quantize(&self, position_millis: f64, subdivision: u32) -> f64 {
+        let subdivision = f64::from(subdivision.max(1));
+        let mut segments = self.segments().peekable();
+        let mut segment = *segments.peek().expect("terminal segment");
+        for next in segments {
+            if next.start_position_millis > position_millis {
+                break;
+            }
+            segment = next;
+        }
+
+        let start = segment.start_position_millis;
+        let beat_length = match (segment.end_position_millis, segment.beats) {
+            (Some(end), Some(beats)) => (end - start) / f64::from(beats),
+            _ => 60_000.0 / segment.bpm,
+        };
+        snap(position_millis, start, beat_length / subdivision)
+    }
+
+    /// Check the beatgrid markers for invalid data, e.g. because downstream calculations would
+    /// divide by zero.
+    ///
+    /// In contrast to [`validate_markers()`], all problems are reported (ordered by the index of
+    /// the marker), and the tempo of the grid has to be plausible, i.e. within [`MIN_BPM`] and
+    /// [`MAX_BPM`]. Parsing is not affected by this.
+    pub fn validate(&self) -> Vec<Finding> {
+        let mut findings = vec![];
+        let mut push = |index, problem| {
+            findings.push(Finding {
+                tag: TagKind::Beatgrid,
+                index,
+                problem,
+            })
+        };
+
+        let positions = self
+            .non_terminal_markers
+            .iter()
+            .map(|marker| marker.position)
+            .chain(std::iter::once(self.terminal_marker.position));
+        let mut previous: Option<(usize, f32)> = None;
+        let mut is_ordered = true;
+        for (index, position) in positions.enumerate() {
+            if !is_valid_position(position) {
+                push(index, Problem::InvalidBeatgridPosition { position });
+                is_ordered = false;
+                continue;
+            }
+            match previous {
+                Some((previous_index, previous_position)) if position <= previous_position => {
+                    push(index, Problem::BeatgridMarkersOutOfOrder { previous_index });
+                    is_ordered = false;
+                }
+                _ => previous = Some((index, position)),
+            }
+        }
+
+        let bpm_range = f64::from(MIN_BPM)..=f64::from(MAX_BPM);
+        for (index, segment) in self.segments().enumerate() {
+            let beats = match segment.beats {
+                Some(beats) => beats,
+                None => continue,
+            };
+            // The tempo is meaningless if the markers are not ordered
+            if beats == 0 || (is_ordered && !bpm_range.contains(&segment.bpm)) {
+                push(index, Problem::InvalidBeatCount { beats });
+            }
+        }
+
+        let bpm = self.terminal_marker.bpm;
+        if !(MIN_BPM..=MAX_BPM).contains(&bpm) {
+            push(
+                self.non_terminal_markers.len(),
+                Problem::BpmOutOfRange { bpm },
+            );
+        }
+
+        findings.sort_by_key(|finding| finding.index);
+        findings
+    }
+
+    /// Shift the positions of all beatgrid markers by the given offset.
+    ///
+    /// Markers that would end up at a negative position are moved forward to their first beat at
+    /// or after the start of the track, so that the beats stay where they are. Non-terminal
+    /// markers that don't have any beats left before the next marker are removed. Use
+    /// [`shift()`](Self::shift) (in milliseconds) to get an error instead.
+    pub fn shift_positions(&mut self, offset_seconds: f32) {
+        for marker in &mut self.non_terminal_markers {
+            marker.position += offset_seconds;
+        }
+        self.terminal_marker.position += offset_seconds;
+
+        while let Some(marker) = self.non_terminal_markers.first_mut() {
+            if marker.position >= 0.0 {
+                return;
+            }
+
+            let next_position = self
+                .non_terminal_markers
+                .get(1)
+                .map(|marker| marker.position)
+                .unwrap_or(self.terminal_marker.position);
+            let marker = &mut self.non_terminal_markers[0];
+            if next_position > 0.0 && marker.beats_till_next_marker > 0 {
+                let beat_length =
+                    (next_position - marker.position) / marker.beats_till_next_marker as f32;
+                let beats = (-marker.position / beat_length).ceil() as u32;
+                if beats < marker.beats_till_next_marker {
+                    marker.position = (marker.position + beats as f32 * beat_length).max(0.0);
+                    marker.beats_till_next_marker -= beats;
+                    return;
+                }
+            }
+            self.non_terminal_markers.remove(0);
+        }
+
+        let marker = &mut self.terminal_marker;
+        if marker.position < 0.0 {
+            let position = if marker.bpm > 0.0 {
+                let beat_length = 60.0 / marker.bpm;
+                let beats = (-marker.position / beat_length).ceil();
+                marker.position + beats * beat_length
+            } else {
+                0.0
+            };
+            marker.position = position.max(0.0);
+        }
+    }
+
+    /// Move all beatgrid markers by the given offset (in milliseconds), e.g. to fix a beatgrid
+    /// that is consistently late.
+    ///
+    /// Unlike [`shift_positions()`](Self::shift_positions), markers are never dropped or moved
+    /// to another beat. Instead, an [`InvalidBeatgridMarker`](Error::InvalidBeatgridMarker) error
+    /// is returned and the beatgrid is left unchanged if a marker would end up at a negative
+    /// position or the resulting beatgrid is invalid (see [`validate_markers()`]).
+    ///
+    /// ```
+    /// use triseratops::tag::Beatgrid;
+    ///
+    /// let mut beatgrid = Beatgrid::constant(120.0, 250.0).unwrap();
+    /// beatgrid.shift(-125.0).unwrap();
+    /// assert_eq!(beatgrid.terminal_marker.position, 0.125);
+    /// assert!(beatgrid.shift(-250.0).is_err());
+    /// assert_eq!(beatgrid.terminal_marker.position, 0.125);
+    /// ```
+    pub fn shift(&mut self, offset_millis: f64) -> Result<(), Error> {
+        let offset_seconds = offset_millis / 1000.0;
+        self.map_markers(
+            |position| (f64::from(position) + offset_seconds) as f32,
+            self.terminal_marker.bpm,
+        )
+    }
+
+    /// Scale all beatgrid marker positions by the given factor and divide the BPM of the terminal
+    /// marker by it, e.g. after the audio has been time-stretched.
+    ///
+    /// A factor of 2 corresponds to audio that is played at half speed, i.e. all beats are twice
+    /// as far from the start of the track and the tempo is halved. The number of beats between
+    /// the markers is unchanged. Returns an [`InvalidNumber`](Error::InvalidNumber) error if the
+    /// factor isn't finite and positive, or an
+    /// [`InvalidBeatgridMarker`](Error::InvalidBeatgridMarker) error if the resulting beatgrid is
+    /// invalid. The beatgrid is left unchanged on error.
+    ///
+    /// ```
+    /// use triseratops::tag::Beatgrid;
+    ///
+    /// let mut beatgrid = Beatgrid::constant(120.0, 250.0).unwrap();
+    /// beatgrid.stretch(2.0).unwrap();
+    /// assert_eq!(beatgrid.terminal_marker.position, 0.5);
+    /// assert_eq!(beatgrid.terminal_marker.bpm, 60.0);
+    /// assert!(beatgrid.stretch(0.0).is_err());
+    /// ```
+    pub fn stretch(&mut self, factor: f64) -> Result<(), Error> {
+        if !(factor.is_finite() && factor > 0.0) {
+            return Err(Error::InvalidNumber { value: factor });
+        }
+        self.map_markers(
+            |position| (f64::from(position) * factor) as f32,
+            (f64::from(self.terminal_marker.bpm) / factor) as f32,
+        )
+    }
+
+    /// Replace the markers by ones with mapped positions and the given terminal BPM, if the
+    /// result is valid.
+    fn map_markers(&mut self, position: impl Fn(f32) -> f32, bpm: f32) -> Result<(), Error> {
+        let non_terminal_markers: Vec<_> = self
+            .non_terminal_markers
+            .iter()
+            .map(|marker| NonTerminalMarker {
+                position: position(marker.position),
+                beats_till_next_marker: marker.beats_till_next_marker,
+            })
+            .collect();
+        let terminal_marker = TerminalMarker {
+            position: position(self.terminal_marker.position),
+            bpm,
+        };
+        validate_markers(&non_terminal_markers, &terminal_marker)?;
+        self.non_terminal_markers = non_terminal_markers;
+        self.terminal_marker = terminal_marker;
+        Ok(())
+    }
+
+    /// Returns the sections of the beatgrid with constant tempo.
+    ///
+    /// Each non-terminal marker starts a segment that ends at the next marker, and its BPM is
+    /// computed from the number of beats and the distance to that marker. The terminal marker
+    /// starts the last segment, which uses its BPM and lasts until the end of the track.
+    ///
+    /// Segments of invalid beatgrids are returned as well, even though their BPM may not be
+    /// finite (e.g. for segments with zero length). Such beatgrids are reported by
+    /// [`validate_markers()`] and [`TagContainer::validate()`](super::TagContainer::validate).
+    ///
+    /// ```
+    /// use triseratops::tag::beatgrid::{Beatgrid, NonTerminalMarker, TerminalMarker};
+    ///
+    /// let beatgrid = Beatgrid::new(
+    ///     vec![NonTerminalMarker { position: 0.0, beats_till_next_marker: 8 }],
+    ///     TerminalMarker { position: 4.0, bpm: 126.0 },
+    /// )
+    /// .unwrap();
+    /// let bpms: Vec<f64> = beatgrid.segments().map(|segment| segment.bpm).collect();
+    /// assert_eq!(bpms, vec![120.0, 126.0]);
+    /// ```
+    pub fn segments(&self) -> impl Iterator<Item = Segment> + '_ {
+        let next_positions = self
+            .non_terminal_markers
+            .iter()
+            .skip(1)
+            .map(|marker| marker.position)
+            .chain(std::iter::once(self.terminal_marker.position));
+        self.non_terminal_markers
+            .iter()
+            .zip(next_positions)
+            .map(|(marker, next_position)| {
+                let start_position_millis = f64::from(marker.position) * 1000.0;
+                let end_position_millis = f64::from(next_position) * 1000.0;
+                let beats = marker.beats_till_next_marker;
+                Segment {
+                    start_position_millis,
+                    end_position_millis: Some(end_position_millis),
+                    beats: Some(beats),
+                    bpm: f64::from(beats) * 60_000.0
+                        / (end_position_millis - start_position_millis),
+                }
+            })
+            .chain(std::iter::once(Segment {
+                start_position_millis: f64::from(self.terminal_marker.position) * 1000.0,
+                end_position_millis: None,
+                beats: None,
+                bpm: f64::from(self.terminal_marker.bpm),
+            }))
+    }
+
+    /// Returns the positions of all beats in milliseconds, from the start of the track up to (but
+    /// excluding) `track_duration_millis`.
+    ///
+    /// The beats between two markers are spaced evenly (see [`segments()`](Self::segments)), and
+    /// the beats after the terminal marker continue with its BPM. The grid doesn't necessarily
+    /// start at the first beat of the track, so the beats before the first marker are
+    /// extrapolated back to the start of the track using the tempo of the first segment.
+    ///
+    /// ```
+    /// use triseratops::tag::beatgrid::{Beatgrid, TerminalMarker};
+    ///
+    /// let terminal_marker = TerminalMarker { position: 1.25, bpm: 120.0 };
+    /// let beatgrid = Beatgrid::new(vec![], terminal_marker).unwrap();
+    /// let beats: Vec<f64> = beatgrid.beats(2500.0).collect();
+    /// assert_eq!(beats, vec![250.0, 750.0, 1250.0, 1750.0, 2250.0]);
+    /// ```
+    pub fn beats(&self, track_duration_millis: f64) -> impl Iterator<Item = f64> + '_ {
+        let first_segment = self.segments().next().expect("terminal segment");
+        let first_beat_length = 60_000.0 / first_segment.bpm;
+        let leading_beats = if is_valid_beat_length(first_beat_length) {
+            (first_segment.start_position_millis / first_beat_length).floor() as u64
+        } else {
+            0
+        };
+        let leading = (1..=leading_beats).rev().map(move |beat| {
+            (first_segment.start_position_millis - beat as f64 * first_beat_length).max(0.0)
+        });
+
+        let segments = self.segments().flat_map(move |segment| {
+            let start = segment.start_position_millis;
+            let (beats, beat_length) = match (segment.end_position_millis, segment.beats) {
+                (Some(end), Some(beats)) => (u64::from(beats), (end - start) / f64::from(beats)),
+                _ => {
+                    let beat_length = 60_000.0 / segment.bpm;
+                    if is_valid_beat_length(beat_length) {
+                        let beats = ((track_duration_millis - start) / beat_length).ceil();
+                        (beats.max(1.0) as u64, beat_length)
+                    } else {
+                        (1, 0.0)
+                    }
+                }
+            };
+            (0..beats).map(move |beat| start + beat as f64 * beat_length)
+        });
+
+        leading
+            .chain(segments)
+            .take_while(move |position| *position < track_duration_millis)
+    }
+}
+
+/// Returns the multiple of `step` after `start` that is closest to `position`, or `position` if the
+/// step is invalid.
+fn snap(position: f64, start: f64, step: f64) -> f64 {
+    if !is_valid_beat_length(step) {
+        return position;
+    }
+    let snapped = start + ((position - start) / step).round() * step;
+    if snapped < 0.0 {
+        snapped + step
+    } else {
+        snapped
+    }
+}
+
+/// Returns `true` if beats with the given distance (in milliseconds) can be enumerated.
+fn is_valid_beat_length(beat_length: f64) -> bool {
+    beat_length.is_finite() && beat_length > 0.0
 }
 
 impl Tag for Beatgrid {
@@ -68,7 +516,7 @@ impl Tag for Beatgrid {
     }
 
     fn write(&self, writer: impl io::Write) -> Result<usize, Error> {
-        write_beatgrid(writer, &self)
+        write_beatgrid(writer, self)
     }
 }
 
@@ -126,17 +574,19 @@ fn take_terminal_marker(input: &[u8]) -> Res<&[u8], TerminalMarker> {
 
 /// Take a [`Beatgrid` struct] parsed from the input slice.
 fn take_beatgrid(input: &[u8]) -> Res<&[u8], Beatgrid> {
-    let (input, version) = take_version(&input)?;
+    let (input, version) = take_version(input)?;
     let (input, non_terminal_markers) =
         nom::multi::length_count(take_non_terminal_marker_count, take_non_terminal_marker)(input)?;
     let (input, terminal_marker) = take_terminal_marker(input)?;
     let (input, footer) = nom::number::complete::u8(input)?;
+    let (input, padding) = take_padding(input)?;
 
     let beatgrid = Beatgrid {
         version,
         non_terminal_markers,
         terminal_marker,
         footer,
+        padding,
     };
     Ok((input, beatgrid))
 }
@@ -159,14 +609,589 @@ pub fn write_terminal_marker(
     Ok(bytes_written)
 }
 
+/// The error reason for marker positions that are rejected by [`is_valid_position()`].
+const POSITION_ERROR: &str = "position must be finite and non-negative";
+
+/// Returns `true` if the position of a beatgrid marker is valid (i.e. finite and non-negative).
+fn is_valid_position(position: f32) -> bool {
+    position.is_finite() && position >= 0.0
+}
+
+/// Check that the beatgrid markers describe a valid beatgrid.
+///
+/// A beatgrid is valid if all marker positions are finite, non-negative and strictly increasing,
+/// each non-terminal marker spans at least one beat and the BPM of the terminal marker is finite
+/// and positive. Otherwise, an [`InvalidBeatgridMarker`](Error::InvalidBeatgridMarker) error
+/// that contains the index of the first offending marker is returned.
+pub fn validate_markers(
+    non_terminal_markers: &[NonTerminalMarker],
+    terminal_marker: &TerminalMarker,
+) -> Result<(), Error> {
+    let mut previous_position = None;
+    for (index, marker) in non_terminal_markers.iter().enumerate() {
+        validate_position(index, marker.position, previous_position)?;
+        if marker.beats_till_next_marker < 1 {
+            return Err(Error::InvalidBeatgridMarker {
+                index,
+                reason: "number of beats till next marker must be at least 1",
+            });
+        }
+        previous_position = Some(marker.position);
+    }
+
+    let index = non_terminal_markers.len();
+    validate_position(index, terminal_marker.position, previous_position)?;
+    if !(terminal_marker.bpm.is_finite() && terminal_marker.bpm > 0.0) {
+        return Err(Error::InvalidBeatgridMarker {
+            index,
+            reason: "BPM must be finite and positive",
+        });
+    }
+
+    Ok(())
+}
+
+fn validate_position(
+    index: usize,
+    position: f32,
+    previous_position: Option<f32>,
+) -> Result<(), Error> {
+    if !is_valid_position(position) {
+        return Err(Error::InvalidBeatgridMarker {
+            index,
+            reason: POSITION_ERROR,
+        });
+    }
+    if let Some(previous_position) = previous_position {
+        if position <= previous_position {
+            return Err(Error::InvalidBeatgridMarker {
+                index,
+                reason: "position must be greater than the previous marker's position",
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Serialize the [`Beatgrid` struct](Beatgrid) to bytes.
+///
+/// Returns an [`InvalidBeatgridMarker`](Error::InvalidBeatgridMarker) error if a marker has a
+/// negative or non-finite position. Since the terminal marker is mandatory, the written marker
+/// count is always non-zero.
 pub fn write_beatgrid(mut writer: impl io::Write, beatgrid: &Beatgrid) -> Result<usize, Error> {
+    let positions = beatgrid
+        .non_terminal_markers
+        .iter()
+        .map(|marker| marker.position)
+        .chain(std::iter::once(beatgrid.terminal_marker.position));
+    for (index, position) in positions.enumerate() {
+        if !is_valid_position(position) {
+            return Err(Error::InvalidBeatgridMarker {
+                index,
+                reason: POSITION_ERROR,
+            });
+        }
+    }
+
     let mut bytes_written = write_version(&mut writer, &beatgrid.version)?;
     let num_markers = beatgrid.non_terminal_markers.len() as u32 + 1;
     bytes_written += writer.write(&num_markers.to_be_bytes())?;
     for marker in &beatgrid.non_terminal_markers {
-        bytes_written += write_non_terminal_marker(&mut writer, &marker)?;
+        bytes_written += write_non_terminal_marker(&mut writer, marker)?;
     }
     bytes_written += write_terminal_marker(&mut writer, &beatgrid.terminal_marker)?;
     bytes_written += writer.write(&[beatgrid.footer])?;
+    bytes_written += write_padding(writer, beatgrid.padding)?;
     Ok(bytes_written)
 }
+
+#[test]
+fn test_write_beatgrid() {
+    use std::io::Cursor;
+
+    let mut beatgrid = Beatgrid {
+        version: Version { major: 1, minor: 0 },
+        non_terminal_markers: vec![NonTerminalMarker {
+            position: 0.5,
+            beats_till_next_marker: 4,
+        }],
+        terminal_marker: TerminalMarker {
+            position: 2.5,
+            bpm: 120.0,
+        },
+        footer: 0x00,
+        padding: 0,
+    };
+
+    let mut writer = Cursor::new(vec![]);
+    let bytes_written = write_beatgrid(&mut writer, &beatgrid).unwrap();
+    let output = writer.get_ref().as_slice();
+    assert_eq!(bytes_written, 2 + 4 + 8 + 8 + 1);
+    assert_eq!(
+        output,
+        &[
+            0x01, 0x00, 0x00, 0x00, 0x00, 0x02, 0x3F, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04,
+            0x40, 0x20, 0x00, 0x00, 0x42, 0xF0, 0x00, 0x00, 0x00
+        ][..]
+    );
+    assert_eq!(Beatgrid::parse(output).unwrap(), beatgrid);
+
+    beatgrid.terminal_marker.position = -1.0;
+    let mut writer = Cursor::new(vec![]);
+    assert!(matches!(
+        write_beatgrid(&mut writer, &beatgrid),
+        Err(Error::InvalidBeatgridMarker { index: 1, .. })
+    ));
+    assert!(writer.get_ref().is_empty());
+
+    beatgrid.terminal_marker.position = 2.5;
+    beatgrid.non_terminal_markers[0].position = f32::NAN;
+    assert!(matches!(
+        write_beatgrid(Cursor::new(vec![]), &beatgrid),
+        Err(Error::InvalidBeatgridMarker { index: 0, .. })
+    ));
+}
+
+#[test]
+fn test_validate_markers() {
+    let marker = |position, beats_till_next_marker| NonTerminalMarker {
+        position,
+        beats_till_next_marker,
+    };
+    let terminal_marker = TerminalMarker {
+        position: 10.0,
+        bpm: 120.0,
+    };
+
+    assert!(validate_markers(&[], &terminal_marker).is_ok());
+    assert!(validate_markers(&[marker(0.0, 4), marker(2.0, 8)], &terminal_marker).is_ok());
+    assert!(matches!(
+        validate_markers(&[marker(0.0, 4), marker(0.0, 8)], &terminal_marker),
+        Err(Error::InvalidBeatgridMarker { index: 1, .. })
+    ));
+    assert!(matches!(
+        validate_markers(&[marker(0.0, 4), marker(12.0, 8)], &terminal_marker),
+        Err(Error::InvalidBeatgridMarker { index: 2, .. })
+    ));
+    assert!(matches!(
+        validate_markers(&[marker(0.0, 0)], &terminal_marker),
+        Err(Error::InvalidBeatgridMarker { index: 0, .. })
+    ));
+    assert!(matches!(
+        validate_markers(&[marker(-1.0, 4)], &terminal_marker),
+        Err(Error::InvalidBeatgridMarker { index: 0, .. })
+    ));
+    for bpm in &[0.0, -120.0, f32::NAN, f32::INFINITY] {
+        let terminal_marker = TerminalMarker {
+            position: 10.0,
+            bpm: *bpm,
+        };
+        assert!(matches!(
+            validate_markers(&[marker(0.0, 4)], &terminal_marker),
+            Err(Error::InvalidBeatgridMarker { index: 1, .. })
+        ));
+    }
+}
+
+#[test]
+fn test_shift_positions() {
+    let mut beatgrid = Beatgrid::new(
+        vec![
+            NonTerminalMarker {
+                position: 0.1,
+                beats_till_next_marker: 4,
+            },
+            NonTerminalMarker {
+                position: 2.1,
+                beats_till_next_marker: 8,
+            },
+        ],
+        TerminalMarker {
+            position: 6.1,
+            bpm: 120.0,
+        },
+    )
+    .unwrap();
+
+    beatgrid.shift_positions(0.5);
+    assert!((beatgrid.non_terminal_markers[0].position - 0.6).abs() < 1e-6);
+    assert!((beatgrid.non_terminal_markers[1].position - 2.6).abs() < 1e-6);
+    assert!((beatgrid.terminal_marker.position - 6.6).abs() < 1e-6);
+
+    // The first marker is moved to its first beat after the start of the track.
+    beatgrid.shift_positions(-1.5);
+    assert_eq!(beatgrid.non_terminal_markers.len(), 2);
+    assert!((beatgrid.non_terminal_markers[0].position - 0.1).abs() < 1e-6);
+    assert_eq!(beatgrid.non_terminal_markers[0].beats_till_next_marker, 2);
+    assert!((beatgrid.non_terminal_markers[1].position - 1.1).abs() < 1e-6);
+
+    // Markers without beats after the start of the track are removed.
+    beatgrid.shift_positions(-1.5);
+    assert_eq!(beatgrid.non_terminal_markers.len(), 1);
+    assert!((beatgrid.non_terminal_markers[0].position - 0.1).abs() < 1e-5);
+    assert_eq!(beatgrid.non_terminal_markers[0].beats_till_next_marker, 7);
+    assert!(validate_markers(&beatgrid.non_terminal_markers, &beatgrid.terminal_marker).is_ok());
+
+    // The terminal marker is kept on the beat, too.
+    beatgrid.shift_positions(-3.6);
+    assert!(beatgrid.non_terminal_markers.is_empty());
+    assert!((beatgrid.terminal_marker.position - 0.0).abs() < 1e-5);
+    beatgrid.shift_positions(-0.2);
+    assert!((beatgrid.terminal_marker.position - 0.3).abs() < 1e-5);
+}
+
+#[test]
+fn test_segments() {
+    let marker = |position, beats_till_next_marker| NonTerminalMarker {
+        position,
+        beats_till_next_marker,
+    };
+
+    // Only a terminal marker
+    let beatgrid = Beatgrid::new(
+        vec![],
+        TerminalMarker {
+            position: 0.05,
+            bpm: 128.0,
+        },
+    )
+    .unwrap();
+    let segments: Vec<_> = beatgrid.segments().collect();
+    assert_eq!(segments.len(), 1);
+    assert!((segments[0].start_position_millis - 50.0).abs() < 1e-3);
+    assert_eq!(segments[0].end_position_millis, None);
+    assert_eq!(segments[0].beats, None);
+    assert_eq!(segments[0].bpm, 128.0);
+
+    // Variable tempo: 8 beats at 120 BPM, 16 beats at 128 BPM, then 130 BPM
+    let beatgrid = Beatgrid::new(
+        vec![marker(0.25, 8), marker(4.25, 16)],
+        TerminalMarker {
+            position: 11.75,
+            bpm: 130.0,
+        },
+    )
+    .unwrap();
+    let segments: Vec<_> = beatgrid.segments().collect();
+    assert_eq!(segments.len(), 3);
+    assert_eq!(segments[0].start_position_millis, 250.0);
+    assert_eq!(segments[0].end_position_millis, Some(4250.0));
+    assert_eq!(segments[0].beats, Some(8));
+    assert!((segments[0].bpm - 120.0).abs() < 1e-6);
+    assert_eq!(segments[1].start_position_millis, 4250.0);
+    assert_eq!(segments[1].end_position_millis, Some(11750.0));
+    assert_eq!(segments[1].beats, Some(16));
+    assert!((segments[1].bpm - 128.0).abs() < 1e-6);
+    assert_eq!(segments[2].start_position_millis, 11750.0);
+    assert_eq!(segments[2].bpm, 130.0);
+
+    // Zero-length segments are invalid, but still returned
+    let beatgrid = Beatgrid {
+        version: Version { major: 1, minor: 0 },
+        non_terminal_markers: vec![marker(1.0, 4), marker(1.0, 4)],
+        terminal_marker: TerminalMarker {
+            position: 3.0,
+            bpm: 120.0,
+        },
+        footer: 0x00,
+        padding: 0,
+    };
+    assert!(validate_markers(&beatgrid.non_terminal_markers, &beatgrid.terminal_marker).is_err());
+    let segments: Vec<_> = beatgrid.segments().collect();
+    assert_eq!(segments.len(), 3);
+    assert_eq!(segments[0].end_position_millis, Some(1000.0));
+    assert!(segments[0].bpm.is_infinite());
+    assert!((segments[1].bpm - 120.0).abs() < 1e-6);
+}
+
+#[test]
+fn test_beats() {
+    let marker = |position, beats_till_next_marker| NonTerminalMarker {
+        position,
+        beats_till_next_marker,
+    };
+
+    let beatgrid = Beatgrid::new(
+        vec![marker(1.25, 8), marker(5.25, 16)],
+        TerminalMarker {
+            position: 12.75,
+            bpm: 130.0,
+        },
+    )
+    .unwrap();
+    let beats: Vec<_> = beatgrid.beats(20_000.0).collect();
+
+    // The beats before the first marker are extrapolated back to the start of the track
+    assert_eq!(&beats[..3], &[250.0, 750.0, 1250.0]);
+    assert!(beats.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(*beats.last().unwrap() < 20_000.0);
+    assert_eq!(beats.len(), 2 + 8 + 16 + 16);
+
+    // The beats between two markers are spaced evenly
+    let segments: Vec<_> = beatgrid.segments().collect();
+    for segment in &segments {
+        let end = segment.end_position_millis.unwrap_or(20_000.0);
+        let beat_length = 60_000.0 / segment.bpm;
+        let segment_beats: Vec<_> = beats
+            .iter()
+            .copied()
+            .filter(|position| {
+                *position >= segment.start_position_millis - 1e-6 && *position < end - 1e-6
+            })
+            .collect();
+        if let Some(beats) = segment.beats {
+            assert_eq!(segment_beats.len(), beats as usize);
+        }
+        assert!(segment_beats
+            .windows(2)
+            .all(|pair| (pair[1] - pair[0] - beat_length).abs() < 1e-6));
+    }
+
+    // The track ends before the terminal marker
+    assert_eq!(beatgrid.beats(1250.0).count(), 2);
+    assert_eq!(beatgrid.beats(0.0).count(), 0);
+
+    // Beats are not enumerated endlessly for invalid grids
+    let beatgrid = Beatgrid {
+        version: Version { major: 1, minor: 0 },
+        non_terminal_markers: vec![marker(1.0, 4), marker(1.0, 4)],
+        terminal_marker: TerminalMarker {
+            position: 3.0,
+            bpm: 0.0,
+        },
+        footer: 0x00,
+        padding: 0,
+    };
+    assert_eq!(beatgrid.beats(10_000.0).count(), 4 + 4 + 1);
+}
+
+#[test]
+fn test_constant() {
+    let beatgrid = Beatgrid::constant(126.0, 1500.0).unwrap();
+    assert!(beatgrid.non_terminal_markers.is_empty());
+    assert_eq!(beatgrid.terminal_marker.position, 1.5);
+    assert_eq!(beatgrid.terminal_marker.bpm, 126.0);
+    assert!(beatgrid.is_constant(0.0));
+    assert_eq!(
+        Beatgrid::constant_from_duration(126.0, Duration::from_millis(1500)).unwrap(),
+        beatgrid
+    );
+
+    for bpm in &[0.0, -120.0, f32::NAN, f32::INFINITY] {
+        assert!(matches!(
+            Beatgrid::constant(*bpm, 0.0),
+            Err(Error::InvalidBeatgridMarker { index: 0, .. })
+        ));
+    }
+    assert!(Beatgrid::constant(120.0, -1.0).is_err());
+
+    // Markers on the beats of the terminal marker's tempo
+    let mut beatgrid = Beatgrid::new(
+        vec![NonTerminalMarker {
+            position: 0.5,
+            beats_till_next_marker: 8,
+        }],
+        TerminalMarker {
+            position: 4.5,
+            bpm: 120.0,
+        },
+    )
+    .unwrap();
+    assert_eq!(beatgrid.constant_bpm(0.01), Some(120.0));
+
+    beatgrid.terminal_marker.bpm = 120.5;
+    assert_eq!(beatgrid.constant_bpm(0.1), None);
+    assert_eq!(beatgrid.constant_bpm(1.0), Some(120.5));
+    assert!(!beatgrid.is_constant(0.1));
+}
+
+#[test]
+fn test_validate() {
+    let parse = |input: &[u8]| Beatgrid::parse(input).unwrap();
+    let problems = |beatgrid: &Beatgrid| -> Vec<(usize, Problem)> {
+        beatgrid
+            .validate()
+            .into_iter()
+            .map(|finding| (finding.index, finding.problem))
+            .collect()
+    };
+
+    let beatgrid = parse(include_bytes!(
+        "../../tests/data/tags/beatgrid/terminal_only_with_footer_0x00.id3.bin"
+    ));
+    assert!(beatgrid.validate().is_empty());
+    assert!(Beatgrid::constant(128.0, 0.0)
+        .unwrap()
+        .validate()
+        .is_empty());
+
+    let beatgrid = parse(include_bytes!(
+        "../../tests/data/corrupted/beatgrid/markers_out_of_order.id3.bin"
+    ));
+    assert_eq!(
+        problems(&beatgrid),
+        vec![(1, Problem::BeatgridMarkersOutOfOrder { previous_index: 0 })]
+    );
+
+    let beatgrid = parse(include_bytes!(
+        "../../tests/data/corrupted/beatgrid/zero_beats.id3.bin"
+    ));
+    assert_eq!(
+        problems(&beatgrid),
+        vec![(0, Problem::InvalidBeatCount { beats: 0 })]
+    );
+
+    let beatgrid = parse(include_bytes!(
+        "../../tests/data/corrupted/beatgrid/absurd_beats.id3.bin"
+    ));
+    assert_eq!(
+        problems(&beatgrid),
+        vec![(0, Problem::InvalidBeatCount { beats: 100_000 })]
+    );
+
+    let beatgrid = parse(include_bytes!(
+        "../../tests/data/corrupted/beatgrid/bpm_out_of_range.id3.bin"
+    ));
+    assert_eq!(
+        problems(&beatgrid),
+        vec![(0, Problem::BpmOutOfRange { bpm: 1000.0 })]
+    );
+
+    let beatgrid = parse(include_bytes!(
+        "../../tests/data/corrupted/beatgrid/nan_position.id3.bin"
+    ));
+    let findings = beatgrid.validate();
+    assert_eq!(findings.len(), 1);
+    assert!(matches!(
+        findings[0].problem,
+        Problem::InvalidBeatgridPosition { position } if position.is_nan()
+    ));
+    assert_eq!(
+        findings[0].to_string(),
+        "Serato BeatGrid (entry 0): position NaN is invalid"
+    );
+}
+
+#[test]
+fn test_quantize() {
+    // 8 beats at 120 BPM (500 ms), then 16 beats at 128 BPM (468.75 ms), then 100 BPM (600 ms)
+    let beatgrid = Beatgrid::new(
+        vec![
+            NonTerminalMarker {
+                position: 1.25,
+                beats_till_next_marker: 8,
+            },
+            NonTerminalMarker {
+                position: 5.25,
+                beats_till_next_marker: 16,
+            },
+        ],
+        TerminalMarker {
+            position: 12.75,
+            bpm: 100.0,
+        },
+    )
+    .unwrap();
+
+    // Before the first marker, the tempo of the first segment is used
+    assert_eq!(beatgrid.nearest_beat(0.0), 250.0);
+    assert_eq!(beatgrid.nearest_beat(700.0), 750.0);
+    assert_eq!(beatgrid.quantize(700.0, 4), 750.0);
+    assert_eq!(beatgrid.quantize(600.0, 4), 625.0);
+
+    // Both sides of the boundary between the first and second segment
+    assert_eq!(beatgrid.nearest_beat(4700.0), 4750.0);
+    assert_eq!(beatgrid.nearest_beat(5100.0), 5250.0);
+    assert_eq!(beatgrid.nearest_beat(5400.0), 5250.0);
+    assert_eq!(beatgrid.nearest_beat(5500.0), 5718.75);
+    assert_eq!(beatgrid.quantize(4900.0, 2), 5000.0);
+    assert_eq!(beatgrid.quantize(5400.0, 2), 5484.375);
+
+    // Both sides of the terminal marker
+    assert_eq!(beatgrid.nearest_beat(12_500.0), 12_281.25);
+    assert_eq!(beatgrid.nearest_beat(12_600.0), 12_750.0);
+    assert_eq!(beatgrid.nearest_beat(13_000.0), 12_750.0);
+    assert_eq!(beatgrid.nearest_beat(13_100.0), 13_350.0);
+    assert_eq!(beatgrid.quantize(13_100.0, 4), 13_050.0);
+    assert_eq!(
+        beatgrid.quantize(60_000.0, 0),
+        beatgrid.nearest_beat(60_000.0)
+    );
+
+    // All snap targets are beats
+    let beats: Vec<_> = beatgrid.beats(20_000.0).collect();
+    for position in (0..20_000).step_by(37) {
+        let beat = beatgrid.nearest_beat(f64::from(position));
+        if beat < 20_000.0 {
+            assert!(beats.iter().any(|b| (b - beat).abs() < 1e-6));
+        }
+    }
+}
+
+#[test]
+fn test_shift_and_stretch() {
+    let mut beatgrid = Beatgrid::new(
+        vec![
+            NonTerminalMarker {
+                position: 0.25,
+                beats_till_next_marker: 8,
+            },
+            NonTerminalMarker {
+                position: 4.25,
+                beats_till_next_marker: 16,
+            },
+        ],
+        TerminalMarker {
+            position: 11.75,
+            bpm: 100.0,
+        },
+    )
+    .unwrap();
+    let assert_beats = |beatgrid: &Beatgrid, duration: f64, expected: &[f64]| {
+        let beats: Vec<_> = beatgrid.beats(duration).collect();
+        assert_eq!(beats.len(), expected.len());
+        for (beat, expected) in beats.iter().zip(expected) {
+            assert!((beat - expected).abs() < 1e-3, "{} != {}", beat, expected);
+        }
+    };
+    let before: Vec<_> = beatgrid.beats(20_000.0).collect();
+
+    // Shifting moves all beats (except for the extrapolated ones that would be negative)
+    beatgrid.shift(100.0).unwrap();
+    let shifted: Vec<_> = before.iter().map(|beat| beat + 100.0).collect();
+    assert_beats(&beatgrid, 20_100.0, &shifted);
+    beatgrid.shift(-100.0).unwrap();
+    assert_beats(&beatgrid, 20_000.0, &before);
+
+    // Negative positions are refused and leave the beatgrid unchanged
+    assert!(matches!(
+        beatgrid.shift(-250.5),
+        Err(Error::InvalidBeatgridMarker { index: 0, .. })
+    ));
+    assert_beats(&beatgrid, 20_000.0, &before);
+    beatgrid.shift(-250.0).unwrap();
+    let shifted: Vec<_> = before.iter().map(|beat| beat - 250.0).collect();
+    assert_eq!(shifted[0], 0.0);
+    assert_beats(&beatgrid, 19_750.0, &shifted);
+    beatgrid.shift(250.0).unwrap();
+
+    // Stretching scales all beats
+    beatgrid.stretch(1.5).unwrap();
+    let stretched: Vec<_> = before.iter().map(|beat| beat * 1.5).collect();
+    assert_beats(&beatgrid, 30_000.0, &stretched);
+    assert_eq!(beatgrid.terminal_marker.bpm, 100.0 / 1.5);
+    let segments: Vec<_> = beatgrid.segments().collect();
+    assert!((segments[0].bpm - 80.0).abs() < 1e-3);
+    assert!((segments[1].bpm - 128.0 / 1.5).abs() < 1e-3);
+    beatgrid.stretch(0.5).unwrap();
+    let compressed: Vec<_> = stretched.iter().map(|beat| beat * 0.5).collect();
+    assert_beats(&beatgrid, 15_000.0, &compressed);
+
+    for factor in &[0.0, -1.0, f64::NAN, f64::INFINITY] {
+        assert!(matches!(
+            beatgrid.stretch(*factor),
+            Err(Error::InvalidNumber { .. })
+        ));
+    }
+    assert_beats(&beatgrid, 15_000.0, &compressed);
+}
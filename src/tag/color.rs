@@ -85,13 +85,23 @@
 //! | 21 | ![999999](https://raw.githubusercontent.com/Holzhaus/triseratops/main/assets/colors/track_picker_999999.gif) `#999999` | ![090909](https://raw.githubusercontent.com/Holzhaus/triseratops/main/assets/colors/track_library_090909.gif) `#090909` |
 
 /// Represents a 3-Byte RGB color value.
-#[derive(Debug, PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub struct Color {
     pub red: u8,
     pub green: u8,
     pub blue: u8,
 }
 
+/// The stored track color that Serato uses for tracks without a color.
+///
+/// This is written to the `Serato Markers_` and `Serato Markers2` tags when the track color is
+/// cleared.
+pub const NO_TRACK_COLOR: Color = Color {
+    red: 0xFF,
+    green: 0xFF,
+    blue: 0xFF,
+};
+
 impl Color {
     /// Return the corresponding Serato DJ Pro Hotcue color for this color.
     ///
@@ -178,11 +188,7 @@ impl Color {
     /// ```
     pub fn into_displayed_track_color(self) -> Option<Self> {
         match self {
-            Color {
-                red: 0xFF,
-                green: 0xFF,
-                blue: 0xFF,
-            } => None,
+            NO_TRACK_COLOR => None,
             _ => Some(Color::from(stored_to_displayed_track_color_code(
                 self.into(),
             ))),
@@ -208,13 +214,7 @@ impl Color {
     pub fn from_displayed_track_color(color: Option<Self>) -> Self {
         let code: u32 = match color {
             Some(color) => color.into(),
-            None => {
-                return Color {
-                    red: 0xFF,
-                    green: 0xFF,
-                    blue: 0xFF,
-                };
-            }
+            None => return NO_TRACK_COLOR,
         };
 
         Color::from(displayed_to_stored_track_color_code(code))
@@ -263,9 +263,9 @@ impl From<u32> for Color {
     }
 }
 
-impl Into<u32> for Color {
-    fn into(self) -> u32 {
-        (self.red as u32) << 16 | (self.green as u32) << 8 | self.blue as u32
+impl From<Color> for u32 {
+    fn from(val: Color) -> Self {
+        (val.red as u32) << 16 | (val.green as u32) << 8 | val.blue as u32
     }
 }
 
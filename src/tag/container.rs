@@ -1,10 +1,21 @@
 //! Provides the [`TagContainer` struct](TagContainer), a simple interface to access data in
 //! Serato's tags.
 use super::{
-    beatgrid, color::Color, format::flac::FLACTag, format::id3::ID3Tag, format::mp4::MP4Tag,
-    format::ogg::OggTag, generic, markers, Autotags, Beatgrid, Markers, Markers2, Overview,
+    analysis, beatgrid, color,
+    color::Color,
+    format::enveloped,
+    format::flac::FLACTag,
+    format::id3::ID3Tag,
+    format::mp4::MP4Tag,
+    format::ogg::OggTag,
+    format::Tag,
+    generic, markers, markers2, overview, util,
+    validation::{Finding, Problem},
+    Analysis, Autotags, Beatgrid, Markers, Markers2, Offsets, Overview, RelVolAd, VidAssoc,
 };
 use crate::error::Error;
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::io;
 
 /// Provides a streamlined interface for retrieving Serato tag data.
@@ -15,17 +26,76 @@ use std::io;
 /// Some of the data in Serato's tags is redundant and may contradict each other. This class
 /// implements the same merge strategies for inconsistent data that Serato uses, too.
 pub struct TagContainer {
+    analysis: Option<Analysis>,
     autotags: Option<Autotags>,
     beatgrid: Option<Beatgrid>,
     markers: Option<Markers>,
     markers2: Option<Markers2>,
+    offsets: Option<Offsets>,
     overview: Option<Overview>,
+    relvolad: Option<RelVolAd>,
+    vidassoc: Option<VidAssoc>,
+    options: ParseOptions,
+    raw: HashMap<&'static str, (TagFormat, Vec<u8>)>,
+    cleared: HashSet<TagKind>,
+}
+
+/// Options that influence how the [`TagContainer`] parses and writes tags.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Keep the original data of each parsed tag.
+    ///
+    /// When an unmodified tag is written in the same format that it was parsed from, the original
+    /// data is written verbatim instead of serializing the parsed tag. This makes roundtrips
+    /// byte-exact, even for the FLAC and MP4 formats where the last base64 characters are junk.
+    pub preserve_raw: bool,
+
+    /// Decides which marker tags are written (see [`WriteStrategy`]).
+    pub write_strategy: WriteStrategy,
+
+    /// Reject tag data that deviates from what Serato writes.
+    ///
+    /// By default, some nonstandard data written by third-party taggers and older Serato versions
+    /// is parsed leniently (e.g. `COLOR` entries with unusual payload sizes in the
+    /// [`Serato Markers2`](Markers2) tag, see [`Markers2::parse_strict`], or trailing bytes in
+    /// the [`Serato Analysis`](Analysis) tag, see [`Analysis::parse_strict`]). Null bytes that
+    /// pad the data of the other tags result in an
+    /// [`UnexpectedPadding`](Error::UnexpectedPadding) error, and FLAC and MP4 envelopes with
+    /// `\r\n` line breaks or other whitespace are rejected (see
+    /// [`check_base64_strict`](enveloped::check_base64_strict)).
+    pub strict: bool,
+}
+
+/// Decides which of the [`Serato Markers_`](Markers) and [`Serato Markers2`](Markers2) tags are
+/// written by [`TagContainer::serialize_tags`] and the file adapters.
+///
+/// Apart from [`Preserve`](Self::Preserve), all strategies write the merged data (as returned by
+/// [`cues()`](TagContainer::cues), [`loops()`](TagContainer::loops) and
+/// [`track_color()`](TagContainer::track_color)), so that the written tags don't contradict each
+/// other. When writing into a file, the marker tag that isn't written is removed from the file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WriteStrategy {
+    /// Write the marker tags that are present in the container as they are (the default).
+    #[default]
+    Preserve,
+    /// Only write the `Serato Markers2` tag, which is all that recent Serato versions need.
+    Markers2Only,
+    /// Only write the legacy `Serato Markers_` tag (e.g. for old hardware).
+    ///
+    /// Since this tag only exists in ID3 and MP4 tags, this results in an
+    /// [`UnsupportedTagFormat`](Error::UnsupportedTagFormat) error for other formats.
+    LegacyOnly,
+    /// Write both marker tags.
+    ///
+    /// The `Serato Markers_` tag is only written for the ID3 and MP4 formats.
+    Both,
 }
 
 /// The tag type of the data.
 ///
 /// The format of the Serato tag data differs between tag types.
 /// Therefore it's necessary to tell the parser from what kind of the the data originates from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TagFormat {
     ID3,
     FLAC,
@@ -33,45 +103,658 @@ pub enum TagFormat {
     Ogg,
 }
 
+impl TagFormat {
+    /// Returns the tag format for a file extension (case-insensitive, with or without leading
+    /// dot), or `None` if Serato doesn't store tags in such files.
+    ///
+    /// AIFF and WAV files use ID3 tags (in a chunk of the file), just like MP3 files. `.aac` files
+    /// are raw AAC streams without MP4 container, which can't store Serato tags, so `None` is
+    /// returned for them. AAC and ALAC audio in `.m4a` or `.mp4` files uses the MP4 format.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        let extension = extension.strip_prefix('.').unwrap_or(extension);
+        [Self::ID3, Self::FLAC, Self::MP4, Self::Ogg]
+            .iter()
+            .copied()
+            .find(|format| {
+                format
+                    .extensions()
+                    .iter()
+                    .any(|ext| ext.eq_ignore_ascii_case(extension))
+            })
+    }
+
+    /// Returns the tag format for a MIME type (e.g. `audio/mpeg`), or `None` if Serato doesn't
+    /// store tags in such files.
+    ///
+    /// Parameters (e.g. `audio/ogg; codecs=opus`) are ignored. Like for
+    /// [`from_extension`](Self::from_extension), `audio/aac` (i.e. raw AAC streams) results in
+    /// `None`.
+    pub fn from_mime(mime: &str) -> Option<Self> {
+        let mime = mime.split(';').next().unwrap_or_default().trim();
+        let format = match mime.to_ascii_lowercase().as_str() {
+            "audio/mpeg" | "audio/mp3" | "audio/x-mpeg" | "audio/x-mp3" => Self::ID3,
+            "audio/aiff" | "audio/x-aiff" => Self::ID3,
+            "audio/wav" | "audio/wave" | "audio/x-wav" | "audio/vnd.wave" => Self::ID3,
+            "audio/flac" | "audio/x-flac" => Self::FLAC,
+            "audio/mp4" | "audio/m4a" | "audio/x-m4a" | "video/mp4" | "video/x-m4v" => Self::MP4,
+            "audio/ogg" | "audio/opus" | "audio/vorbis" | "application/ogg" => Self::Ogg,
+            _ => return None,
+        };
+        Some(format)
+    }
+
+    /// Returns the file extensions (lowercase, without leading dot) of the files that use this
+    /// tag format, starting with the most common one.
+    pub fn extensions(self) -> &'static [&'static str] {
+        match self {
+            Self::ID3 => &["mp3", "aiff", "aif", "wav"],
+            Self::FLAC => &["flac"],
+            Self::MP4 => &["m4a", "mp4", "m4v"],
+            Self::Ogg => &["ogg", "oga", "opus"],
+        }
+    }
+}
+
+/// Decides which data wins when [merging](TagContainer::merge) two containers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergePolicy {
+    /// Prefer the data from the container that `merge()` is called on.
+    PreferSelf,
+    /// Prefer the data from the container that is passed to `merge()`.
+    PreferOther,
+    /// Prefer the data from the container with the newer [`Serato Analysis`](Analysis) version.
+    ///
+    /// Containers without an analysis tag are considered older than all others. If both versions
+    /// are equal, the data from the container that `merge()` is called on is preferred.
+    PreferNewerAnalysis,
+}
+
+/// Describes whether a track has been analyzed by Serato DJ (see [`TagContainer::analysis_state`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnalysisState {
+    /// The [`Serato Analysis`](Analysis) tag is missing.
+    NotAnalyzed,
+    /// The track was analyzed by a version older than
+    /// [`MIN_ANALYSIS_VERSION`](analysis::MIN_ANALYSIS_VERSION).
+    Outdated { version: generic::Version },
+    /// The analysis version is supported, but the [`Serato Autotags`](Autotags) tag is missing or
+    /// the [`Serato Overview`](Overview) tag is missing or empty.
+    Incomplete,
+    /// The track has been analyzed completely by a supported version.
+    Analyzed,
+}
+
+/// Describes which tag each field of a merged cue was taken from (see
+/// [`TagContainer::cues_with_provenance`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CueProvenance {
+    /// The tag that the position was taken from.
+    pub position: TagKind,
+    /// The tag that the color was taken from.
+    pub color: TagKind,
+    /// The tag that the label was taken from, or `None` if the cue is only stored in the `Serato
+    /// Markers_` tag (which doesn't store labels).
+    pub label: Option<TagKind>,
+}
+
+/// Describes which tag each field of a merged loop was taken from (see
+/// [`TagContainer::loops_with_provenance`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoopProvenance {
+    /// The tag that the start and end positions were taken from.
+    pub position: TagKind,
+    /// The tag that the color was taken from.
+    pub color: TagKind,
+    /// The tag that the locked state was taken from.
+    pub is_locked: TagKind,
+    /// The tag that the label was taken from, or `None` if the loop is only stored in the `Serato
+    /// Markers_` tag (which doesn't store labels).
+    pub label: Option<TagKind>,
+}
+
+/// Cue and loop positions in samples (see [`TagContainer::cue_positions_in_samples`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SamplePositions {
+    /// The index and position of each cue.
+    pub cues: Vec<(u8, u64)>,
+    /// The index, start position and end position of each loop.
+    pub loops: Vec<(u8, u64, u64)>,
+    /// `true` if the positions have been corrected using the offset table of the [`Serato
+    /// Offsets_`](Offsets) tag, `false` if they are the uncorrected values.
+    pub corrected: bool,
+}
+
+/// Data that was changed by [`TagContainer::retain_within_duration`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum OutOfRange {
+    /// The cue was removed because it's positioned after the end of the track.
+    RemovedCue(generic::Cue),
+    /// The loop was removed because it starts after the end of the track.
+    RemovedLoop(generic::Loop),
+    /// The loop was truncated because it ends after the end of the track (contains the loop
+    /// before truncation).
+    TruncatedLoop(generic::Loop),
+    /// The flip was removed because one of its actions is positioned after the end of the track.
+    RemovedFlip(generic::Flip),
+}
+
+/// The kind of a Serato tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TagKind {
+    Analysis,
+    Autotags,
+    Beatgrid,
+    Markers,
+    Markers2,
+    Offsets,
+    Overview,
+    RelVolAd,
+    VidAssoc,
+}
+
+impl TagKind {
+    /// All tag kinds, in the order that tags are serialized in.
+    pub const ALL: [TagKind; 9] = [
+        TagKind::Analysis,
+        TagKind::Autotags,
+        TagKind::Beatgrid,
+        TagKind::Markers,
+        TagKind::Markers2,
+        TagKind::Offsets,
+        TagKind::Overview,
+        TagKind::RelVolAd,
+        TagKind::VidAssoc,
+    ];
+
+    /// Returns the [name](Tag::NAME) of the tag, e.g. `Serato Markers2`.
+    pub fn name(self) -> &'static str {
+        match self {
+            TagKind::Analysis => Analysis::NAME,
+            TagKind::Autotags => Autotags::NAME,
+            TagKind::Beatgrid => Beatgrid::NAME,
+            TagKind::Markers => Markers::NAME,
+            TagKind::Markers2 => Markers2::NAME,
+            TagKind::Offsets => Offsets::NAME,
+            TagKind::Overview => Overview::NAME,
+            TagKind::RelVolAd => RelVolAd::NAME,
+            TagKind::VidAssoc => VidAssoc::NAME,
+        }
+    }
+
+    /// Returns the identifier of the tag in the given format.
+    ///
+    /// Returns `None` if the tag is not stored in files of that format (or if the identifier is
+    /// not known, which is the case for some tags in Ogg files).
+    pub fn tag_name(self, tag_format: TagFormat) -> Option<TagName> {
+        match (self, tag_format) {
+            (TagKind::Analysis, TagFormat::ID3) => Some(Analysis::ID3_TAG),
+            (TagKind::Analysis, TagFormat::FLAC) => Some(Analysis::FLAC_COMMENT),
+            (TagKind::Analysis, TagFormat::MP4) => Some(Analysis::MP4_ATOM_FREEFORM_NAME),
+            (TagKind::Analysis, TagFormat::Ogg) => Some(Analysis::OGG_COMMENT),
+            (TagKind::Autotags, TagFormat::ID3) => Some(Autotags::ID3_TAG),
+            (TagKind::Autotags, TagFormat::FLAC) => Some(Autotags::FLAC_COMMENT),
+            (TagKind::Autotags, TagFormat::MP4) => Some(Autotags::MP4_ATOM_FREEFORM_NAME),
+            (TagKind::Beatgrid, TagFormat::ID3) => Some(Beatgrid::ID3_TAG),
+            (TagKind::Beatgrid, TagFormat::FLAC) => Some(Beatgrid::FLAC_COMMENT),
+            (TagKind::Beatgrid, TagFormat::MP4) => Some(Beatgrid::MP4_ATOM_FREEFORM_NAME),
+            (TagKind::Markers, TagFormat::ID3) => Some(Markers::ID3_TAG),
+            (TagKind::Markers, TagFormat::MP4) => Some(Markers::MP4_ATOM_FREEFORM_NAME),
+            (TagKind::Markers2, TagFormat::ID3) => Some(Markers2::ID3_TAG),
+            (TagKind::Markers2, TagFormat::FLAC) => Some(Markers2::FLAC_COMMENT),
+            (TagKind::Markers2, TagFormat::MP4) => Some(Markers2::MP4_ATOM_FREEFORM_NAME),
+            (TagKind::Markers2, TagFormat::Ogg) => Some(Markers2::OGG_COMMENT),
+            (TagKind::Offsets, TagFormat::ID3) => Some(Offsets::ID3_TAG),
+            (TagKind::Overview, TagFormat::ID3) => Some(Overview::ID3_TAG),
+            (TagKind::Overview, TagFormat::FLAC) => Some(Overview::FLAC_COMMENT),
+            (TagKind::Overview, TagFormat::MP4) => Some(Overview::MP4_ATOM_FREEFORM_NAME),
+            (TagKind::RelVolAd, TagFormat::FLAC) => Some(RelVolAd::FLAC_COMMENT),
+            (TagKind::RelVolAd, TagFormat::MP4) => Some(RelVolAd::MP4_ATOM_FREEFORM_NAME),
+            (TagKind::VidAssoc, TagFormat::FLAC) => Some(VidAssoc::FLAC_COMMENT),
+            (TagKind::VidAssoc, TagFormat::MP4) => Some(VidAssoc::MP4_ATOM_FREEFORM_NAME),
+            _ => None,
+        }
+    }
+
+    /// Returns the mean part of the freeform MP4 atom that stores the tag (if any).
+    fn mp4_atom_freeform_mean(self) -> Option<&'static str> {
+        match self {
+            TagKind::Analysis => Some(Analysis::MP4_ATOM_FREEFORM_MEAN),
+            TagKind::Autotags => Some(Autotags::MP4_ATOM_FREEFORM_MEAN),
+            TagKind::Beatgrid => Some(Beatgrid::MP4_ATOM_FREEFORM_MEAN),
+            TagKind::Markers => Some(Markers::MP4_ATOM_FREEFORM_MEAN),
+            TagKind::Markers2 => Some(Markers2::MP4_ATOM_FREEFORM_MEAN),
+            TagKind::Offsets => None,
+            TagKind::Overview => Some(Overview::MP4_ATOM_FREEFORM_MEAN),
+            TagKind::RelVolAd => Some(RelVolAd::MP4_ATOM_FREEFORM_MEAN),
+            TagKind::VidAssoc => Some(VidAssoc::MP4_ATOM_FREEFORM_MEAN),
+        }
+    }
+
+    /// Returns the full identifier of the tag as it appears in files of the given format.
+    ///
+    /// This is the same as [`tag_name()`](Self::tag_name), except for MP4 files, where the
+    /// identifier of the freeform atom (e.g. `----:com.serato.dj:markersv2`) is composed from
+    /// its mean and name parts.
+    ///
+    /// ```
+    /// use triseratops::tag::{TagFormat, TagKind};
+    ///
+    /// let markers2 = TagKind::Markers2;
+    /// assert_eq!(markers2.identifier(TagFormat::ID3).as_deref(), Some("Serato Markers2"));
+    /// assert_eq!(markers2.identifier(TagFormat::FLAC).as_deref(), Some("SERATO_MARKERS_V2"));
+    /// assert_eq!(
+    ///     markers2.identifier(TagFormat::MP4).as_deref(),
+    ///     Some("----:com.serato.dj:markersv2")
+    /// );
+    /// assert_eq!(TagKind::Markers.identifier(TagFormat::FLAC), None);
+    /// ```
+    pub fn identifier(self, tag_format: TagFormat) -> Option<Cow<'static, str>> {
+        let name = self.tag_name(tag_format)?;
+        if tag_format != TagFormat::MP4 {
+            return Some(Cow::Borrowed(name));
+        }
+        let mean = self.mp4_atom_freeform_mean()?;
+        Some(Cow::Owned(format!("----:{}:{}", mean, name)))
+    }
+
+    /// Returns the tag kind for a full identifier in the given format (i.e. the reverse of
+    /// [`identifier()`](Self::identifier)).
+    ///
+    /// For MP4 files, the name part of the freeform atom (e.g. `markersv2`) is accepted as well.
+    /// Vorbis comment keys (in FLAC and Ogg files) are matched case-insensitively.
+    pub fn from_identifier(tag_format: TagFormat, identifier: &str) -> Option<Self> {
+        if tag_format == TagFormat::MP4 && identifier.starts_with("----:") {
+            return TagKind::ALL
+                .iter()
+                .copied()
+                .find(|kind| kind.identifier(tag_format).as_deref() == Some(identifier));
+        }
+        Self::from_tag_name(identifier, tag_format)
+    }
+
+    /// Returns the description of the ID3 `GEOB` frame that stores the tag (if any).
+    pub fn id3_description(self) -> Option<&'static str> {
+        self.tag_name(TagFormat::ID3)
+    }
+
+    /// Returns the key of the `VORBIS_COMMENT` that stores the tag in FLAC files (if any).
+    pub fn flac_comment(self) -> Option<&'static str> {
+        self.tag_name(TagFormat::FLAC)
+    }
+
+    /// Returns the name part of the freeform MP4 atom (`----:com.serato.dj:<name>`) that stores
+    /// the tag (if any).
+    pub fn mp4_atom_name(self) -> Option<&'static str> {
+        self.tag_name(TagFormat::MP4)
+    }
+
+    /// Returns the key of the Vorbis comment that stores the tag in Ogg files (if known).
+    pub fn ogg_comment(self) -> Option<&'static str> {
+        self.tag_name(TagFormat::Ogg)
+    }
+
+    /// Returns the tag kind for an identifier in the given format (i.e. the reverse of
+    /// [`tag_name()`](Self::tag_name)).
+    ///
+    /// Vorbis comment keys (in FLAC and Ogg files) are matched case-insensitively. Returns `None`
+    /// if the identifier is unknown.
+    pub fn from_tag_name(name: &str, tag_format: TagFormat) -> Option<Self> {
+        TagKind::ALL
+            .iter()
+            .copied()
+            .find(|kind| match kind.tag_name(tag_format) {
+                Some(tag_name) => match tag_format {
+                    TagFormat::FLAC | TagFormat::Ogg => tag_name.eq_ignore_ascii_case(name),
+                    TagFormat::ID3 | TagFormat::MP4 => tag_name == name,
+                },
+                None => false,
+            })
+    }
+}
+
+/// The identifier of a tag in a specific [`TagFormat`].
+///
+/// Depending on the format, this is the ID3 `GEOB` description (e.g. `Serato Markers2`), the
+/// Vorbis comment key (e.g. `SERATO_MARKERS_V2`) or the name part of the freeform MP4 atom (e.g.
+/// `markersv2`).
+pub type TagName = &'static str;
+
+/// Returns `true` if the identifier of an ID3 `GEOB` frame, a Vorbis comment or an MP4 freeform
+/// atom belongs to a Serato tag (see [`TagKind::from_identifier()`]).
+///
+/// This can be used to quickly filter the frames, comments or atoms of a file.
+///
+/// ```
+/// use triseratops::tag::{is_serato_identifier, TagFormat};
+///
+/// assert!(is_serato_identifier(TagFormat::FLAC, "serato_markers_v2"));
+/// assert!(is_serato_identifier(TagFormat::MP4, "----:com.serato.dj:beatgrid"));
+/// assert!(!is_serato_identifier(TagFormat::Ogg, "TITLE"));
+/// ```
+pub fn is_serato_identifier(tag_format: TagFormat, identifier: &str) -> bool {
+    TagKind::from_identifier(tag_format, identifier).is_some()
+}
+
+/// A tag name together with the serialized tag data (see [`TagContainer::serialize_tags`]).
+pub type SerializedTag = (TagName, Vec<u8>);
+
 impl TagContainer {
     /// Create an empty Serato tag container.
     pub fn new() -> Self {
+        Self::with_options(ParseOptions::default())
+    }
+
+    /// Create an empty Serato tag container that uses the given options.
+    ///
+    /// ```
+    /// use triseratops::tag::{ParseOptions, TagContainer, TagFormat};
+    ///
+    /// let options = ParseOptions {
+    ///     preserve_raw: true,
+    ///     ..ParseOptions::default()
+    /// };
+    /// let mut container = TagContainer::with_options(options);
+    /// container.parse_analysis(b"2.1", TagFormat::Ogg).unwrap();
+    /// ```
+    pub fn with_options(options: ParseOptions) -> Self {
         Self {
+            analysis: None,
             autotags: None,
             beatgrid: None,
             markers: None,
             markers2: None,
+            offsets: None,
             overview: None,
+            relvolad: None,
+            vidassoc: None,
+            options,
+            raw: HashMap::new(),
+            cleared: HashSet::new(),
         }
     }
 
-    /// Parse the [`Serato Autotags`](Autotags) tag.
-    pub fn parse_autotags(&mut self, input: &[u8], tag_format: TagFormat) -> Result<(), Error> {
+    /// Change the [`WriteStrategy`] that decides which marker tags are written.
+    pub fn set_write_strategy(&mut self, write_strategy: WriteStrategy) {
+        self.options.write_strategy = write_strategy;
+    }
+
+    /// Discard the original data of all parsed tags, so that all tags are serialized from their
+    /// parsed values when writing.
+    ///
+    /// This only has an effect if the container was created with
+    /// [`preserve_raw`](ParseOptions::preserve_raw) enabled.
+    pub fn into_modified(mut self) -> Self {
+        self.raw.clear();
+        self
+    }
+
+    /// Remove a single tag from the container.
+    ///
+    /// When writing into a tag object of one of the integration features (e.g.
+    /// `write_into_id3_tag()`), the corresponding frame, comment or atom is removed from the tag
+    /// object as well (unless the tag has been set again in the meantime).
+    pub fn clear_tag(&mut self, kind: TagKind) {
+        match kind {
+            TagKind::Analysis => self.analysis = None,
+            TagKind::Autotags => self.autotags = None,
+            TagKind::Beatgrid => self.beatgrid = None,
+            TagKind::Markers => self.markers = None,
+            TagKind::Markers2 => self.markers2 = None,
+            TagKind::Offsets => self.offsets = None,
+            TagKind::Overview => self.overview = None,
+            TagKind::RelVolAd => self.relvolad = None,
+            TagKind::VidAssoc => self.vidassoc = None,
+        }
+        self.raw.remove(kind.name());
+        self.cleared.insert(kind);
+    }
+
+    /// Remove all tags from the container.
+    ///
+    /// Afterwards, [`serialize_tags()`](Self::serialize_tags) returns an empty list and writing
+    /// into a tag object of one of the integration features removes all Serato data from it (see
+    /// [`clear_tag()`](Self::clear_tag)).
+    pub fn clear_serato_data(&mut self) {
+        for kind in TagKind::ALL.iter() {
+            self.clear_tag(*kind);
+        }
+    }
+
+    /// Returns the kinds of all tags that have been removed using [`clear_tag()`](Self::clear_tag)
+    /// and that are still absent.
+    ///
+    /// When writing the serialized tags into a file without one of the integration features, the
+    /// entries for these tags should be removed from the file.
+    pub fn cleared_tags(&self) -> impl Iterator<Item = TagKind> + '_ {
+        self.cleared
+            .iter()
+            .copied()
+            .filter(move |kind| !self.has(*kind))
+    }
+
+    /// Returns `true` if the container holds a tag of the given kind.
+    pub fn has(&self, kind: TagKind) -> bool {
+        match kind {
+            TagKind::Analysis => self.analysis.is_some(),
+            TagKind::Autotags => self.autotags.is_some(),
+            TagKind::Beatgrid => self.beatgrid.is_some(),
+            TagKind::Markers => self.markers.is_some(),
+            TagKind::Markers2 => self.markers2.is_some(),
+            TagKind::Offsets => self.offsets.is_some(),
+            TagKind::Overview => self.overview.is_some(),
+            TagKind::RelVolAd => self.relvolad.is_some(),
+            TagKind::VidAssoc => self.vidassoc.is_some(),
+        }
+    }
+
+    /// Returns the kinds of all tags that the container holds.
+    ///
+    /// ```
+    /// use triseratops::tag::{TagContainer, TagFormat, TagKind};
+    ///
+    /// let mut container = TagContainer::new();
+    /// assert!(container.present_tags().is_empty());
+    /// container.parse_analysis(b"2.1", TagFormat::Ogg).unwrap();
+    /// assert_eq!(container.present_tags(), vec![TagKind::Analysis]);
+    /// ```
+    pub fn present_tags(&self) -> Vec<TagKind> {
+        TagKind::ALL
+            .iter()
+            .copied()
+            .filter(|kind| self.has(*kind))
+            .collect()
+    }
+
+    /// Remember (or forget) the original data of a tag after it has been parsed.
+    fn store_raw(&mut self, name: &'static str, input: &[u8], tag_format: TagFormat) {
+        if self.options.preserve_raw {
+            self.raw.insert(name, (tag_format, input.to_vec()));
+        } else {
+            self.raw.remove(name);
+        }
+    }
+
+    /// Write the original data of a tag if it is available for the given format.
+    fn write_raw(
+        &self,
+        mut writer: impl io::Write,
+        name: &'static str,
+        tag_format: TagFormat,
+    ) -> Result<Option<usize>, Error> {
+        match self.raw.get(name) {
+            Some((format, data)) if *format == tag_format => {
+                writer.write_all(data)?;
+                Ok(Some(data.len()))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Parse a tag using its identifier in the given format.
+    ///
+    /// The identifier is the ID3 `GEOB` description, the Vorbis comment key or the name part of
+    /// the freeform MP4 atom (see [`TagKind::tag_name()`]), and the data is passed to the
+    /// matching parse method (e.g. [`parse_markers2()`](Self::parse_markers2)).
+    ///
+    /// Returns the kind of the parsed tag, or `None` if the identifier doesn't belong to a known
+    /// Serato tag (in that case, the data is ignored).
+    ///
+    /// ```
+    /// use triseratops::tag::{TagContainer, TagFormat, TagKind};
+    ///
+    /// let mut container = TagContainer::new();
+    /// let kind = container.parse_tag("serato_analysis_ver", b"2.1", TagFormat::Ogg).unwrap();
+    /// assert_eq!(kind, Some(TagKind::Analysis));
+    /// let kind = container.parse_tag("TITLE", b"Foo", TagFormat::Ogg).unwrap();
+    /// assert_eq!(kind, None);
+    /// ```
+    pub fn parse_tag(
+        &mut self,
+        name: &str,
+        input: &[u8],
+        tag_format: TagFormat,
+    ) -> Result<Option<TagKind>, Error> {
+        let kind = match TagKind::from_tag_name(name, tag_format) {
+            Some(kind) => kind,
+            None => return Ok(None),
+        };
+        self.parse_kind(kind, input, tag_format)?;
+        Ok(Some(kind))
+    }
+
+    /// Parse tag data of unknown origin, detecting the tag format from the data (see
+    /// [`format::detect()`](super::format::detect)).
+    ///
+    /// Returns the detected format, or an [`UnsupportedTagFormat`](Error::UnsupportedTagFormat)
+    /// error if the format can't be detected.
+    ///
+    /// ```
+    /// use triseratops::tag::{TagContainer, TagFormat, TagKind};
+    ///
+    /// let mut container = TagContainer::new();
+    /// let format = container.parse_any(TagKind::Analysis, b"2.1").unwrap();
+    /// assert_eq!(format, TagFormat::Ogg);
+    /// assert!(container.has(TagKind::Analysis));
+    /// ```
+    pub fn parse_any(&mut self, kind: TagKind, input: &[u8]) -> Result<TagFormat, Error> {
+        let tag_format = super::format::detect(input).ok_or(Error::UnsupportedTagFormat)?;
+        self.parse_kind(kind, input, tag_format)?;
+        Ok(tag_format)
+    }
+
+    fn parse_kind(
+        &mut self,
+        kind: TagKind,
+        input: &[u8],
+        tag_format: TagFormat,
+    ) -> Result<(), Error> {
+        match kind {
+            TagKind::Analysis => self.parse_analysis(input, tag_format),
+            TagKind::Autotags => self.parse_autotags(input, tag_format),
+            TagKind::Beatgrid => self.parse_beatgrid(input, tag_format),
+            TagKind::Markers => self.parse_markers(input, tag_format),
+            TagKind::Markers2 => self.parse_markers2(input, tag_format),
+            TagKind::Offsets => self.parse_offsets(input, tag_format),
+            TagKind::Overview => self.parse_overview(input, tag_format),
+            TagKind::RelVolAd => self.parse_relvolad(input, tag_format),
+            TagKind::VidAssoc => self.parse_vidassoc(input, tag_format),
+        }
+    }
+
+    /// Returns a [`Base64DecodeError`](Error::Base64DecodeError) if the base64-encoded envelope of
+    /// a FLAC or MP4 tag contains characters that Serato doesn't write (e.g. `\r\n` line breaks),
+    /// but strict parsing was requested.
+    fn check_encoding(&self, input: &[u8], tag_format: TagFormat) -> Result<(), Error> {
+        match tag_format {
+            TagFormat::FLAC | TagFormat::MP4 if self.options.strict => {
+                enveloped::check_base64_strict(input)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns an [`UnexpectedPadding`](Error::UnexpectedPadding) error if the tag data was
+    /// padded, but strict parsing was requested.
+    fn check_padding(&self, padding: usize) -> Result<(), Error> {
+        if self.options.strict && padding > 0 {
+            return Err(Error::UnexpectedPadding { length: padding });
+        }
+        Ok(())
+    }
+
+    /// Parse the [`Serato Analysis`](Analysis) tag.
+    pub fn parse_analysis(&mut self, input: &[u8], tag_format: TagFormat) -> Result<(), Error> {
+        self.check_encoding(input, tag_format)?;
         match tag_format {
+            _ if self.options.strict => {
+                self.analysis = Some(Analysis::parse_strict(input, tag_format)?);
+            }
             TagFormat::ID3 => {
-                self.autotags = Some(Autotags::parse_id3(input)?);
+                self.analysis = Some(Analysis::parse_id3(input)?);
             }
             TagFormat::FLAC => {
-                self.autotags = Some(Autotags::parse_flac(input)?);
+                self.analysis = Some(Analysis::parse_flac(input)?);
             }
             TagFormat::MP4 => {
-                self.autotags = Some(Autotags::parse_mp4(input)?);
+                self.analysis = Some(Analysis::parse_mp4(input)?);
             }
-            _ => return Err(Error::UnsupportedTagFormat),
+            TagFormat::Ogg => {
+                self.analysis = Some(Analysis::parse_ogg(input)?);
+            }
+        }
+        self.store_raw(Analysis::NAME, input, tag_format);
+        Ok(())
+    }
+
+    /// Write the [`Serato Analysis`](Analysis) tag.
+    pub fn write_analysis(
+        &self,
+        mut writer: impl io::Write,
+        tag_format: TagFormat,
+    ) -> Result<usize, Error> {
+        let tag = match &self.analysis {
+            Some(x) => x,
+            None => return Err(Error::NoTagDataAvailable),
+        };
+        if let Some(bytes_written) = self.write_raw(&mut writer, Analysis::NAME, tag_format)? {
+            return Ok(bytes_written);
+        }
+        match tag_format {
+            TagFormat::ID3 => tag.write_id3(writer),
+            TagFormat::FLAC => tag.write_flac(writer),
+            TagFormat::MP4 => tag.write_mp4(writer),
+            TagFormat::Ogg => tag.write_ogg(writer),
         }
+    }
+
+    /// Parse the [`Serato Autotags`](Autotags) tag.
+    pub fn parse_autotags(&mut self, input: &[u8], tag_format: TagFormat) -> Result<(), Error> {
+        self.check_encoding(input, tag_format)?;
+        let tag = match tag_format {
+            TagFormat::ID3 => Autotags::parse_id3(input)?,
+            TagFormat::FLAC => Autotags::parse_flac(input)?,
+            TagFormat::MP4 => Autotags::parse_mp4(input)?,
+            _ => return Err(Error::UnsupportedTagFormat),
+        };
+        self.check_padding(tag.padding)?;
+        self.autotags = Some(tag);
+        self.store_raw(Autotags::NAME, input, tag_format);
         Ok(())
     }
 
     /// Write the [`Serato Autotags`](Autotags) tag.
     pub fn write_autotags(
         &self,
-        writer: impl io::Write,
+        mut writer: impl io::Write,
         tag_format: TagFormat,
     ) -> Result<usize, Error> {
         let tag = match &self.autotags {
             Some(x) => x,
             None => return Err(Error::NoTagDataAvailable),
         };
+        if let Some(bytes_written) = self.write_raw(&mut writer, Autotags::NAME, tag_format)? {
+            return Ok(bytes_written);
+        }
         match tag_format {
             TagFormat::ID3 => tag.write_id3(writer),
             TagFormat::FLAC => tag.write_flac(writer),
@@ -82,31 +765,32 @@ impl TagContainer {
 
     /// Parse the [`Serato BeatGrid`](Beatgrid) tag.
     pub fn parse_beatgrid(&mut self, input: &[u8], tag_format: TagFormat) -> Result<(), Error> {
-        match tag_format {
-            TagFormat::ID3 => {
-                self.beatgrid = Some(Beatgrid::parse_id3(input)?);
-            }
-            TagFormat::FLAC => {
-                self.beatgrid = Some(Beatgrid::parse_flac(input)?);
-            }
-            TagFormat::MP4 => {
-                self.beatgrid = Some(Beatgrid::parse_mp4(input)?);
-            }
+        self.check_encoding(input, tag_format)?;
+        let tag = match tag_format {
+            TagFormat::ID3 => Beatgrid::parse_id3(input)?,
+            TagFormat::FLAC => Beatgrid::parse_flac(input)?,
+            TagFormat::MP4 => Beatgrid::parse_mp4(input)?,
             _ => return Err(Error::UnsupportedTagFormat),
-        }
+        };
+        self.check_padding(tag.padding)?;
+        self.beatgrid = Some(tag);
+        self.store_raw(Beatgrid::NAME, input, tag_format);
         Ok(())
     }
 
     /// Write the [`Serato BeatGrid`](Beatgrid) tag.
     pub fn write_beatgrid(
         &self,
-        writer: impl io::Write,
+        mut writer: impl io::Write,
         tag_format: TagFormat,
     ) -> Result<usize, Error> {
         let tag = match &self.beatgrid {
             Some(x) => x,
             None => return Err(Error::NoTagDataAvailable),
         };
+        if let Some(bytes_written) = self.write_raw(&mut writer, Beatgrid::NAME, tag_format)? {
+            return Ok(bytes_written);
+        }
         match tag_format {
             TagFormat::ID3 => tag.write_id3(writer),
             TagFormat::FLAC => tag.write_flac(writer),
@@ -117,38 +801,41 @@ impl TagContainer {
 
     /// Parse the [`Serato Markers_`](Markers) tag.
     pub fn parse_markers(&mut self, input: &[u8], tag_format: TagFormat) -> Result<(), Error> {
-        match tag_format {
-            TagFormat::ID3 => {
-                self.markers = Some(Markers::parse_id3(input)?);
-            }
-            TagFormat::MP4 => {
-                self.markers = Some(Markers::parse_mp4(input)?);
-            }
+        self.check_encoding(input, tag_format)?;
+        let tag = match tag_format {
+            TagFormat::ID3 => Markers::parse_id3(input)?,
+            TagFormat::MP4 => Markers::parse_mp4(input)?,
             _ => return Err(Error::UnsupportedTagFormat),
-        }
+        };
+        self.check_padding(tag.padding)?;
+        self.markers = Some(tag);
+        self.store_raw(Markers::NAME, input, tag_format);
         Ok(())
     }
 
     /// Write the [`Serato Markers_`](Markers) tag.
     pub fn write_markers(
         &self,
-        writer: impl io::Write,
+        mut writer: impl io::Write,
         tag_format: TagFormat,
     ) -> Result<usize, Error> {
         let tag = match &self.markers {
             Some(x) => x,
             None => return Err(Error::NoTagDataAvailable),
         };
-        match tag_format {
-            TagFormat::ID3 => tag.write_id3(writer),
-            TagFormat::MP4 => tag.write_mp4(writer),
-            _ => Err(Error::UnsupportedTagFormat),
+        if let Some(bytes_written) = self.write_raw(&mut writer, Markers::NAME, tag_format)? {
+            return Ok(bytes_written);
         }
+        write_markers_data(tag, writer, tag_format)
     }
 
     /// Parse the [`Serato Markers2`](Markers2) tag.
     pub fn parse_markers2(&mut self, input: &[u8], tag_format: TagFormat) -> Result<(), Error> {
+        self.check_encoding(input, tag_format)?;
         match tag_format {
+            _ if self.options.strict => {
+                self.markers2 = Some(Markers2::parse_strict(input, tag_format)?);
+            }
             TagFormat::ID3 => {
                 self.markers2 = Some(Markers2::parse_id3(input)?);
             }
@@ -162,54 +849,86 @@ impl TagContainer {
                 self.markers2 = Some(Markers2::parse_ogg(input)?);
             }
         }
+        self.store_raw(Markers2::NAME, input, tag_format);
         Ok(())
     }
 
     /// Write the [`Serato Markers2`](Markers2) tag.
     pub fn write_markers2(
         &self,
-        writer: impl io::Write,
+        mut writer: impl io::Write,
         tag_format: TagFormat,
     ) -> Result<usize, Error> {
         let tag = match &self.markers2 {
             Some(x) => x,
             None => return Err(Error::NoTagDataAvailable),
         };
+        if let Some(bytes_written) = self.write_raw(&mut writer, Markers2::NAME, tag_format)? {
+            return Ok(bytes_written);
+        }
+        write_markers2_data(tag, writer, tag_format)
+    }
+
+    /// Parse the [`Serato Offsets_`](Offsets) tag.
+    pub fn parse_offsets(&mut self, input: &[u8], tag_format: TagFormat) -> Result<(), Error> {
+        self.check_encoding(input, tag_format)?;
+        match tag_format {
+            TagFormat::ID3 => {
+                self.offsets = Some(Offsets::parse_id3(input)?);
+            }
+            _ => return Err(Error::UnsupportedTagFormat),
+        }
+        self.store_raw(Offsets::NAME, input, tag_format);
+        Ok(())
+    }
+
+    /// Write the [`Serato Offsets_`](Offsets) tag.
+    pub fn write_offsets(
+        &self,
+        mut writer: impl io::Write,
+        tag_format: TagFormat,
+    ) -> Result<usize, Error> {
+        let tag = match &self.offsets {
+            Some(x) => x,
+            None => return Err(Error::NoTagDataAvailable),
+        };
+        if let Some(bytes_written) = self.write_raw(&mut writer, Offsets::NAME, tag_format)? {
+            return Ok(bytes_written);
+        }
         match tag_format {
             TagFormat::ID3 => tag.write_id3(writer),
-            TagFormat::FLAC => tag.write_flac(writer),
-            TagFormat::MP4 => tag.write_mp4(writer),
             _ => Err(Error::UnsupportedTagFormat),
         }
     }
 
     /// Parse the [`Serato Overview`](Overview) tag.
     pub fn parse_overview(&mut self, input: &[u8], tag_format: TagFormat) -> Result<(), Error> {
-        match tag_format {
-            TagFormat::ID3 => {
-                self.overview = Some(Overview::parse_id3(input)?);
-            }
-            TagFormat::FLAC => {
-                self.overview = Some(Overview::parse_flac(input)?);
-            }
-            TagFormat::MP4 => {
-                self.overview = Some(Overview::parse_mp4(input)?);
-            }
+        self.check_encoding(input, tag_format)?;
+        let tag = match tag_format {
+            TagFormat::ID3 => Overview::parse_id3(input)?,
+            TagFormat::FLAC => Overview::parse_flac(input)?,
+            TagFormat::MP4 => Overview::parse_mp4(input)?,
             _ => return Err(Error::UnsupportedTagFormat),
-        }
+        };
+        self.check_padding(tag.padding)?;
+        self.overview = Some(tag);
+        self.store_raw(Overview::NAME, input, tag_format);
         Ok(())
     }
 
     /// Write the [`Serato Overview`](Overview) tag.
     pub fn write_overview(
         &self,
-        writer: impl io::Write,
+        mut writer: impl io::Write,
         tag_format: TagFormat,
     ) -> Result<usize, Error> {
         let tag = match &self.overview {
             Some(x) => x,
             None => return Err(Error::NoTagDataAvailable),
         };
+        if let Some(bytes_written) = self.write_raw(&mut writer, Overview::NAME, tag_format)? {
+            return Ok(bytes_written);
+        }
         match tag_format {
             TagFormat::ID3 => tag.write_id3(writer),
             TagFormat::FLAC => tag.write_flac(writer),
@@ -218,212 +937,3620 @@ impl TagContainer {
         }
     }
 
-    /// Returns the [`auto_gain`](Autotags::auto_gain) value from the [`Serato Autotags`](Autotags) tag.
-    pub fn auto_gain(&self) -> Option<f64> {
-        if let Some(tag) = &self.autotags {
-            return Some(tag.auto_gain);
+    /// Parse the [`Serato RelVolAd`](RelVolAd) tag.
+    pub fn parse_relvolad(&mut self, input: &[u8], tag_format: TagFormat) -> Result<(), Error> {
+        self.check_encoding(input, tag_format)?;
+        match tag_format {
+            TagFormat::FLAC => {
+                self.relvolad = Some(RelVolAd::parse_flac(input)?);
+            }
+            TagFormat::MP4 => {
+                self.relvolad = Some(RelVolAd::parse_mp4(input)?);
+            }
+            _ => return Err(Error::UnsupportedTagFormat),
         }
-
-        None
+        self.store_raw(RelVolAd::NAME, input, tag_format);
+        Ok(())
     }
 
-    /// Returns the [`gain_db`](Autotags::gain_db) value from the [`Serato Autotags`](Autotags) tag.
-    pub fn gain_db(&self) -> Option<f64> {
-        if let Some(tag) = &self.autotags {
-            return Some(tag.gain_db);
+    /// Write the [`Serato RelVolAd`](RelVolAd) tag.
+    pub fn write_relvolad(
+        &self,
+        mut writer: impl io::Write,
+        tag_format: TagFormat,
+    ) -> Result<usize, Error> {
+        let tag = match &self.relvolad {
+            Some(x) => x,
+            None => return Err(Error::NoTagDataAvailable),
+        };
+        if let Some(bytes_written) = self.write_raw(&mut writer, RelVolAd::NAME, tag_format)? {
+            return Ok(bytes_written);
+        }
+        match tag_format {
+            TagFormat::FLAC => tag.write_flac(writer),
+            TagFormat::MP4 => tag.write_mp4(writer),
+            _ => Err(Error::UnsupportedTagFormat),
         }
-
-        None
     }
 
-    /// Returns the beatgrid from the [`Serato BeatGrid`](Beatgrid) tag.
-    pub fn beatgrid(
-        &self,
-    ) -> Option<(&Vec<beatgrid::NonTerminalMarker>, &beatgrid::TerminalMarker)> {
-        if let Some(tag) = &self.beatgrid {
-            return Some((&tag.non_terminal_markers, &tag.terminal_marker));
+    /// Parse the [`Serato VidAssoc`](VidAssoc) tag.
+    pub fn parse_vidassoc(&mut self, input: &[u8], tag_format: TagFormat) -> Result<(), Error> {
+        self.check_encoding(input, tag_format)?;
+        match tag_format {
+            TagFormat::FLAC => {
+                self.vidassoc = Some(VidAssoc::parse_flac(input)?);
+            }
+            TagFormat::MP4 => {
+                self.vidassoc = Some(VidAssoc::parse_mp4(input)?);
+            }
+            _ => return Err(Error::UnsupportedTagFormat),
         }
+        self.store_raw(VidAssoc::NAME, input, tag_format);
+        Ok(())
+    }
 
-        None
+    /// Write the [`Serato VidAssoc`](VidAssoc) tag.
+    pub fn write_vidassoc(
+        &self,
+        mut writer: impl io::Write,
+        tag_format: TagFormat,
+    ) -> Result<usize, Error> {
+        let tag = match &self.vidassoc {
+            Some(x) => x,
+            None => return Err(Error::NoTagDataAvailable),
+        };
+        if let Some(bytes_written) = self.write_raw(&mut writer, VidAssoc::NAME, tag_format)? {
+            return Ok(bytes_written);
+        }
+        match tag_format {
+            TagFormat::FLAC => tag.write_flac(writer),
+            TagFormat::MP4 => tag.write_mp4(writer),
+            _ => Err(Error::UnsupportedTagFormat),
+        }
     }
 
-    /// Returns BPM lock status from the [`Serato Markers2`](Markers2) tag.
-    pub fn bpm_locked(&self) -> Option<bool> {
-        if let Some(m) = &self.markers2 {
-            return m.bpm_locked();
+    /// Serialize all available tags for the given tag format.
+    ///
+    /// Returns a list of tag names (appropriate for the tag format) and the serialized tag data,
+    /// e.g. `("Serato Markers2", data)` for ID3 or `("SERATO_MARKERS_V2", data)` for FLAC. Tags
+    /// that are not available in this container are omitted, as well as tags that are not stored
+    /// in files of the given format (e.g. `Serato Markers_` in FLAC files or `Serato RelVolAd` in
+    /// ID3 tags). Tags whose encoding for the given format is not known yet (e.g. `Serato
+    /// BeatGrid` in Ogg files) are skipped as well.
+    ///
+    /// The marker tags are written according to the [`WriteStrategy`] from the
+    /// [`ParseOptions`]. Use [`serialize_tags_checked()`](Self::serialize_tags_checked) to find
+    /// out about skipped tags and about cues and loops that couldn't be written into the `Serato
+    /// Markers_` tag.
+    pub fn serialize_tags(&self, tag_format: TagFormat) -> Result<Vec<(TagName, Vec<u8>)>, Error> {
+        let (tags, _) = self.serialize_tags_checked(tag_format)?;
+        Ok(tags)
+    }
+
+    /// Serialize all available tags like [`serialize_tags()`](Self::serialize_tags), together
+    /// with a list of all cues and loops that couldn't be stored in the `Serato Markers_` tag and
+    /// of all tags that were skipped because they can't be written to files of the given format
+    /// yet ([`Problem::UnsupportedTagFormat`]).
+    pub fn serialize_tags_checked(
+        &self,
+        tag_format: TagFormat,
+    ) -> Result<(Vec<SerializedTag>, Vec<Finding>), Error> {
+        let mut tags = vec![];
+        let mut findings = vec![];
+
+        if self.analysis.is_some() {
+            match TagKind::Analysis.tag_name(tag_format) {
+                Some(name) => {
+                    let mut data = vec![];
+                    self.write_analysis(&mut data, tag_format)?;
+                    tags.push((name, data));
+                }
+                None => findings.push(Finding {
+                    tag: TagKind::Analysis,
+                    index: 0,
+                    problem: Problem::UnsupportedTagFormat { tag_format },
+                }),
+            }
         }
 
-        None
+        if self.autotags.is_some() {
+            match TagKind::Autotags.tag_name(tag_format) {
+                Some(name) => {
+                    let mut data = vec![];
+                    self.write_autotags(&mut data, tag_format)?;
+                    tags.push((name, data));
+                }
+                None => findings.push(Finding {
+                    tag: TagKind::Autotags,
+                    index: 0,
+                    problem: Problem::UnsupportedTagFormat { tag_format },
+                }),
+            }
+        }
+
+        if self.beatgrid.is_some() {
+            match TagKind::Beatgrid.tag_name(tag_format) {
+                Some(name) => {
+                    let mut data = vec![];
+                    self.write_beatgrid(&mut data, tag_format)?;
+                    tags.push((name, data));
+                }
+                None => findings.push(Finding {
+                    tag: TagKind::Beatgrid,
+                    index: 0,
+                    problem: Problem::UnsupportedTagFormat { tag_format },
+                }),
+            }
+        }
+
+        findings.extend(self.serialize_marker_tags(tag_format, &mut tags)?);
+
+        if self.offsets.is_some() {
+            // Only MP3 files contain a `Serato Offsets_` tag.
+            if let Some(name) = TagKind::Offsets.tag_name(tag_format) {
+                let mut data = vec![];
+                self.write_offsets(&mut data, tag_format)?;
+                tags.push((name, data));
+            }
+        }
+
+        if self.overview.is_some() {
+            match TagKind::Overview.tag_name(tag_format) {
+                Some(name) => {
+                    let mut data = vec![];
+                    self.write_overview(&mut data, tag_format)?;
+                    tags.push((name, data));
+                }
+                None => findings.push(Finding {
+                    tag: TagKind::Overview,
+                    index: 0,
+                    problem: Problem::UnsupportedTagFormat { tag_format },
+                }),
+            }
+        }
+
+        if self.relvolad.is_some() {
+            // ID3 and Ogg files don't contain a `Serato RelVolAd` tag.
+            if let Some(name) = TagKind::RelVolAd.tag_name(tag_format) {
+                let mut data = vec![];
+                self.write_relvolad(&mut data, tag_format)?;
+                tags.push((name, data));
+            }
+        }
+
+        if self.vidassoc.is_some() {
+            // ID3 and Ogg files don't contain a `Serato VidAssoc` tag.
+            if let Some(name) = TagKind::VidAssoc.tag_name(tag_format) {
+                let mut data = vec![];
+                self.write_vidassoc(&mut data, tag_format)?;
+                tags.push((name, data));
+            }
+        }
+
+        Ok((tags, findings))
     }
 
-    /// Returns cues from the [`Serato Markers_`](Markers) and [`Serato Markers2`](Markers2) tags.
+    /// Serialize the `Serato Markers_` and `Serato Markers2` tags according to the
+    /// [`WriteStrategy`].
+    fn serialize_marker_tags(
+        &self,
+        tag_format: TagFormat,
+        tags: &mut Vec<(TagName, Vec<u8>)>,
+    ) -> Result<Vec<Finding>, Error> {
+        // FLAC and Ogg files don't contain a `Serato Markers_` tag.
+        let markers_name = TagKind::Markers.tag_name(tag_format);
+        let markers2_name = TagKind::Markers2
+            .tag_name(tag_format)
+            .ok_or(Error::UnsupportedTagFormat)?;
+
+        let strategy = self.options.write_strategy;
+        if strategy == WriteStrategy::Preserve {
+            if let (Some(name), Some(_)) = (markers_name, &self.markers) {
+                let mut data = vec![];
+                self.write_markers(&mut data, tag_format)?;
+                tags.push((name, data));
+            }
+            if self.markers2.is_some() {
+                let mut data = vec![];
+                self.write_markers2(&mut data, tag_format)?;
+                tags.push((markers2_name, data));
+            }
+            return Ok(vec![]);
+        }
+
+        let legacy_name = match (strategy, markers_name) {
+            (WriteStrategy::LegacyOnly, None) => return Err(Error::UnsupportedTagFormat),
+            (WriteStrategy::LegacyOnly, name) | (WriteStrategy::Both, name) => name,
+            _ => None,
+        };
+        if self.markers.is_none() && self.markers2.is_none() {
+            return Ok(vec![]);
+        }
+
+        let cues = self.cues();
+        let loops = self.loops();
+        let track_color = self.track_color();
+        let mut findings = vec![];
+
+        if let Some(name) = legacy_name {
+            let mut legacy_cues = vec![];
+            for cue in &cues {
+                if usize::from(cue.index) < markers::NUM_CUES {
+                    legacy_cues.push(cue.clone());
+                } else {
+                    findings.push(Finding {
+                        tag: TagKind::Markers,
+                        index: cue.index.into(),
+                        problem: Problem::CueNotInMarkers,
+                    });
+                }
+            }
+            let mut legacy_loops = vec![];
+            for saved_loop in &loops {
+                if usize::from(saved_loop.index) < markers::NUM_LOOPS {
+                    legacy_loops.push(saved_loop.clone());
+                } else {
+                    findings.push(Finding {
+                        tag: TagKind::Markers,
+                        index: saved_loop.index.into(),
+                        problem: Problem::LoopNotInMarkers,
+                    });
+                }
+            }
+
+            let mut data = vec![];
+            let tag = match &self.markers {
+                Some(tag) => {
+                    let mut tag = tag.clone();
+                    tag.set_cues(&legacy_cues);
+                    tag.set_loops(&legacy_loops);
+                    tag.track_color = track_color.unwrap_or(tag.track_color);
+                    tag
+                }
+                None => {
+                    let track_color = track_color.unwrap_or(color::NO_TRACK_COLOR);
+                    Markers::from_cues_and_loops(&legacy_cues, &legacy_loops, track_color)?
+                }
+            };
+            if self.markers.as_ref() == Some(&tag) {
+                self.write_markers(&mut data, tag_format)?;
+            } else {
+                write_markers_data(&tag, &mut data, tag_format)?;
+            }
+            tags.push((name, data));
+        }
+
+        if strategy != WriteStrategy::LegacyOnly {
+            let mut tag = self.markers2.clone().unwrap_or_default();
+            if self.markers.is_some() {
+                tag.set_cues(cues);
+                tag.set_loops(loops);
+                if let Some(color) = track_color {
+                    tag.set_track_color(color);
+                }
+            }
+
+            let mut data = vec![];
+            if self.markers2.as_ref() == Some(&tag) {
+                self.write_markers2(&mut data, tag_format)?;
+            } else {
+                write_markers2_data(&tag, &mut data, tag_format)?;
+            }
+            tags.push((markers2_name, data));
+        }
+
+        Ok(findings)
+    }
+
+    /// Returns the tags that need to be removed from a file when writing this container into it.
     ///
-    /// This retrieves the `Serato Markers2` cues first, then overwrite the values with those from
-    /// `Serato Markers_`. This is what Serato does too (i.e. if `Serato Markers_` and `Serato
-    /// Markers2` contradict each other, Serato will use the values from `Serato Markers_`).
-    pub fn cues(&self) -> Vec<generic::Cue> {
-        let mut map = std::collections::BTreeMap::new();
+    /// These are the tags that have been [cleared](Self::clear_tag) and the marker tag that is not
+    /// written due to the [`WriteStrategy`].
+    #[cfg(any(feature = "id3", feature = "mp4ameta", feature = "lofty"))]
+    pub(crate) fn removed_tags(&self) -> Vec<TagKind> {
+        let mut removed: Vec<TagKind> = self.cleared_tags().collect();
+        match self.options.write_strategy {
+            WriteStrategy::Markers2Only => removed.push(TagKind::Markers),
+            WriteStrategy::LegacyOnly => removed.push(TagKind::Markers2),
+            WriteStrategy::Preserve | WriteStrategy::Both => {}
+        }
+        removed
+    }
+
+    /// Merge the tags of two containers, e.g. tags from a file and from the Serato database.
+    ///
+    /// Tags that are only present in one of the containers are taken as they are. If a tag is
+    /// present in both, the `policy` decides which one is used. Cues and saved loops are merged
+    /// per index instead: For each index, the entry (as returned by [`cues()`](Self::cues) and
+    /// [`loops()`](Self::loops)) from the preferred container is used if it has one, otherwise
+    /// the entry from the other container. The same applies to the track color and the BPM lock
+    /// status. Flips and unknown `Serato Markers2` entries are taken from the preferred container's
+    /// `Serato Markers2` tag.
+    ///
+    /// The merged cues and loops are written into both markers tags (see
+    /// [`set_cues()`](Self::set_cues)), so the result is always consistent. The resulting
+    /// container uses the options of this container, but doesn't keep any original tag data.
+    pub fn merge(self, other: TagContainer, policy: MergePolicy) -> Result<TagContainer, Error> {
+        let prefer_self = match policy {
+            MergePolicy::PreferSelf => true,
+            MergePolicy::PreferOther => false,
+            MergePolicy::PreferNewerAnalysis => {
+                let version =
+                    |container: &TagContainer| container.analysis.as_ref().map(|tag| tag.version);
+                version(&self) >= version(&other)
+            }
+        };
+        let options = self.options;
+        let (preferred, fallback) = if prefer_self {
+            (self, other)
+        } else {
+            (other, self)
+        };
+
+        let mut cues = std::collections::BTreeMap::new();
+        let mut loops = std::collections::BTreeMap::new();
+        for container in &[&fallback, &preferred] {
+            cues.extend(container.cues().into_iter().map(|cue| (cue.index, cue)));
+            loops.extend(
+                container
+                    .loops()
+                    .into_iter()
+                    .map(|saved_loop| (saved_loop.index, saved_loop)),
+            );
+        }
+        let track_color = preferred.track_color().or_else(|| fallback.track_color());
+        let bpm_locked = preferred.bpm_locked().or_else(|| fallback.bpm_locked());
+
+        let mut container = TagContainer::with_options(options);
+        container.analysis = preferred.analysis.or(fallback.analysis);
+        container.autotags = preferred.autotags.or(fallback.autotags);
+        container.beatgrid = preferred.beatgrid.or(fallback.beatgrid);
+        container.markers = preferred.markers.or(fallback.markers);
+        container.markers2 = preferred.markers2.or(fallback.markers2);
+        container.offsets = preferred.offsets.or(fallback.offsets);
+        container.overview = preferred.overview.or(fallback.overview);
+        container.relvolad = preferred.relvolad.or(fallback.relvolad);
+        container.vidassoc = preferred.vidassoc.or(fallback.vidassoc);
+
+        if container.markers.is_some() || container.markers2.is_some() {
+            container.set_cues(cues.into_values().collect())?;
+            container.set_loops(loops.into_values().collect())?;
+            container.set_track_color(track_color);
+            if bpm_locked.is_some() {
+                container.set_bpm_locked(bpm_locked);
+            }
+        }
+
+        Ok(container)
+    }
+
+    /// Check the tags for invalid data and for contradictions between the tags.
+    ///
+    /// The getters (e.g. [`cues()`](Self::cues)) silently skip such data, the same way Serato
+    /// does. This method reports it instead, so that it can be displayed to the user. Parsing and
+    /// the getters are not affected.
+    ///
+    /// Returns an empty list if no problems were found.
+    pub fn validate(&self) -> Vec<Finding> {
+        let mut findings = vec![];
+        let mut push = |tag, index: usize, problem| {
+            findings.push(Finding {
+                tag,
+                index,
+                problem,
+            })
+        };
 
-        // First, insert all cue from the `Serato Markers2` tag into the map.
         if let Some(m) = &self.markers2 {
+            if let Some(version) = m.unknown_version() {
+                push(TagKind::Markers2, 0, Problem::UnknownVersion { version });
+            }
             for cue in m.cues() {
-                map.insert(cue.index, cue);
+                if usize::from(cue.index) >= markers2::NUM_CUES {
+                    push(
+                        TagKind::Markers2,
+                        cue.index.into(),
+                        Problem::IndexOutOfRange,
+                    );
+                }
+            }
+            for saved_loop in m.loops() {
+                let index = saved_loop.index.into();
+                if usize::from(saved_loop.index) >= markers2::NUM_LOOPS {
+                    push(TagKind::Markers2, index, Problem::IndexOutOfRange);
+                }
+                if saved_loop.end_position_millis <= saved_loop.start_position_millis {
+                    let problem = Problem::InvalidLoopPositions {
+                        start_position_millis: saved_loop.start_position_millis,
+                        end_position_millis: saved_loop.end_position_millis,
+                    };
+                    push(TagKind::Markers2, index, problem);
+                }
             }
         }
 
-        // Now, iterate over the cue markers from the `Serato Markers_` tag.
         if let Some(m) = &self.markers {
+            let markers2_cues = self.markers2.as_ref().map(|m| m.cues()).unwrap_or_default();
             for (index, marker) in m.cues() {
                 match marker.marker_type {
-                    // If a cue is set in `Serato Markers2` but is invalid in `Serato Markers_`,
-                    // remove it.
                     markers::MarkerType::Invalid => {
-                        map.remove(&index);
-                        continue;
+                        if markers2_cues.iter().any(|cue| cue.index == index) {
+                            push(TagKind::Markers, index.into(), Problem::CueUnsetInMarkers);
+                        }
                     }
-                    markers::MarkerType::Cue => {
-                        if marker.start_position_millis == None {
-                            // This shouldn't be possible if the `Serato Markers_` data is valid.
-                            // Ideally, this should be checked during the parsing state.
-                            // FIXME: Throw error here?
-                            map.remove(&index);
-                            continue;
+                    _ => {
+                        if marker.start_position_millis.is_none() {
+                            push(TagKind::Markers, index.into(), Problem::MissingPosition);
+                        }
+                    }
+                }
+            }
+            for (index, marker) in m.loops() {
+                match (marker.start_position_millis, marker.end_position_millis) {
+                    (None, None) => {} // Unset loop
+                    (Some(start_position_millis), Some(end_position_millis)) => {
+                        if end_position_millis <= start_position_millis {
+                            let problem = Problem::InvalidLoopPositions {
+                                start_position_millis,
+                                end_position_millis,
+                            };
+                            push(TagKind::Markers, index.into(), problem);
                         }
-
-                        let position_millis = marker.start_position_millis.unwrap();
-
-                        // If the cue is set in both `Serato Markers2` and `Serato Markers_`, use
-                        // the version from `Serato Markers_`, but keep the label from `Serato
-                        // Markers2` because the `Serato Markers_` tag doesn't contain labels.
-                        let markers2_cue = map.remove(&index);
-                        let label = match markers2_cue {
-                            Some(c) => c.label,
-                            None => String::new(),
-                        };
-
-                        map.insert(
-                            index,
-                            generic::Cue {
-                                index,
-                                position_millis,
-                                color: marker.color,
-                                label,
-                            },
-                        );
                     }
-                    _ => {} // Ignore loop markers
+                    _ => push(TagKind::Markers, index.into(), Problem::MissingPosition),
+                }
+            }
+        }
+
+        if let Some(tag) = &self.beatgrid {
+            for finding in tag.validate() {
+                push(finding.tag, finding.index, finding.problem);
+            }
+        }
+
+        if let Some(tag) = &self.overview {
+            for (index, chunk) in tag.data.iter().enumerate() {
+                if chunk.len() != overview::CHUNK_SIZE {
+                    let problem = Problem::InvalidOverviewChunk {
+                        length: chunk.len(),
+                    };
+                    push(TagKind::Overview, index, problem);
                 }
             }
         }
 
-        // Return the sorted list of cues.
-        map.values().cloned().collect()
+        findings
     }
 
-    /// Returns loops from the [`Serato Markers_`](Markers) and [`Serato Markers2`](Markers2) tags.
-    ///
-    /// This retrieves the `Serato Markers2` loops first, then overwrite the values with those from
-    /// `Serato Markers_`. This is what Serato does too (i.e. if `Serato Markers_` and `Serato
-    /// Markers2` contradict each other, Serato will use the values from `Serato Markers_`).
-    pub fn loops(&self) -> Vec<generic::Loop> {
-        let mut map = std::collections::BTreeMap::new();
+    /// Returns the analysis state of the track, based on the [`Serato Analysis`](Analysis),
+    /// [`Serato Autotags`](Autotags) and [`Serato Overview`](Overview) tags.
+    pub fn analysis_state(&self) -> AnalysisState {
+        let version = match &self.analysis {
+            Some(tag) => tag.version,
+            None => return AnalysisState::NotAnalyzed,
+        };
+        if version < analysis::MIN_ANALYSIS_VERSION {
+            return AnalysisState::Outdated { version };
+        }
 
-        // First, insert all cue from the `Serato Markers2` tag into the map.
-        if let Some(m) = &self.markers2 {
-            for saved_loop in m.loops() {
-                map.insert(saved_loop.index, saved_loop);
-            }
+        let has_overview = self
+            .overview
+            .as_ref()
+            .is_some_and(|tag| !tag.data.is_empty());
+        if self.autotags.is_none() || !has_overview {
+            return AnalysisState::Incomplete;
         }
 
-        // Now, iterate over the cue markers from the `Serato Markers_` tag.
-        if let Some(m) = &self.markers {
-            for (index, marker) in m.loops() {
-                if marker.marker_type != markers::MarkerType::Loop {
-                    // This can only happen is `Markers::cues()` returns non-cue markers, which
-                    // would be a bug.
-                    // FIXME: Throw error here?
-                    continue;
-                }
+        AnalysisState::Analyzed
+    }
 
-                if marker.start_position_millis == None || marker.end_position_millis == None {
-                    // This may happen even for valid data, because unset loops lack the start/end
-                    // position.
-                    map.remove(&index);
-                    continue;
-                }
+    /// Returns `true` if the track has been analyzed completely by a supported Serato version.
+    ///
+    /// Tracks for which this returns `false` will be re-analyzed by Serato.
+    pub fn is_analyzed(&self) -> bool {
+        self.analysis_state() == AnalysisState::Analyzed
+    }
 
-                let start_position_millis = marker.start_position_millis.unwrap();
-                let end_position_millis = marker.end_position_millis.unwrap();
+    /// Returns the [`auto_gain`](Autotags::auto_gain) value from the [`Serato Autotags`](Autotags) tag.
+    pub fn auto_gain(&self) -> Option<f64> {
+        if let Some(tag) = &self.autotags {
+            return Some(tag.auto_gain);
+        }
 
-                // If the loop is set in both `Serato Markers2` and `Serato Markers_`, use
-                // the version from `Serato Markers_`, but keep the label from `Serato
-                // Markers2` because the `Serato Markers_` tag doesn't contain labels.
-                let markers2_loop = map.remove(&index);
-                let label = match markers2_loop {
-                    Some(c) => c.label,
-                    None => String::new(),
-                };
+        None
+    }
 
-                map.insert(
-                    index,
-                    generic::Loop {
-                        index,
-                        start_position_millis,
-                        end_position_millis,
-                        color: marker.color,
-                        label,
-                        is_locked: marker.is_locked,
-                    },
-                );
-            }
+    /// Returns the [`gain_db`](Autotags::gain_db) value from the [`Serato Autotags`](Autotags) tag.
+    pub fn gain_db(&self) -> Option<f64> {
+        if let Some(tag) = &self.autotags {
+            return Some(tag.gain_db);
         }
 
-        // Return the sorted list of cues.
-        map.values().cloned().collect()
+        None
     }
 
-    /// Returns [flips](https://serato.com/dj/pro/expansions/flip) from the [`Serato Markers2`](Markers2) tag.
-    pub fn flips(&self) -> Vec<generic::Flip> {
-        if let Some(m) = &self.markers2 {
-            return m.flips();
+    /// Returns the beatgrid from the [`Serato BeatGrid`](Beatgrid) tag.
+    pub fn beatgrid(
+        &self,
+    ) -> Option<(&Vec<beatgrid::NonTerminalMarker>, &beatgrid::TerminalMarker)> {
+        if let Some(tag) = &self.beatgrid {
+            return Some((&tag.non_terminal_markers, &tag.terminal_marker));
         }
 
-        vec![]
+        None
     }
 
-    /// Returns the track color from the [`Serato Markers_`](Markers) and [`Serato
-    /// Markers2`](Markers2) tags.
+    /// Replace the beatgrid in the [`Serato BeatGrid`](Beatgrid) tag.
     ///
-    /// This retrieves the `Serato Markers2` track color first, then overwrites the value with the
-    /// one from `Serato Markers_`. This is what Serato does too (i.e. if `Serato Markers_` and
-    /// `Serato Markers2` contradict each other, Serato will use the value from `Serato
-    /// Markers_`).
-    pub fn track_color(&self) -> Option<Color> {
-        let mut track_color = None;
+    /// The markers are validated first (see [`beatgrid::validate_markers()`]), and invalid markers
+    /// result in an [`InvalidBeatgridMarker`](Error::InvalidBeatgridMarker) error that names the
+    /// offending marker. The tag is created if necessary, otherwise the version and footer of the
+    /// existing tag are kept.
+    pub fn set_beatgrid(
+        &mut self,
+        non_terminal_markers: Vec<beatgrid::NonTerminalMarker>,
+        terminal_marker: beatgrid::TerminalMarker,
+    ) -> Result<(), Error> {
+        let mut tag = Beatgrid::new(non_terminal_markers, terminal_marker)?;
+        if let Some(old_tag) = self.beatgrid.take() {
+            tag.version = old_tag.version;
+            tag.footer = old_tag.footer;
+        }
+        self.beatgrid = Some(tag);
+        self.raw.remove(Beatgrid::NAME);
+        Ok(())
+    }
 
+    /// Returns BPM lock status from the [`Serato Markers2`](Markers2) tag.
+    pub fn bpm_locked(&self) -> Option<bool> {
         if let Some(m) = &self.markers2 {
-            track_color = m.track_color();
+            return m.bpm_locked();
+        }
+
+        None
+    }
+
+    /// Set the BPM lock status in the [`Serato Markers2`](Markers2) tag.
+    ///
+    /// The `BPMLOCK` entry is updated or inserted at the position where Serato would write it (the
+    /// tag is created if necessary). Passing `None` removes the entry, so that Serato falls back to
+    /// its default (unlocked).
+    pub fn set_bpm_locked(&mut self, is_locked: Option<bool>) {
+        let markers2 = self.markers2.get_or_insert_with(Markers2::new);
+        if markers2.bpm_locked() != is_locked {
+            markers2.set_bpm_locked(is_locked);
+            self.raw.remove(Markers2::NAME);
         }
+    }
+
+    /// Returns cues from the [`Serato Markers_`](Markers) and [`Serato Markers2`](Markers2) tags.
+    ///
+    /// This retrieves the `Serato Markers2` cues first, then overwrite the values with those from
+    /// `Serato Markers_`. This is what Serato does too (i.e. if `Serato Markers_` and `Serato
+    /// Markers2` contradict each other, Serato will use the values from `Serato Markers_`).
+    ///
+    /// Cues that are rejected during the merge are skipped silently. Use
+    /// [`cues_checked()`](Self::cues_checked) to find out about them.
+    pub fn cues(&self) -> Vec<generic::Cue> {
+        self.cues_checked().0
+    }
+
+    /// Returns cues like [`cues()`](Self::cues), together with a list of all cues that were
+    /// rejected during the merge and the reason why.
+    pub fn cues_checked(&self) -> (Vec<generic::Cue>, Vec<Finding>) {
+        let (cues, findings) = self.merge_cues();
+        (cues.into_iter().map(|(cue, _)| cue).collect(), findings)
+    }
+
+    /// Returns cues like [`cues()`](Self::cues), together with the tags that each of their
+    /// fields was taken from.
+    pub fn cues_with_provenance(&self) -> Vec<(generic::Cue, CueProvenance)> {
+        self.merge_cues().0
+    }
+
+    /// Returns the cue with the given index, or `None` if that cue is not set.
+    ///
+    /// This applies the same merge strategy as [`cues()`](Self::cues), but only looks at a
+    /// single cue slot.
+    pub fn cue_at(&self, index: u8) -> Option<generic::Cue> {
+        self.merge_cue(index).0.map(|(cue, _)| cue)
+    }
 
+    /// Merges all cue slots that are used in either of the marker tags.
+    fn merge_cues(&self) -> (Vec<(generic::Cue, CueProvenance)>, Vec<Finding>) {
+        let mut indices = BTreeSet::new();
+        if let Some(m) = &self.markers2 {
+            indices.extend(m.cues().iter().map(|cue| cue.index));
+        }
         if let Some(m) = &self.markers {
-            track_color = Some(m.track_color());
+            indices.extend(m.cues().iter().map(|(index, _)| *index));
         }
 
-        track_color
+        let mut cues = vec![];
+        let mut findings = vec![];
+        for index in indices {
+            let (cue, problem) = self.merge_cue(index);
+            cues.extend(cue);
+            if let Some(problem) = problem {
+                findings.push(Finding {
+                    tag: TagKind::Markers,
+                    index: index.into(),
+                    problem,
+                });
+            }
+        }
+
+        (cues, findings)
     }
 
-    /// Returns the waveform overview data color from the [`Serato Overview`](Overview) tag.
-    pub fn overview(&self) -> Option<&Vec<Vec<u8>>> {
-        if let Some(tag) = &self.overview {
-            return Some(&tag.data);
+    /// Merges a single cue slot.
+    ///
+    /// Returns the merged cue (if the slot is set) and the reason why the cue was rejected or
+    /// the `Serato Markers_` entry was ignored (if any).
+    fn merge_cue(&self, index: u8) -> (Option<(generic::Cue, CueProvenance)>, Option<Problem>) {
+        let markers2_cue = self.markers2.as_ref().and_then(|m| m.cue(index));
+        let from_markers2 = || {
+            markers2_cue.map(|cue| {
+                let provenance = CueProvenance {
+                    position: TagKind::Markers2,
+                    color: TagKind::Markers2,
+                    label: Some(TagKind::Markers2),
+                };
+                (cue.clone(), provenance)
+            })
+        };
+        let marker = match self.markers.as_ref().and_then(|m| m.cue(index)) {
+            Some(marker) => marker,
+            None => return (from_markers2(), None),
+        };
+
+        match marker.marker_type {
+            // If a cue is set in `Serato Markers2` but is invalid in `Serato Markers_`, remove it.
+            markers::MarkerType::Invalid => {
+                let problem = markers2_cue.map(|_| Problem::CueUnsetInMarkers);
+                (None, problem)
+            }
+            markers::MarkerType::Cue => {
+                let position_millis = match marker.start_position_millis {
+                    Some(position_millis) => position_millis,
+                    // This shouldn't be possible if the `Serato Markers_` data is valid.
+                    None => return (None, Some(Problem::MissingPosition)),
+                };
+
+                // If the cue is set in both `Serato Markers2` and `Serato Markers_`, use the
+                // version from `Serato Markers_`, but keep the label from `Serato Markers2`
+                // because the `Serato Markers_` tag doesn't contain labels.
+                let cue = generic::Cue {
+                    index,
+                    position_millis,
+                    color: marker.color,
+                    label: markers2_cue
+                        .map(|cue| cue.label.clone())
+                        .unwrap_or_default(),
+                };
+                let provenance = CueProvenance {
+                    position: TagKind::Markers,
+                    color: TagKind::Markers,
+                    label: markers2_cue.map(|_| TagKind::Markers2),
+                };
+                (Some((cue, provenance)), None)
+            }
+            // This can only happen if `Markers::cues()` returns loop or unknown markers, which
+            // would be a bug.
+            markers::MarkerType::Loop | markers::MarkerType::Unknown(_) => {
+                (from_markers2(), Some(Problem::UnexpectedMarkerType))
+            }
         }
+    }
 
-        None
+    /// Returns loops from the [`Serato Markers_`](Markers) and [`Serato Markers2`](Markers2) tags.
+    ///
+    /// This retrieves the `Serato Markers2` loops first, then overwrite the values with those from
+    /// `Serato Markers_`. This is what Serato does too (i.e. if `Serato Markers_` and `Serato
+    /// Markers2` contradict each other, Serato will use the values from `Serato Markers_`).
+    ///
+    /// Loops that are rejected during the merge are skipped silently. Use
+    /// [`loops_checked()`](Self::loops_checked) to find out about them.
+    pub fn loops(&self) -> Vec<generic::Loop> {
+        self.loops_checked().0
     }
-}
 
-impl Default for TagContainer {
-    fn default() -> Self {
-        Self::new()
+    /// Returns loops like [`loops()`](Self::loops), together with a list of all loops that were
+    /// rejected during the merge and the reason why.
+    pub fn loops_checked(&self) -> (Vec<generic::Loop>, Vec<Finding>) {
+        let (loops, findings) = self.merge_loops();
+        let loops = loops
+            .into_iter()
+            .map(|(saved_loop, _)| saved_loop)
+            .collect();
+        (loops, findings)
     }
+
+    /// Returns loops like [`loops()`](Self::loops), together with the tags that each of their
+    /// fields was taken from.
+    pub fn loops_with_provenance(&self) -> Vec<(generic::Loop, LoopProvenance)> {
+        self.merge_loops().0
+    }
+
+    /// Returns the loop with the given index, or `None` if that loop is not set.
+    ///
+    /// This applies the same merge strategy as [`loops()`](Self::loops), but only looks at a
+    /// single loop slot.
+    pub fn loop_at(&self, index: u8) -> Option<generic::Loop> {
+        self.merge_loop(index).0.map(|(saved_loop, _)| saved_loop)
+    }
+
+    /// Merges all loop slots that are used in either of the marker tags.
+    fn merge_loops(&self) -> (Vec<(generic::Loop, LoopProvenance)>, Vec<Finding>) {
+        let mut indices = BTreeSet::new();
+        if let Some(m) = &self.markers2 {
+            indices.extend(m.loops().iter().map(|saved_loop| saved_loop.index));
+        }
+        if let Some(m) = &self.markers {
+            indices.extend(m.loops().iter().map(|(index, _)| *index));
+        }
+
+        let mut loops = vec![];
+        let mut findings = vec![];
+        for index in indices {
+            let (saved_loop, problem) = self.merge_loop(index);
+            loops.extend(saved_loop);
+            if let Some(problem) = problem {
+                findings.push(Finding {
+                    tag: TagKind::Markers,
+                    index: index.into(),
+                    problem,
+                });
+            }
+        }
+
+        (loops, findings)
+    }
+
+    /// Merges a single loop slot.
+    ///
+    /// Returns the merged loop (if the slot is set) and the reason why the loop was rejected or
+    /// the `Serato Markers_` entry was ignored (if any).
+    fn merge_loop(&self, index: u8) -> (Option<(generic::Loop, LoopProvenance)>, Option<Problem>) {
+        let markers2_loop = self.markers2.as_ref().and_then(|m| m.saved_loop(index));
+        let from_markers2 = || {
+            markers2_loop.map(|saved_loop| {
+                let provenance = LoopProvenance {
+                    position: TagKind::Markers2,
+                    color: TagKind::Markers2,
+                    is_locked: TagKind::Markers2,
+                    label: Some(TagKind::Markers2),
+                };
+                (saved_loop.clone(), provenance)
+            })
+        };
+        let marker = match self.markers.as_ref().and_then(|m| m.saved_loop(index)) {
+            Some(marker) => marker,
+            None => return (from_markers2(), None),
+        };
+
+        if marker.marker_type != markers::MarkerType::Loop {
+            // This can only happen is `Markers::loops()` returns non-loop markers, which would be
+            // a bug.
+            return (from_markers2(), Some(Problem::UnexpectedMarkerType));
+        }
+
+        let (start_position_millis, end_position_millis) =
+            match (marker.start_position_millis, marker.end_position_millis) {
+                (Some(start), Some(end)) => (start, end),
+                // Unset loops lack both the start and end position.
+                (None, None) => {
+                    let problem = markers2_loop.map(|_| Problem::LoopUnsetInMarkers);
+                    return (None, problem);
+                }
+                _ => return (None, Some(Problem::MissingPosition)),
+            };
+
+        // If the loop is set in both `Serato Markers2` and `Serato Markers_`, use the version
+        // from `Serato Markers_`, but keep the label from `Serato Markers2` because the `Serato
+        // Markers_` tag doesn't contain labels.
+        let saved_loop = generic::Loop {
+            index,
+            start_position_millis,
+            end_position_millis,
+            color: marker.color,
+            label: markers2_loop
+                .map(|saved_loop| saved_loop.label.clone())
+                .unwrap_or_default(),
+            is_locked: marker.is_locked,
+        };
+        let provenance = LoopProvenance {
+            position: TagKind::Markers,
+            color: TagKind::Markers,
+            is_locked: TagKind::Markers,
+            label: markers2_loop.map(|_| TagKind::Markers2),
+        };
+        (Some((saved_loop, provenance)), None)
+    }
+
+    /// Returns the positions of all cues and loops (as returned by [`cues()`](Self::cues) and
+    /// [`loops()`](Self::loops)) in samples at the given sample rate.
+    ///
+    /// The positions are rounded to the nearest sample. MP3 decoders differ in how many samples
+    /// they skip at the start of the file, which the [`Serato Offsets_`](Offsets) tag compensates
+    /// for: If the container holds that tag and its offset table could be decoded, the offset that
+    /// applies at each position (see [`Offsets::offset_at()`]) is added to it, scaled to the given
+    /// sample rate, and [`corrected`](SamplePositions::corrected) is set. Otherwise, the
+    /// uncorrected positions are returned.
+    pub fn cue_positions_in_samples(&self, sample_rate: u32) -> SamplePositions {
+        let offsets = self
+            .offsets
+            .as_ref()
+            .and_then(|offsets| Some((offsets, offsets.sample_rate()?)))
+            .filter(|_| sample_rate > 0);
+        let to_samples = |position_millis: u32| {
+            let samples = (u64::from(position_millis) * u64::from(sample_rate) + 500) / 1000;
+            let (offsets, offsets_sample_rate) = match offsets {
+                Some(offsets) => offsets,
+                None => return samples,
+            };
+            let position = samples * u64::from(offsets_sample_rate) / u64::from(sample_rate);
+            let offset = offsets.offset_at(position).unwrap_or(0);
+            let offset =
+                i64::from(offset) * i64::from(sample_rate) / i64::from(offsets_sample_rate);
+            (samples as i64 + offset).max(0) as u64
+        };
+        let cues = self
+            .cues()
+            .iter()
+            .map(|cue| (cue.index, to_samples(cue.position_millis)))
+            .collect();
+        let loops = self
+            .loops()
+            .iter()
+            .map(|saved_loop| {
+                (
+                    saved_loop.index,
+                    to_samples(saved_loop.start_position_millis),
+                    to_samples(saved_loop.end_position_millis),
+                )
+            })
+            .collect();
+
+        SamplePositions {
+            cues,
+            loops,
+            corrected: offsets.is_some(),
+        }
+    }
+
+    /// Replace all cues in the [`Serato Markers_`](Markers) and [`Serato Markers2`](Markers2)
+    /// tags.
+    ///
+    /// The cues are written to the `Serato Markers2` tag (which is created if necessary), so that
+    /// [`cues()`](Self::cues) returns exactly these cues afterwards. If the container also holds a
+    /// `Serato Markers_` tag, its cue slots are updated as well so that the tags don't contradict
+    /// each other. Since that tag only stores position and color of the first 5 cues, cues with a
+    /// larger index and all labels are only stored in `Serato Markers2`. Cues that are not part of
+    /// `cues` are removed from both tags.
+    ///
+    /// Cues with an index of 8 or larger result in an
+    /// [`InvalidMarkerIndex`](Error::InvalidMarkerIndex) error and duplicate indices in a
+    /// [`DuplicateMarkerIndex`](Error::DuplicateMarkerIndex) error. In both cases, the tags are
+    /// left unchanged.
+    pub fn set_cues(&mut self, mut cues: Vec<generic::Cue>) -> Result<(), Error> {
+        cues.sort_by_key(|cue| cue.index);
+        for (i, cue) in cues.iter().enumerate() {
+            if usize::from(cue.index) >= markers2::NUM_CUES {
+                return Err(Error::InvalidMarkerIndex { index: cue.index });
+            }
+            if i > 0 && cues[i - 1].index == cue.index {
+                return Err(Error::DuplicateMarkerIndex { index: cue.index });
+            }
+        }
+
+        if let Some(m) = &mut self.markers {
+            m.set_cues(&cues);
+            self.raw.remove(Markers::NAME);
+        }
+
+        self.markers2
+            .get_or_insert_with(Markers2::new)
+            .set_cues(cues);
+        self.raw.remove(Markers2::NAME);
+
+        Ok(())
+    }
+
+    /// Add a single cue to the [`Serato Markers_`](Markers) and [`Serato Markers2`](Markers2)
+    /// tags.
+    ///
+    /// If the slot is already occupied, this returns a
+    /// [`MarkerSlotOccupied`](Error::MarkerSlotOccupied) error unless `overwrite` is `true`.
+    /// Indices larger than 7 result in an [`InvalidMarkerIndex`](Error::InvalidMarkerIndex) error.
+    ///
+    /// Returns the cue that was previously stored in the slot (as returned by
+    /// [`cues()`](Self::cues)), which can be used to undo the change. Afterwards, both tags contain
+    /// the same cues (see [`set_cues()`](Self::set_cues)).
+    pub fn add_cue(
+        &mut self,
+        cue: generic::Cue,
+        overwrite: bool,
+    ) -> Result<Option<generic::Cue>, Error> {
+        if usize::from(cue.index) >= markers2::NUM_CUES {
+            return Err(Error::InvalidMarkerIndex { index: cue.index });
+        }
+
+        let mut cues = self.cues();
+        let previous = cues
+            .iter()
+            .position(|c| c.index == cue.index)
+            .map(|i| cues.remove(i));
+        if previous.is_some() && !overwrite {
+            return Err(Error::MarkerSlotOccupied { index: cue.index });
+        }
+
+        cues.push(cue);
+        self.set_cues(cues)?;
+        Ok(previous)
+    }
+
+    /// Remove a single cue from the [`Serato Markers_`](Markers) and [`Serato Markers2`](Markers2)
+    /// tags.
+    ///
+    /// Returns the removed cue (as returned by [`cues()`](Self::cues)) or `None` if the slot was
+    /// empty. Afterwards, both tags contain the same cues (see [`set_cues()`](Self::set_cues)).
+    pub fn remove_cue(&mut self, index: u8) -> Result<Option<generic::Cue>, Error> {
+        let mut cues = self.cues();
+        let previous = match cues.iter().position(|c| c.index == index) {
+            Some(i) => cues.remove(i),
+            None => return Ok(None),
+        };
+
+        self.set_cues(cues)?;
+        Ok(Some(previous))
+    }
+
+    /// Replace all saved loops in the [`Serato Markers_`](Markers) and [`Serato
+    /// Markers2`](Markers2) tags.
+    ///
+    /// This works like [`set_cues()`](Self::set_cues): The loops are written to the `Serato
+    /// Markers2` tag (which is created if necessary) and, if present, into the loop slots of the
+    /// `Serato Markers_` tag, so that [`loops()`](Self::loops) returns these loops afterwards.
+    /// Loops that are not part of `loops` are removed from both tags.
+    ///
+    /// The `Serato Markers_` tag stores the start and end positions and the locked state, but
+    /// always uses Serato's default loop color (which `loops()` will return if that tag is
+    /// present). Loops that don't have a slot in that tag are only
+    /// stored in `Serato Markers2`. Their indices are returned, so that callers can tell which
+    /// loops will be missing for software that only reads the legacy tag.
+    ///
+    /// Loops with an index of 8 or larger result in an
+    /// [`InvalidMarkerIndex`](Error::InvalidMarkerIndex) error, duplicate indices in a
+    /// [`DuplicateMarkerIndex`](Error::DuplicateMarkerIndex) error and loops that don't end after
+    /// their start position in an [`InvalidLoopPositions`](Error::InvalidLoopPositions) error. In
+    /// all cases, the tags are left unchanged.
+    pub fn set_loops(&mut self, mut loops: Vec<generic::Loop>) -> Result<Vec<u8>, Error> {
+        loops.sort_by_key(|saved_loop| saved_loop.index);
+        for (i, saved_loop) in loops.iter().enumerate() {
+            if usize::from(saved_loop.index) >= markers2::NUM_LOOPS {
+                return Err(Error::InvalidMarkerIndex {
+                    index: saved_loop.index,
+                });
+            }
+            if i > 0 && loops[i - 1].index == saved_loop.index {
+                return Err(Error::DuplicateMarkerIndex {
+                    index: saved_loop.index,
+                });
+            }
+            if saved_loop.end_position_millis <= saved_loop.start_position_millis {
+                return Err(Error::InvalidLoopPositions {
+                    index: saved_loop.index,
+                    start_position_millis: saved_loop.start_position_millis,
+                    end_position_millis: saved_loop.end_position_millis,
+                });
+            }
+        }
+
+        let mut unmirrored = vec![];
+        if let Some(m) = &mut self.markers {
+            unmirrored = m.set_loops(&loops);
+            self.raw.remove(Markers::NAME);
+        }
+
+        self.markers2
+            .get_or_insert_with(Markers2::new)
+            .set_loops(loops);
+        self.raw.remove(Markers2::NAME);
+
+        Ok(unmirrored)
+    }
+
+    /// Create the legacy [`Serato Markers_`](Markers) tag from the current cues, loops and track
+    /// color.
+    ///
+    /// Files written by older Serato versions contain this tag, and setters like
+    /// [`set_cues()`](Self::set_cues) only update it if it is already present. Call this to
+    /// add it to files that don't have it yet (an existing tag is replaced). Cues and loops that
+    /// can't be stored in this tag are left out.
+    pub fn create_markers(&mut self) -> Result<(), Error> {
+        let cues: Vec<generic::Cue> = self
+            .cues()
+            .into_iter()
+            .filter(|cue| usize::from(cue.index) < markers::NUM_CUES)
+            .collect();
+        let loops: Vec<generic::Loop> = self
+            .loops()
+            .into_iter()
+            .filter(|saved_loop| usize::from(saved_loop.index) < markers::NUM_LOOPS)
+            .collect();
+        let track_color = self.track_color().unwrap_or(color::NO_TRACK_COLOR);
+
+        self.markers = Some(Markers::from_cues_and_loops(&cues, &loops, track_color)?);
+        self.raw.remove(Markers::NAME);
+        Ok(())
+    }
+
+    /// Shift all positions by the given offset (e.g. to compensate for a different encoder delay
+    /// after transcoding a file).
+    ///
+    /// This affects the cues and loops in the [`Serato Markers_`](Markers) and [`Serato
+    /// Markers2`](Markers2) tags, the flips in the `Serato Markers2` tag and the markers in the
+    /// [`Serato BeatGrid`](Beatgrid) tag. Positions are clamped at zero and cues and loops that
+    /// would end up entirely before the start of the track are removed. See
+    /// [`Beatgrid::shift_positions()`] for details on how the beatgrid is adjusted.
+    ///
+    /// Use [`shift_beatgrid()`](Self::shift_beatgrid) instead to fail rather than adjust the
+    /// beatgrid markers that would end up at a negative position.
+    pub fn shift_positions(&mut self, offset_millis: i32) {
+        if offset_millis == 0 {
+            return;
+        }
+
+        self.shift_marker_tags(offset_millis);
+        if let Some(tag) = &mut self.beatgrid {
+            tag.shift_positions(offset_millis as f32 / 1000.0);
+            self.raw.remove(Beatgrid::NAME);
+        }
+    }
+
+    /// Move the [`Serato BeatGrid`](Beatgrid) markers by the given offset, optionally together
+    /// with all cues, loops and flips.
+    ///
+    /// The beatgrid is moved using [`Beatgrid::shift()`], so the operation fails without
+    /// changing anything if a beatgrid marker would end up at a negative position. If
+    /// `shift_markers` is `true`, the [`Serato Markers_`](Markers) and [`Serato
+    /// Markers2`](Markers2) tags are moved afterwards like in
+    /// [`shift_positions()`](Self::shift_positions), which clamps the beatgrid with
+    /// [`Beatgrid::shift_positions()`] instead of failing.
+    pub fn shift_beatgrid(&mut self, offset_millis: i32, shift_markers: bool) -> Result<(), Error> {
+        if let Some(tag) = &mut self.beatgrid {
+            tag.shift(f64::from(offset_millis))?;
+            self.raw.remove(Beatgrid::NAME);
+        }
+        if shift_markers {
+            self.shift_marker_tags(offset_millis);
+        }
+        Ok(())
+    }
+
+    /// Shift the cues, loops and flips in the [`Serato Markers_`](Markers) and [`Serato
+    /// Markers2`](Markers2) tags by the given offset.
+    fn shift_marker_tags(&mut self, offset_millis: i32) {
+        if let Some(tag) = &mut self.markers {
+            tag.shift_positions(offset_millis);
+            self.raw.remove(Markers::NAME);
+        }
+        if let Some(tag) = &mut self.markers2 {
+            tag.shift_positions(offset_millis);
+            self.raw.remove(Markers2::NAME);
+        }
+    }
+
+    /// Stretch the [`Serato BeatGrid`](Beatgrid) by the given factor (see
+    /// [`Beatgrid::stretch()`]), optionally together with all cues and loops.
+    ///
+    /// If `stretch_markers` is `true`, the positions of the cues and loops in the [`Serato
+    /// Markers_`](Markers) and [`Serato Markers2`](Markers2) tags are scaled by the same factor.
+    /// Nothing is changed if the factor or the resulting beatgrid is invalid.
+    pub fn stretch_beatgrid(&mut self, factor: f64, stretch_markers: bool) -> Result<(), Error> {
+        if let Some(tag) = &mut self.beatgrid {
+            tag.stretch(factor)?;
+            self.raw.remove(Beatgrid::NAME);
+        } else if !(factor.is_finite() && factor > 0.0) {
+            return Err(Error::InvalidNumber { value: factor });
+        }
+        if !stretch_markers {
+            return Ok(());
+        }
+
+        let stretch = |position_millis: u32| (f64::from(position_millis) * factor).round() as u32;
+        if let Some(tag) = &mut self.markers {
+            tag.map_positions(stretch);
+            self.raw.remove(Markers::NAME);
+        }
+        if let Some(tag) = &mut self.markers2 {
+            tag.map_positions(stretch);
+            self.raw.remove(Markers2::NAME);
+        }
+        Ok(())
+    }
+
+    /// Snap all cues and loop boundaries to the beatgrid, e.g. to whole beats for a `subdivision`
+    /// of 1 or to half beats for 2 (see [`Beatgrid::quantize()`]).
+    ///
+    /// Both the [`Serato Markers_`](Markers) and [`Serato Markers2`](Markers2) tags are updated.
+    /// Loops that would collapse to a single position are left unchanged. Nothing happens if
+    /// there is no beatgrid.
+    pub fn quantize_cues(&mut self, subdivision: u32) {
+        let beatgrid = match &self.beatgrid {
+            Some(tag) => tag,
+            None => return,
+        };
+        let quantize = |position_millis: u32| {
+            beatgrid
+                .quantize(f64::from(position_millis), subdivision)
+                .round() as u32
+        };
+
+        if let Some(tag) = &mut self.markers {
+            tag.map_positions(quantize);
+            self.raw.remove(Markers::NAME);
+        }
+        if let Some(tag) = &mut self.markers2 {
+            tag.map_positions(quantize);
+            self.raw.remove(Markers2::NAME);
+        }
+    }
+
+    /// Remove all cues, loops and flips that are positioned after the end of the track.
+    ///
+    /// Positions up to `tolerance_millis` after the given track duration are accepted, because
+    /// the duration that different decoders report may differ slightly. Loops that start before
+    /// the end of the track but end after it are truncated. Both the [`Serato
+    /// Markers_`](Markers) and [`Serato Markers2`](Markers2) tags are updated.
+    ///
+    /// Returns the merged cues, loops and flips (as returned by [`cues()`](Self::cues),
+    /// [`loops()`](Self::loops) and [`flips()`](Self::flips)) that were removed or truncated.
+    pub fn retain_within_duration(
+        &mut self,
+        duration_millis: u32,
+        tolerance_millis: u32,
+    ) -> Vec<OutOfRange> {
+        let max_position_millis = duration_millis.saturating_add(tolerance_millis);
+        let mut changes = vec![];
+        for cue in self.cues() {
+            if cue.position_millis > max_position_millis {
+                changes.push(OutOfRange::RemovedCue(cue));
+            }
+        }
+        for saved_loop in self.loops() {
+            match util::retain_range_millis(
+                saved_loop.start_position_millis,
+                saved_loop.end_position_millis,
+                duration_millis,
+                tolerance_millis,
+            ) {
+                Some((_, end)) if end == saved_loop.end_position_millis => {}
+                Some(_) => changes.push(OutOfRange::TruncatedLoop(saved_loop)),
+                None => changes.push(OutOfRange::RemovedLoop(saved_loop)),
+            }
+        }
+        let flips_before = self.flips();
+
+        if let Some(tag) = &mut self.markers {
+            tag.retain_within_duration(duration_millis, tolerance_millis);
+            self.raw.remove(Markers::NAME);
+        }
+        if let Some(tag) = &mut self.markers2 {
+            tag.retain_within_duration(duration_millis, tolerance_millis);
+            self.raw.remove(Markers2::NAME);
+        }
+
+        let flips_after = self.flips();
+        for flip in flips_before {
+            if !flips_after.contains(&flip) {
+                changes.push(OutOfRange::RemovedFlip(flip));
+            }
+        }
+
+        changes
+    }
+
+    /// Resolve all contradictions between the [`Serato Markers_`](Markers) and [`Serato
+    /// Markers2`](Markers2) tags.
+    ///
+    /// The merged cues, loops and track color (as returned by [`cues()`](Self::cues),
+    /// [`loops()`](Self::loops) and [`track_color()`](Self::track_color)) are written back to
+    /// both tags, so that reading either tag on its own yields the same data (except for the
+    /// labels and the cues and loops that the `Serato Markers_` tag can't store). If only the
+    /// `Serato Markers_` tag is present, the `Serato Markers2` tag is created. Calling this method
+    /// more than once doesn't change the data any further.
+    ///
+    /// Fails if the merged data contains cues or loops that can't be stored (see
+    /// [`set_cues()`](Self::set_cues) and [`set_loops()`](Self::set_loops)).
+    pub fn canonicalize(&mut self) -> Result<(), Error> {
+        if self.markers.is_none() && self.markers2.is_none() {
+            return Ok(());
+        }
+
+        let cues = self.cues();
+        let loops = self.loops();
+        let track_color = self.track_color();
+
+        self.set_cues(cues)?;
+        self.set_loops(loops)?;
+        if track_color.is_some() {
+            self.set_track_color(track_color);
+        }
+
+        Ok(())
+    }
+
+    /// Returns [flips](https://serato.com/dj/pro/expansions/flip) from the [`Serato Markers2`](Markers2) tag.
+    pub fn flips(&self) -> Vec<generic::Flip> {
+        if let Some(m) = &self.markers2 {
+            return m.flips();
+        }
+
+        vec![]
+    }
+
+    /// Returns the track color from the [`Serato Markers_`](Markers) and [`Serato
+    /// Markers2`](Markers2) tags.
+    ///
+    /// This retrieves the `Serato Markers2` track color first, then overwrites the value with the
+    /// one from `Serato Markers_`. This is what Serato does too (i.e. if `Serato Markers_` and
+    /// `Serato Markers2` contradict each other, Serato will use the value from `Serato
+    /// Markers_`).
+    pub fn track_color(&self) -> Option<Color> {
+        let mut track_color = None;
+
+        if let Some(m) = &self.markers2 {
+            track_color = m.track_color();
+        }
+
+        // The track color of version 1 `Serato Markers_` tags is unknown.
+        if let Some(m) = self.markers.as_ref().filter(|m| m.legacy_data.is_none()) {
+            track_color = Some(m.track_color());
+        }
+
+        track_color
+    }
+
+    /// Set the track color in the [`Serato Markers_`](Markers) and [`Serato Markers2`](Markers2)
+    /// tags.
+    ///
+    /// The color is written to the `COLOR` entry of the `Serato Markers2` tag (which is created if
+    /// necessary) and, if present, to the `Serato Markers_` tag. Passing `None` clears the track
+    /// color by writing [`NO_TRACK_COLOR`](color::NO_TRACK_COLOR), just like Serato does, so that
+    /// [`track_color()`](Self::track_color) returns that value afterwards.
+    ///
+    /// Tags that already contain the color are left untouched, so setting the same color again
+    /// doesn't change the written data (even if the original data is preserved).
+    pub fn set_track_color(&mut self, color: Option<Color>) {
+        let color = color.unwrap_or(color::NO_TRACK_COLOR);
+
+        if let Some(m) = &mut self.markers {
+            if m.track_color != color {
+                m.track_color = color;
+                self.raw.remove(Markers::NAME);
+            }
+        }
+
+        let markers2 = self.markers2.get_or_insert_with(Markers2::new);
+        if markers2.track_color() != Some(color) {
+            markers2.set_track_color(color);
+            self.raw.remove(Markers2::NAME);
+        }
+    }
+
+    /// Returns the waveform overview data color from the [`Serato Overview`](Overview) tag.
+    pub fn overview(&self) -> Option<&Vec<Vec<u8>>> {
+        if let Some(tag) = &self.overview {
+            return Some(&tag.data);
+        }
+
+        None
+    }
+
+    /// Returns the [`Serato Offsets_`](Offsets) tag.
+    ///
+    /// Use [`Offsets::entries()`] to get the decoded offset table.
+    pub fn offsets(&self) -> Option<&Offsets> {
+        self.offsets.as_ref()
+    }
+
+    /// Returns the [`Serato RelVolAd`](RelVolAd) tag.
+    ///
+    /// **Note:** The encoding of the relative volume adjustment hasn't been reverse-engineered yet,
+    /// so it can't be converted to a dB value. The tag is returned as is instead.
+    pub fn relvolad(&self) -> Option<&RelVolAd> {
+        self.relvolad.as_ref()
+    }
+
+    /// Returns the [`Serato VidAssoc`](VidAssoc) tag.
+    ///
+    /// **Note:** None of the available sample files have a video file associated, so the location
+    /// of the video path inside the tag data is unknown. The tag is returned as is instead, and
+    /// preserved when writing tags.
+    pub fn vidassoc(&self) -> Option<&VidAssoc> {
+        self.vidassoc.as_ref()
+    }
+}
+
+impl Default for TagContainer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Write a `Serato Markers_` tag in the given format.
+fn write_markers_data(
+    tag: &Markers,
+    writer: impl io::Write,
+    tag_format: TagFormat,
+) -> Result<usize, Error> {
+    match tag_format {
+        TagFormat::ID3 => tag.write_id3(writer),
+        TagFormat::MP4 => tag.write_mp4(writer),
+        _ => Err(Error::UnsupportedTagFormat),
+    }
+}
+
+/// Write a `Serato Markers2` tag in the given format.
+fn write_markers2_data(
+    tag: &Markers2,
+    writer: impl io::Write,
+    tag_format: TagFormat,
+) -> Result<usize, Error> {
+    match tag_format {
+        TagFormat::ID3 => tag.write_id3(writer),
+        TagFormat::FLAC => tag.write_flac(writer),
+        TagFormat::MP4 => tag.write_mp4(writer),
+        TagFormat::Ogg => tag.write_ogg(writer),
+    }
+}
+
+/// Builds a [`TagContainer`] for a file that doesn't contain any Serato data yet.
+///
+/// The resulting container holds a [`Serato Analysis`](Analysis) tag with the
+/// [latest version](analysis::LATEST_ANALYSIS_VERSION) and a [`Serato Markers2`](Markers2) tag
+/// with the cues, loops, track color and BPM lock state (Serato's defaults are used for values
+/// that have not been set). If a beatgrid has been set, it also holds a [`Serato
+/// BeatGrid`](Beatgrid) tag.
+///
+/// ```
+/// use triseratops::tag::{color::Color, TagContainerBuilder};
+///
+/// let container = TagContainerBuilder::new()
+///     .cue(0, 1500, Color::from(0xCC0000), "Intro")
+///     .saved_loop(0, 30000, 45000, Color::from(0x27AAE1), false, "Break")
+///     .beatgrid_constant(128.0, 1500.0)
+///     .track_color(Color::from(0xFF99FF))
+///     .bpm_locked(true)
+///     .build()
+///     .expect("Invalid data");
+///
+/// assert_eq!(container.cues()[0].label, "Intro");
+/// assert_eq!(container.bpm_locked(), Some(true));
+/// ```
+#[derive(Debug, Default)]
+pub struct TagContainerBuilder {
+    cues: Vec<generic::Cue>,
+    loops: Vec<generic::Loop>,
+    beatgrid: Option<(f32, f64)>,
+    track_color: Option<Color>,
+    bpm_locked: bool,
+}
+
+impl TagContainerBuilder {
+    /// Create a builder without any data.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a cue.
+    pub fn cue(mut self, index: u8, position_millis: u32, color: Color, label: &str) -> Self {
+        self.cues.push(generic::Cue {
+            index,
+            position_millis,
+            color,
+            label: label.to_owned(),
+        });
+        self
+    }
+
+    /// Add a saved loop.
+    pub fn saved_loop(
+        mut self,
+        index: u8,
+        start_position_millis: u32,
+        end_position_millis: u32,
+        color: Color,
+        is_locked: bool,
+        label: &str,
+    ) -> Self {
+        self.loops.push(generic::Loop {
+            index,
+            start_position_millis,
+            end_position_millis,
+            color,
+            is_locked,
+            label: label.to_owned(),
+        });
+        self
+    }
+
+    /// Set a beatgrid with a constant tempo that starts at the first beat (see
+    /// [`Beatgrid::constant()`]).
+    pub fn beatgrid_constant(mut self, bpm: f32, first_beat_millis: f64) -> Self {
+        self.beatgrid = Some((bpm, first_beat_millis));
+        self
+    }
+
+    /// Set the track color.
+    pub fn track_color(mut self, color: Color) -> Self {
+        self.track_color = Some(color);
+        self
+    }
+
+    /// Set the BPM lock status.
+    pub fn bpm_locked(mut self, is_locked: bool) -> Self {
+        self.bpm_locked = is_locked;
+        self
+    }
+
+    /// Build the container.
+    ///
+    /// Returns an error if the data is invalid, e.g. a
+    /// [`DuplicateMarkerIndex`](Error::DuplicateMarkerIndex) error for duplicate cue or loop
+    /// indices, an
+    /// [`InvalidLoopPositions`](Error::InvalidLoopPositions) error for loops that end before they
+    /// start or an [`InvalidBeatgridMarker`](Error::InvalidBeatgridMarker) error for an invalid
+    /// beatgrid.
+    pub fn build(self) -> Result<TagContainer, Error> {
+        let mut container = TagContainer::new();
+        container.analysis = Some(Analysis::new(analysis::LATEST_ANALYSIS_VERSION));
+        container.set_track_color(self.track_color);
+        container.set_cues(self.cues)?;
+        container.set_loops(self.loops)?;
+        container.set_bpm_locked(Some(self.bpm_locked));
+        if let Some((bpm, first_beat_millis)) = self.beatgrid {
+            container.beatgrid = Some(Beatgrid::constant(bpm, first_beat_millis)?);
+        }
+        Ok(container)
+    }
+}
+
+#[test]
+fn test_tag_format_detection() {
+    assert_eq!(TagFormat::from_extension("mp3"), Some(TagFormat::ID3));
+    assert_eq!(TagFormat::from_extension(".AIFF"), Some(TagFormat::ID3));
+    assert_eq!(TagFormat::from_extension("Wav"), Some(TagFormat::ID3));
+    assert_eq!(TagFormat::from_extension("flac"), Some(TagFormat::FLAC));
+    assert_eq!(TagFormat::from_extension("m4v"), Some(TagFormat::MP4));
+    assert_eq!(TagFormat::from_extension("opus"), Some(TagFormat::Ogg));
+    assert_eq!(TagFormat::from_extension("aac"), None);
+    assert_eq!(TagFormat::from_extension(""), None);
+
+    assert_eq!(TagFormat::from_mime("audio/mpeg"), Some(TagFormat::ID3));
+    assert_eq!(TagFormat::from_mime("audio/x-aiff"), Some(TagFormat::ID3));
+    assert_eq!(TagFormat::from_mime("Audio/FLAC"), Some(TagFormat::FLAC));
+    assert_eq!(TagFormat::from_mime("audio/x-m4a"), Some(TagFormat::MP4));
+    assert_eq!(
+        TagFormat::from_mime("audio/ogg; codecs=opus"),
+        Some(TagFormat::Ogg)
+    );
+    assert_eq!(TagFormat::from_mime("audio/aac"), None);
+    assert_eq!(TagFormat::from_mime("text/plain"), None);
+
+    for format in &[
+        TagFormat::ID3,
+        TagFormat::FLAC,
+        TagFormat::MP4,
+        TagFormat::Ogg,
+    ] {
+        for extension in format.extensions() {
+            assert_eq!(TagFormat::from_extension(extension), Some(*format));
+        }
+    }
+}
+
+#[test]
+fn test_parse_lenient_envelopes() {
+    let strict_options = ParseOptions {
+        strict: true,
+        ..Default::default()
+    };
+
+    let mut expected = TagContainer::new();
+    expected
+        .parse_markers2(
+            include_bytes!("../../tests/data/tags/markers2/hotcues_and_loops.flac.bin"),
+            TagFormat::FLAC,
+        )
+        .unwrap();
+    expected
+        .parse_beatgrid(
+            include_bytes!(
+                "../../tests/data/tags/beatgrid/terminal_only_with_footer_0x41.flac.bin"
+            ),
+            TagFormat::FLAC,
+        )
+        .unwrap();
+    let expected_markers2 = &expected.markers2.as_ref().unwrap().content;
+    let expected_beatgrid = expected.beatgrid.as_ref().unwrap();
+
+    // (markers2, beatgrid, accepted in strict mode)
+    let variants: &[(&[u8], &[u8], bool)] = &[
+        (
+            include_bytes!("../../tests/data/envelopes/markers2/hotcues_and_loops_crlf.flac.bin"),
+            include_bytes!(
+                "../../tests/data/envelopes/beatgrid/terminal_only_with_footer_0x41_crlf.flac.bin"
+            ),
+            false,
+        ),
+        (
+            include_bytes!("../../tests/data/envelopes/markers2/hotcues_and_loops_cr.flac.bin"),
+            include_bytes!(
+                "../../tests/data/envelopes/beatgrid/terminal_only_with_footer_0x41_cr.flac.bin"
+            ),
+            false,
+        ),
+        (
+            include_bytes!(
+                "../../tests/data/envelopes/markers2/hotcues_and_loops_whitespace.flac.bin"
+            ),
+            include_bytes!(
+                "../../tests/data/envelopes/beatgrid/terminal_only_with_footer_0x41_whitespace.flac.bin"
+            ),
+            false,
+        ),
+        (
+            include_bytes!(
+                "../../tests/data/envelopes/markers2/hotcues_and_loops_unwrapped.flac.bin"
+            ),
+            include_bytes!(
+                "../../tests/data/envelopes/beatgrid/terminal_only_with_footer_0x41_unwrapped.flac.bin"
+            ),
+            true,
+        ),
+        (
+            include_bytes!("../../tests/data/envelopes/markers2/hotcues_and_loops_mime.flac.bin"),
+            include_bytes!(
+                "../../tests/data/envelopes/beatgrid/terminal_only_with_footer_0x41_mime.flac.bin"
+            ),
+            true,
+        ),
+        (
+            include_bytes!(
+                "../../tests/data/envelopes/markers2/hotcues_and_loops_unpadded.flac.bin"
+            ),
+            include_bytes!(
+                "../../tests/data/envelopes/beatgrid/terminal_only_with_footer_0x41_unpadded.flac.bin"
+            ),
+            true,
+        ),
+    ];
+    for (markers2_data, beatgrid_data, is_strict) in variants {
+        let mut container = TagContainer::new();
+        container
+            .parse_markers2(markers2_data, TagFormat::FLAC)
+            .unwrap();
+        container
+            .parse_beatgrid(beatgrid_data, TagFormat::FLAC)
+            .unwrap();
+        assert_eq!(
+            &container.markers2.as_ref().unwrap().content,
+            expected_markers2
+        );
+        assert_eq!(container.beatgrid.as_ref().unwrap(), expected_beatgrid);
+
+        let mut container = TagContainer::with_options(strict_options);
+        assert_eq!(
+            container
+                .parse_markers2(markers2_data, TagFormat::FLAC)
+                .is_ok(),
+            *is_strict
+        );
+        assert_eq!(
+            container
+                .parse_beatgrid(beatgrid_data, TagFormat::FLAC)
+                .is_ok(),
+            *is_strict
+        );
+    }
+
+    // Unpadded data that ends with a group of 3 characters
+    let mut container = TagContainer::new();
+    container
+        .parse_markers2(
+            include_bytes!(
+                "../../tests/data/envelopes/markers2/hotcues_and_loops_unpadded_nul.flac.bin"
+            ),
+            TagFormat::FLAC,
+        )
+        .unwrap();
+    assert_eq!(
+        &container.markers2.as_ref().unwrap().content,
+        expected_markers2
+    );
+    container
+        .parse_beatgrid(
+            include_bytes!("../../tests/data/envelopes/beatgrid/two_markers_unpadded.flac.bin"),
+            TagFormat::FLAC,
+        )
+        .unwrap();
+    let beatgrid = container.beatgrid.as_ref().unwrap();
+    assert_eq!(beatgrid.non_terminal_markers.len(), 1);
+    assert_eq!(beatgrid.non_terminal_markers[0].beats_till_next_marker, 16);
+    assert_eq!(beatgrid.terminal_marker.bpm, 115.0);
+    assert_eq!(beatgrid.footer, 0x41);
+}
+
+#[test]
+fn test_serialize_tags() {
+    let mut container = TagContainer::new();
+    assert!(container.serialize_tags(TagFormat::ID3).unwrap().is_empty());
+
+    container
+        .parse_analysis(&[0x02, 0x01], TagFormat::ID3)
+        .unwrap();
+    container
+        .parse_autotags(b"\x01\x01115.00\0-3.257\x000.000\0", TagFormat::ID3)
+        .unwrap();
+    container
+        .parse_markers2(
+            include_bytes!("../../tests/data/tags/markers2/hotcues_and_loops.flac.bin"),
+            TagFormat::FLAC,
+        )
+        .unwrap();
+
+    let tags = container.serialize_tags(TagFormat::ID3).unwrap();
+    let names: Vec<TagName> = tags.iter().map(|(name, _)| *name).collect();
+    assert_eq!(
+        names,
+        vec!["Serato Analysis", "Serato Autotags", "Serato Markers2"]
+    );
+    assert_eq!(tags[0].1, vec![0x02, 0x01]);
+
+    let tags = container.serialize_tags(TagFormat::FLAC).unwrap();
+    let names: Vec<TagName> = tags.iter().map(|(name, _)| *name).collect();
+    assert_eq!(
+        names,
+        vec!["SERATO_ANALYSIS", "SERATO_AUTOGAIN", "SERATO_MARKERS_V2"]
+    );
+    let mut reparsed = TagContainer::new();
+    reparsed
+        .parse_markers2(&tags[2].1, TagFormat::FLAC)
+        .unwrap();
+    assert_eq!(reparsed.cues(), container.cues());
+    assert_eq!(reparsed.loops(), container.loops());
+
+    let tags = container.serialize_tags(TagFormat::MP4).unwrap();
+    let names: Vec<TagName> = tags.iter().map(|(name, _)| *name).collect();
+    assert_eq!(names, vec!["analysisVersion", "autgain", "markersv2"]);
+
+    // Autotags can't be written to Ogg files yet, so they are skipped.
+    let (tags, findings) = container.serialize_tags_checked(TagFormat::Ogg).unwrap();
+    let names: Vec<TagName> = tags.iter().map(|(name, _)| *name).collect();
+    assert_eq!(names, vec!["serato_analysis_ver", "serato_markers2"]);
+    assert_eq!(
+        findings,
+        vec![Finding {
+            tag: TagKind::Autotags,
+            index: 0,
+            problem: Problem::UnsupportedTagFormat {
+                tag_format: TagFormat::Ogg
+            },
+        }]
+    );
+}
+
+#[test]
+fn test_preserve_raw() {
+    let input = include_bytes!("../../tests/data/tags/analysis/version_2_1_encoded_HH.flac.bin");
+
+    let mut container = TagContainer::new();
+    container.parse_analysis(input, TagFormat::FLAC).unwrap();
+    let mut output = vec![];
+    container
+        .write_analysis(&mut output, TagFormat::FLAC)
+        .unwrap();
+    assert_ne!(&output[..], &input[..]);
+
+    let mut container = TagContainer::with_options(ParseOptions {
+        preserve_raw: true,
+        ..ParseOptions::default()
+    });
+    container.parse_analysis(input, TagFormat::FLAC).unwrap();
+    let mut output = vec![];
+    let bytes_written = container
+        .write_analysis(&mut output, TagFormat::FLAC)
+        .unwrap();
+    assert_eq!(bytes_written, input.len());
+    assert_eq!(&output[..], &input[..]);
+
+    // The original data is only used for the format it was parsed from.
+    let mut output = vec![];
+    container
+        .write_analysis(&mut output, TagFormat::ID3)
+        .unwrap();
+    assert_eq!(output, vec![0x02, 0x01]);
+
+    let container = container.into_modified();
+    let mut output = vec![];
+    container
+        .write_analysis(&mut output, TagFormat::FLAC)
+        .unwrap();
+    assert_ne!(&output[..], &input[..]);
+}
+
+#[test]
+fn test_ogg_roundtrip() {
+    let mut container = TagContainer::new();
+    container
+        .parse_markers2(
+            include_bytes!("../../tests/data/tags/markers2/hotcues_and_loops.ogg.bin"),
+            TagFormat::Ogg,
+        )
+        .unwrap();
+    container.parse_analysis(b"2.1", TagFormat::Ogg).unwrap();
+
+    let cues = container.cues();
+    assert_eq!(cues.len(), 6);
+    assert_eq!(cues[0].index, 0);
+    assert_eq!(cues[0].position_millis, 0);
+    assert_eq!(cues[0].label, "Start of Track");
+    assert_eq!(cues[5].index, 7);
+    assert_eq!(cues[5].position_millis, 213391);
+    assert_eq!(cues[5].label, "End of Track");
+
+    let loops = container.loops();
+    assert_eq!(loops.len(), 3);
+    assert_eq!(loops[2].start_position_millis, 254);
+    assert_eq!(loops[2].end_position_millis, 2341);
+    assert!(loops[2].is_locked);
+
+    let tags = container.serialize_tags(TagFormat::Ogg).unwrap();
+    assert_eq!(tags[0], ("serato_analysis_ver", b"2.1".to_vec()));
+    assert_eq!(tags[1].0, "serato_markers2");
+
+    let mut reparsed = TagContainer::new();
+    reparsed.parse_markers2(&tags[1].1, TagFormat::Ogg).unwrap();
+    assert_eq!(reparsed.cues(), cues);
+    assert_eq!(reparsed.loops(), loops);
+}
+
+#[test]
+fn test_set_cues() {
+    let mut container = TagContainer::with_options(ParseOptions {
+        preserve_raw: true,
+        ..ParseOptions::default()
+    });
+    container
+        .parse_markers(
+            include_bytes!("../../tests/data/tags/markers/hotcues_with_names.id3.bin"),
+            TagFormat::ID3,
+        )
+        .unwrap();
+    container
+        .parse_markers2(
+            include_bytes!("../../tests/data/tags/markers2/hotcues_with_names.id3.bin"),
+            TagFormat::ID3,
+        )
+        .unwrap();
+    assert_eq!(container.cues().len(), 3);
+
+    let cues = vec![
+        generic::Cue {
+            index: 6,
+            position_millis: 60000,
+            color: Color::from(0x00CC00),
+            label: String::from("Outro"),
+        },
+        generic::Cue {
+            index: 1,
+            position_millis: 1234,
+            color: Color::from(0xCC0000),
+            label: String::from("Drop"),
+        },
+    ];
+    container.set_cues(cues.clone()).unwrap();
+
+    let mut expected = cues.clone();
+    expected.sort_by_key(|cue| cue.index);
+    assert_eq!(container.cues(), expected);
+
+    // `Serato Markers2` contains the cues (including labels) in Serato's entry order.
+    let tag = container.markers2.as_ref().unwrap();
+    assert_eq!(tag.cues(), expected);
+    assert!(matches!(tag.content.markers[0], markers2::Marker::Color(_)));
+    assert!(matches!(
+        tag.content.markers.last(),
+        Some(markers2::Marker::BPMLock(_))
+    ));
+
+    // `Serato Markers_` only contains the first 5 cues and no labels.
+    let legacy_cues = container.markers.as_ref().unwrap().cues();
+    assert_eq!(legacy_cues.len(), markers::NUM_CUES);
+    for (index, marker) in legacy_cues {
+        if index == 1 {
+            assert_eq!(marker.marker_type, markers::MarkerType::Cue);
+            assert_eq!(marker.start_position_millis, Some(1234));
+            assert_eq!(marker.color, Color::from(0xCC0000));
+        } else {
+            assert_eq!(marker.marker_type, markers::MarkerType::Invalid);
+            assert_eq!(marker.start_position_millis, None);
+        }
+    }
+
+    // The modified tags must not be written from the preserved original data.
+    let tags = container.serialize_tags(TagFormat::ID3).unwrap();
+    let mut reparsed = TagContainer::new();
+    reparsed.parse_markers(&tags[0].1, TagFormat::ID3).unwrap();
+    reparsed.parse_markers2(&tags[1].1, TagFormat::ID3).unwrap();
+    assert_eq!(reparsed.cues(), expected);
+
+    // Invalid or duplicate indices are rejected.
+    let mut invalid = expected.clone();
+    invalid[0].index = 8;
+    assert!(matches!(
+        container.set_cues(invalid),
+        Err(Error::InvalidMarkerIndex { index: 8 })
+    ));
+    let mut duplicate = expected.clone();
+    duplicate[1].index = 1;
+    assert!(matches!(
+        container.set_cues(duplicate),
+        Err(Error::DuplicateMarkerIndex { index: 1 })
+    ));
+    assert_eq!(container.cues(), expected);
+
+    container.set_cues(vec![]).unwrap();
+    assert!(container.cues().is_empty());
+    assert!(container.markers2.as_ref().unwrap().cues().is_empty());
+}
+
+#[test]
+fn test_create_markers() {
+    let mut container = TagContainer::new();
+    container
+        .set_cues(vec![
+            generic::Cue {
+                index: 0,
+                position_millis: 0,
+                color: Color::from(0xCC0000),
+                label: String::from("Start"),
+            },
+            generic::Cue {
+                index: 7,
+                position_millis: 5000,
+                color: Color::from(0xCC0000),
+                label: String::new(),
+            },
+        ])
+        .unwrap();
+    assert!(container.markers.is_none());
+
+    container.create_markers().unwrap();
+    let markers = container.markers.as_ref().unwrap();
+    assert_eq!(
+        markers.entries.len(),
+        markers::NUM_CUES + markers::NUM_LOOPS
+    );
+    assert_eq!(markers.track_color, Color::from(0xFFFFFF));
+    assert_eq!(container.cues().len(), 2);
+    assert_eq!(container.cues()[0].label, "Start");
+}
+
+#[test]
+fn test_set_loops() {
+    let saved_loop = |index, start_position_millis, is_locked| generic::Loop {
+        index,
+        start_position_millis,
+        end_position_millis: start_position_millis + 1000,
+        color: Color::from(0x27AAE1),
+        is_locked,
+        label: format!("Loop {}", index),
+    };
+
+    let mut container = TagContainer::new();
+    container
+        .parse_markers(
+            include_bytes!("../../tests/data/tags/markers/saved_loops.id3.bin"),
+            TagFormat::ID3,
+        )
+        .unwrap();
+    container
+        .parse_markers2(
+            include_bytes!("../../tests/data/tags/markers2/saved_loops.id3.bin"),
+            TagFormat::ID3,
+        )
+        .unwrap();
+    let mut loops = container.loops();
+    assert_eq!(loops.len(), 4);
+
+    // Add a loop and update an existing one.
+    loops.push(saved_loop(7, 90000, true));
+    loops[0].start_position_millis = 500;
+    loops[0].is_locked = true;
+    let unmirrored = container.set_loops(loops.clone()).unwrap();
+    assert!(unmirrored.is_empty());
+    assert_eq!(container.loops(), loops);
+    assert_eq!(container.markers2.as_ref().unwrap().loops(), loops);
+    let legacy_loops = container.markers.as_ref().unwrap().loops();
+    assert_eq!(legacy_loops[0].1.start_position_millis, Some(500));
+    assert!(legacy_loops[0].1.is_locked);
+    assert_eq!(legacy_loops[7].1.start_position_millis, Some(90000));
+    assert_eq!(legacy_loops[8].1.start_position_millis, None);
+
+    // Delete all but one loop.
+    container.set_loops(vec![loops[4].clone()]).unwrap();
+    assert_eq!(container.loops(), vec![loops[4].clone()]);
+    let legacy_loops = container.markers.as_ref().unwrap().loops();
+    assert!(legacy_loops
+        .iter()
+        .filter(|(index, _)| *index != 7)
+        .all(|(_, marker)| marker.start_position_millis.is_none()
+            && marker.marker_type == markers::MarkerType::Loop));
+
+    // Loops that don't have a slot in `Serato Markers_` are reported.
+    container
+        .markers
+        .as_mut()
+        .unwrap()
+        .entries
+        .truncate(markers::NUM_CUES + 2);
+    let unmirrored = container
+        .set_loops(vec![saved_loop(1, 0, false), saved_loop(5, 0, false)])
+        .unwrap();
+    assert_eq!(unmirrored, vec![5]);
+    assert_eq!(container.markers2.as_ref().unwrap().loops().len(), 2);
+    assert_eq!(container.loops().len(), 2);
+
+    assert!(matches!(
+        container.set_loops(vec![saved_loop(8, 0, false)]),
+        Err(Error::InvalidMarkerIndex { index: 8 })
+    ));
+    assert!(matches!(
+        container.set_loops(vec![saved_loop(2, 0, false), saved_loop(2, 1000, false)]),
+        Err(Error::DuplicateMarkerIndex { index: 2 })
+    ));
+    assert_eq!(container.loops().len(), 2);
+}
+
+#[test]
+fn test_set_track_color() {
+    let color = Color::from(0xFF99FF);
+
+    // Without any tags
+    let mut container = TagContainer::new();
+    container.set_track_color(Some(color));
+    assert_eq!(container.track_color(), Some(color));
+    assert_eq!(
+        container.markers2.as_ref().unwrap().track_color(),
+        Some(color)
+    );
+    assert!(container.markers.is_none());
+
+    // With both tags
+    let markers_data = include_bytes!("../../tests/data/tags/markers/hotcue_colors.id3.bin");
+    let markers2_data = include_bytes!("../../tests/data/tags/markers2/hotcue_colors.id3.bin");
+    let mut container = TagContainer::with_options(ParseOptions {
+        preserve_raw: true,
+        ..ParseOptions::default()
+    });
+    container
+        .parse_markers(markers_data, TagFormat::ID3)
+        .unwrap();
+    container
+        .parse_markers2(markers2_data, TagFormat::ID3)
+        .unwrap();
+    let cues = container.cues();
+
+    // Setting the current color again doesn't modify the data.
+    container.set_track_color(Some(color::NO_TRACK_COLOR));
+    let tags = container.serialize_tags(TagFormat::ID3).unwrap();
+    assert_eq!(&tags[0].1[..], &markers_data[..]);
+    assert_eq!(&tags[1].1[..], &markers2_data[..]);
+
+    container.set_track_color(Some(color));
+    assert_eq!(container.track_color(), Some(color));
+    assert_eq!(container.markers.as_ref().unwrap().track_color, color);
+    assert_eq!(
+        container.markers2.as_ref().unwrap().track_color(),
+        Some(color)
+    );
+    assert_eq!(container.cues(), cues);
+    let tags = container.serialize_tags(TagFormat::ID3).unwrap();
+    let mut reparsed = TagContainer::new();
+    reparsed.parse_markers(&tags[0].1, TagFormat::ID3).unwrap();
+    reparsed.parse_markers2(&tags[1].1, TagFormat::ID3).unwrap();
+    assert_eq!(reparsed.track_color(), Some(color));
+
+    // Clearing the color writes the "no color" value.
+    container.set_track_color(None);
+    assert_eq!(container.track_color(), Some(color::NO_TRACK_COLOR));
+    assert_eq!(
+        container.markers2.as_ref().unwrap().track_color(),
+        Some(color::NO_TRACK_COLOR)
+    );
+    assert_eq!(
+        container
+            .track_color()
+            .and_then(Color::into_displayed_track_color),
+        None
+    );
+}
+
+#[test]
+fn test_set_beatgrid() {
+    use beatgrid::{NonTerminalMarker, TerminalMarker};
+
+    let mut container = TagContainer::new();
+    assert!(container.beatgrid().is_none());
+    container
+        .set_beatgrid(
+            vec![NonTerminalMarker {
+                position: 0.05,
+                beats_till_next_marker: 16,
+            }],
+            TerminalMarker {
+                position: 8.05,
+                bpm: 120.0,
+            },
+        )
+        .unwrap();
+    let (non_terminal_markers, terminal_marker) = container.beatgrid().unwrap();
+    assert_eq!(non_terminal_markers.len(), 1);
+    assert_eq!(non_terminal_markers[0].beats_till_next_marker, 16);
+    assert_eq!(terminal_marker.bpm, 120.0);
+
+    let mut data = vec![];
+    container.write_beatgrid(&mut data, TagFormat::ID3).unwrap();
+    assert_eq!(
+        Beatgrid::parse(&data).unwrap().terminal_marker.position,
+        8.05
+    );
+
+    // Invalid grids are rejected and leave the current beatgrid untouched.
+    let result = container.set_beatgrid(
+        vec![NonTerminalMarker {
+            position: 8.05,
+            beats_till_next_marker: 16,
+        }],
+        TerminalMarker {
+            position: 0.05,
+            bpm: 120.0,
+        },
+    );
+    assert!(matches!(
+        result,
+        Err(Error::InvalidBeatgridMarker { index: 1, .. })
+    ));
+    let result = container.set_beatgrid(
+        vec![],
+        TerminalMarker {
+            position: 0.05,
+            bpm: 0.0,
+        },
+    );
+    assert!(matches!(
+        result,
+        Err(Error::InvalidBeatgridMarker { index: 0, .. })
+    ));
+    assert_eq!(container.beatgrid().unwrap().1.position, 8.05);
+
+    // The version and footer of existing tags are kept.
+    let mut container = TagContainer::new();
+    container
+        .parse_beatgrid(
+            include_bytes!("../../tests/data/tags/beatgrid/terminal_only_with_footer_0x37.id3.bin"),
+            TagFormat::ID3,
+        )
+        .unwrap();
+    container
+        .set_beatgrid(
+            vec![],
+            TerminalMarker {
+                position: 1.0,
+                bpm: 128.0,
+            },
+        )
+        .unwrap();
+    assert_eq!(container.beatgrid.as_ref().unwrap().footer, 0x37);
+    assert_eq!(container.beatgrid().unwrap().1.bpm, 128.0);
+}
+
+#[test]
+fn test_set_bpm_locked() {
+    let mut container = TagContainer::new();
+    container
+        .parse_markers2(
+            include_bytes!("../../tests/data/tags/markers2/analyzed.id3.bin"),
+            TagFormat::ID3,
+        )
+        .unwrap();
+    assert_eq!(container.bpm_locked(), Some(false));
+
+    // Removing the entry and adding it again yields the same ordering as in files written by
+    // Serato with BPMLOCK enabled.
+    container.set_bpm_locked(None);
+    assert_eq!(container.bpm_locked(), None);
+    assert_eq!(
+        container.markers2.as_ref().unwrap().content.markers.len(),
+        1
+    );
+    container.set_bpm_locked(Some(true));
+    assert_eq!(container.bpm_locked(), Some(true));
+    let expected = Markers2::parse_id3(include_bytes!(
+        "../../tests/data/tags/markers2/bpmlock_enabled.id3.bin"
+    ))
+    .unwrap();
+    let mut data = vec![];
+    container.write_markers2(&mut data, TagFormat::ID3).unwrap();
+    assert_eq!(
+        Markers2::parse_id3(&data).unwrap().content,
+        expected.content
+    );
+
+    // The entry is inserted between loops and flips.
+    let mut container = TagContainer::new();
+    container
+        .parse_markers2(
+            include_bytes!("../../tests/data/tags/markers2/flips.id3.bin"),
+            TagFormat::ID3,
+        )
+        .unwrap();
+    let original = container.markers2.as_ref().unwrap().content.markers.len();
+    container.set_bpm_locked(None);
+    container.set_bpm_locked(Some(true));
+    let markers = &container.markers2.as_ref().unwrap().content.markers;
+    assert_eq!(markers.len(), original);
+    let position = markers
+        .iter()
+        .position(|m| matches!(m, markers2::Marker::BPMLock(_)))
+        .unwrap();
+    assert!(matches!(markers[position - 1], markers2::Marker::Cue(_)));
+    assert!(matches!(markers[position + 1], markers2::Marker::Flip(_)));
+
+    // Without any tags
+    let mut container = TagContainer::new();
+    container.set_bpm_locked(Some(true));
+    assert_eq!(container.bpm_locked(), Some(true));
+}
+
+#[test]
+fn test_add_and_remove_cue() {
+    let cue = |index, position_millis| generic::Cue {
+        index,
+        position_millis,
+        color: Color::from(0xCC0000),
+        label: String::new(),
+    };
+
+    let mut container = TagContainer::new();
+    container
+        .parse_markers(
+            include_bytes!("../../tests/data/tags/markers/hotcues_with_names.id3.bin"),
+            TagFormat::ID3,
+        )
+        .unwrap();
+    container
+        .parse_markers2(
+            include_bytes!("../../tests/data/tags/markers2/hotcues_with_names.id3.bin"),
+            TagFormat::ID3,
+        )
+        .unwrap();
+
+    // Make the tags disagree: `Serato Markers_` moves cue 0, which takes precedence.
+    let markers2_cue = container.markers2.as_ref().unwrap().cues()[0].clone();
+    assert_eq!(markers2_cue.index, 0);
+    container.markers.as_mut().unwrap().entries[0].start_position_millis = Some(4242);
+    let merged_cue = container.cues()[0].clone();
+    assert_eq!(merged_cue.position_millis, 4242);
+    assert_eq!(merged_cue.label, markers2_cue.label);
+
+    // Occupied or non-existing slots are rejected.
+    assert!(matches!(
+        container.add_cue(cue(0, 1000), false),
+        Err(Error::MarkerSlotOccupied { index: 0 })
+    ));
+    assert!(matches!(
+        container.add_cue(cue(8, 1000), true),
+        Err(Error::InvalidMarkerIndex { index: 8 })
+    ));
+
+    // Adding a cue to an empty slot syncs the other cues between both tags.
+    assert_eq!(container.add_cue(cue(6, 1000), false).unwrap(), None);
+    assert_eq!(
+        container.markers2.as_ref().unwrap().cues()[0],
+        merged_cue.clone()
+    );
+    assert_eq!(container.cues().last(), Some(&cue(6, 1000)));
+
+    // Overwriting returns the previous (merged) value.
+    let previous = container.add_cue(cue(0, 1000), true).unwrap();
+    assert_eq!(previous, Some(merged_cue.clone()));
+    assert_eq!(container.cues()[0], cue(0, 1000));
+    assert_eq!(
+        container.markers.as_ref().unwrap().entries[0].start_position_millis,
+        Some(1000)
+    );
+
+    // Removing the cue clears the slot in both tags.
+    assert_eq!(container.remove_cue(0).unwrap(), Some(cue(0, 1000)));
+    assert_eq!(container.remove_cue(0).unwrap(), None);
+    assert_eq!(
+        container.markers.as_ref().unwrap().entries[0].marker_type,
+        markers::MarkerType::Invalid
+    );
+    assert!(container
+        .markers2
+        .as_ref()
+        .unwrap()
+        .cues()
+        .iter()
+        .all(|c| c.index != 0));
+
+    // Undo
+    container.add_cue(previous.unwrap(), false).unwrap();
+    assert_eq!(container.cues()[0], merged_cue);
+}
+
+#[test]
+fn test_clear_serato_data() {
+    let mut container = TagContainer::new();
+    container
+        .parse_analysis(&[0x02, 0x01], TagFormat::ID3)
+        .unwrap();
+    container
+        .parse_markers(
+            include_bytes!("../../tests/data/tags/markers/hotcues_with_names.id3.bin"),
+            TagFormat::ID3,
+        )
+        .unwrap();
+    container
+        .parse_markers2(
+            include_bytes!("../../tests/data/tags/markers2/hotcues_with_names.id3.bin"),
+            TagFormat::ID3,
+        )
+        .unwrap();
+
+    container.clear_tag(TagKind::Markers);
+    let tags = container.serialize_tags(TagFormat::ID3).unwrap();
+    let names: Vec<TagName> = tags.iter().map(|(name, _)| *name).collect();
+    assert_eq!(names, vec!["Serato Analysis", "Serato Markers2"]);
+    assert!(!container.cues().is_empty());
+    assert_eq!(
+        container.cleared_tags().collect::<Vec<TagKind>>(),
+        vec![TagKind::Markers]
+    );
+
+    container.clear_serato_data();
+    for tag_format in &[
+        TagFormat::ID3,
+        TagFormat::FLAC,
+        TagFormat::MP4,
+        TagFormat::Ogg,
+    ] {
+        assert!(container.serialize_tags(*tag_format).unwrap().is_empty());
+    }
+    assert!(container.cues().is_empty());
+    assert_eq!(container.cleared_tags().count(), TagKind::ALL.len());
+
+    // Tags that are set again are no longer reported as cleared.
+    container.set_bpm_locked(Some(true));
+    assert!(container
+        .cleared_tags()
+        .all(|kind| kind != TagKind::Markers2));
+}
+
+#[test]
+fn test_builder() {
+    // Without any data, the `Serato Markers2` tag looks like the one of a freshly analyzed track.
+    let container = TagContainerBuilder::new().build().unwrap();
+    let expected = Markers2::parse_id3(include_bytes!(
+        "../../tests/data/tags/markers2/analyzed.id3.bin"
+    ))
+    .unwrap();
+    assert_eq!(container.markers2.as_ref().unwrap(), &expected);
+    assert_eq!(
+        container.analysis.as_ref().unwrap().version,
+        analysis::LATEST_ANALYSIS_VERSION
+    );
+    assert!(container.beatgrid().is_none());
+    assert!(container.markers.is_none());
+
+    let container = TagContainerBuilder::new()
+        .cue(3, 1000, Color::from(0xCC0000), "Drop")
+        .cue(0, 0, Color::from(0x0000CC), "")
+        .saved_loop(1, 2000, 4000, Color::from(0x27AAE1), true, "Loop")
+        .beatgrid_constant(120.0, 500.0)
+        .track_color(Color::from(0x99FF99))
+        .bpm_locked(true)
+        .build()
+        .unwrap();
+    let cues = container.cues();
+    assert_eq!(cues.len(), 2);
+    assert_eq!(cues[0].index, 0);
+    assert_eq!(cues[1].label, "Drop");
+    assert!(container.loops()[0].is_locked);
+    assert_eq!(container.track_color(), Some(Color::from(0x99FF99)));
+    assert_eq!(container.bpm_locked(), Some(true));
+    let (non_terminal_markers, terminal_marker) = container.beatgrid().unwrap();
+    assert!(non_terminal_markers.is_empty());
+    assert_eq!(terminal_marker.position, 0.5);
+    assert_eq!(terminal_marker.bpm, 120.0);
+
+    // All tags can be written.
+    let tags = container.serialize_tags(TagFormat::ID3).unwrap();
+    let names: Vec<TagName> = tags.iter().map(|(name, _)| *name).collect();
+    assert_eq!(
+        names,
+        vec!["Serato Analysis", "Serato BeatGrid", "Serato Markers2"]
+    );
+
+    // Validation errors
+    let result = TagContainerBuilder::new()
+        .cue(1, 0, Color::from(0xCC0000), "")
+        .cue(1, 1000, Color::from(0xCC0000), "")
+        .build();
+    assert!(matches!(
+        result,
+        Err(Error::DuplicateMarkerIndex { index: 1 })
+    ));
+    assert_eq!(
+        result.err().unwrap().to_string(),
+        "Duplicate marker index 1"
+    );
+    let result = TagContainerBuilder::new()
+        .saved_loop(2, 4000, 2000, Color::from(0x27AAE1), false, "")
+        .build();
+    assert!(matches!(
+        result,
+        Err(Error::InvalidLoopPositions {
+            index: 2,
+            start_position_millis: 4000,
+            end_position_millis: 2000,
+        })
+    ));
+    let result = TagContainerBuilder::new()
+        .beatgrid_constant(-1.0, 0.0)
+        .build();
+    assert!(matches!(
+        result,
+        Err(Error::InvalidBeatgridMarker { index: 0, .. })
+    ));
+}
+
+#[test]
+fn test_merge() {
+    let cue = |index, color| generic::Cue {
+        index,
+        position_millis: 1000 * u32::from(index),
+        color: Color::from(color),
+        label: String::new(),
+    };
+
+    let beatgrid = |bpm| beatgrid::TerminalMarker { position: 0.0, bpm };
+
+    let mut file = TagContainer::new();
+    file.parse_analysis(&[0x02, 0x01], TagFormat::ID3).unwrap();
+    file.set_cues(vec![cue(0, 0xCC0000), cue(1, 0xCC0000)])
+        .unwrap();
+    file.set_beatgrid(vec![], beatgrid(120.0)).unwrap();
+    file.set_bpm_locked(Some(true));
+
+    let mut database = TagContainer::new();
+    database
+        .parse_analysis(&[0x02, 0x05], TagFormat::ID3)
+        .unwrap();
+    database
+        .set_cues(vec![cue(1, 0x0000CC), cue(2, 0x0000CC)])
+        .unwrap();
+    database.set_beatgrid(vec![], beatgrid(128.0)).unwrap();
+    database
+        .parse_autotags(b"\x01\x01115.00\0-3.257\x000.000\0", TagFormat::ID3)
+        .unwrap();
+
+    let merged = file.merge(database, MergePolicy::PreferSelf).unwrap();
+    assert_eq!(
+        merged.cues(),
+        vec![cue(0, 0xCC0000), cue(1, 0xCC0000), cue(2, 0x0000CC)]
+    );
+    assert_eq!(merged.beatgrid().unwrap().1.bpm, 120.0);
+    assert_eq!(merged.analysis.as_ref().unwrap().version.minor, 1);
+    // Tags that are missing on one side are taken from the other one.
+    assert_eq!(merged.auto_gain(), Some(-3.257));
+    assert_eq!(merged.bpm_locked(), Some(true));
+
+    // Conflicts can also be resolved in favor of the other container.
+    let file = merged;
+    let mut database = TagContainer::new();
+    database.set_cues(vec![cue(1, 0x00CC00)]).unwrap();
+    database.set_beatgrid(vec![], beatgrid(90.0)).unwrap();
+    let merged = file.merge(database, MergePolicy::PreferOther).unwrap();
+    assert_eq!(merged.cues()[1], cue(1, 0x00CC00));
+    assert_eq!(merged.cues().len(), 3);
+    assert_eq!(merged.beatgrid().unwrap().1.bpm, 90.0);
+    assert_eq!(merged.bpm_locked(), Some(true));
+    assert_eq!(merged.markers2.as_ref().unwrap().cues(), merged.cues());
+
+    // The container with the newer analysis version wins.
+    let mut old = TagContainer::new();
+    old.parse_analysis(&[0x02, 0x01], TagFormat::ID3).unwrap();
+    old.set_cues(vec![cue(0, 0xCC0000)]).unwrap();
+    let mut new = TagContainer::new();
+    new.parse_analysis(&[0x02, 0x05], TagFormat::ID3).unwrap();
+    new.set_cues(vec![cue(0, 0x0000CC)]).unwrap();
+    let merged = old.merge(new, MergePolicy::PreferNewerAnalysis).unwrap();
+    assert_eq!(merged.cues(), vec![cue(0, 0x0000CC)]);
+    assert_eq!(merged.analysis.as_ref().unwrap().version.minor, 5);
+
+    // Merging empty containers doesn't create any tags.
+    let merged = TagContainer::new()
+        .merge(TagContainer::new(), MergePolicy::PreferNewerAnalysis)
+        .unwrap();
+    assert!(merged.serialize_tags(TagFormat::ID3).unwrap().is_empty());
+}
+
+#[test]
+fn test_validate() {
+    let mut container = TagContainer::new();
+    container
+        .parse_markers(
+            include_bytes!("../../tests/data/tags/markers/hotcues_with_names.id3.bin"),
+            TagFormat::ID3,
+        )
+        .unwrap();
+    container
+        .parse_markers2(
+            include_bytes!("../../tests/data/tags/markers2/hotcues_with_names.id3.bin"),
+            TagFormat::ID3,
+        )
+        .unwrap();
+    container
+        .parse_beatgrid(
+            include_bytes!("../../tests/data/tags/beatgrid/terminal_only_with_footer_0x00.id3.bin"),
+            TagFormat::ID3,
+        )
+        .unwrap();
+    assert_eq!(container.validate(), vec![]);
+
+    let markers = container.markers.as_mut().unwrap();
+    markers.entries[0].marker_type = markers::MarkerType::Invalid;
+    markers.entries[1].start_position_millis = None;
+    markers.entries[markers::NUM_CUES].start_position_millis = Some(1000);
+    markers.entries[markers::NUM_CUES + 1].start_position_millis = Some(1000);
+    markers.entries[markers::NUM_CUES + 1].end_position_millis = Some(500);
+    container.markers2.as_mut().unwrap().set_cues(vec![
+        generic::Cue {
+            index: 0,
+            position_millis: 0,
+            color: Color::from(0xCC0000),
+            label: String::new(),
+        },
+        generic::Cue {
+            index: 9,
+            position_millis: 0,
+            color: Color::from(0xCC0000),
+            label: String::new(),
+        },
+    ]);
+    container.beatgrid.as_mut().unwrap().terminal_marker.bpm = 0.0;
+    container.overview = Some(Overview {
+        version: generic::Version { major: 1, minor: 5 },
+        data: vec![vec![0; 16], vec![0; 15]],
+        padding: 0,
+    });
+
+    let findings = container.validate();
+    let summary: Vec<(TagKind, usize)> = findings.iter().map(|f| (f.tag, f.index)).collect();
+    assert_eq!(
+        summary,
+        vec![
+            (TagKind::Markers2, 9),
+            (TagKind::Markers, 0),
+            (TagKind::Markers, 1),
+            (TagKind::Markers, 0),
+            (TagKind::Markers, 1),
+            (TagKind::Beatgrid, 0),
+            (TagKind::Overview, 1),
+        ]
+    );
+    assert_eq!(findings[0].problem, Problem::IndexOutOfRange);
+    assert_eq!(findings[1].problem, Problem::CueUnsetInMarkers);
+    assert_eq!(findings[2].problem, Problem::MissingPosition);
+    assert_eq!(findings[3].problem, Problem::MissingPosition);
+    assert_eq!(
+        findings[4].problem,
+        Problem::InvalidLoopPositions {
+            start_position_millis: 1000,
+            end_position_millis: 500
+        }
+    );
+    assert_eq!(findings[5].problem, Problem::BpmOutOfRange { bpm: 0.0 });
+    assert_eq!(
+        findings[6].to_string(),
+        "Serato Overview (entry 1): chunk has 15 bytes instead of 16"
+    );
+
+    // Tags with unknown future versions are still parsed, but reported.
+    container.markers2.as_mut().unwrap().content.version = generic::Version { major: 1, minor: 2 };
+    let findings = container.validate();
+    assert_eq!(findings[0].tag, TagKind::Markers2);
+    assert_eq!(
+        findings[0].problem,
+        Problem::UnknownVersion {
+            version: generic::Version { major: 1, minor: 2 }
+        }
+    );
+}
+
+#[test]
+fn test_parse_tag() {
+    let tags_dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/tags");
+    let formats = [
+        ("id3", TagFormat::ID3),
+        ("flac", TagFormat::FLAC),
+        ("mp4", TagFormat::MP4),
+        ("ogg", TagFormat::Ogg),
+    ];
+    for (extension, tag_format) in formats.iter() {
+        let mut container = TagContainer::new();
+        let mut expected = HashSet::new();
+        for kind in TagKind::ALL.iter() {
+            let dir = tags_dir.join(kind.name()["Serato ".len()..].to_ascii_lowercase());
+            let entries = match std::fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            for entry in entries {
+                let path = entry.unwrap().path();
+                let suffix = format!(".{}.bin", extension);
+                if !path.to_str().unwrap().ends_with(&suffix) {
+                    continue;
+                }
+                let data = std::fs::read(&path).unwrap();
+                let name = kind.tag_name(*tag_format).unwrap();
+                let parsed = container.parse_tag(name, &data, *tag_format).unwrap();
+                assert_eq!(parsed, Some(*kind));
+                expected.insert(*kind);
+            }
+        }
+
+        assert!(!expected.is_empty());
+        for kind in TagKind::ALL.iter() {
+            assert_eq!(container.has(*kind), expected.contains(kind));
+        }
+    }
+
+    // Vorbis comment keys are case-insensitive.
+    let mut container = TagContainer::new();
+    let kind = container
+        .parse_tag(
+            "serato_analysis",
+            include_bytes!("../../tests/data/tags/analysis/version_2_1_encoded_HH.flac.bin"),
+            TagFormat::FLAC,
+        )
+        .unwrap();
+    assert_eq!(kind, Some(TagKind::Analysis));
+    assert_eq!(
+        TagKind::from_tag_name("serato analysis", TagFormat::ID3),
+        None
+    );
+
+    // Unknown identifiers are reported, but not treated as errors.
+    let kind = container
+        .parse_tag("Serato Unknown", b"foo", TagFormat::ID3)
+        .unwrap();
+    assert_eq!(kind, None);
+}
+
+#[test]
+fn test_serato_identifiers() {
+    // All identifiers that the parsers and writers use.
+    let identifiers = [
+        (TagFormat::ID3, "Serato Analysis", TagKind::Analysis),
+        (TagFormat::ID3, "Serato Autotags", TagKind::Autotags),
+        (TagFormat::ID3, "Serato BeatGrid", TagKind::Beatgrid),
+        (TagFormat::ID3, "Serato Markers_", TagKind::Markers),
+        (TagFormat::ID3, "Serato Markers2", TagKind::Markers2),
+        (TagFormat::ID3, "Serato Offsets_", TagKind::Offsets),
+        (TagFormat::ID3, "Serato Overview", TagKind::Overview),
+        (TagFormat::FLAC, "SERATO_ANALYSIS", TagKind::Analysis),
+        (TagFormat::FLAC, "SERATO_AUTOGAIN", TagKind::Autotags),
+        (TagFormat::FLAC, "SERATO_BEATGRID", TagKind::Beatgrid),
+        (TagFormat::FLAC, "SERATO_MARKERS_V2", TagKind::Markers2),
+        (TagFormat::FLAC, "SERATO_OVERVIEW", TagKind::Overview),
+        (TagFormat::FLAC, "SERATO_RELVOL", TagKind::RelVolAd),
+        (TagFormat::FLAC, "SERATO_VIDASSOC", TagKind::VidAssoc),
+        (
+            TagFormat::MP4,
+            "----:com.serato.dj:analysisVersion",
+            TagKind::Analysis,
+        ),
+        (
+            TagFormat::MP4,
+            "----:com.serato.dj:autgain",
+            TagKind::Autotags,
+        ),
+        (
+            TagFormat::MP4,
+            "----:com.serato.dj:beatgrid",
+            TagKind::Beatgrid,
+        ),
+        (
+            TagFormat::MP4,
+            "----:com.serato.dj:markers",
+            TagKind::Markers,
+        ),
+        (
+            TagFormat::MP4,
+            "----:com.serato.dj:markersv2",
+            TagKind::Markers2,
+        ),
+        (
+            TagFormat::MP4,
+            "----:com.serato.dj:overview",
+            TagKind::Overview,
+        ),
+        (
+            TagFormat::MP4,
+            "----:com.serato.dj:relvol",
+            TagKind::RelVolAd,
+        ),
+        (
+            TagFormat::MP4,
+            "----:com.serato.dj:videoassociation",
+            TagKind::VidAssoc,
+        ),
+        (TagFormat::Ogg, "serato_analysis_ver", TagKind::Analysis),
+        (TagFormat::Ogg, "serato_markers2", TagKind::Markers2),
+    ];
+    for (tag_format, identifier, kind) in identifiers.iter() {
+        assert_eq!(kind.identifier(*tag_format).as_deref(), Some(*identifier));
+        assert_eq!(
+            TagKind::from_identifier(*tag_format, identifier),
+            Some(*kind)
+        );
+        assert!(is_serato_identifier(*tag_format, identifier));
+    }
+
+    // There are no other identifiers, and the name part of the MP4 atoms matches.
+    for kind in TagKind::ALL.iter() {
+        for tag_format in &[
+            TagFormat::ID3,
+            TagFormat::FLAC,
+            TagFormat::MP4,
+            TagFormat::Ogg,
+        ] {
+            let identifier = kind.identifier(*tag_format);
+            assert_eq!(
+                identifier.is_some(),
+                identifiers
+                    .iter()
+                    .any(|(format, _, k)| format == tag_format && k == kind)
+            );
+            if *tag_format == TagFormat::MP4 {
+                let expected = kind
+                    .tag_name(TagFormat::MP4)
+                    .map(|name| format!("----:{}:{}", super::format::mp4::FREEFORM_MEAN, name));
+                assert_eq!(identifier.map(Cow::into_owned), expected);
+            }
+        }
+    }
+
+    assert_eq!(
+        TagKind::from_identifier(TagFormat::MP4, "markersv2"),
+        Some(TagKind::Markers2)
+    );
+    assert_eq!(
+        TagKind::from_identifier(TagFormat::FLAC, "serato_beatgrid"),
+        Some(TagKind::Beatgrid)
+    );
+    assert!(!is_serato_identifier(
+        TagFormat::MP4,
+        "----:com.apple.iTunes:markersv2"
+    ));
+    assert!(!is_serato_identifier(TagFormat::ID3, "serato markers2"));
+    assert!(!is_serato_identifier(TagFormat::FLAC, "SERATO_MARKERS_"));
+    assert!(!is_serato_identifier(TagFormat::Ogg, "SERATO_OVERVIEW"));
+}
+
+#[test]
+fn test_tag_kind_identifiers() {
+    assert_eq!(TagKind::Analysis.id3_description(), Some(Analysis::ID3_TAG));
+    assert_eq!(TagKind::Markers2.id3_description(), Some("Serato Markers2"));
+    assert_eq!(TagKind::RelVolAd.id3_description(), None);
+    assert_eq!(TagKind::Offsets.id3_description(), Some("Serato Offsets_"));
+    assert_eq!(TagKind::Offsets.flac_comment(), None);
+    assert_eq!(TagKind::Markers2.flac_comment(), Some("SERATO_MARKERS_V2"));
+    assert_eq!(TagKind::Markers.flac_comment(), None);
+    assert_eq!(TagKind::VidAssoc.mp4_atom_name(), Some("videoassociation"));
+    assert_eq!(TagKind::Markers2.ogg_comment(), Some("serato_markers2"));
+    assert_eq!(TagKind::Overview.ogg_comment(), None);
+
+    // All identifiers are accepted by the corresponding parsers.
+    for kind in TagKind::ALL.iter() {
+        for tag_format in &[
+            TagFormat::ID3,
+            TagFormat::FLAC,
+            TagFormat::MP4,
+            TagFormat::Ogg,
+        ] {
+            if let Some(name) = kind.tag_name(*tag_format) {
+                assert_eq!(TagKind::from_tag_name(name, *tag_format), Some(*kind));
+            }
+        }
+    }
+
+    let mut container = TagContainer::new();
+    container
+        .parse_analysis(&[0x02, 0x01], TagFormat::ID3)
+        .unwrap();
+    container.set_bpm_locked(Some(true));
+    assert_eq!(
+        container.present_tags(),
+        vec![TagKind::Analysis, TagKind::Markers2]
+    );
+    assert!(container.has(TagKind::Markers2));
+    assert!(!container.has(TagKind::Markers));
+}
+
+#[test]
+fn test_cues_and_loops_checked() {
+    let mut container = TagContainer::new();
+    container
+        .parse_markers(
+            include_bytes!("../../tests/data/tags/markers/very_long_names.id3.bin"),
+            TagFormat::ID3,
+        )
+        .unwrap();
+    container
+        .parse_markers2(
+            include_bytes!("../../tests/data/tags/markers2/very_long_names.id3.bin"),
+            TagFormat::ID3,
+        )
+        .unwrap();
+    let (cues, findings) = container.cues_checked();
+    assert_eq!(cues, container.cues());
+    assert!(findings.is_empty());
+    let (loops, findings) = container.loops_checked();
+    assert_eq!(loops, container.loops());
+    assert!(findings.is_empty());
+
+    let markers = container.markers.as_mut().unwrap();
+    markers.entries[0].marker_type = markers::MarkerType::Invalid;
+    markers.entries[1].start_position_millis = None;
+    markers.entries[markers::NUM_CUES].start_position_millis = None;
+    markers.entries[markers::NUM_CUES + 1].start_position_millis = None;
+    markers.entries[markers::NUM_CUES + 1].end_position_millis = None;
+
+    let (cues, findings) = container.cues_checked();
+    assert_eq!(cues.len(), 6);
+    assert_eq!(cues, container.cues());
+    assert_eq!(
+        findings,
+        vec![
+            Finding {
+                tag: TagKind::Markers,
+                index: 0,
+                problem: Problem::CueUnsetInMarkers,
+            },
+            Finding {
+                tag: TagKind::Markers,
+                index: 1,
+                problem: Problem::MissingPosition,
+            },
+        ]
+    );
+
+    let (loops, findings) = container.loops_checked();
+    assert_eq!(loops.len(), 6);
+    assert_eq!(loops, container.loops());
+    assert_eq!(
+        findings,
+        vec![
+            Finding {
+                tag: TagKind::Markers,
+                index: 0,
+                problem: Problem::MissingPosition,
+            },
+            Finding {
+                tag: TagKind::Markers,
+                index: 1,
+                problem: Problem::LoopUnsetInMarkers,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_analysis_state() {
+    let mut container = TagContainer::new();
+    assert_eq!(container.analysis_state(), AnalysisState::NotAnalyzed);
+    assert!(!container.is_analyzed());
+
+    container
+        .parse_autotags(
+            include_bytes!("../../tests/data/tags/autotags/analyzed.id3.bin"),
+            TagFormat::ID3,
+        )
+        .unwrap();
+    container
+        .parse_overview(
+            include_bytes!("../../tests/data/tags/overview/analyzed.id3.bin"),
+            TagFormat::ID3,
+        )
+        .unwrap();
+    assert_eq!(container.analysis_state(), AnalysisState::NotAnalyzed);
+
+    container
+        .parse_analysis(
+            include_bytes!("../../tests/data/tags/analysis/version_0_0.ogg.bin"),
+            TagFormat::Ogg,
+        )
+        .unwrap();
+    assert_eq!(
+        container.analysis_state(),
+        AnalysisState::Outdated {
+            version: generic::Version { major: 0, minor: 0 }
+        }
+    );
+    assert!(!container.is_analyzed());
+
+    container
+        .parse_analysis(
+            include_bytes!("../../tests/data/tags/analysis/analyzed.id3.bin"),
+            TagFormat::ID3,
+        )
+        .unwrap();
+    assert_eq!(container.analysis_state(), AnalysisState::Analyzed);
+    assert!(container.is_analyzed());
+
+    container.clear_tag(TagKind::Overview);
+    assert_eq!(container.analysis_state(), AnalysisState::Incomplete);
+    assert!(!container.is_analyzed());
+
+    // A freshly built container lacks the data that only Serato's analysis produces.
+    let container = TagContainerBuilder::new().build().unwrap();
+    assert_eq!(container.analysis_state(), AnalysisState::Incomplete);
+}
+
+#[test]
+fn test_cue_and_loop_at() {
+    let tags_dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/tags");
+    let fixtures = |name: &str| -> Vec<(TagFormat, Vec<u8>)> {
+        let mut fixtures = vec![];
+        for entry in std::fs::read_dir(tags_dir.join(name)).unwrap() {
+            let path = entry.unwrap().path();
+            let tag_format = match path.to_str().unwrap().rsplit('.').nth(1) {
+                Some("id3") => TagFormat::ID3,
+                Some("flac") => TagFormat::FLAC,
+                Some("mp4") => TagFormat::MP4,
+                Some("ogg") => TagFormat::Ogg,
+                _ => continue,
+            };
+            fixtures.push((tag_format, std::fs::read(&path).unwrap()));
+        }
+        fixtures
+    };
+    let markers_fixtures = fixtures("markers");
+    let markers2_fixtures = fixtures("markers2");
+    assert!(!markers_fixtures.is_empty());
+    assert!(!markers2_fixtures.is_empty());
+
+    // Combine all fixtures (including contradicting ones) and check that the single-slot
+    // lookups always agree with the merged lists.
+    let markers_fixtures = std::iter::once(None).chain(markers_fixtures.iter().map(Some));
+    for markers_fixture in markers_fixtures {
+        let markers2_fixtures = std::iter::once(None).chain(markers2_fixtures.iter().map(Some));
+        for markers2_fixture in markers2_fixtures {
+            let mut container = TagContainer::new();
+            if let Some((tag_format, data)) = markers_fixture {
+                container.parse_markers(data, *tag_format).unwrap();
+            }
+            if let Some((tag_format, data)) = markers2_fixture {
+                container.parse_markers2(data, *tag_format).unwrap();
+            }
+
+            let cues = container.cues();
+            let loops = container.loops();
+            for index in 0..=u8::MAX {
+                let expected = cues.iter().find(|cue| cue.index == index);
+                assert_eq!(container.cue_at(index).as_ref(), expected);
+                let expected = loops.iter().find(|saved_loop| saved_loop.index == index);
+                assert_eq!(container.loop_at(index).as_ref(), expected);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_shift_positions() {
+    let mut container = TagContainerBuilder::new()
+        .cue(0, 500, Color::from(0xCC0000), "Start")
+        .cue(1, 60000, Color::from(0x0000CC), "Drop")
+        .saved_loop(0, 1000, 5000, Color::from(0x27AAE1), false, "Intro")
+        .saved_loop(1, 20000, 24000, Color::from(0x27AAE1), true, "")
+        .beatgrid_constant(120.0, 1000.0)
+        .build()
+        .unwrap();
+    container.create_markers().unwrap();
+
+    container.shift_positions(250);
+    let positions: Vec<u32> = container.cues().iter().map(|c| c.position_millis).collect();
+    assert_eq!(positions, vec![750, 60250]);
+    let ranges: Vec<(u32, u32)> = container
+        .loops()
+        .iter()
+        .map(|l| (l.start_position_millis, l.end_position_millis))
+        .collect();
+    assert_eq!(ranges, vec![(1250, 5250), (20250, 24250)]);
+    let (_, terminal_marker) = container.beatgrid().unwrap();
+    assert!((terminal_marker.position - 1.25).abs() < 1e-6);
+
+    // The first cue is removed, the first loop is truncated and the terminal beatgrid marker is
+    // moved to the next beat.
+    container.shift_positions(-1500);
+    let cues = container.cues();
+    assert_eq!(cues.len(), 1);
+    assert_eq!(cues[0].index, 1);
+    assert_eq!(cues[0].position_millis, 58750);
+    assert_eq!(cues[0].label, "Drop");
+    let loops = container.loops();
+    assert_eq!(loops[0].start_position_millis, 0);
+    assert_eq!(loops[0].end_position_millis, 3750);
+    assert_eq!(loops[0].label, "Intro");
+    assert_eq!(loops[1].start_position_millis, 18750);
+    let (_, terminal_marker) = container.beatgrid().unwrap();
+    assert!((terminal_marker.position - 0.25).abs() < 1e-6);
+
+    // Both marker tags are updated consistently.
+    assert!(container.validate().is_empty());
+    let markers = container.markers.as_ref().unwrap();
+    assert_eq!(
+        markers.cues()[0].1.marker_type,
+        markers::MarkerType::Invalid
+    );
+    assert_eq!(markers.cues()[1].1.start_position_millis, Some(58750));
+    assert_eq!(container.markers2.as_ref().unwrap().cues().len(), 1);
+
+    // Loops that end before the start of the track are removed, too.
+    container.shift_positions(-4000);
+    assert_eq!(container.loops().len(), 1);
+    assert_eq!(container.loops()[0].index, 1);
+    assert!(container.validate().is_empty());
+}
+
+#[test]
+fn test_quantize_cues() {
+    let mut container = TagContainerBuilder::new()
+        .cue(0, 1240, Color::from(0xCC0000), "")
+        .cue(1, 60130, Color::from(0x0000CC), "")
+        .saved_loop(0, 2100, 3900, Color::from(0x27AAE1), false, "")
+        .saved_loop(1, 5010, 5120, Color::from(0x27AAE1), false, "")
+        .build()
+        .unwrap();
+    container.create_markers().unwrap();
+
+    // Nothing happens without a beatgrid.
+    container.quantize_cues(1);
+    assert_eq!(container.cues()[0].position_millis, 1240);
+
+    container.beatgrid = Some(Beatgrid::constant(120.0, 1000.0).unwrap());
+    container.quantize_cues(1);
+    let positions: Vec<u32> = container.cues().iter().map(|c| c.position_millis).collect();
+    assert_eq!(positions, vec![1000, 60000]);
+    let ranges: Vec<(u32, u32)> = container
+        .loops()
+        .iter()
+        .map(|l| (l.start_position_millis, l.end_position_millis))
+        .collect();
+    // The second loop would collapse to a single beat, so it's left unchanged.
+    assert_eq!(ranges, vec![(2000, 4000), (5010, 5120)]);
+
+    container.quantize_cues(8);
+    assert_eq!(container.loops()[1].start_position_millis, 5000);
+    assert_eq!(container.loops()[1].end_position_millis, 5125);
+
+    // Both marker tags are updated consistently.
+    assert!(container.validate().is_empty());
+    let markers = container.markers.as_ref().unwrap();
+    assert_eq!(markers.cues()[0].1.start_position_millis, Some(1000));
+    assert!(!container.raw.contains_key(Markers::NAME));
+    assert!(!container.raw.contains_key(Markers2::NAME));
+}
+
+#[test]
+fn test_shift_and_stretch_beatgrid() {
+    let mut container = TagContainerBuilder::new()
+        .cue(0, 500, Color::from(0xCC0000), "")
+        .saved_loop(0, 1000, 5000, Color::from(0x27AAE1), false, "")
+        .beatgrid_constant(120.0, 250.0)
+        .build()
+        .unwrap();
+    container.create_markers().unwrap();
+    let beats = |container: &TagContainer| -> Vec<f64> {
+        container.beatgrid.as_ref().unwrap().beats(3000.0).collect()
+    };
+    assert_eq!(
+        beats(&container),
+        vec![250.0, 750.0, 1250.0, 1750.0, 2250.0, 2750.0]
+    );
+
+    // Only the beatgrid is moved.
+    container.shift_beatgrid(-125, false).unwrap();
+    assert_eq!(
+        beats(&container),
+        vec![125.0, 625.0, 1125.0, 1625.0, 2125.0, 2625.0]
+    );
+    assert_eq!(container.cues()[0].position_millis, 500);
+
+    // A beatgrid marker can't be moved before the start of the track.
+    assert!(container.shift_beatgrid(-250, true).is_err());
+    assert_eq!(beats(&container)[0], 125.0);
+    assert_eq!(container.cues()[0].position_millis, 500);
+
+    container.shift_beatgrid(125, true).unwrap();
+    assert_eq!(beats(&container)[0], 250.0);
+    assert_eq!(container.cues()[0].position_millis, 625);
+    assert_eq!(container.loops()[0].start_position_millis, 1125);
+
+    container.stretch_beatgrid(2.0, true).unwrap();
+    assert_eq!(beats(&container), vec![500.0, 1500.0, 2500.0]);
+    assert_eq!(container.cues()[0].position_millis, 1250);
+    assert_eq!(container.loops()[0].start_position_millis, 2250);
+    assert_eq!(container.loops()[0].end_position_millis, 10250);
+    assert!(container.stretch_beatgrid(-1.0, true).is_err());
+    assert_eq!(container.cues()[0].position_millis, 1250);
+    assert!(container.validate().is_empty());
+}
+
+#[test]
+fn test_retain_within_duration() {
+    let mut container = TagContainerBuilder::new()
+        .cue(0, 1000, Color::from(0xCC0000), "")
+        .cue(1, 180_050, Color::from(0xCC0000), "")
+        .cue(2, 999_999, Color::from(0xCC0000), "Corrupt")
+        .saved_loop(0, 170_000, 178_000, Color::from(0x27AAE1), false, "")
+        .saved_loop(1, 176_000, 184_000, Color::from(0x27AAE1), false, "Outro")
+        .saved_loop(2, 190_000, 194_000, Color::from(0x27AAE1), false, "")
+        .build()
+        .unwrap();
+    container.create_markers().unwrap();
+    let markers2 = container.markers2.as_mut().unwrap();
+    markers2
+        .content
+        .markers
+        .push(markers2::Marker::Flip(generic::Flip {
+            index: 0,
+            is_enabled: true,
+            label: String::new(),
+            is_loop: false,
+            actions: vec![generic::FlipAction::Jump(generic::JumpFlipAction {
+                source_position_seconds: 200.0,
+                target_position_seconds: 10.0,
+            })],
+        }));
+    let flip = container.flips()[0].clone();
+
+    let changes = container.retain_within_duration(180_000, 100);
+    assert_eq!(
+        changes,
+        vec![
+            OutOfRange::RemovedCue(generic::Cue {
+                index: 2,
+                position_millis: 999_999,
+                color: Color::from(0xCC0000),
+                label: String::from("Corrupt"),
+            }),
+            OutOfRange::TruncatedLoop(generic::Loop {
+                index: 1,
+                start_position_millis: 176_000,
+                end_position_millis: 184_000,
+                color: Color::from(0x27AAE1),
+                is_locked: false,
+                label: String::from("Outro"),
+            }),
+            OutOfRange::RemovedLoop(generic::Loop {
+                index: 2,
+                start_position_millis: 190_000,
+                end_position_millis: 194_000,
+                color: Color::from(0x27AAE1),
+                is_locked: false,
+                label: String::new(),
+            }),
+            OutOfRange::RemovedFlip(flip),
+        ]
+    );
+
+    let cues = container.cues();
+    assert_eq!(cues.len(), 2);
+    assert_eq!(cues[1].position_millis, 180_050);
+    let loops = container.loops();
+    assert_eq!(loops.len(), 2);
+    assert_eq!(loops[1].end_position_millis, 180_000);
+    assert_eq!(loops[1].label, "Outro");
+    assert!(container.flips().is_empty());
+    assert!(container.validate().is_empty());
+
+    // Calling it again doesn't change anything.
+    assert!(container.retain_within_duration(180_000, 100).is_empty());
+}
+
+#[test]
+fn test_canonicalize() {
+    // Combine the tags from different tracks, so that they contradict each other.
+    let mut container = TagContainer::new();
+    container
+        .parse_markers(
+            include_bytes!("../../tests/data/tags/markers/hotcues_and_loops.mp4.bin"),
+            TagFormat::MP4,
+        )
+        .unwrap();
+    container
+        .parse_markers2(
+            include_bytes!("../../tests/data/tags/markers2/hotcues_with_names.id3.bin"),
+            TagFormat::ID3,
+        )
+        .unwrap();
+    let cues = container.cues();
+    let loops = container.loops();
+    let track_color = container.track_color();
+    assert!(container.markers2.as_ref().unwrap().cues() != cues);
+    assert!(!container.validate().is_empty());
+
+    container.canonicalize().unwrap();
+    assert_eq!(container.cues(), cues);
+    assert_eq!(container.loops(), loops);
+    assert_eq!(container.track_color(), track_color);
+    assert!(container.validate().is_empty());
+
+    // Each tag on its own yields the merged data now.
+    let mut markers_only = TagContainer::new();
+    let mut data = vec![];
+    container
+        .markers
+        .as_ref()
+        .unwrap()
+        .write_id3(&mut data)
+        .unwrap();
+    markers_only.parse_markers(&data, TagFormat::ID3).unwrap();
+    let mut markers2_only = TagContainer::new();
+    let mut data = vec![];
+    container
+        .markers2
+        .as_ref()
+        .unwrap()
+        .write_id3(&mut data)
+        .unwrap();
+    markers2_only.parse_markers2(&data, TagFormat::ID3).unwrap();
+
+    assert_eq!(markers2_only.cues(), cues);
+    assert_eq!(markers2_only.loops(), loops);
+    assert_eq!(markers2_only.track_color(), track_color);
+    let without_label = |mut cue: generic::Cue| {
+        cue.label.clear();
+        cue
+    };
+    let expected: Vec<_> = cues
+        .iter()
+        .filter(|cue| usize::from(cue.index) < markers::NUM_CUES)
+        .cloned()
+        .map(without_label)
+        .collect();
+    assert_eq!(markers_only.cues(), expected);
+    let loop_ranges = |loops: Vec<generic::Loop>| -> Vec<(u8, u32, u32)> {
+        loops
+            .into_iter()
+            .map(|l| (l.index, l.start_position_millis, l.end_position_millis))
+            .collect()
+    };
+    assert_eq!(
+        loop_ranges(markers_only.loops()),
+        loop_ranges(loops.clone())
+    );
+    assert_eq!(markers_only.track_color(), track_color);
+
+    // Canonicalizing again is a no-op.
+    container.canonicalize().unwrap();
+    assert_eq!(container.cues(), cues);
+    assert_eq!(container.loops(), loops);
+    assert_eq!(container.markers2.as_ref().unwrap().cues(), cues);
+
+    // A missing `Serato Markers2` tag is created.
+    markers_only.canonicalize().unwrap();
+    assert_eq!(markers_only.markers2.as_ref().unwrap().cues(), expected);
+}
+
+#[test]
+fn test_cues_and_loops_with_provenance() {
+    // Combine the tags from different tracks, so that they contradict each other.
+    let mut container = TagContainer::new();
+    container
+        .parse_markers(
+            include_bytes!("../../tests/data/tags/markers/hotcues_and_loops.mp4.bin"),
+            TagFormat::MP4,
+        )
+        .unwrap();
+    container
+        .parse_markers2(
+            include_bytes!("../../tests/data/tags/markers2/hotcues_with_names.id3.bin"),
+            TagFormat::ID3,
+        )
+        .unwrap();
+
+    let merged = CueProvenance {
+        position: TagKind::Markers,
+        color: TagKind::Markers,
+        label: Some(TagKind::Markers2),
+    };
+    let markers_only = CueProvenance {
+        label: None,
+        ..merged
+    };
+    let cues = container.cues_with_provenance();
+    let summary: Vec<_> = cues
+        .iter()
+        .map(|(cue, provenance)| {
+            (
+                cue.index,
+                cue.position_millis,
+                cue.label.as_str(),
+                *provenance,
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (0, 254, "Hello, World!", merged),
+            (1, 21124, "äöüß", merged),
+            (3, 125472, "", markers_only),
+            (4, 158863, "", markers_only),
+        ]
+    );
+    let plain: Vec<_> = cues.into_iter().map(|(cue, _)| cue).collect();
+    assert_eq!(plain, container.cues());
+
+    let loops = container.loops_with_provenance();
+    assert_eq!(loops.len(), 3);
+    for (saved_loop, provenance) in &loops {
+        assert_eq!(provenance.position, TagKind::Markers);
+        assert_eq!(provenance.is_locked, TagKind::Markers);
+        assert_eq!(provenance.label, None);
+        assert!(saved_loop.label.is_empty());
+    }
+    let plain: Vec<_> = loops
+        .into_iter()
+        .map(|(saved_loop, _)| saved_loop)
+        .collect();
+    assert_eq!(plain, container.loops());
+
+    // Without the `Serato Markers_` tag, all data is taken from `Serato Markers2`.
+    container.clear_tag(TagKind::Markers);
+    let expected = CueProvenance {
+        position: TagKind::Markers2,
+        color: TagKind::Markers2,
+        label: Some(TagKind::Markers2),
+    };
+    let cues = container.cues_with_provenance();
+    assert_eq!(cues.len(), 3);
+    assert!(cues.iter().all(|(_, provenance)| *provenance == expected));
+}
+
+#[test]
+fn test_write_strategy() {
+    // Combine the tags from different tracks, so that they contradict each other.
+    let mut container = TagContainer::new();
+    container
+        .parse_markers(
+            include_bytes!("../../tests/data/tags/markers/hotcues_and_loops.mp4.bin"),
+            TagFormat::MP4,
+        )
+        .unwrap();
+    container
+        .parse_markers2(
+            include_bytes!("../../tests/data/tags/markers2/hotcues_with_names.id3.bin"),
+            TagFormat::ID3,
+        )
+        .unwrap();
+    let cues = container.cues();
+    let loops = container.loops();
+
+    let reparse = |tags: &[(TagName, Vec<u8>)]| {
+        let mut reparsed = TagContainer::new();
+        for (name, data) in tags {
+            reparsed.parse_tag(name, data, TagFormat::ID3).unwrap();
+        }
+        reparsed
+    };
+    let names = |tags: &[(TagName, Vec<u8>)]| -> Vec<TagName> {
+        tags.iter().map(|(name, _)| *name).collect()
+    };
+
+    // The tags are written as they are.
+    let (tags, findings) = container.serialize_tags_checked(TagFormat::ID3).unwrap();
+    assert_eq!(names(&tags), vec![Markers::ID3_TAG, Markers2::ID3_TAG]);
+    assert!(findings.is_empty());
+    assert_ne!(reparse(&tags[1..]).cues(), cues);
+
+    container.set_write_strategy(WriteStrategy::Markers2Only);
+    let (tags, findings) = container.serialize_tags_checked(TagFormat::ID3).unwrap();
+    assert_eq!(names(&tags), vec![Markers2::ID3_TAG]);
+    assert!(findings.is_empty());
+    let reparsed = reparse(&tags);
+    assert_eq!(reparsed.cues(), cues);
+    assert_eq!(reparsed.loops(), loops);
+
+    container.set_write_strategy(WriteStrategy::LegacyOnly);
+    let (tags, findings) = container.serialize_tags_checked(TagFormat::ID3).unwrap();
+    assert_eq!(names(&tags), vec![Markers::ID3_TAG]);
+    assert!(findings.is_empty());
+    let reparsed = reparse(&tags);
+    let positions = |cues: Vec<generic::Cue>| -> Vec<(u8, u32)> {
+        cues.into_iter()
+            .map(|cue| (cue.index, cue.position_millis))
+            .collect()
+    };
+    assert_eq!(positions(reparsed.cues()), positions(cues.clone()));
+    assert_eq!(reparsed.loops().len(), loops.len());
+    assert!(matches!(
+        container.serialize_tags(TagFormat::FLAC),
+        Err(Error::UnsupportedTagFormat)
+    ));
+
+    container.set_write_strategy(WriteStrategy::Both);
+    let (tags, findings) = container.serialize_tags_checked(TagFormat::ID3).unwrap();
+    assert_eq!(names(&tags), vec![Markers::ID3_TAG, Markers2::ID3_TAG]);
+    assert!(findings.is_empty());
+    let reparsed = reparse(&tags);
+    assert_eq!(reparsed.cues(), cues);
+    assert!(reparsed.validate().is_empty());
+    assert_eq!(reparse(&tags[1..]).cues(), cues);
+    let tags = container.serialize_tags(TagFormat::FLAC).unwrap();
+    assert_eq!(names(&tags), vec![Markers2::FLAC_COMMENT]);
+
+    // Cues that can't be stored in the legacy tag are reported.
+    let mut container = TagContainerBuilder::new()
+        .cue(1, 1000, Color::from(0xCC0000), "")
+        .cue(6, 2000, Color::from(0xCC0000), "")
+        .build()
+        .unwrap();
+    for strategy in [WriteStrategy::LegacyOnly, WriteStrategy::Both].iter() {
+        container.set_write_strategy(*strategy);
+        let (tags, findings) = container.serialize_tags_checked(TagFormat::MP4).unwrap();
+        assert_eq!(
+            findings,
+            vec![Finding {
+                tag: TagKind::Markers,
+                index: 6,
+                problem: Problem::CueNotInMarkers,
+            }]
+        );
+        let (_, data) = tags
+            .iter()
+            .find(|(name, _)| *name == Markers::MP4_ATOM_FREEFORM_NAME)
+            .unwrap();
+        let mut reparsed = TagContainer::new();
+        reparsed.parse_markers(data, TagFormat::MP4).unwrap();
+        assert_eq!(positions(reparsed.cues()), vec![(1, 1000)]);
+    }
+    container.set_write_strategy(WriteStrategy::Markers2Only);
+    let (_, findings) = container.serialize_tags_checked(TagFormat::MP4).unwrap();
+    assert!(findings.is_empty());
+}
+
+#[test]
+fn test_write_strategy_preserves_consistent_tags() {
+    let markers = include_bytes!("../../tests/data/tags/markers/hotcues_with_names.id3.bin");
+    let markers2 = include_bytes!("../../tests/data/tags/markers2/hotcues_with_names.id3.bin");
+    let mut container = TagContainer::with_options(ParseOptions {
+        preserve_raw: true,
+        write_strategy: WriteStrategy::Both,
+        ..ParseOptions::default()
+    });
+    container.parse_markers(markers, TagFormat::ID3).unwrap();
+    container.parse_markers2(markers2, TagFormat::ID3).unwrap();
+
+    // Tags that already agree with the merged data are written verbatim.
+    let tags = container.serialize_tags(TagFormat::ID3).unwrap();
+    assert_eq!(tags[0].1, &markers[..]);
+    assert_eq!(tags[1].1, &markers2[..]);
+}
+
+#[test]
+fn test_offsets() {
+    let data = [0x01, 0x02, 0x00, 0x11, 0x22, 0x33];
+    let mut container = TagContainer::new();
+    let kind = container
+        .parse_tag("Serato Offsets_", &data, TagFormat::ID3)
+        .unwrap();
+    assert_eq!(kind, Some(TagKind::Offsets));
+    assert!(container.has(TagKind::Offsets));
+
+    // The data is written back as is, but only into ID3 tags.
+    let tags = container.serialize_tags(TagFormat::ID3).unwrap();
+    assert_eq!(tags, vec![(Offsets::ID3_TAG, data.to_vec())]);
+    assert!(container.serialize_tags(TagFormat::MP4).unwrap().is_empty());
+    assert!(matches!(
+        container.parse_offsets(&data, TagFormat::FLAC),
+        Err(Error::UnsupportedTagFormat)
+    ));
+    assert_eq!(container.offsets().unwrap().entries(), None);
+
+    let data = include_bytes!("../../tests/data/tags/offsets/table.id3.bin");
+    container.parse_offsets(data, TagFormat::ID3).unwrap();
+    let offsets = container.offsets().unwrap();
+    assert_eq!(offsets.sample_rate(), Some(44100));
+    assert_eq!(offsets.entries().map(<[_]>::len), Some(3));
+}
+
+#[test]
+fn test_padding() {
+    let tags: [(&str, &[u8]); 4] = [
+        (
+            "Serato Autotags",
+            include_bytes!("../../tests/data/tags/autotags/analyzed.id3.bin"),
+        ),
+        (
+            "Serato BeatGrid",
+            include_bytes!("../../tests/data/tags/beatgrid/terminal_only_with_footer_0x37.id3.bin"),
+        ),
+        (
+            "Serato Markers_",
+            include_bytes!("../../tests/data/tags/markers/hotcues_with_names.id3.bin"),
+        ),
+        (
+            "Serato Overview",
+            include_bytes!("../../tests/data/tags/overview/analyzed.id3.bin"),
+        ),
+    ];
+    for (name, data) in &tags {
+        let mut input = data.to_vec();
+        input.extend_from_slice(&[0x00; 5]);
+
+        // The padding is kept, so that the serialized tag matches the input.
+        let mut container = TagContainer::new();
+        container.parse_tag(name, &input, TagFormat::ID3).unwrap();
+        let tags = container.serialize_tags(TagFormat::ID3).unwrap();
+        assert_eq!(tags, vec![(*name, input.clone())]);
+
+        let mut container = TagContainer::with_options(ParseOptions {
+            strict: true,
+            ..ParseOptions::default()
+        });
+        container.parse_tag(name, data, TagFormat::ID3).unwrap();
+        assert!(matches!(
+            container.parse_tag(name, &input, TagFormat::ID3),
+            Err(Error::UnexpectedPadding { length: 5 })
+        ));
+    }
+}
+
+#[test]
+fn test_relvolad() {
+    let mut container = TagContainer::new();
+    assert!(container.relvolad().is_none());
+
+    let input = include_bytes!("../../tests/data/tags/relvolad/analyzed_enc_AA.flac.bin");
+    let kind = container
+        .parse_tag(RelVolAd::FLAC_COMMENT, input, TagFormat::FLAC)
+        .unwrap();
+    assert_eq!(kind, Some(TagKind::RelVolAd));
+    let tag = container.relvolad().unwrap();
+    assert_eq!(tag.version, generic::Version { major: 1, minor: 1 });
+    assert_eq!(tag.data, vec![0x01, 0x00, 0x00]);
+
+    // Serato doesn't write this tag to MP3 files.
+    assert!(container.serialize_tags(TagFormat::ID3).unwrap().is_empty());
+    assert!(matches!(
+        container.parse_relvolad(input, TagFormat::ID3),
+        Err(Error::UnsupportedTagFormat)
+    ));
+}
+
+#[test]
+fn test_vidassoc() {
+    let mut container = TagContainer::new();
+    assert!(container.vidassoc().is_none());
+
+    // Tracks without an associated video still carry the tag.
+    let input = include_bytes!("../../tests/data/tags/vidassoc/analyzed_enc_AA.flac.bin");
+    let kind = container
+        .parse_tag(VidAssoc::FLAC_COMMENT, input, TagFormat::FLAC)
+        .unwrap();
+    assert_eq!(kind, Some(TagKind::VidAssoc));
+    let tag = container.vidassoc().unwrap();
+    assert_eq!(tag.version, generic::Version { major: 1, minor: 1 });
+    assert_eq!(tag.data, vec![0x01, 0x00, 0x00]);
+
+    let input = include_bytes!("../../tests/data/tags/vidassoc/analyzed_enc_BQ.mp4.bin");
+    let mut container = TagContainer::new();
+    container.parse_vidassoc(input, TagFormat::MP4).unwrap();
+    let tags = container.serialize_tags(TagFormat::MP4).unwrap();
+    assert_eq!(tags.len(), 1);
+    assert_eq!(tags[0].0, VidAssoc::MP4_ATOM_FREEFORM_NAME);
+    let mut reparsed = TagContainer::new();
+    reparsed.parse_vidassoc(&tags[0].1, TagFormat::MP4).unwrap();
+    assert_eq!(reparsed.vidassoc(), container.vidassoc());
+}
+
+#[test]
+fn test_cue_positions_in_samples() {
+    let mut container = TagContainerBuilder::new()
+        .cue(0, 1000, Color::from(0xCC0000), "")
+        .cue(2, 1234, Color::from(0xCC0000), "")
+        .saved_loop(1, 500, 2001, Color::from(0x27AAE1), false, "")
+        .build()
+        .unwrap();
+
+    let positions = container.cue_positions_in_samples(44100);
+    assert_eq!(positions.cues, vec![(0, 44100), (2, 54419)]);
+    assert_eq!(positions.loops, vec![(1, 22050, 88244)]);
+    assert!(!positions.corrected);
+    let positions_48k = container.cue_positions_in_samples(48000);
+    assert_eq!(positions_48k.cues, vec![(0, 48000), (2, 59232)]);
+
+    // A Serato Offsets_ tag without a decodable offset table is not taken into account.
+    container
+        .parse_offsets(&[0x01, 0x02, 0x00], TagFormat::ID3)
+        .unwrap();
+    assert_eq!(container.cue_positions_in_samples(44100), positions);
+
+    // Otherwise, the offset that applies at each position is added.
+    container
+        .parse_offsets(
+            include_bytes!("../../tests/data/tags/offsets/table.id3.bin"),
+            TagFormat::ID3,
+        )
+        .unwrap();
+    let positions = container.cue_positions_in_samples(44100);
+    assert!(positions.corrected);
+    assert_eq!(positions.cues, vec![(0, 44100 - 1105), (2, 54419 - 1105)]);
+    assert_eq!(positions.loops, vec![(1, 22050 - 1105, 88244 - 1105)]);
+    // The offsets are scaled to the requested sample rate.
+    let positions_48k = container.cue_positions_in_samples(48000);
+    assert_eq!(
+        positions_48k.cues,
+        vec![(0, 48000 - 1202), (2, 59232 - 1202)]
+    );
+
+    container
+        .set_cues(vec![generic::Cue {
+            index: 0,
+            position_millis: 12_000,
+            color: Color::from(0xCC0000),
+            label: String::new(),
+        }])
+        .unwrap();
+    let positions = container.cue_positions_in_samples(44100);
+    assert_eq!(positions.cues, vec![(0, 529_200 - 1152)]);
+}
+
+#[test]
+fn test_legacy_markers() {
+    let mut data =
+        include_bytes!("../../tests/data/tags/markers/hotcues_with_names.id3.bin").to_vec();
+    data[0] = 0x01;
+    let mut container = TagContainer::new();
+    container.parse_markers(&data, TagFormat::ID3).unwrap();
+    container
+        .parse_markers2(
+            include_bytes!("../../tests/data/tags/markers2/hotcues_with_names.id3.bin"),
+            TagFormat::ID3,
+        )
+        .unwrap();
+
+    // The data of the version 1 tag is unknown, so the `Serato Markers2` tag is used.
+    let markers2 = container.markers2.as_ref().unwrap();
+    assert_eq!(container.cues(), markers2.cues());
+    assert_eq!(container.track_color(), markers2.track_color());
+    assert!(container.validate().is_empty());
+
+    // The version 1 tag is written back unchanged, even if the cues are modified.
+    container.set_cues(vec![]).unwrap();
+    assert!(container.cues().is_empty());
+    let tags = container.serialize_tags(TagFormat::ID3).unwrap();
+    assert_eq!(tags[0], ("Serato Markers_", data));
 }
@@ -0,0 +1,71 @@
+//! AIFF files
+//!
+//! Serato stores the tags of AIFF files in an ID3v2 tag in the `ID3 ` chunk of the file, just
+//! like in [WAV files](super::riff). The tag data uses
+//! [`TagFormat::ID3`](crate::tag::TagFormat::ID3).
+
+use super::riff::find_chunk;
+use crate::error::Error;
+use std::io;
+use std::ops::Range;
+
+/// Find the `ID3 ` chunk of an AIFF (or AIFF-C) file and return the position of its data in the
+/// file.
+///
+/// Returns `None` if the file doesn't contain an ID3 chunk. Like in WAV files, the chunk doesn't
+/// have to be the last chunk of the file and the file has to start at the current position of the
+/// reader (see [`riff::find_id3_chunk`](super::riff::find_id3_chunk)).
+pub fn find_id3_chunk(reader: impl io::Read + io::Seek) -> Result<Option<Range<u64>>, Error> {
+    find_chunk(
+        reader,
+        b"FORM",
+        &[b"AIFF", b"AIFC"],
+        &[b"ID3 ", b"id3 "],
+        u32::from_be_bytes,
+    )
+}
+
+#[test]
+fn test_find_id3_chunk() {
+    use super::riff::chunk;
+
+    let aiff = |form_type: &[u8], chunks: &[Vec<u8>]| {
+        let data = [form_type, &chunks.concat()].concat();
+        chunk(b"FORM", &data, u32::to_be_bytes)
+    };
+    let comm = chunk(b"COMM", &[0x01; 18], u32::to_be_bytes);
+    let ssnd = chunk(b"SSND", &[0x02; 77], u32::to_be_bytes);
+    let id3_chunk = chunk(
+        b"ID3 ",
+        b"ID3\x04\x00\x00\x00\x00\x00\x00",
+        u32::to_be_bytes,
+    );
+
+    for form_type in &[b"AIFF", b"AIFC"] {
+        let file = aiff(
+            *form_type,
+            &[comm.clone(), ssnd.clone(), id3_chunk.clone(), comm.clone()],
+        );
+        let range = find_id3_chunk(io::Cursor::new(&file)).unwrap().unwrap();
+        assert_eq!(
+            &file[range.start as usize..range.end as usize],
+            b"ID3\x04\x00\x00\x00\x00\x00\x00"
+        );
+    }
+
+    let file = aiff(b"AIFF", &[comm.clone(), ssnd]);
+    assert!(find_id3_chunk(io::Cursor::new(&file)).unwrap().is_none());
+
+    // The size is big-endian
+    let file = aiff(
+        b"AIFF",
+        &[chunk(b"COMM", &[0x01; 18], u32::to_le_bytes), id3_chunk],
+    );
+    assert!(find_id3_chunk(io::Cursor::new(&file)).unwrap().is_none());
+
+    let file = chunk(b"RIFF", &[&b"WAVE"[..], &comm].concat(), u32::to_le_bytes);
+    assert!(matches!(
+        find_id3_chunk(io::Cursor::new(&file)),
+        Err(Error::InvalidContainer { .. })
+    ));
+}
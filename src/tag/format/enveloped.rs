@@ -4,15 +4,34 @@ extern crate nom;
 
 use super::Tag;
 use crate::error::Error;
-use crate::util::{take_utf8, Res};
+use crate::tag::TagFormat;
+use crate::util::{read_full, take_utf8, Res};
 use std::io;
-use std::io::Cursor;
+use std::io::{Cursor, Read};
 
 pub trait EnvelopedTag: Tag {
     fn parse_enveloped(input: &[u8]) -> Result<Self, Error> {
-        let (_, encoded) = nom::combinator::all_consuming(take_base64_with_newline)(input)?;
-        let content = envelope_decode_with_name(encoded, Self::NAME)?;
-        Self::parse(&content)
+        let content = decode_enveloped(input, Self::NAME)?;
+        Self::parse(&content).or_else(|err| {
+            // Unpadded data written by other tools may end with a complete group of characters,
+            // which is ambiguous (see `base64_decode`).
+            match decode_enveloped_with(input, Self::NAME, base64_decode_complete) {
+                Ok((complete_content, _)) if complete_content != content => {
+                    Self::parse(&complete_content).map_err(|_| err)
+                }
+                _ => Err(err),
+            }
+        })
+    }
+
+    /// Parse the base64-encoded envelope from a reader, decoding the data incrementally (see
+    /// [`EnvelopeReader`]).
+    ///
+    /// In contrast to [`parse_enveloped`](EnvelopedTag::parse_enveloped), unpadded data that ends
+    /// with a complete group of 4 characters is always decoded like Serato data.
+    fn parse_enveloped_from_reader(reader: impl io::Read) -> Result<Self, Error> {
+        let content = decode_enveloped_from_reader(reader, Self::NAME)?;
+        Self::parse_from_reader(content)
     }
 
     fn write_enveloped(&self, writer: impl io::Write) -> Result<usize, Error> {
@@ -23,8 +42,267 @@ pub trait EnvelopedTag: Tag {
     }
 }
 
+/// Decode the base64-encoded envelope and return the contained tag data.
+///
+/// Returns an [`EnvelopeNameMismatch`](Error::EnvelopeNameMismatch) error if the envelope
+/// doesn't contain the tag with the expected name. Data that has been base64-encoded twice is
+/// recovered (see [`decode_enveloped_checked`]).
+pub fn decode_enveloped(input: &[u8], expected_name: &str) -> Result<Vec<u8>, Error> {
+    let (content, _) = decode_enveloped_checked(input, expected_name)?;
+    Ok(content)
+}
+
+/// The base64 encoding of `application/octet-stream`, i.e. the start of an encoded envelope.
+pub(super) const ENCODED_ENVELOPE_HEADER: &[u8] = b"YXBwbGljYXRpb24vb2N0ZXQtc3RyZWFt";
+
+/// Decode the base64-encoded envelope like [`decode_enveloped`], and also return whether the
+/// data has been base64-encoded twice.
+///
+/// Buggy tag copy tools sometimes encode the data twice, either by encoding the already encoded
+/// envelope again, or by wrapping the encoded envelope in another envelope. Serato recovers the
+/// tag data from such files, so this function decodes the additional level as well.
+pub fn decode_enveloped_checked(
+    input: &[u8],
+    expected_name: &str,
+) -> Result<(Vec<u8>, bool), Error> {
+    decode_enveloped_with(input, expected_name, base64_decode)
+}
+
+fn decode_enveloped_with(
+    input: &[u8],
+    expected_name: &str,
+    decode: fn(&[u8]) -> Result<Vec<u8>, Error>,
+) -> Result<(Vec<u8>, bool), Error> {
+    let (_, encoded) = nom::combinator::all_consuming(take_base64_with_newline)(input)?;
+    let mut data = decode(encoded)?;
+    let mut is_double_encoded = false;
+    if data.starts_with(ENCODED_ENVELOPE_HEADER) {
+        data = base64_decode(&data)?;
+        is_double_encoded = true;
+    }
+
+    let (name, mut content) = parse_envelope(&data)?;
+    if !is_double_encoded && content.starts_with(ENCODED_ENVELOPE_HEADER) {
+        let (inner_name, inner_content) = envelope_decode(&content)?;
+        if inner_name != name {
+            return Err(Error::EnvelopeNameMismatch {
+                actual: inner_name,
+                expected: name,
+            });
+        }
+        content = inner_content;
+        is_double_encoded = true;
+    }
+    if expected_name != name {
+        return Err(Error::EnvelopeNameMismatch {
+            actual: name,
+            expected: expected_name.to_owned(),
+        });
+    }
+    Ok((content, is_double_encoded))
+}
+
+/// Decode the base64-encoded envelope from a reader like [`decode_enveloped`], and return a
+/// reader for the contained tag data.
+///
+/// Returns an [`EnvelopeNameMismatch`](Error::EnvelopeNameMismatch) error if the envelope
+/// doesn't contain the tag with the expected name.
+///
+/// ```
+/// use std::io::Read;
+/// use triseratops::tag::format::enveloped::decode_enveloped_from_reader;
+///
+/// let input = &b"YXBwbGljYXRpb24vb2N0ZXQtc3RyZWFtAABTZXJhdG8gQW5hbHlzaXMAAgEA"[..];
+/// let mut content = vec![];
+/// decode_enveloped_from_reader(input, "Serato Analysis")
+///     .unwrap()
+///     .read_to_end(&mut content)
+///     .unwrap();
+/// assert_eq!(content, &[0x02, 0x01]);
+/// ```
+pub fn decode_enveloped_from_reader<'r>(
+    reader: impl io::Read + 'r,
+    expected_name: &str,
+) -> Result<EnvelopeReader<'r>, Error> {
+    let envelope = EnvelopeReader::new(reader)?;
+    if expected_name != envelope.name {
+        return Err(Error::EnvelopeNameMismatch {
+            actual: envelope.name,
+            expected: expected_name.to_owned(),
+        });
+    }
+    Ok(envelope)
+}
+
+/// Reads the tag data from a base64-encoded envelope.
+///
+/// The envelope header is parsed when the reader is created, the remaining data is decoded
+/// incrementally while reading. Data that has been base64-encoded twice is recovered (see
+/// [`decode_enveloped_checked`]). Malformed base64 data is reported as
+/// [`Base64DecodeError`](Error::Base64DecodeError) when it is converted to an [`Error`].
+pub struct EnvelopeReader<'r> {
+    content: Box<dyn io::Read + 'r>,
+    name: String,
+    is_double_encoded: bool,
+}
+
+impl<'r> EnvelopeReader<'r> {
+    /// Parse the envelope header from a reader.
+    pub fn new(reader: impl io::Read + 'r) -> Result<Self, Error> {
+        let (mut data, is_double_encoded) =
+            peek_encoded_envelope(Box::new(Base64Reader::new(reader)))?;
+        if is_double_encoded {
+            data = Box::new(Base64Reader::new(data));
+        }
+        let name = read_envelope_header(&mut data)?;
+
+        let (mut content, is_inner_encoded) = peek_encoded_envelope(data)?;
+        if !is_double_encoded && is_inner_encoded {
+            let mut inner_content = Base64Reader::new(content);
+            let inner_name = read_envelope_header(&mut inner_content)?;
+            if inner_name != name {
+                return Err(Error::EnvelopeNameMismatch {
+                    actual: inner_name,
+                    expected: name,
+                });
+            }
+            content = Box::new(inner_content);
+        }
+
+        Ok(Self {
+            content,
+            name,
+            is_double_encoded: is_double_encoded || is_inner_encoded,
+        })
+    }
+
+    /// The tag name stored in the envelope.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns `true` if the data has been base64-encoded twice.
+    pub fn is_double_encoded(&self) -> bool {
+        self.is_double_encoded
+    }
+}
+
+impl io::Read for EnvelopeReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.content.read(buf)
+    }
+}
+
+/// Read the start of the data and check if it is an encoded envelope. The bytes are put back
+/// into the returned reader.
+fn peek_encoded_envelope<'r>(
+    mut reader: Box<dyn io::Read + 'r>,
+) -> Result<(Box<dyn io::Read + 'r>, bool), Error> {
+    let mut start = vec![0; ENCODED_ENVELOPE_HEADER.len()];
+    let length = read_full(&mut reader, &mut start)?;
+    start.truncate(length);
+    let is_encoded_envelope = start == ENCODED_ENVELOPE_HEADER;
+    Ok((
+        Box::new(Cursor::new(start).chain(reader)),
+        is_encoded_envelope,
+    ))
+}
+
+/// Read the `application/octet-stream` header and the null-terminated tag name.
+fn read_envelope_header(mut reader: impl io::Read) -> Result<String, Error> {
+    let mut header = [0; ENVELOPE_HEADER.len()];
+    let length = read_full(&mut reader, &mut header)?;
+    take_envelope_header(&header[..length])?;
+
+    let mut name = vec![];
+    let mut byte = [0];
+    while read_full(&mut reader, &mut byte)? != 0 {
+        name.push(byte[0]);
+        if byte[0] == b'\x00' {
+            break;
+        }
+    }
+    let (_, name) = take_utf8(&name)?;
+    Ok(name)
+}
+
+const ENVELOPE_HEADER: &[u8] = b"application/octet-stream\x00\x00";
+
+fn take_envelope_header(input: &[u8]) -> Res<&[u8], &[u8]> {
+    nom::bytes::complete::tag(ENVELOPE_HEADER)(input)
+}
+
+/// Decodes base64 data from a reader like [`base64_decode`].
+///
+/// The last characters are held back until the end of the data is reached, so that a stray
+/// character can be dropped.
+struct Base64Reader<R> {
+    reader: R,
+    encoded: Vec<u8>,
+    decoded: Vec<u8>,
+    position: usize,
+    is_finished: bool,
+}
+
+impl<R: io::Read> Base64Reader<R> {
+    /// The number of characters that are held back, i.e. the last complete group and the
+    /// characters after it.
+    const TAIL_LENGTH: usize = 8;
+
+    fn new(reader: R) -> Self {
+        Self {
+            reader,
+            encoded: vec![],
+            decoded: vec![],
+            position: 0,
+            is_finished: false,
+        }
+    }
+
+    fn fill(&mut self) -> io::Result<()> {
+        let mut buf = [0; 4096];
+        let length = loop {
+            match self.reader.read(&mut buf) {
+                Ok(n) => break n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+                Err(err) => return Err(err),
+            }
+        };
+        let decoded = if length == 0 {
+            self.is_finished = true;
+            decode_base64(&self.encoded, true)
+        } else {
+            self.encoded
+                .extend(buf[..length].iter().filter(|&b| !is_whitespace(*b)));
+            if self.encoded.len() <= Self::TAIL_LENGTH {
+                return Ok(());
+            }
+            let length = (self.encoded.len() - Self::TAIL_LENGTH) / 4 * 4;
+            let encoded: Vec<u8> = self.encoded.drain(..length).collect();
+            base64::decode_config(encoded, BASE64_FORGIVING)
+                .map_err(|source| Error::Base64DecodeError { source })
+        };
+        self.decoded = decoded.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        self.position = 0;
+        Ok(())
+    }
+}
+
+impl<R: io::Read> io::Read for Base64Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.decoded.len() && !self.is_finished {
+            self.fill()?;
+        }
+        let available = &self.decoded[self.position..];
+        let length = available.len().min(buf.len());
+        buf[..length].copy_from_slice(&available[..length]);
+        self.position += length;
+        Ok(length)
+    }
+}
+
 pub fn parse_envelope(input: &[u8]) -> Result<(String, Vec<u8>), Error> {
-    let (input, _) = nom::bytes::complete::tag(b"application/octet-stream\x00\x00")(input)?;
+    let (input, _) = take_envelope_header(input)?;
     let (input, name) = take_utf8(input)?;
     Ok((name, input.to_vec()))
 }
@@ -37,15 +315,53 @@ pub fn is_newline(byte: u8) -> bool {
     byte == b'\n'
 }
 
+/// Returns `true` for line breaks (`\n`, `\r\n` or `\r`) and other whitespace, which is ignored
+/// when decoding base64 data.
+pub fn is_whitespace(byte: u8) -> bool {
+    byte.is_ascii_whitespace()
+}
+
+/// Take base64-encoded data, including line breaks and surrounding whitespace.
 pub fn take_base64_with_newline(input: &[u8]) -> Res<&[u8], &[u8]> {
-    nom::bytes::complete::take_while(|b| is_base64(b) || is_newline(b))(input)
+    nom::bytes::complete::take_while(|b| is_base64(b) || is_whitespace(b))(input)
 }
 
 const BASE64_FORGIVING: base64::Config = base64::STANDARD_NO_PAD.decode_allow_trailing_bits(true);
 
+/// Decode base64 data like Serato does.
+///
+/// Serato doesn't pad the encoded data, but appends a stray character unless the last group of
+/// characters has 2 characters (see [`base64_encode`]). That character is dropped before
+/// decoding. Data written by other tools is accepted as well: Whitespace and line breaks are
+/// ignored regardless of the line length, and the data may or may not be padded. Unpadded data
+/// that ends with a complete group of 4 characters is ambiguous, though, and is decoded like
+/// Serato data.
 pub fn base64_decode(input: &[u8]) -> Result<Vec<u8>, Error> {
-    let mut encoded: Vec<u8> = input.iter().filter(|&b| !is_newline(*b)).copied().collect();
-    if encoded.len() % 4 != 2 {
+    decode_base64(input, true)
+}
+
+/// Decode base64 data like [`base64_decode`], but treat unpadded data that ends with a complete
+/// group of 4 characters like standard base64 data.
+fn base64_decode_complete(input: &[u8]) -> Result<Vec<u8>, Error> {
+    decode_base64(input, false)
+}
+
+fn decode_base64(input: &[u8], is_complete_group_stray: bool) -> Result<Vec<u8>, Error> {
+    let mut encoded: Vec<u8> = input
+        .iter()
+        .filter(|&b| !is_whitespace(*b))
+        .copied()
+        .collect();
+    let is_padded = encoded.last() == Some(&b'=');
+    while encoded.last() == Some(&b'=') {
+        encoded.pop();
+    }
+    let has_stray_character = match encoded.len() % 4 {
+        0 => is_complete_group_stray && !is_padded,
+        1 => true,
+        _ => false,
+    };
+    if has_stray_character {
         encoded.pop();
     }
     let decoded = base64::decode_config(encoded, BASE64_FORGIVING);
@@ -55,24 +371,36 @@ pub fn base64_decode(input: &[u8]) -> Result<Vec<u8>, Error> {
     }
 }
 
+/// Check that base64 data only contains characters that Serato writes itself, i.e. no line
+/// breaks other than `\n` and no other whitespace.
+///
+/// This is used for strict parsing, because [`base64_decode`] is more lenient than Serato's own
+/// encoding.
+pub fn check_base64_strict(input: &[u8]) -> Result<(), Error> {
+    match input.iter().position(|&b| !is_base64(b) && !is_newline(b)) {
+        Some(offset) => Err(Error::Base64DecodeError {
+            source: base64::DecodeError::InvalidByte(offset, input[offset]),
+        }),
+        None => Ok(()),
+    }
+}
+
 pub fn base64_encode(mut writer: impl io::Write, input: &[u8]) -> Result<usize, Error> {
     let mut bytes_written = 0;
     let chunks = input.chunks(54);
-    let last_chunk_index = chunks.len() - 1;
+    let last_chunk_index = chunks.len().saturating_sub(1);
     for (i, chunk) in chunks.enumerate() {
-        let mut buf = Vec::new();
-        buf.resize(72, 0);
-        let bytes_encoded = base64::encode_config_slice(&chunk, BASE64_FORGIVING, &mut buf);
+        let mut buf = vec![0; 72];
+        let bytes_encoded = base64::encode_config_slice(chunk, BASE64_FORGIVING, &mut buf);
         bytes_written += writer.write(&buf[..bytes_encoded])?;
         if i == last_chunk_index {
             if bytes_encoded % 4 != 2 {
                 bytes_written += writer.write(b"A")?;
             }
         } else {
-            bytes_written += writer.write(&[b'\n'])?;
+            bytes_written += writer.write(b"\n")?;
         }
     }
-    println!("{}", bytes_written);
     Ok(bytes_written)
 }
 
@@ -106,3 +434,264 @@ pub fn envelope_encode_with_name(
     .concat();
     base64_encode(writer, &data)
 }
+
+/// Wrap the tag data in the envelope that Serato uses for the given tag format.
+///
+/// For [FLAC](TagFormat::FLAC) and [MP4](TagFormat::MP4) tags, the envelope consists of the
+/// `application/octet-stream\0\0` header, followed by the null-terminated tag name and the
+/// payload. The result is base64-encoded and wrapped after 72 characters. If the encoded data
+/// would end with a dangling base64 character, a stray `A` is appended to the last line (this is
+/// what Serato does as well).
+///
+/// [Ogg](TagFormat::Ogg) tags contain the same base64 encoding, but without the header and the
+/// tag name. ID3 tags are not enveloped and result in an
+/// [`UnsupportedTagFormat`](Error::UnsupportedTagFormat) error.
+///
+/// ```
+/// use triseratops::tag::{format::enveloped::encode_envelope, TagFormat};
+///
+/// let data = encode_envelope("Serato Analysis", &[0x02, 0x01], TagFormat::FLAC).unwrap();
+/// assert_eq!(data, b"YXBwbGljYXRpb24vb2N0ZXQtc3RyZWFtAABTZXJhdG8gQW5hbHlzaXMAAgEA");
+/// ```
+pub fn encode_envelope(
+    tag_name: &str,
+    payload: &[u8],
+    tag_format: TagFormat,
+) -> Result<Vec<u8>, Error> {
+    let mut writer = vec![];
+    match tag_format {
+        TagFormat::FLAC | TagFormat::MP4 => {
+            envelope_encode_with_name(&mut writer, payload, tag_name)?;
+        }
+        TagFormat::Ogg => {
+            base64_encode(&mut writer, payload)?;
+        }
+        TagFormat::ID3 => return Err(Error::UnsupportedTagFormat),
+    }
+    Ok(writer)
+}
+
+/// Unwrap the tag data from the envelope that Serato uses for the given tag format.
+///
+/// This is the inverse of [`encode_envelope`]. The decoder is forgiving: it accepts data with or
+/// without line breaks and padding, and ignores a trailing junk character. For
+/// [FLAC](TagFormat::FLAC) and [MP4](TagFormat::MP4) tags, the tag name in the envelope has to
+/// match `tag_name`, otherwise an [`EnvelopeNameMismatch`](Error::EnvelopeNameMismatch) error is
+/// returned. Envelopes that have been base64-encoded twice are recovered.
+///
+/// ```
+/// use triseratops::tag::{format::enveloped::decode_envelope, TagFormat};
+///
+/// let input = b"YXBwbGljYXRpb24vb2N0ZXQtc3RyZWFtAABTZXJhdG8gQW5hbHlzaXMAAgEA";
+/// let data = decode_envelope("Serato Analysis", input, TagFormat::FLAC).unwrap();
+/// assert_eq!(data, &[0x02, 0x01]);
+/// ```
+pub fn decode_envelope(
+    tag_name: &str,
+    input: &[u8],
+    tag_format: TagFormat,
+) -> Result<Vec<u8>, Error> {
+    match tag_format {
+        TagFormat::FLAC | TagFormat::MP4 => decode_enveloped(input, tag_name),
+        TagFormat::Ogg => {
+            let (_, encoded) = nom::combinator::all_consuming(take_base64_with_newline)(input)?;
+            base64_decode(encoded)
+        }
+        TagFormat::ID3 => Err(Error::UnsupportedTagFormat),
+    }
+}
+
+#[test]
+fn test_envelope_encode_with_name() {
+    let data: Vec<u8> = (0..=255).collect();
+    let mut writer = Cursor::new(vec![]);
+    let bytes_written = envelope_encode_with_name(&mut writer, &data, "Serato Markers2").unwrap();
+    let output = writer.get_ref().as_slice();
+    assert_eq!(bytes_written, output.len());
+
+    // Base64 of `application/octet-stream\0\0Serato Markers2\0`
+    assert!(output.starts_with(b"YXBwbGljYXRpb24vb2N0ZXQtc3RyZWFtAABTZXJhdG8gTWFya2VyczIA"));
+
+    // All lines except the last one are wrapped after 72 characters
+    let lines: Vec<&[u8]> = output.split(|&b| b == b'\n').collect();
+    assert!(lines.len() > 1);
+    for line in &lines[..lines.len() - 1] {
+        assert_eq!(line.len(), 72);
+    }
+    assert!(lines[lines.len() - 1].len() <= 72);
+
+    let content = envelope_decode_with_name(output, "Serato Markers2").unwrap();
+    assert_eq!(content, data);
+    assert!(envelope_decode_with_name(output, "Serato Markers_").is_err());
+}
+
+#[test]
+fn test_decode_envelope() {
+    // Unwrapped, without padding and with a trailing junk character.
+    let input = include_bytes!("../../../tests/data/tags/analysis/version_2_1_encoded_HH.flac.bin");
+    let data = decode_envelope("Serato Analysis", input, TagFormat::FLAC).unwrap();
+    assert_eq!(data, &[0x02, 0x01]);
+    assert!(matches!(
+        decode_envelope("Serato Autotags", input, TagFormat::FLAC),
+        Err(Error::EnvelopeNameMismatch { .. })
+    ));
+    assert!(matches!(
+        decode_envelope("Serato Analysis", input, TagFormat::ID3),
+        Err(Error::UnsupportedTagFormat)
+    ));
+
+    // Wrapped after 72 characters.
+    let input = include_bytes!("../../../tests/data/tags/overview/analyzed_enc_EM.flac.bin");
+    assert!(input.contains(&b'\n'));
+    let data = decode_envelope("Serato Overview", input, TagFormat::FLAC).unwrap();
+    assert_eq!(&data[..2], &[0x01, 0x05]);
+    assert_eq!((data.len() - 2) % 16, 0);
+
+    // Unwrapped, with padding.
+    let data = decode_envelope("", b"AAECAw==", TagFormat::Ogg).unwrap();
+    assert_eq!(data, &[0x00, 0x01, 0x02, 0x03]);
+
+    // Unpadded, with CRLF line breaks and surrounding whitespace.
+    let data = decode_envelope("", b" AAEC\r\nAwQ\r\n", TagFormat::Ogg).unwrap();
+    assert_eq!(data, &[0x00, 0x01, 0x02, 0x03, 0x04]);
+    assert!(check_base64_strict(b"AAEC\nAwQA").is_ok());
+    assert!(check_base64_strict(b"AAEC\r\nAwQA").is_err());
+}
+
+#[test]
+fn test_encode_envelope() {
+    // The `Serato Analysis` envelope needs a stray `A` at the end.
+    let data = encode_envelope("Serato Analysis", &[0x02, 0x01], TagFormat::MP4).unwrap();
+    assert_eq!(
+        &data[..],
+        &b"YXBwbGljYXRpb24vb2N0ZXQtc3RyZWFtAABTZXJhdG8gQW5hbHlzaXMAAgEA"[..]
+    );
+    assert_eq!(
+        decode_envelope("Serato Analysis", &data, TagFormat::MP4).unwrap(),
+        &[0x02, 0x01]
+    );
+
+    // Long payloads are wrapped and roundtrip as well.
+    let payload: Vec<u8> = (0..=255).collect();
+    let data = encode_envelope("Serato Overview", &payload, TagFormat::FLAC).unwrap();
+    assert!(data.contains(&b'\n'));
+    assert_eq!(
+        decode_envelope("Serato Overview", &data, TagFormat::FLAC).unwrap(),
+        payload
+    );
+
+    // Ogg tags don't contain the header, but also get the stray `A`.
+    let data = encode_envelope("Serato Markers2", &[0x00, 0x01, 0x02], TagFormat::Ogg).unwrap();
+    assert_eq!(&data[..], &b"AAECA"[..]);
+    assert_eq!(
+        decode_envelope("Serato Markers2", &data, TagFormat::Ogg).unwrap(),
+        &[0x00, 0x01, 0x02]
+    );
+    assert!(encode_envelope("Serato Markers2", &[], TagFormat::Ogg)
+        .unwrap()
+        .is_empty());
+
+    assert!(matches!(
+        encode_envelope("Serato Analysis", &[0x02, 0x01], TagFormat::ID3),
+        Err(Error::UnsupportedTagFormat)
+    ));
+}
+
+#[test]
+fn test_decode_enveloped_checked() {
+    let input = include_bytes!("../../../tests/data/tags/markers2/hotcues_and_loops.flac.bin");
+    let (content, is_double_encoded) = decode_enveloped_checked(input, "Serato Markers2").unwrap();
+    assert!(!is_double_encoded);
+
+    // The encoded envelope has been encoded again.
+    let mut double_encoded = vec![];
+    base64_encode(&mut double_encoded, input).unwrap();
+    assert_eq!(
+        decode_enveloped_checked(&double_encoded, "Serato Markers2").unwrap(),
+        (content.clone(), true)
+    );
+
+    // The encoded envelope has been wrapped in another envelope.
+    let mut double_encoded = vec![];
+    envelope_encode_with_name(&mut double_encoded, input, "Serato Markers2").unwrap();
+    assert_eq!(
+        decode_enveloped_checked(&double_encoded, "Serato Markers2").unwrap(),
+        (content.clone(), true)
+    );
+    assert_eq!(
+        decode_envelope("Serato Markers2", &double_encoded, TagFormat::FLAC).unwrap(),
+        content
+    );
+    assert!(matches!(
+        decode_enveloped_checked(&double_encoded, "Serato Markers_"),
+        Err(Error::EnvelopeNameMismatch { .. })
+    ));
+}
+
+#[test]
+fn test_decode_enveloped_from_reader() {
+    /// Returns the data byte by byte and fails at the end unless `is_complete` is set.
+    struct SlowReader<'a> {
+        data: &'a [u8],
+        is_complete: bool,
+    }
+
+    impl io::Read for SlowReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.data.split_first() {
+                Some((byte, data)) => {
+                    buf[0] = *byte;
+                    self.data = data;
+                    Ok(1)
+                }
+                None if self.is_complete => Ok(0),
+                None => Err(io::Error::from(io::ErrorKind::ConnectionReset)),
+            }
+        }
+    }
+
+    fn decode(data: &[u8], is_complete: bool) -> Result<(Vec<u8>, bool), Error> {
+        let reader = SlowReader { data, is_complete };
+        let mut envelope = decode_enveloped_from_reader(reader, "Serato Markers2")?;
+        let mut content = vec![];
+        envelope.read_to_end(&mut content)?;
+        Ok((content, envelope.is_double_encoded()))
+    }
+
+    let input = include_bytes!("../../../tests/data/tags/markers2/hotcues_and_loops.flac.bin");
+    let content = decode_enveloped_checked(input, "Serato Markers2").unwrap();
+    assert_eq!(decode(input, true).unwrap(), content);
+
+    let input =
+        include_bytes!("../../../tests/data/envelopes/markers2/hotcues_and_loops_crlf.flac.bin");
+    assert_eq!(decode(input, true).unwrap(), content);
+
+    let mut double_encoded = vec![];
+    base64_encode(&mut double_encoded, input).unwrap();
+    assert_eq!(
+        decode(&double_encoded, true).unwrap(),
+        (content.0.clone(), true)
+    );
+
+    let mut double_encoded = vec![];
+    envelope_encode_with_name(&mut double_encoded, input, "Serato Markers2").unwrap();
+    assert_eq!(decode(&double_encoded, true).unwrap(), (content.0, true));
+    assert!(matches!(
+        decode_enveloped_from_reader(&double_encoded[..], "Serato Markers_"),
+        Err(Error::EnvelopeNameMismatch { .. })
+    ));
+
+    // Failures of the reader can be told apart from malformed data
+    let err = decode(input, false).unwrap_err();
+    assert!(err.is_io_error());
+    assert!(matches!(err, Error::IOError(err) if err.kind() == io::ErrorKind::ConnectionReset));
+
+    let mut malformed = input.to_vec();
+    malformed[200] = b'!';
+    let err = decode(&malformed, true).unwrap_err();
+    assert!(!err.is_io_error());
+    assert!(matches!(err, Error::Base64DecodeError { .. }));
+
+    let err = decode(&input[..20], true).unwrap_err();
+    assert!(!err.is_io_error());
+}
@@ -10,11 +10,137 @@ pub trait FLACTag: EnvelopedTag {
     /// Name of the `VORBIS_COMMENT` that this data is stored in.
     const FLAC_COMMENT: &'static str;
 
+    /// Parse the base64-encoded envelope stored in the `VORBIS_COMMENT`.
     fn parse_flac(input: &[u8]) -> Result<Self, Error> {
-        Self::parse_enveloped(&input)
+        Self::parse_enveloped(input)
     }
 
+    /// Parse the base64-encoded envelope from a reader (see
+    /// [`parse_enveloped_from_reader`](EnvelopedTag::parse_enveloped_from_reader)).
+    fn parse_flac_from_reader(reader: impl io::Read) -> Result<Self, Error> {
+        Self::parse_enveloped_from_reader(reader)
+    }
+
+    /// Write the tag data wrapped in the base64-encoded envelope, ready to be used as the value of
+    /// the `VORBIS_COMMENT`.
+    ///
+    /// The envelope consists of the `application/octet-stream\0\0` header, followed by the
+    /// null-terminated tag name and the tag data. The base64 output is wrapped after 72 characters,
+    /// just like Serato does it.
     fn write_flac(&self, writer: impl io::Write) -> Result<usize, Error> {
         self.write_enveloped(writer)
     }
 }
+
+#[cfg(feature = "metaflac")]
+use crate::tag::{is_serato_identifier, TagContainer, TagFormat};
+
+#[cfg(feature = "metaflac")]
+impl TagContainer {
+    /// Parse all Serato tags from the `VORBIS_COMMENT` block of a
+    /// [`metaflac::Tag`](::metaflac::Tag).
+    ///
+    /// Vorbis comment keys are matched case-insensitively. If a key has multiple values, only the
+    /// first one is used.
+    ///
+    /// *Requires the `metaflac` feature.*
+    pub fn read_from_flac_tag(tag: &::metaflac::Tag) -> Result<Self, Error> {
+        let mut container = Self::new();
+        let comments = match tag.vorbis_comments() {
+            Some(vorbis) => &vorbis.comments,
+            None => return Ok(container),
+        };
+        for (key, values) in comments {
+            let data = match values.first() {
+                Some(value) => value.as_bytes(),
+                None => continue,
+            };
+            container.parse_tag(key, data, TagFormat::FLAC)?;
+        }
+        Ok(container)
+    }
+
+    /// Write all available Serato tags into the `VORBIS_COMMENT` block of a
+    /// [`metaflac::Tag`](::metaflac::Tag).
+    ///
+    /// All existing Serato comments are removed first (regardless of the case of their keys), so
+    /// that no stale data remains in the tag. Other comments are left untouched.
+    ///
+    /// *Requires the `metaflac` feature.*
+    pub fn write_into_flac_tag(&self, tag: &mut ::metaflac::Tag) -> Result<(), Error> {
+        let tags = self.serialize_tags(TagFormat::FLAC)?;
+        let vorbis = tag.vorbis_comments_mut();
+        vorbis
+            .comments
+            .retain(|key, _| !is_serato_identifier(TagFormat::FLAC, key));
+        for (name, data) in tags {
+            // The data is base64-encoded, so it's always valid ASCII.
+            let value = String::from_utf8_lossy(&data).into_owned();
+            vorbis.set(name, vec![value]);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "metaflac")]
+#[test]
+fn test_flac_tag_roundtrip() {
+    use std::io::Cursor;
+
+    let markers2 = include_str!("../../../tests/data/tags/markers2/hotcues_and_loops.flac.bin");
+    let beatgrid =
+        include_str!("../../../tests/data/tags/beatgrid/terminal_only_with_footer_0x00.flac.bin");
+
+    let mut tag = ::metaflac::Tag::new();
+    tag.set_vorbis("TITLE", vec!["Some Title"]);
+    tag.set_vorbis("SERATO_MARKERS_V2", vec![markers2]);
+    tag.set_vorbis("serato_beatgrid", vec![beatgrid]);
+
+    let container = TagContainer::read_from_flac_tag(&tag).unwrap();
+    assert!(!container.cues().is_empty());
+    assert!(container.beatgrid().is_some());
+
+    // Stale Serato comments are removed.
+    tag.set_vorbis("serato_overview", vec!["stale"]);
+    container.write_into_flac_tag(&mut tag).unwrap();
+    let comments = &tag.vorbis_comments().unwrap().comments;
+    assert_eq!(comments.len(), 3);
+    assert!(comments.get("serato_beatgrid").is_none());
+    assert_eq!(tag.get_vorbis("TITLE").unwrap().next(), Some("Some Title"));
+
+    // Write the tag to bytes and read it back, like it would happen with a FLAC file.
+    let mut bytes = vec![];
+    tag.write_to(&mut bytes).unwrap();
+    let tag = ::metaflac::Tag::read_from(&mut Cursor::new(bytes)).unwrap();
+    assert_eq!(tag.get_vorbis("TITLE").unwrap().next(), Some("Some Title"));
+
+    let reparsed = TagContainer::read_from_flac_tag(&tag).unwrap();
+    assert_eq!(reparsed.cues(), container.cues());
+    assert_eq!(reparsed.loops(), container.loops());
+    assert_eq!(reparsed.beatgrid(), container.beatgrid());
+}
+
+#[cfg(feature = "metaflac")]
+#[test]
+fn test_flac_tag_clear() {
+    let markers2 = include_str!("../../../tests/data/tags/markers2/hotcues_and_loops.flac.bin");
+    let beatgrid =
+        include_str!("../../../tests/data/tags/beatgrid/terminal_only_with_footer_0x00.flac.bin");
+
+    let mut tag = ::metaflac::Tag::new();
+    tag.set_vorbis("TITLE", vec!["Some Title"]);
+    tag.set_vorbis("SERATO_MARKERS_V2", vec![markers2]);
+    tag.set_vorbis("SERATO_BEATGRID", vec![beatgrid]);
+
+    let mut container = TagContainer::read_from_flac_tag(&tag).unwrap();
+    container.clear_tag(crate::tag::TagKind::Beatgrid);
+    container.write_into_flac_tag(&mut tag).unwrap();
+    assert!(tag.get_vorbis("SERATO_BEATGRID").is_none());
+    assert!(tag.get_vorbis("SERATO_MARKERS_V2").is_some());
+
+    container.clear_serato_data();
+    container.write_into_flac_tag(&mut tag).unwrap();
+    let comments = &tag.vorbis_comments().unwrap().comments;
+    assert_eq!(comments.len(), 1);
+    assert_eq!(tag.get_vorbis("TITLE").unwrap().next(), Some("Some Title"));
+}
@@ -2,17 +2,961 @@
 
 use super::Tag;
 use crate::error::Error;
+use crate::util::read_full;
 use std::io;
+use std::io::Read;
 
 pub trait ID3Tag: Tag {
     /// Name of the ID3 tag that this data is stored in.
     const ID3_TAG: &'static str = Self::NAME;
 
     fn parse_id3(input: &[u8]) -> Result<Self, Error> {
-        Self::parse(&input)
+        Self::parse(input)
+    }
+
+    /// Parse the body of the `GEOB` frame from a reader (see [`Tag::parse_from_reader`]).
+    fn parse_id3_from_reader(reader: impl io::Read) -> Result<Self, Error> {
+        Self::parse_from_reader(reader)
     }
 
     fn write_id3(&self, writer: impl io::Write) -> Result<usize, Error> {
         self.write(writer)
     }
 }
+
+use crate::tag::{TagContainer, TagFormat, TagKind};
+
+/// MIME type of the `GEOB` frames that Serato uses.
+#[cfg(feature = "id3")]
+const GEOB_MIME_TYPE: &str = "application/octet-stream";
+
+/// The version of an ID3v2 tag, which decides how the frames are encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ID3Version {
+    /// ID3v2.3, which is written by iTunes and older versions of Serato.
+    V2_3,
+    /// ID3v2.4.
+    V2_4,
+}
+
+/// A raw `GEOB` (general encapsulated object) frame, which stores a Serato tag in an ID3 tag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GeobFrame {
+    /// The MIME type, which is always `application/octet-stream` for Serato tags.
+    pub mime_type: String,
+    /// The file name, which is empty for Serato tags.
+    pub filename: String,
+    /// The description, i.e. the name of the Serato tag (e.g. `Serato Markers2`).
+    pub description: String,
+    /// The encapsulated object, i.e. the tag data.
+    pub data: Vec<u8>,
+}
+
+impl GeobFrame {
+    /// Parse a raw `GEOB` frame, starting with the 10-byte frame header.
+    ///
+    /// The differences between the ID3 versions are handled according to `version`:
+    ///
+    /// - The frame size is a plain 32-bit integer in ID3v2.3 tags, and a syncsafe integer in
+    ///   ID3v2.4 tags. Sizes that aren't syncsafe are accepted in ID3v2.4 tags as well, because
+    ///   some taggers write them.
+    /// - In ID3v2.3 tags, unsynchronisation is applied to the whole tag, so `unsynchronised`
+    ///   has to be set if the tag header has the unsynchronisation flag. In ID3v2.4 tags, it is
+    ///   applied to each frame, which is decided by the frame's flags (or by `unsynchronised`
+    ///   for taggers that only set the flag in the tag header).
+    ///
+    /// Bytes after the frame (e.g. other frames or padding) are ignored. Returns an
+    /// [`InvalidID3Frame`](Error::InvalidID3Frame) error if the frame isn't a `GEOB` frame or
+    /// if it is compressed or encrypted.
+    pub fn parse(input: &[u8], version: ID3Version, unsynchronised: bool) -> Result<Self, Error> {
+        let resynchronised;
+        let input = if version == ID3Version::V2_3 && unsynchronised {
+            resynchronised = resynchronise(input);
+            &resynchronised[..]
+        } else {
+            input
+        };
+        if input.len() < 10 {
+            return Err(Error::InvalidID3Frame {
+                reason: "frame header is incomplete",
+            });
+        }
+        if &input[..4] != b"GEOB" {
+            return Err(Error::InvalidID3Frame {
+                reason: "not a GEOB frame",
+            });
+        }
+        let size = frame_size([input[4], input[5], input[6], input[7]], version);
+        let flags = input[9];
+        let body = input.get(10..10 + size).ok_or(Error::InvalidID3Frame {
+            reason: "frame is incomplete",
+        })?;
+
+        match version {
+            ID3Version::V2_3 => {
+                if flags & 0xC0 != 0 {
+                    return Err(Error::InvalidID3Frame {
+                        reason: "frame is compressed or encrypted",
+                    });
+                }
+                // Skip the grouping identity.
+                let body = if flags & 0x20 != 0 {
+                    body.get(1..).unwrap_or_default()
+                } else {
+                    body
+                };
+                Self::parse_body(body)
+            }
+            ID3Version::V2_4 => {
+                if flags & 0x0C != 0 {
+                    return Err(Error::InvalidID3Frame {
+                        reason: "frame is compressed or encrypted",
+                    });
+                }
+                // Skip the grouping identity and the data length indicator.
+                let mut skip = 0;
+                if flags & 0x40 != 0 {
+                    skip += 1;
+                }
+                if flags & 0x01 != 0 {
+                    skip += 4;
+                }
+                let body = body.get(skip..).unwrap_or_default();
+                if unsynchronised || flags & 0x02 != 0 {
+                    Self::parse_body(&resynchronise(body))
+                } else {
+                    Self::parse_body(body)
+                }
+            }
+        }
+    }
+
+    /// Parse the content of a `GEOB` frame (without the frame header).
+    ///
+    /// The file name and description are decoded according to the text encoding byte (see
+    /// [`parse_geob()`]).
+    pub fn parse_body(input: &[u8]) -> Result<Self, Error> {
+        let (mime_type, filename, description, data) = split_geob(input)?;
+        Ok(Self {
+            mime_type,
+            filename,
+            description,
+            data: data.to_vec(),
+        })
+    }
+}
+
+/// Parse the content of a `GEOB` frame (without the frame header) and return the description and
+/// the encapsulated object, i.e. the name and data of the Serato tag.
+///
+/// The text is decoded according to the text encoding byte (Latin-1, UTF-16 with byte order
+/// mark, UTF-16BE or UTF-8). The latter two are only defined for ID3v2.4, but are accepted in
+/// ID3v2.3 tags as well. UTF-16 text without byte order mark is decoded as little endian. Empty
+/// UTF-16 file names that are terminated by a single null byte (as written by some taggers) are
+/// accepted, too. A byte order mark or null characters left in the description are removed.
+///
+/// ```
+/// use triseratops::tag::format::id3::parse_geob;
+///
+/// let (description, data) =
+///     parse_geob(b"\x00application/octet-stream\x00\x00Serato Analysis\x00\x02\x01").unwrap();
+/// assert_eq!(description, "Serato Analysis");
+/// assert_eq!(data, &[0x02, 0x01]);
+/// ```
+pub fn parse_geob(frame_body: &[u8]) -> Result<(String, &[u8]), Error> {
+    let (_, _, description, data) = split_geob(frame_body)?;
+    Ok((geob_description(&description).to_owned(), data))
+}
+
+/// Split the content of a `GEOB` frame into MIME type, file name, description and data.
+fn split_geob(input: &[u8]) -> Result<(String, String, String, &[u8]), Error> {
+    let (&encoding, input) = input.split_first().ok_or(Error::InvalidID3Frame {
+        reason: "frame is empty",
+    })?;
+    if encoding > 3 {
+        return Err(Error::InvalidID3Frame {
+            reason: "invalid text encoding",
+        });
+    }
+    let (mime_type, input) = take_text(input, 0)?;
+    let (filename, input) = match input {
+        [0x00, 0xFF, 0xFE, ..] | [0x00, 0xFE, 0xFF, ..] if encoding == 1 => {
+            (String::new(), &input[1..])
+        }
+        _ => take_text(input, encoding)?,
+    };
+    let (description, data) = take_text(input, encoding)?;
+    Ok((mime_type, filename, description, data))
+}
+
+/// Returns the size of a frame from the frame header.
+fn frame_size(bytes: [u8; 4], version: ID3Version) -> usize {
+    let size = u32::from_be_bytes(bytes);
+    if version == ID3Version::V2_3 || bytes.iter().any(|b| b & 0x80 != 0) {
+        return size as usize;
+    }
+    syncsafe(bytes)
+}
+
+/// Undo the unsynchronisation scheme, i.e. remove the null bytes that have been inserted after
+/// each `0xFF` byte.
+fn resynchronise(input: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(input.len());
+    let mut previous = 0;
+    for &byte in input {
+        if previous != 0xFF || byte != 0x00 {
+            output.push(byte);
+        }
+        previous = byte;
+    }
+    output
+}
+
+/// Take a null-terminated string in the given ID3 text encoding.
+fn take_text(input: &[u8], encoding: u8) -> Result<(String, &[u8]), Error> {
+    let incomplete = Error::InvalidID3Frame {
+        reason: "unterminated text",
+    };
+    match encoding {
+        // Latin-1 and UTF-8
+        0 | 3 => {
+            let end = input.iter().position(|b| *b == 0).ok_or(incomplete)?;
+            let text = match encoding {
+                0 => input[..end].iter().map(|b| char::from(*b)).collect(),
+                _ => String::from_utf8_lossy(&input[..end]).into_owned(),
+            };
+            Ok((text, &input[end + 1..]))
+        }
+        // UTF-16 with byte order mark and UTF-16BE
+        _ => {
+            let end = input
+                .chunks_exact(2)
+                .position(|c| c == [0, 0])
+                .ok_or(incomplete)?
+                * 2;
+            let mut text = &input[..end];
+            let mut is_big_endian = encoding == 2;
+            if encoding == 1 {
+                match text {
+                    [0xFE, 0xFF, ..] => is_big_endian = true,
+                    [0xFF, 0xFE, ..] => is_big_endian = false,
+                    _ => {}
+                }
+                if text.starts_with(&[0xFE, 0xFF]) || text.starts_with(&[0xFF, 0xFE]) {
+                    text = &text[2..];
+                }
+            }
+            let units: Vec<u16> = text
+                .chunks_exact(2)
+                .map(|c| match is_big_endian {
+                    true => u16::from_be_bytes([c[0], c[1]]),
+                    false => u16::from_le_bytes([c[0], c[1]]),
+                })
+                .collect();
+            Ok((String::from_utf16_lossy(&units), &input[end + 2..]))
+        }
+    }
+}
+
+/// Read an ID3v2 tag and return all `GEOB` frames that contain Serato tags.
+///
+/// This is a minimal, read-only alternative to a full ID3 library: it parses the tag header,
+/// skips the extended header, undoes the unsynchronisation and iterates over the frames, but
+/// ignores all frames except `GEOB` frames with the description of a Serato tag (see
+/// [`TagKind::from_identifier()`]). The descriptions of the returned frames are normalized, so
+/// that the frames can be passed to [`TagContainer::parse_tag()`]. `GEOB` frames that are
+/// compressed or encrypted are skipped, because Serato never writes those.
+///
+/// The reader has to be positioned at the start of the tag (e.g. at the start of an MP3 file).
+/// Only ID3v2.3 and ID3v2.4 tags are supported, other versions result in an
+/// [`InvalidID3Frame`](Error::InvalidID3Frame) error, like a tag that ends prematurely.
+///
+/// ```no_run
+/// use std::fs::File;
+/// use triseratops::tag::{TagContainer, TagFormat, format::id3};
+///
+/// let mut container = TagContainer::new();
+/// for frame in id3::scan(File::open("track.mp3").unwrap()).unwrap() {
+///     container.parse_tag(&frame.description, &frame.data, TagFormat::ID3).unwrap();
+/// }
+/// ```
+pub fn scan(mut reader: impl io::Read) -> Result<Vec<GeobFrame>, Error> {
+    let mut header = [0; 10];
+    if read_full(&mut reader, &mut header)? < header.len() || &header[..3] != b"ID3" {
+        return Err(Error::InvalidID3Frame {
+            reason: "missing ID3v2 tag header",
+        });
+    }
+    let version = match header[3] {
+        3 => ID3Version::V2_3,
+        4 => ID3Version::V2_4,
+        _ => {
+            return Err(Error::InvalidID3Frame {
+                reason: "unsupported ID3v2 version",
+            })
+        }
+    };
+    let flags = header[5];
+    let unsynchronised = flags & 0x80 != 0;
+    let has_extended_header = flags & 0x40 != 0;
+    let size = syncsafe([header[6], header[7], header[8], header[9]]);
+    let mut body = vec![];
+    reader.take(size as u64).read_to_end(&mut body)?;
+    if body.len() < size {
+        return Err(Error::InvalidID3Frame {
+            reason: "tag is incomplete",
+        });
+    }
+
+    // In ID3v2.3 tags, the unsynchronisation is applied to the whole tag.
+    if version == ID3Version::V2_3 && unsynchronised {
+        body = resynchronise(&body);
+    }
+    let mut input = &body[..];
+    if has_extended_header {
+        let size_bytes = match input {
+            [a, b, c, d, ..] => [*a, *b, *c, *d],
+            _ => {
+                return Err(Error::InvalidID3Frame {
+                    reason: "extended header is incomplete",
+                })
+            }
+        };
+        let extended_header_size = match version {
+            ID3Version::V2_3 => u32::from_be_bytes(size_bytes) as usize + 4,
+            ID3Version::V2_4 => syncsafe(size_bytes),
+        };
+        input = input.get(extended_header_size..).unwrap_or_default();
+    }
+
+    let mut frames = vec![];
+    // A null byte instead of a frame identifier marks the start of the padding.
+    while input.len() >= 10 && input[0] != 0 {
+        let frame_size = 10 + frame_size([input[4], input[5], input[6], input[7]], version);
+        if frame_size > input.len() {
+            return Err(Error::InvalidID3Frame {
+                reason: "frame is incomplete",
+            });
+        }
+        let (frame, rest) = input.split_at(frame_size);
+        input = rest;
+        if &frame[..4] != b"GEOB" {
+            continue;
+        }
+        let is_tag_unsynchronised = version == ID3Version::V2_4 && unsynchronised;
+        let mut geob = match GeobFrame::parse(frame, version, is_tag_unsynchronised) {
+            Ok(geob) => geob,
+            Err(Error::InvalidID3Frame { .. }) => continue,
+            Err(err) => return Err(err),
+        };
+        if TagKind::from_identifier(TagFormat::ID3, geob_description(&geob.description)).is_none() {
+            continue;
+        }
+        geob.description = geob_description(&geob.description).to_owned();
+        frames.push(geob);
+    }
+    Ok(frames)
+}
+
+/// Decode a syncsafe integer (7 bits per byte) from the tag header.
+fn syncsafe(bytes: [u8; 4]) -> usize {
+    bytes
+        .iter()
+        .fold(0usize, |size, b| (size << 7) | usize::from(*b & 0x7F))
+}
+
+impl TagContainer {
+    /// Parse the Serato tag from a raw `GEOB` frame (see [`GeobFrame::parse()`]).
+    ///
+    /// Returns the kind of the parsed tag, or `None` if the description doesn't belong to a
+    /// known Serato tag (see [`parse_tag()`](Self::parse_tag)).
+    pub fn parse_id3_frame(
+        &mut self,
+        input: &[u8],
+        version: ID3Version,
+        unsynchronised: bool,
+    ) -> Result<Option<TagKind>, Error> {
+        let frame = GeobFrame::parse(input, version, unsynchronised)?;
+        self.parse_tag(
+            geob_description(&frame.description),
+            &frame.data,
+            TagFormat::ID3,
+        )
+    }
+
+    /// Parse the Serato tag from the content of a `GEOB` frame (without the frame header).
+    ///
+    /// The tag is parsed with the parser for the description of the frame (see
+    /// [`parse_geob()`]). Returns the kind of the parsed tag, or `None` if the description
+    /// doesn't belong to a known Serato tag.
+    ///
+    /// ```
+    /// use triseratops::tag::{TagContainer, TagKind};
+    ///
+    /// let mut container = TagContainer::new();
+    /// let body = b"\x00application/octet-stream\x00\x00Serato Analysis\x00\x02\x01";
+    /// assert_eq!(container.parse_geob(body).unwrap(), Some(TagKind::Analysis));
+    /// ```
+    pub fn parse_geob(&mut self, frame_body: &[u8]) -> Result<Option<TagKind>, Error> {
+        let (description, data) = parse_geob(frame_body)?;
+        self.parse_tag(&description, data, TagFormat::ID3)
+    }
+}
+
+#[cfg(feature = "id3")]
+impl TagContainer {
+    /// Parse all Serato tags from the `GEOB` frames of an [`id3::Tag`](::id3::Tag).
+    ///
+    /// `GEOB` frames with unknown descriptions are skipped.
+    ///
+    /// *Requires the `id3` feature.*
+    pub fn read_from_id3_tag(tag: &::id3::Tag) -> Result<Self, Error> {
+        let mut container = Self::new();
+        for geob in tag.encapsulated_objects() {
+            container.parse_tag(
+                geob_description(&geob.description),
+                &geob.data,
+                TagFormat::ID3,
+            )?;
+        }
+        Ok(container)
+    }
+
+    /// Write all available Serato tags into `GEOB` frames of an [`id3::Tag`](::id3::Tag).
+    ///
+    /// Existing `GEOB` frames for the written tags are replaced, and frames of tags that have been
+    /// [cleared](TagContainer::clear_tag) are removed. Frames of other tags that are not available
+    /// in this container are left untouched, except for the marker tag that isn't written due to
+    /// the [`WriteStrategy`](crate::tag::WriteStrategy), which is removed as well.
+    ///
+    /// *Requires the `id3` feature.*
+    pub fn write_into_id3_tag(&self, tag: &mut ::id3::Tag) -> Result<(), Error> {
+        let tags = self.serialize_tags(TagFormat::ID3)?;
+        for name in self
+            .removed_tags()
+            .into_iter()
+            .filter_map(|kind| kind.tag_name(TagFormat::ID3))
+        {
+            remove_geob_frames(tag, name);
+        }
+        for (name, data) in tags {
+            remove_geob_frames(tag, name);
+            tag.add_encapsulated_object(name, GEOB_MIME_TYPE, "", data);
+        }
+        Ok(())
+    }
+}
+
+/// Strip a byte order mark and trailing null characters that some taggers leave in the decoded
+/// `GEOB` description.
+///
+/// The [`id3`](https://docs.rs/id3) crate and [`GeobFrame`] already decode the description
+/// according to the text encoding byte of the frame (i.e. Latin-1, UTF-16 with BOM, UTF-16BE or
+/// UTF-8), but a duplicated BOM ends up as `U+FEFF` in the text.
+fn geob_description(description: &str) -> &str {
+    description
+        .trim_start_matches('\u{feff}')
+        .trim_end_matches('\0')
+}
+
+/// Remove all `GEOB` frames whose (normalized) description matches the tag name.
+#[cfg(feature = "id3")]
+fn remove_geob_frames(tag: &mut ::id3::Tag, name: &str) {
+    let descriptions: Vec<String> = tag
+        .encapsulated_objects()
+        .filter(|geob| geob_description(&geob.description) == name)
+        .map(|geob| geob.description.clone())
+        .collect();
+    for description in descriptions {
+        tag.remove_encapsulated_object(Some(&description), None, None, None);
+    }
+}
+
+#[cfg(feature = "id3")]
+#[test]
+fn test_id3_tag_roundtrip() {
+    let markers = include_bytes!("../../../tests/data/tags/markers/hotcues_with_names.id3.bin");
+    let markers2 = include_bytes!("../../../tests/data/tags/markers2/hotcues_with_names.id3.bin");
+
+    let mut tag = ::id3::Tag::new();
+    tag.set_title("Some Title");
+    tag.add_encapsulated_object("Serato Markers_", GEOB_MIME_TYPE, "", &markers[..]);
+    tag.add_encapsulated_object("Serato Markers2", GEOB_MIME_TYPE, "", &markers2[..]);
+    tag.add_encapsulated_object("Serato Unknown", GEOB_MIME_TYPE, "", &b"foo"[..]);
+
+    let container = TagContainer::read_from_id3_tag(&tag).unwrap();
+    assert!(!container.cues().is_empty());
+
+    container.write_into_id3_tag(&mut tag).unwrap();
+    assert_eq!(tag.title(), Some("Some Title"));
+    assert_eq!(tag.encapsulated_objects().count(), 3);
+    for geob in tag.encapsulated_objects() {
+        match geob.description.as_str() {
+            "Serato Markers_" => assert_eq!(&geob.data[..], &markers[..]),
+            "Serato Markers2" => assert_eq!(&geob.data[..], &markers2[..]),
+            "Serato Unknown" => assert_eq!(&geob.data[..], b"foo"),
+            _ => unreachable!(),
+        }
+    }
+
+    let reparsed = TagContainer::read_from_id3_tag(&tag).unwrap();
+    assert_eq!(reparsed.cues(), container.cues());
+}
+
+#[cfg(feature = "id3")]
+#[test]
+fn test_id3_tag_clear() {
+    let markers = include_bytes!("../../../tests/data/tags/markers/hotcues_with_names.id3.bin");
+    let markers2 = include_bytes!("../../../tests/data/tags/markers2/hotcues_with_names.id3.bin");
+
+    let mut tag = ::id3::Tag::new();
+    tag.add_encapsulated_object("Serato Markers_", GEOB_MIME_TYPE, "", &markers[..]);
+    tag.add_encapsulated_object("Serato Markers2", GEOB_MIME_TYPE, "", &markers2[..]);
+    tag.add_encapsulated_object("Serato Analysis", GEOB_MIME_TYPE, "", &[0x02, 0x01][..]);
+    tag.add_encapsulated_object("Other", GEOB_MIME_TYPE, "", &b"foo"[..]);
+
+    let mut container = TagContainer::read_from_id3_tag(&tag).unwrap();
+    container.clear_tag(crate::tag::TagKind::Analysis);
+    container.write_into_id3_tag(&mut tag).unwrap();
+    let descriptions: Vec<&str> = tag
+        .encapsulated_objects()
+        .map(|geob| geob.description.as_str())
+        .collect();
+    assert_eq!(descriptions.len(), 3);
+    assert!(!descriptions.contains(&"Serato Analysis"));
+
+    container.clear_serato_data();
+    container.write_into_id3_tag(&mut tag).unwrap();
+    let descriptions: Vec<&str> = tag
+        .encapsulated_objects()
+        .map(|geob| geob.description.as_str())
+        .collect();
+    assert_eq!(descriptions, vec!["Other"]);
+}
+
+#[cfg(feature = "id3")]
+#[test]
+fn test_id3_tag_write_strategy() {
+    use crate::tag::WriteStrategy;
+
+    let markers = include_bytes!("../../../tests/data/tags/markers/hotcues_with_names.id3.bin");
+    let markers2 = include_bytes!("../../../tests/data/tags/markers2/hotcues_with_names.id3.bin");
+
+    let mut tag = ::id3::Tag::new();
+    tag.add_encapsulated_object("Serato Markers_", GEOB_MIME_TYPE, "", &markers[..]);
+    tag.add_encapsulated_object("Serato Markers2", GEOB_MIME_TYPE, "", &markers2[..]);
+    let mut container = TagContainer::read_from_id3_tag(&tag).unwrap();
+    let descriptions = |tag: &::id3::Tag| -> Vec<String> {
+        tag.encapsulated_objects()
+            .map(|geob| geob.description.clone())
+            .collect()
+    };
+
+    container.set_write_strategy(WriteStrategy::Markers2Only);
+    container.write_into_id3_tag(&mut tag).unwrap();
+    assert_eq!(descriptions(&tag), vec!["Serato Markers2"]);
+    let reparsed = TagContainer::read_from_id3_tag(&tag).unwrap();
+    assert_eq!(reparsed.cues(), container.cues());
+
+    container.set_write_strategy(WriteStrategy::LegacyOnly);
+    container.write_into_id3_tag(&mut tag).unwrap();
+    assert_eq!(descriptions(&tag), vec!["Serato Markers_"]);
+
+    container.set_write_strategy(WriteStrategy::Both);
+    container.write_into_id3_tag(&mut tag).unwrap();
+    assert_eq!(descriptions(&tag).len(), 2);
+}
+
+#[cfg(feature = "id3")]
+#[test]
+fn test_id3_tag_utf16_descriptions() {
+    use crate::tag::TagKind;
+
+    fn syncsafe(size: usize) -> [u8; 4] {
+        let size = size as u32;
+        [
+            ((size >> 21) & 0x7f) as u8,
+            ((size >> 14) & 0x7f) as u8,
+            ((size >> 7) & 0x7f) as u8,
+            (size & 0x7f) as u8,
+        ]
+    }
+
+    // Build a raw ID3v2.4 tag with `GEOB` frames (encoding byte, encoded description, data).
+    fn build_tag(frames: &[(u8, Vec<u8>, &[u8])]) -> ::id3::Tag {
+        let mut body = Vec::new();
+        for (encoding, description, data) in frames {
+            let mut content = vec![*encoding];
+            content.extend_from_slice(GEOB_MIME_TYPE.as_bytes());
+            content.push(0);
+            content.extend_from_slice(&[0, 0]);
+            content.extend_from_slice(description);
+            content.extend_from_slice(&[0, 0]);
+            content.extend_from_slice(data);
+            body.extend_from_slice(b"GEOB");
+            body.extend_from_slice(&syncsafe(content.len()));
+            body.extend_from_slice(&[0, 0]);
+            body.extend(content);
+        }
+        let mut tag = b"ID3\x04\x00\x00".to_vec();
+        tag.extend_from_slice(&syncsafe(body.len()));
+        tag.extend(body);
+        ::id3::Tag::read_from(io::Cursor::new(tag)).unwrap()
+    }
+
+    fn utf16le(text: &str, boms: usize) -> Vec<u8> {
+        let mut bytes = [0xFF, 0xFE].repeat(boms);
+        bytes.extend(text.encode_utf16().flat_map(|c| c.to_le_bytes().to_vec()));
+        bytes
+    }
+
+    fn utf16be(text: &str) -> Vec<u8> {
+        text.encode_utf16()
+            .flat_map(|c| c.to_be_bytes().to_vec())
+            .collect()
+    }
+
+    let tags: [(TagKind, &[u8]); 7] = [
+        (
+            TagKind::Analysis,
+            include_bytes!("../../../tests/data/tags/analysis/analyzed.id3.bin"),
+        ),
+        (
+            TagKind::Autotags,
+            include_bytes!("../../../tests/data/tags/autotags/analyzed.id3.bin"),
+        ),
+        (
+            TagKind::Beatgrid,
+            include_bytes!(
+                "../../../tests/data/tags/beatgrid/terminal_only_with_footer_0x00.id3.bin"
+            ),
+        ),
+        (
+            TagKind::Markers,
+            include_bytes!("../../../tests/data/tags/markers/analyzed.id3.bin"),
+        ),
+        (
+            TagKind::Markers2,
+            include_bytes!("../../../tests/data/tags/markers2/hotcues_with_names.id3.bin"),
+        ),
+        (TagKind::Offsets, &[0x01, 0x02, 0x00]),
+        (
+            TagKind::Overview,
+            include_bytes!("../../../tests/data/tags/overview/analyzed.id3.bin"),
+        ),
+    ];
+
+    type Encoder = fn(&str) -> (u8, Vec<u8>);
+    let encodings: [(&str, Encoder); 3] = [
+        ("UTF-16LE", |name| (0x01, utf16le(name, 1))),
+        ("UTF-16BE", |name| (0x02, utf16be(name))),
+        ("UTF-16LE (duplicated BOM)", |name| (0x01, utf16le(name, 2))),
+    ];
+    for (encoding_name, encode) in encodings.iter() {
+        let frames: Vec<(u8, Vec<u8>, &[u8])> = tags
+            .iter()
+            .map(|(kind, data)| {
+                let (encoding, description) = encode(kind.id3_description().unwrap());
+                (encoding, description, *data)
+            })
+            .collect();
+        let mut tag = build_tag(&frames);
+
+        let container = TagContainer::read_from_id3_tag(&tag).unwrap();
+        assert_eq!(
+            container.present_tags(),
+            tags.iter().map(|(kind, _)| *kind).collect::<Vec<_>>(),
+            "{}",
+            encoding_name
+        );
+
+        container.write_into_id3_tag(&mut tag).unwrap();
+        assert_eq!(tag.encapsulated_objects().count(), tags.len());
+        for geob in tag.encapsulated_objects() {
+            let kind = TagKind::from_tag_name(&geob.description, TagFormat::ID3).unwrap();
+            let (_, data) = tags.iter().find(|(k, _)| *k == kind).unwrap();
+            assert_eq!(&geob.data[..], *data, "{}: {:?}", encoding_name, kind);
+        }
+    }
+}
+
+#[test]
+fn test_parse_geob_frame() {
+    fn unsynchronise(data: &[u8]) -> Vec<u8> {
+        let mut output = vec![];
+        for (i, &byte) in data.iter().enumerate() {
+            output.push(byte);
+            if byte == 0xFF && data.get(i + 1).map_or(true, |b| *b >= 0xE0 || *b == 0x00) {
+                output.push(0x00);
+            }
+        }
+        output
+    }
+
+    fn frame(size: [u8; 4], flags: u8, body: &[u8]) -> Vec<u8> {
+        let mut frame = b"GEOB".to_vec();
+        frame.extend_from_slice(&size);
+        frame.extend_from_slice(&[0x00, flags]);
+        frame.extend_from_slice(body);
+        frame
+    }
+
+    let data = [0x01, 0xFF, 0xE0, 0xFF, 0x00, 0x02, 0xFF];
+
+    // ID3v2.3 with UTF-16 text and unsynchronisation of the whole tag (e.g. iTunes)
+    let mut body = vec![0x01];
+    body.extend_from_slice(b"application/octet-stream\0");
+    body.extend_from_slice(&[0xFF, 0xFE, 0x00, 0x00]);
+    body.extend_from_slice(&[0xFF, 0xFE]);
+    body.extend("Serato Markers2".encode_utf16().flat_map(u16::to_le_bytes));
+    body.extend_from_slice(&[0x00, 0x00]);
+    body.extend_from_slice(&data);
+    let size = (body.len() as u32).to_be_bytes();
+    let input = unsynchronise(&frame(size, 0x00, &body));
+    assert_ne!(input.len(), body.len() + 10);
+    let geob = GeobFrame::parse(&input, ID3Version::V2_3, true).unwrap();
+    assert_eq!(geob.mime_type, "application/octet-stream");
+    assert_eq!(geob.filename, "");
+    assert_eq!(geob.description, "Serato Markers2");
+    assert_eq!(geob.data, data);
+
+    // ID3v2.4 with Latin-1 text, a data length indicator and unsynchronisation of the frame
+    // (e.g. Mixed In Key)
+    let mut body = vec![0x00];
+    body.extend_from_slice(b"application/octet-stream\0Caf\xE9\0Serato Analysis\0");
+    body.extend_from_slice(&data);
+    let mut content = vec![0x00, 0x00, 0x00, body.len() as u8];
+    content.extend(unsynchronise(&body));
+    let size = [0x00, 0x00, 0x00, content.len() as u8];
+    let mut input = frame(size, 0x03, &content);
+    input.extend_from_slice(b"TIT2\x00\x00\x00\x01\x00\x00\x00");
+    let geob = GeobFrame::parse(&input, ID3Version::V2_4, false).unwrap();
+    assert_eq!(geob.filename, "Café");
+    assert_eq!(geob.description, "Serato Analysis");
+    assert_eq!(geob.data, data);
+
+    // Syncsafe and plain frame sizes
+    let mut body = vec![0x03];
+    body.extend_from_slice(b"application/octet-stream\0\0Serato Offsets_\0");
+    body.resize(200, 0x01);
+    let geob = GeobFrame::parse(&frame([0, 0, 1, 72], 0x00, &body), ID3Version::V2_4, false);
+    assert_eq!(geob.unwrap().data.len(), 200 - 43);
+    let geob = GeobFrame::parse(&frame([0, 0, 0, 200], 0x00, &body), ID3Version::V2_4, false);
+    assert_eq!(geob.unwrap().data.len(), 200 - 43);
+    let geob = GeobFrame::parse(&frame([0, 0, 0, 200], 0x00, &body), ID3Version::V2_3, false);
+    assert_eq!(geob.unwrap().description, "Serato Offsets_");
+
+    assert!(matches!(
+        GeobFrame::parse(&frame([0, 0, 0, 200], 0x80, &body), ID3Version::V2_3, false),
+        Err(Error::InvalidID3Frame { .. })
+    ));
+    assert!(matches!(
+        GeobFrame::parse(&frame([0, 0, 0, 201], 0x00, &body), ID3Version::V2_3, false),
+        Err(Error::InvalidID3Frame { .. })
+    ));
+    assert!(matches!(
+        GeobFrame::parse(b"TIT2\x00\x00\x00\x01\x00\x00\x00", ID3Version::V2_4, false),
+        Err(Error::InvalidID3Frame { .. })
+    ));
+
+    // The tag data can be parsed directly.
+    let markers2 = include_bytes!("../../../tests/data/tags/markers2/hotcues_with_names.id3.bin");
+    let mut body = vec![0x00];
+    body.extend_from_slice(b"application/octet-stream\0\0Serato Markers2\0");
+    body.extend_from_slice(markers2);
+    let size = (body.len() as u32).to_be_bytes();
+    let mut container = TagContainer::new();
+    let kind = container
+        .parse_id3_frame(&frame(size, 0x00, &body), ID3Version::V2_3, false)
+        .unwrap();
+    assert_eq!(kind, Some(TagKind::Markers2));
+    let mut expected = TagContainer::new();
+    expected.parse_markers2(markers2, TagFormat::ID3).unwrap();
+    assert_eq!(container.cues(), expected.cues());
+}
+
+#[cfg(feature = "id3")]
+#[test]
+fn test_id3_tag_versions() {
+    let markers = include_bytes!("../../../tests/data/tags/markers/hotcues_with_names.id3.bin");
+    let markers2 = include_bytes!("../../../tests/data/tags/markers2/hotcues_with_names.id3.bin");
+
+    let mut tag = ::id3::Tag::new();
+    tag.add_encapsulated_object("Serato Markers_", GEOB_MIME_TYPE, "", &markers[..]);
+    tag.add_encapsulated_object("Serato Markers2", GEOB_MIME_TYPE, "", &markers2[..]);
+    let expected = TagContainer::read_from_id3_tag(&tag).unwrap();
+
+    for version in &[::id3::Version::Id3v23, ::id3::Version::Id3v24] {
+        for unsynchronisation in &[false, true] {
+            let mut data = vec![];
+            ::id3::Encoder::new()
+                .version(*version)
+                .unsynchronisation(*unsynchronisation)
+                .encode(&tag, &mut data)
+                .unwrap();
+            let tag = ::id3::Tag::read_from(io::Cursor::new(data)).unwrap();
+            let container = TagContainer::read_from_id3_tag(&tag).unwrap();
+            assert_eq!(container.cues(), expected.cues());
+            assert_eq!(container.track_color(), expected.track_color());
+        }
+    }
+}
+
+#[test]
+fn test_parse_geob() {
+    fn utf16le(text: &str) -> Vec<u8> {
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+        bytes.extend_from_slice(&[0x00, 0x00]);
+        bytes
+    }
+
+    let markers2 = include_bytes!("../../../tests/data/tags/markers2/hotcues_with_names.id3.bin");
+    let mime_type = b"application/octet-stream\0";
+
+    // Latin-1
+    let mut latin1 = vec![0x00];
+    latin1.extend_from_slice(mime_type);
+    latin1.extend_from_slice(b"\0Serato Markers2\0");
+    latin1.extend_from_slice(markers2);
+
+    // UTF-16 with empty file names with and without byte order mark
+    let mut utf16 = vec![0x01];
+    utf16.extend_from_slice(mime_type);
+    utf16.extend_from_slice(&[0x00, 0x00]);
+    utf16.extend(utf16le("Serato Markers2"));
+    utf16.extend_from_slice(markers2);
+    let mut utf16_bom = vec![0x01];
+    utf16_bom.extend_from_slice(mime_type);
+    utf16_bom.extend(utf16le(""));
+    utf16_bom.extend(utf16le("Serato Markers2"));
+    utf16_bom.extend_from_slice(markers2);
+
+    // UTF-16 with an empty file name that is terminated by a single null byte
+    let mut utf16_single_null = vec![0x01];
+    utf16_single_null.extend_from_slice(mime_type);
+    utf16_single_null.push(0x00);
+    utf16_single_null.extend(utf16le("Serato Markers2"));
+    utf16_single_null.extend_from_slice(markers2);
+
+    let mut expected = TagContainer::new();
+    expected.parse_markers2(markers2, TagFormat::ID3).unwrap();
+    for body in &[latin1, utf16, utf16_bom, utf16_single_null] {
+        let (description, data) = parse_geob(body).unwrap();
+        assert_eq!(description, "Serato Markers2");
+        assert_eq!(data, &markers2[..]);
+
+        let mut container = TagContainer::new();
+        assert_eq!(container.parse_geob(body).unwrap(), Some(TagKind::Markers2));
+        assert_eq!(container.cues(), expected.cues());
+    }
+
+    let mut container = TagContainer::new();
+    let body = b"\x00application/octet-stream\x00\x00Serato Unknown\x00foo";
+    assert_eq!(container.parse_geob(body).unwrap(), None);
+    assert!(matches!(
+        parse_geob(b"\x00application/octet-stream\x00\x00Serato Analysis"),
+        Err(Error::InvalidID3Frame { .. })
+    ));
+    assert!(matches!(
+        parse_geob(b""),
+        Err(Error::InvalidID3Frame { .. })
+    ));
+}
+
+#[test]
+fn test_scan() {
+    let geobs: [(&str, &[u8]); 4] = [
+        (
+            "Serato Analysis",
+            include_bytes!("../../../tests/data/tags/analysis/analyzed.id3.bin"),
+        ),
+        (
+            "Serato Autotags",
+            include_bytes!("../../../tests/data/tags/autotags/analyzed.id3.bin"),
+        ),
+        (
+            "Serato Markers_",
+            include_bytes!("../../../tests/data/tags/markers/hotcues_with_names.id3.bin"),
+        ),
+        (
+            "Serato Markers2",
+            include_bytes!("../../../tests/data/tags/markers2/hotcues_with_names.id3.bin"),
+        ),
+    ];
+    let mut tag = ::id3::Tag::new();
+    tag.set_title("Some Title");
+    for (description, data) in geobs.iter() {
+        tag.add_encapsulated_object(*description, "application/octet-stream", "", *data);
+    }
+    tag.add_encapsulated_object("Other", "application/octet-stream", "", &b"foo"[..]);
+
+    // Insert an extended header into an encoded tag.
+    fn with_extended_header(mut data: Vec<u8>, extended_header: &[u8]) -> Vec<u8> {
+        let size = syncsafe([data[6], data[7], data[8], data[9]]) + extended_header.len();
+        data[5] |= 0x40;
+        for (i, byte) in data[6..10].iter_mut().enumerate() {
+            *byte = ((size >> (7 * (3 - i))) & 0x7F) as u8;
+        }
+        data.splice(10..10, extended_header.iter().copied());
+        data
+    }
+
+    for version in &[::id3::Version::Id3v23, ::id3::Version::Id3v24] {
+        for unsynchronisation in &[false, true] {
+            let mut data = vec![];
+            ::id3::Encoder::new()
+                .version(*version)
+                .unsynchronisation(*unsynchronisation)
+                .encode(&tag, &mut data)
+                .unwrap();
+            let extended_header: &[u8] = match version {
+                ::id3::Version::Id3v23 => &[0, 0, 0, 6, 0, 0, 0, 0, 0, 0],
+                _ => &[0, 0, 0, 6, 1, 0],
+            };
+            let extended = with_extended_header(data.clone(), extended_header);
+            for data in &[data, extended] {
+                let mut reader = io::Cursor::new(data);
+                let frames = scan(&mut reader).unwrap();
+                assert_eq!(reader.position(), data.len() as u64);
+                assert_eq!(frames.len(), geobs.len());
+                for (frame, (description, data)) in frames.iter().zip(geobs.iter()) {
+                    assert_eq!(frame.description, *description);
+                    assert_eq!(frame.data, *data);
+                }
+
+                let mut container = TagContainer::new();
+                for frame in &frames {
+                    let kind = container
+                        .parse_tag(&frame.description, &frame.data, TagFormat::ID3)
+                        .unwrap();
+                    assert!(kind.is_some());
+                }
+                assert!(!container.cues().is_empty());
+            }
+        }
+    }
+
+    assert!(matches!(
+        scan(&b"ID3\x02\x00\x00\x00\x00\x00\x00"[..]),
+        Err(Error::InvalidID3Frame { .. })
+    ));
+    assert!(matches!(
+        scan(&b"TAG\x04\x00\x00\x00\x00\x00\x00"[..]),
+        Err(Error::InvalidID3Frame { .. })
+    ));
+    // Truncated tags are parse errors, not I/O errors
+    assert!(matches!(
+        scan(&b"ID3\x04\x00\x00\x00\x00\x00\x10"[..]),
+        Err(Error::InvalidID3Frame { .. })
+    ));
+    assert!(matches!(
+        scan(&b"ID3\x04\x00"[..]),
+        Err(Error::InvalidID3Frame { .. })
+    ));
+    assert!(
+        scan(&b"ID3\x04\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00"[..])
+            .unwrap()
+            .is_empty()
+    );
+}
@@ -0,0 +1,203 @@
+//! Integration with the [`lofty`](https://docs.rs/lofty) crate.
+//!
+//! In contrast to the other integrations, lofty reads all supported file types into a generic
+//! [`TaggedFile`], so the tag format is picked based on the primary tag type of the file.
+
+use super::id3::parse_geob;
+use super::mp4::FREEFORM_MEAN;
+use crate::error::Error;
+use crate::tag::{is_serato_identifier, TagContainer, TagFormat, TagName};
+use ::lofty::file::{FileType, TaggedFile, TaggedFileExt};
+use ::lofty::id3::v2::{BinaryFrame, Frame, FrameId, GeneralEncapsulatedObject, Id3v2Tag};
+use ::lofty::mp4::{Atom, AtomData, AtomIdent, DataType, Ilst};
+use ::lofty::ogg::VorbisComments;
+use ::lofty::tag::{Tag, TagType};
+use ::lofty::TextEncoding;
+use std::borrow::Cow;
+
+/// MIME type of the `GEOB` frames that Serato uses.
+const GEOB_MIME_TYPE: &str = "application/octet-stream";
+
+/// Returns the tag format that Serato uses for the primary tag of the file.
+///
+/// Vorbis comments are used by FLAC and Ogg Vorbis files, which store the Serato tags
+/// differently, so the file type decides between both formats. Tag types that Serato doesn't
+/// read (e.g. APE tags or the Vorbis comments of Opus files) are rejected with an
+/// [`UnsupportedTagType`](Error::UnsupportedTagType) error.
+fn tag_format(file: &TaggedFile) -> Result<TagFormat, Error> {
+    let tag_type = file.primary_tag_type();
+    let file_type = file.file_type();
+    match (tag_type, file_type) {
+        (TagType::Id3v2, FileType::Mpeg | FileType::Aiff | FileType::Wav) => Ok(TagFormat::ID3),
+        (TagType::VorbisComments, FileType::Flac) => Ok(TagFormat::FLAC),
+        (TagType::VorbisComments, FileType::Vorbis) => Ok(TagFormat::Ogg),
+        (TagType::Mp4Ilst, FileType::Mp4) => Ok(TagFormat::MP4),
+        _ => Err(Error::UnsupportedTagType {
+            tag_type: format!("{:?}", tag_type),
+            file_type: format!("{:?}", file_type),
+        }),
+    }
+}
+
+/// Returns the body of the frame if it is a `GEOB` frame.
+fn geob_body<'a>(frame: &'a Frame<'_>) -> Option<&'a [u8]> {
+    match frame {
+        Frame::Binary(frame) if frame.id().as_str() == "GEOB" => Some(&frame.data),
+        _ => None,
+    }
+}
+
+/// Returns the identifier of the freeform atom (`----:com.serato.dj:<name>`) for the tag name.
+fn freeform_ident(name: TagName) -> AtomIdent<'static> {
+    AtomIdent::Freeform {
+        mean: Cow::Borrowed(FREEFORM_MEAN),
+        name: Cow::Borrowed(name),
+    }
+}
+
+impl TagContainer {
+    /// Parse all Serato tags from the primary tag of a
+    /// [`lofty::file::TaggedFile`](::lofty::file::TaggedFile).
+    ///
+    /// The tag format is picked based on the primary tag type of the file: ID3v2 tags of MP3,
+    /// AIFF and WAV files are read from their `GEOB` frames, Vorbis comments of FLAC and Ogg
+    /// Vorbis files from their Serato comments, and MP4 tags from the freeform atoms. Returns an
+    /// [`UnsupportedTagType`](Error::UnsupportedTagType) error for files that Serato doesn't store
+    /// tags in (e.g. Opus or APE files).
+    ///
+    /// *Requires the `lofty` feature.*
+    pub fn read_from_lofty(file: &TaggedFile) -> Result<Self, Error> {
+        let tag_format = tag_format(file)?;
+        let mut container = Self::new();
+        let tag = match file.primary_tag() {
+            Some(tag) => tag.clone(),
+            None => return Ok(container),
+        };
+        match tag_format {
+            TagFormat::ID3 => {
+                // lofty only keeps the `GEOB` frames in the format-specific tag.
+                for frame in &Id3v2Tag::from(tag) {
+                    if let Some(body) = geob_body(frame) {
+                        container.parse_geob(body)?;
+                    }
+                }
+            }
+            TagFormat::FLAC | TagFormat::Ogg => {
+                for (key, value) in VorbisComments::from(tag).items() {
+                    container.parse_tag(key, value.as_bytes(), tag_format)?;
+                }
+            }
+            TagFormat::MP4 => {
+                // Serato stores the data with the implicit (reserved) data type, which lofty only
+                // keeps in the format-specific tag. UTF-8 data is accepted too.
+                for atom in &Ilst::from(tag) {
+                    let name = match atom.ident() {
+                        AtomIdent::Freeform { mean, name } if mean == FREEFORM_MEAN => name,
+                        _ => continue,
+                    };
+                    let data = atom.data().find_map(|data| match data {
+                        AtomData::Unknown { data, .. } => Some(data.as_slice()),
+                        AtomData::UTF8(text) => Some(text.as_bytes()),
+                        _ => None,
+                    });
+                    if let Some(data) = data {
+                        container.parse_tag(name, data, TagFormat::MP4)?;
+                    }
+                }
+            }
+        }
+        Ok(container)
+    }
+
+    /// Write all available Serato tags into the primary tag of a
+    /// [`lofty::file::TaggedFile`](::lofty::file::TaggedFile).
+    ///
+    /// The tag format is picked like in [`read_from_lofty`](Self::read_from_lofty), and the
+    /// primary tag is created if the file doesn't have one yet. The tags are written the same way
+    /// as with the format-specific integrations: Existing `GEOB` frames and freeform atoms for the
+    /// written tags are replaced, and those of tags that have been
+    /// [cleared](TagContainer::clear_tag) are removed. In FLAC and Ogg Vorbis files, all existing
+    /// Serato comments are removed first.
+    ///
+    /// Serato tags are never written into tag types that Serato doesn't read (e.g. APE tags or the
+    /// Vorbis comments of Opus files). For these files, an
+    /// [`UnsupportedTagType`](Error::UnsupportedTagType) error is returned and the file is left
+    /// untouched. The changes have to be saved with lofty afterwards.
+    ///
+    /// *Requires the `lofty` feature.*
+    pub fn write_into_lofty(&self, file: &mut TaggedFile) -> Result<(), Error> {
+        let tag_format = tag_format(file)?;
+        let tags = self.serialize_tags(tag_format)?;
+        let removed_names: Vec<TagName> = self
+            .removed_tags()
+            .into_iter()
+            .filter_map(|kind| kind.tag_name(tag_format))
+            .collect();
+        let tag = file
+            .primary_tag()
+            .cloned()
+            .unwrap_or_else(|| Tag::new(file.primary_tag_type()));
+        let tag = match tag_format {
+            TagFormat::ID3 => {
+                let mut id3v2 = Id3v2Tag::from(tag);
+                id3v2.retain(|frame| {
+                    let description = match geob_body(frame).map(parse_geob) {
+                        Some(Ok((description, _))) => description,
+                        _ => return true,
+                    };
+                    !removed_names
+                        .iter()
+                        .chain(tags.iter().map(|(name, _)| name))
+                        .any(|name| *name == description)
+                });
+                for (name, data) in tags {
+                    let geob = GeneralEncapsulatedObject::new(
+                        TextEncoding::Latin1,
+                        Some(GEOB_MIME_TYPE.to_owned()),
+                        None,
+                        Some(name.to_owned()),
+                        data,
+                    );
+                    let id = FrameId::Valid(Cow::Borrowed("GEOB"));
+                    id3v2.insert(Frame::Binary(BinaryFrame::new(id, geob.as_bytes())));
+                }
+                Tag::from(id3v2)
+            }
+            TagFormat::FLAC | TagFormat::Ogg => {
+                let mut vorbis = VorbisComments::from(tag);
+                let keys: Vec<String> = vorbis
+                    .items()
+                    .map(|(key, _)| key)
+                    .filter(|key| is_serato_identifier(tag_format, key))
+                    .map(str::to_owned)
+                    .collect();
+                for key in keys {
+                    vorbis.remove(&key).for_each(drop);
+                }
+                for (name, data) in tags {
+                    // The data of FLAC and Ogg tags is always valid ASCII.
+                    let value = String::from_utf8_lossy(&data).into_owned();
+                    vorbis.push(name.to_owned(), value);
+                }
+                Tag::from(vorbis)
+            }
+            TagFormat::MP4 => {
+                let mut ilst = Ilst::from(tag);
+                for name in removed_names {
+                    ilst.remove(&freeform_ident(name)).for_each(drop);
+                }
+                for (name, data) in tags {
+                    ilst.remove(&freeform_ident(name)).for_each(drop);
+                    let data = AtomData::Unknown {
+                        code: DataType::Reserved,
+                        data,
+                    };
+                    ilst.insert(Atom::new(freeform_ident(name), data));
+                }
+                Tag::from(ilst)
+            }
+        };
+        file.insert_tag(tag);
+        Ok(())
+    }
+}
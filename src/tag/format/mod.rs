@@ -1,16 +1,197 @@
 //! Traits for the different tag types ([ID3](id3::ID3Tag), [FLAC](flac::FLACTag), etc.)
 
+pub mod aiff;
 pub mod enveloped;
 pub mod flac;
 pub mod id3;
+#[cfg(feature = "lofty")]
+mod lofty;
 pub mod mp4;
 pub mod ogg;
+pub mod riff;
 
 use crate::error::Error;
+use crate::tag::TagFormat;
 use std::io;
+use std::io::Read;
+
+/// The maximum size (in bytes) of tag data that is read by [`Tag::parse_from_reader`].
+///
+/// This is the size limit of FLAC metadata blocks. Serato tags are much smaller (the largest one,
+/// `Serato Overview`, has less than 4 KiB), so larger data can't be a Serato tag.
+pub const MAX_TAG_SIZE: u64 = 1 << 24;
 
 pub trait Tag: Sized {
     const NAME: &'static str;
     fn parse(input: &[u8]) -> Result<Self, Error>;
+
+    /// Parse the tag data from a reader, e.g. a file or a network stream.
+    ///
+    /// Returns the same result as [`parse`](Tag::parse). Errors of the reader are returned as
+    /// [`IOError`](Error::IOError) and can be told apart from malformed data using
+    /// [`Error::is_io_error`].
+    ///
+    /// The reader has to end after the tag data (e.g. by limiting it to the size of the `GEOB`
+    /// frame or MP4 atom with [`Read::take`]). The default implementation buffers the input, i.e.
+    /// it reads all data into memory before parsing it. Data larger than [`MAX_TAG_SIZE`] is
+    /// rejected with a [`TagTooLarge`](Error::TagTooLarge) error without reading it completely.
+    fn parse_from_reader(reader: impl io::Read) -> Result<Self, Error> {
+        let mut input = vec![];
+        reader.take(MAX_TAG_SIZE + 1).read_to_end(&mut input)?;
+        if input.len() as u64 > MAX_TAG_SIZE {
+            return Err(Error::TagTooLarge {
+                max_size: MAX_TAG_SIZE,
+            });
+        }
+        Self::parse(&input)
+    }
+
     fn write(&self, writer: impl io::Write) -> Result<usize, Error>;
 }
+
+/// Guess the tag format from the raw tag data, e.g. if the data was extracted by a generic tagging
+/// tool.
+///
+/// The formats are recognized as follows:
+///
+/// - ID3 tag data (i.e. the body of the `GEOB` frame) is binary and starts with the two version
+///   bytes of the tag.
+/// - FLAC and MP4 tag data is the base64-encoded `application/octet-stream` envelope. The
+///   envelope is the same for both formats, so only the `Serato Markers_` tag (which is not used
+///   in FLAC files) is detected as [`MP4`](TagFormat::MP4). All other enveloped tags are detected
+///   as [`FLAC`](TagFormat::FLAC), which parses them the same way.
+/// - Ogg tag data is either plain base64 without envelope (`Serato Markers2`) or an analysis
+///   version string like `2.1`.
+///
+/// Returns `None` if the data is too short to tell the formats apart or doesn't look like any of
+/// them.
+///
+/// ```
+/// use triseratops::tag::{format, TagFormat};
+///
+/// assert_eq!(format::detect(b"\x02\x01"), Some(TagFormat::ID3));
+/// assert_eq!(format::detect(b"2.1"), Some(TagFormat::Ogg));
+/// assert_eq!(format::detect(b"AQ"), None);
+/// ```
+pub fn detect(input: &[u8]) -> Option<TagFormat> {
+    if input.len() < 2 {
+        return None;
+    }
+    if is_version(input) {
+        return Some(TagFormat::ID3);
+    }
+    if is_analysis_version(input) {
+        return Some(TagFormat::Ogg);
+    }
+
+    // The Ogg `Serato Markers2` data may be padded with null bytes
+    let end = input.iter().rposition(|b| *b != b'\x00')? + 1;
+    let encoded = &input[..end];
+    if !encoded
+        .iter()
+        .all(|b| enveloped::is_base64(*b) || enveloped::is_whitespace(*b))
+    {
+        return None;
+    }
+    let mut data = enveloped::base64_decode(encoded).ok()?;
+    if data.starts_with(enveloped::ENCODED_ENVELOPE_HEADER) {
+        data = enveloped::base64_decode(&data).ok()?;
+    }
+    if let Ok((name, _)) = enveloped::parse_envelope(&data) {
+        if name == crate::tag::Markers::NAME {
+            return Some(TagFormat::MP4);
+        }
+        return Some(TagFormat::FLAC);
+    }
+    if data.len() >= 2 && is_version(&data) {
+        return Some(TagFormat::Ogg);
+    }
+    None
+}
+
+/// Returns `true` if the data starts with the binary version bytes of a tag (e.g. `0x01 0x01`).
+fn is_version(input: &[u8]) -> bool {
+    matches!(input, [major, minor, ..] if (1..=2).contains(major) && *minor < 0x10)
+}
+
+/// Returns `true` if the data is an analysis version string like `2.1`.
+fn is_analysis_version(input: &[u8]) -> bool {
+    let mut parts = input.split(|b| *b == b'.');
+    let is_number = |part: Option<&[u8]>| {
+        part.is_some_and(|part| !part.is_empty() && part.iter().all(u8::is_ascii_digit))
+    };
+    is_number(parts.next()) && is_number(parts.next()) && parts.next().is_none()
+}
+
+#[test]
+fn test_detect() {
+    assert_eq!(detect(b""), None);
+    assert_eq!(detect(b"\x01"), None);
+    assert_eq!(detect(b"2"), None);
+    assert_eq!(detect(b"AQ"), None);
+    assert_eq!(detect(b"\x00\x00\x00"), None);
+    assert_eq!(detect(b"1."), None);
+    assert_eq!(detect(b"1.2.3"), None);
+    assert_eq!(detect(b"Foo Bar"), None);
+    assert_eq!(detect(b"\x01\x05"), Some(TagFormat::ID3));
+    assert_eq!(detect(b"0.0"), Some(TagFormat::Ogg));
+    assert_eq!(detect(b"AQEA"), Some(TagFormat::Ogg));
+    assert_eq!(detect(b"AQEA\x00\x00"), Some(TagFormat::Ogg));
+
+    let mut enveloped = vec![];
+    enveloped::envelope_encode_with_name(&mut enveloped, b"\x01\x01", "Serato Autotags").unwrap();
+    assert_eq!(detect(&enveloped), Some(TagFormat::FLAC));
+    let mut enveloped = vec![];
+    enveloped::envelope_encode_with_name(&mut enveloped, b"\x02\x05", "Serato Markers_").unwrap();
+    assert_eq!(detect(&enveloped), Some(TagFormat::MP4));
+}
+
+#[test]
+fn test_parse_from_reader() {
+    use crate::tag::{Beatgrid, Overview};
+
+    /// A reader that returns at most 3 bytes per call and is interrupted every other call.
+    struct ShortReads<'a> {
+        data: &'a [u8],
+        interrupt: bool,
+    }
+
+    impl io::Read for ShortReads<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.interrupt = !self.interrupt;
+            if self.interrupt {
+                return Err(io::ErrorKind::Interrupted.into());
+            }
+            let length = buf.len().min(self.data.len()).min(3);
+            buf[..length].copy_from_slice(&self.data[..length]);
+            self.data = &self.data[length..];
+            Ok(length)
+        }
+    }
+
+    let short_reads = |data| ShortReads {
+        data,
+        interrupt: false,
+    };
+    let input =
+        include_bytes!("../../../tests/data/tags/beatgrid/terminal_only_with_footer_0x00.id3.bin");
+    assert_eq!(
+        Beatgrid::parse_from_reader(short_reads(input)).unwrap(),
+        Beatgrid::parse(input).unwrap()
+    );
+    let input = include_bytes!("../../../tests/data/tags/overview/analyzed.id3.bin");
+    assert_eq!(
+        Overview::parse_from_reader(short_reads(input)).unwrap(),
+        Overview::parse(input).unwrap()
+    );
+
+    // The size of the data is limited, so that endless readers are rejected
+    assert!(matches!(
+        Beatgrid::parse_from_reader(io::repeat(0)),
+        Err(Error::TagTooLarge { .. })
+    ));
+    assert!(matches!(
+        Overview::parse_from_reader(io::repeat(0)),
+        Err(Error::TagTooLarge { .. })
+    ));
+}
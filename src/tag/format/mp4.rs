@@ -3,22 +3,213 @@
 extern crate base64;
 extern crate nom;
 
-use super::enveloped::EnvelopedTag;
+use super::enveloped::{self, EnvelopedTag};
 use crate::error::Error;
 use std::io;
 
+/// The mean part of the freeform atoms that Serato uses.
+pub const FREEFORM_MEAN: &str = "com.serato.dj";
+
 pub trait MP4Tag: EnvelopedTag {
     /// The mean part of the freeform `MP4_ATOM` that this data is stored in.
-    const MP4_ATOM_FREEFORM_MEAN: &'static str = "com.serato.dj";
+    const MP4_ATOM_FREEFORM_MEAN: &'static str = FREEFORM_MEAN;
 
-    /// The mean part of the freeform `MP4_ATOM` that this data is stored in.
+    /// The name part of the freeform `MP4_ATOM` that this data is stored in.
     const MP4_ATOM_FREEFORM_NAME: &'static str;
 
+    /// Parse the base64-encoded envelope stored in the freeform atom.
     fn parse_mp4(input: &[u8]) -> Result<Self, Error> {
-        Self::parse_enveloped(&input)
+        Self::parse_enveloped(input)
     }
 
+    /// Parse the base64-encoded envelope from a reader (see
+    /// [`parse_enveloped_from_reader`](EnvelopedTag::parse_enveloped_from_reader)).
+    fn parse_mp4_from_reader(reader: impl io::Read) -> Result<Self, Error> {
+        Self::parse_enveloped_from_reader(reader)
+    }
+
+    /// Write the tag data wrapped in the base64-encoded envelope, ready to be used as the data of
+    /// the freeform atom.
     fn write_mp4(&self, writer: impl io::Write) -> Result<usize, Error> {
         self.write_enveloped(writer)
     }
 }
+
+/// Compose the data of a freeform MP4 atom for the tag type `T` from its plain payload.
+///
+/// The payload is prefixed with the `application/octet-stream\0\0` header and the
+/// null-terminated tag name (i.e. [`T::NAME`](super::Tag::NAME), the same name that is expected
+/// when parsing), then base64-encoded. The result can be stored in the
+/// `----:com.serato.dj:<name>` atom, where `<name>` is
+/// [`T::MP4_ATOM_FREEFORM_NAME`](MP4Tag::MP4_ATOM_FREEFORM_NAME).
+///
+/// ```
+/// use triseratops::tag::{Analysis, format::mp4::{encode_atom_data, MP4Tag}};
+///
+/// let data = encode_atom_data::<Analysis>(&[0x02, 0x01]).unwrap();
+/// let analysis = Analysis::parse_mp4(&data).unwrap();
+/// assert_eq!(analysis.version.major, 2);
+/// ```
+pub fn encode_atom_data<T: MP4Tag>(payload: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut data = vec![];
+    enveloped::envelope_encode_with_name(&mut data, payload, T::NAME)?;
+    Ok(data)
+}
+
+#[cfg(feature = "mp4ameta")]
+use crate::tag::{TagContainer, TagFormat, TagKind};
+
+/// Returns the identifier of the freeform atom (`----:com.serato.dj:<name>`) for the tag name
+/// (see [`TagKind::tag_name()`]).
+#[cfg(feature = "mp4ameta")]
+fn freeform_ident(name: &'static str) -> ::mp4ameta::ident::FreeformIdentStatic {
+    ::mp4ameta::FreeformIdent::new_static(FREEFORM_MEAN, name)
+}
+
+/// Returns the data of the freeform atom for the tag name (if present).
+///
+/// Serato stores the data with the implicit (reserved) data type, but UTF-8 data is accepted too.
+#[cfg(feature = "mp4ameta")]
+fn freeform_data<'a>(tag: &'a ::mp4ameta::Tag, name: &'static str) -> Option<&'a [u8]> {
+    tag.data_of(&freeform_ident(name))
+        .find_map(|data| data.bytes().or_else(|| data.string().map(str::as_bytes)))
+}
+
+#[cfg(feature = "mp4ameta")]
+impl TagContainer {
+    /// Parse all Serato tags from the freeform atoms of an [`mp4ameta::Tag`](::mp4ameta::Tag).
+    ///
+    /// *Requires the `mp4ameta` feature.*
+    pub fn read_from_mp4_tag(tag: &::mp4ameta::Tag) -> Result<Self, Error> {
+        let mut container = Self::new();
+        for kind in TagKind::ALL.iter() {
+            let name = match kind.tag_name(TagFormat::MP4) {
+                Some(name) => name,
+                None => continue,
+            };
+            if let Some(data) = freeform_data(tag, name) {
+                container.parse_tag(name, data, TagFormat::MP4)?;
+            }
+        }
+        Ok(container)
+    }
+
+    /// Write all available Serato tags into freeform atoms of an
+    /// [`mp4ameta::Tag`](::mp4ameta::Tag).
+    ///
+    /// Existing atoms for the written tags are replaced, and atoms of tags that have been
+    /// [cleared](TagContainer::clear_tag) are removed. Atoms of other tags that are not available
+    /// in this container are left untouched, except for the marker tag that isn't written due to
+    /// the [`WriteStrategy`](crate::tag::WriteStrategy), which is removed as well.
+    ///
+    /// *Requires the `mp4ameta` feature.*
+    pub fn write_into_mp4_tag(&self, tag: &mut ::mp4ameta::Tag) -> Result<(), Error> {
+        let tags = self.serialize_tags(TagFormat::MP4)?;
+        for name in self
+            .removed_tags()
+            .into_iter()
+            .filter_map(|kind| kind.tag_name(TagFormat::MP4))
+        {
+            tag.remove_data_of(&freeform_ident(name));
+        }
+        for (name, data) in tags {
+            tag.set_data(freeform_ident(name), ::mp4ameta::Data::Reserved(data));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "mp4ameta")]
+#[test]
+fn test_mp4_tag_roundtrip() {
+    use crate::tag::Markers2;
+
+    let markers = include_bytes!("../../../tests/data/tags/markers/hotcues_and_loops.mp4.bin");
+    let markers2 = include_bytes!("../../../tests/data/tags/markers2/hotcues_and_loops.mp4.bin");
+
+    let mut tag = ::mp4ameta::Tag::default();
+    tag.set_title("Some Title");
+    tag.set_data(
+        freeform_ident("markers"),
+        ::mp4ameta::Data::Reserved(markers.to_vec()),
+    );
+    tag.set_data(
+        freeform_ident("markersv2"),
+        ::mp4ameta::Data::Utf8(String::from_utf8(markers2.to_vec()).unwrap()),
+    );
+
+    let container = TagContainer::read_from_mp4_tag(&tag).unwrap();
+    assert!(!container.cues().is_empty());
+    assert!(!container.loops().is_empty());
+
+    let mut tag = ::mp4ameta::Tag::default();
+    tag.set_title("Some Title");
+    container.write_into_mp4_tag(&mut tag).unwrap();
+    assert_eq!(tag.title(), Some("Some Title"));
+
+    // The written atoms can be parsed with the low-level parsers.
+    let ident = ::mp4ameta::FreeformIdent::new_static("com.serato.dj", "markersv2");
+    let data = tag.data_of(&ident).next().unwrap();
+    assert!(matches!(data, ::mp4ameta::Data::Reserved(_)));
+    let parsed = Markers2::parse_mp4(data.bytes().unwrap()).unwrap();
+    assert_eq!(parsed, Markers2::parse_mp4(markers2).unwrap());
+
+    let reparsed = TagContainer::read_from_mp4_tag(&tag).unwrap();
+    assert_eq!(reparsed.cues(), container.cues());
+    assert_eq!(reparsed.loops(), container.loops());
+    assert_eq!(reparsed.track_color(), container.track_color());
+}
+
+#[cfg(feature = "mp4ameta")]
+#[test]
+fn test_mp4_tag_clear() {
+    let markers = include_bytes!("../../../tests/data/tags/markers/hotcues_and_loops.mp4.bin");
+    let markers2 = include_bytes!("../../../tests/data/tags/markers2/hotcues_and_loops.mp4.bin");
+
+    let mut tag = ::mp4ameta::Tag::default();
+    tag.set_title("Some Title");
+    tag.set_data(
+        freeform_ident("markers"),
+        ::mp4ameta::Data::Reserved(markers.to_vec()),
+    );
+    tag.set_data(
+        freeform_ident("markersv2"),
+        ::mp4ameta::Data::Reserved(markers2.to_vec()),
+    );
+
+    let mut container = TagContainer::read_from_mp4_tag(&tag).unwrap();
+    container.clear_tag(crate::tag::TagKind::Markers);
+    container.write_into_mp4_tag(&mut tag).unwrap();
+    assert!(tag.data_of(&freeform_ident("markers")).next().is_none());
+    assert!(tag.data_of(&freeform_ident("markersv2")).next().is_some());
+
+    container.clear_serato_data();
+    container.write_into_mp4_tag(&mut tag).unwrap();
+    assert!(tag.data_of(&freeform_ident("markersv2")).next().is_none());
+    assert_eq!(tag.title(), Some("Some Title"));
+    assert!(TagContainer::read_from_mp4_tag(&tag)
+        .unwrap()
+        .serialize_tags(TagFormat::MP4)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn test_encode_atom_data() {
+    use super::Tag;
+    use crate::tag::{Beatgrid, Markers2};
+
+    let input = include_bytes!("../../../tests/data/tags/markers2/hotcues_and_loops.mp4.bin");
+    let markers2 = Markers2::parse_mp4(input).unwrap();
+    let mut payload = vec![];
+    markers2.write(&mut payload).unwrap();
+    let data = encode_atom_data::<Markers2>(&payload).unwrap();
+    assert!(data.starts_with(b"YXBwbGljYXRpb24vb2N0ZXQtc3RyZWFtAABTZXJhdG8gTWFya2VyczIA"));
+    assert_eq!(Markers2::parse_mp4(&data).unwrap(), markers2);
+
+    // The tag name in the envelope has to match the tag type.
+    assert!(matches!(
+        Beatgrid::parse_mp4(&data),
+        Err(Error::EnvelopeNameMismatch { .. })
+    ));
+}
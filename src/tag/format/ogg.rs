@@ -5,9 +5,24 @@ use crate::error::Error;
 use std::io;
 
 pub trait OggTag: Tag {
-    /// Name of the `MP4_ATOM` that this data is stored in.
+    /// Name of the Vorbis comment that this data is stored in.
     const OGG_COMMENT: &'static str;
 
+    /// Parse the value of the Vorbis comment.
+    ///
+    /// In contrast to [FLAC](super::flac::FLACTag) tags, the data is not wrapped in an envelope
+    /// with a MIME type header, so the format of the value differs from tag to tag.
     fn parse_ogg(input: &[u8]) -> Result<Self, Error>;
+
+    /// Parse the value of the Vorbis comment from a reader (see [`Tag::parse_from_reader`]).
+    ///
+    /// The format of the value differs from tag to tag, so it is read into memory first.
+    fn parse_ogg_from_reader(mut reader: impl io::Read) -> Result<Self, Error> {
+        let mut input = vec![];
+        reader.read_to_end(&mut input)?;
+        Self::parse_ogg(&input)
+    }
+
+    /// Write the tag data, ready to be used as the value of the Vorbis comment.
     fn write_ogg(&self, writer: impl io::Write) -> Result<usize, Error>;
 }
@@ -0,0 +1,200 @@
+//! WAV files
+//!
+//! Serato stores the tags of WAV files in an ID3v2 tag in the `id3 ` (or `ID3 `) chunk of the
+//! RIFF container. The tag has the same format as in MP3 files, so the tag data uses
+//! [`TagFormat::ID3`](crate::tag::TagFormat::ID3) and can be read with [`id3::scan`](super::id3::scan).
+
+use crate::error::Error;
+use crate::util::read_full;
+use std::io;
+use std::ops::Range;
+
+/// The identifiers of the chunks that contain the ID3 tag.
+const ID3_CHUNK_IDS: [&[u8; 4]; 2] = [b"id3 ", b"ID3 "];
+
+/// Find the `id3 ` chunk of a WAV file and return the position of its data in the file.
+///
+/// The file has to start at the current position of the reader (e.g. at the start of the file,
+/// or inside a larger stream). The returned range consists of positions of the reader, i.e. it
+/// can be passed to [`Seek::seek`](io::Seek::seek) in both cases.
+///
+/// Returns `None` if the file doesn't contain an ID3 chunk. The chunk doesn't have to be the last
+/// chunk of the file, and chunks that have been appended after the end of the RIFF data (as
+/// declared in the file header) are found as well.
+///
+/// ```no_run
+/// use std::fs::File;
+/// use std::io::{Read, Seek, SeekFrom};
+/// use triseratops::tag::format::{id3, riff};
+///
+/// let mut file = File::open("track.wav").unwrap();
+/// if let Some(range) = riff::find_id3_chunk(&mut file).unwrap() {
+///     file.seek(SeekFrom::Start(range.start)).unwrap();
+///     let frames = id3::scan(file.take(range.end - range.start)).unwrap();
+/// }
+/// ```
+pub fn find_id3_chunk(reader: impl io::Read + io::Seek) -> Result<Option<Range<u64>>, Error> {
+    find_chunk(
+        reader,
+        b"RIFF",
+        &[b"WAVE"],
+        &ID3_CHUNK_IDS,
+        u32::from_le_bytes,
+    )
+}
+
+/// Find a chunk in an IFF-style container (i.e. RIFF or AIFF) and return the position of its
+/// data.
+///
+/// The container starts at the current position of the reader with a 12 byte header consisting
+/// of the `form_id`, the size and the form type. The header is followed by chunks that consist
+/// of a 4 byte identifier, the size (decoded by `decode_size`) and the data, which is padded to
+/// an even length.
+pub(super) fn find_chunk(
+    mut reader: impl io::Read + io::Seek,
+    form_id: &[u8; 4],
+    form_types: &[&[u8; 4]],
+    chunk_ids: &[&[u8; 4]],
+    decode_size: fn([u8; 4]) -> u32,
+) -> Result<Option<Range<u64>>, Error> {
+    let container_start = reader.stream_position()?;
+    let mut header = [0; 12];
+    let length = read_full(&mut reader, &mut header)?;
+    if length < header.len()
+        || &header[..4] != form_id
+        || !form_types
+            .iter()
+            .any(|form_type| header[8..] == form_type[..])
+    {
+        return Err(Error::InvalidContainer {
+            reason: "missing container header",
+        });
+    }
+
+    // The size in the header is ignored, because some tools don't update it when appending a
+    // chunk to the file.
+    let mut position = container_start + header.len() as u64;
+    loop {
+        let mut chunk_header = [0; 8];
+        if read_full(&mut reader, &mut chunk_header)? < chunk_header.len() {
+            return Ok(None);
+        }
+        let size = u64::from(decode_size([
+            chunk_header[4],
+            chunk_header[5],
+            chunk_header[6],
+            chunk_header[7],
+        ]));
+        let start = position + 8;
+        if chunk_ids.iter().any(|id| chunk_header[..4] == id[..]) {
+            let end = start + size;
+            if end > reader.seek(io::SeekFrom::End(0))? {
+                return Err(Error::InvalidContainer {
+                    reason: "chunk is incomplete",
+                });
+            }
+            return Ok(Some(start..end));
+        }
+        position = start + size + size % 2;
+        reader.seek(io::SeekFrom::Start(position))?;
+    }
+}
+
+#[cfg(test)]
+pub(super) fn chunk(id: &[u8; 4], data: &[u8], encode_size: fn(u32) -> [u8; 4]) -> Vec<u8> {
+    let mut chunk = id.to_vec();
+    chunk.extend_from_slice(&encode_size(data.len() as u32));
+    chunk.extend_from_slice(data);
+    if data.len() % 2 != 0 {
+        chunk.push(0);
+    }
+    chunk
+}
+
+#[test]
+fn test_find_id3_chunk() {
+    use super::id3;
+    use crate::tag::{TagContainer, TagFormat, TagKind};
+
+    let mut tag = ::id3::Tag::new();
+    let analysis = include_bytes!("../../../tests/data/tags/analysis/analyzed.id3.bin");
+    tag.add_encapsulated_object(
+        "Serato Analysis",
+        "application/octet-stream",
+        "",
+        &analysis[..],
+    );
+    let mut id3_data = vec![];
+    ::id3::Encoder::new()
+        .version(::id3::Version::Id3v23)
+        .encode(&tag, &mut id3_data)
+        .unwrap();
+
+    let wav = |chunks: &[Vec<u8>]| {
+        let data = [&b"WAVE"[..], &chunks.concat()].concat();
+        chunk(b"RIFF", &data, u32::to_le_bytes)
+    };
+    let fmt = chunk(b"fmt ", &[0x01; 16], u32::to_le_bytes);
+    // An odd-sized chunk is followed by a padding byte
+    let data = chunk(b"data", &[0x02; 101], u32::to_le_bytes);
+    let list = chunk(
+        b"LIST",
+        b"INFOISFT\x03\x00\x00\x00ab\x00\x00",
+        u32::to_le_bytes,
+    );
+    let id3_chunk = chunk(b"id3 ", &id3_data, u32::to_le_bytes);
+
+    // The ID3 chunk is not the last chunk of the file
+    let file = wav(&[fmt.clone(), id3_chunk.clone(), data.clone(), list.clone()]);
+    let mut reader = io::Cursor::new(&file);
+    let range = find_id3_chunk(&mut reader).unwrap().unwrap();
+    assert_eq!(
+        range,
+        12 + fmt.len() as u64 + 8..12 + (fmt.len() + id3_chunk.len()) as u64
+    );
+    let frames = id3::scan(&file[range.start as usize..range.end as usize]).unwrap();
+    let mut container = TagContainer::new();
+    for frame in frames {
+        container
+            .parse_tag(&frame.description, &frame.data, TagFormat::ID3)
+            .unwrap();
+    }
+    assert!(container.has(TagKind::Analysis));
+
+    // The ID3 chunk has been appended after the end of the RIFF data
+    let file = [
+        wav(&[fmt.clone(), data.clone()]),
+        chunk(b"ID3 ", &id3_data, u32::to_le_bytes),
+    ]
+    .concat();
+    let range = find_id3_chunk(io::Cursor::new(&file)).unwrap().unwrap();
+    assert_eq!(
+        &file[range.start as usize..range.end as usize],
+        &id3_data[..]
+    );
+
+    // The file is embedded in a larger stream
+    let file = wav(&[fmt.clone(), id3_chunk.clone()]);
+    let stream = [&[0xAA; 7][..], &file].concat();
+    let mut reader = io::Cursor::new(&stream);
+    reader.set_position(7);
+    let range = find_id3_chunk(&mut reader).unwrap().unwrap();
+    assert_eq!(
+        &stream[range.start as usize..range.end as usize],
+        &id3_data[..]
+    );
+
+    let file = wav(&[fmt.clone(), data, list]);
+    assert!(find_id3_chunk(io::Cursor::new(&file)).unwrap().is_none());
+
+    let mut file = wav(&[fmt, id3_chunk]);
+    file.truncate(file.len() - 1);
+    assert!(matches!(
+        find_id3_chunk(io::Cursor::new(&file)),
+        Err(Error::InvalidContainer { .. })
+    ));
+    assert!(matches!(
+        find_id3_chunk(io::Cursor::new(b"RIFF\x04\x00\x00\x00AIFF")),
+        Err(Error::InvalidContainer { .. })
+    ));
+}
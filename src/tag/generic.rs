@@ -2,14 +2,16 @@
 use super::color::Color;
 
 /// Represents 2-Byte version value.
-#[derive(Debug, Eq, PartialEq)]
+///
+/// Versions are ordered by their major version first, then by their minor version.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
 pub struct Version {
     pub major: u8,
     pub minor: u8,
 }
 
 /// A [cue point](https://support.serato.com/hc/en-us/articles/360000067696-Cue-Points).
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Cue {
     pub index: u8,
     pub position_millis: u32,
@@ -18,7 +20,7 @@ pub struct Cue {
 }
 
 /// A [saved loops](https://serato.com/latest/blog/17885/pro-tip-trigger-saved-loops).
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Loop {
     pub index: u8,
     pub start_position_millis: u32,
@@ -29,7 +31,7 @@ pub struct Loop {
 }
 
 /// A [Serato Flip](https://serato.com/dj/pro/expansions/flip) performances.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Flip {
     pub index: u8,
     pub is_enabled: bool,
@@ -44,7 +46,7 @@ pub struct Flip {
 /// recording was stopped. If looping is enabled, it's target position is the source position of
 /// the first entry. If not, the target position of that last entry is the same as its source
 /// position.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum FlipAction {
     Censor(CensorFlipAction),
     Jump(JumpFlipAction),
@@ -56,7 +58,7 @@ pub enum FlipAction {
 /// Actions of this type are used for censoring (playback speed factor is -1.0) and are followed
 /// with a jump marker from `end_position_seconds` to the playback position that the track would be
 /// at without the reverse playback.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct CensorFlipAction {
     /// The start position of the censoring.
     ///
@@ -71,7 +73,7 @@ pub struct CensorFlipAction {
 }
 
 /// A "Jump" action inside of a [`Flip`](Flip) performance.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct JumpFlipAction {
     /// The source position of the jump.
     ///
@@ -83,7 +85,7 @@ pub struct JumpFlipAction {
 }
 
 /// A unknown action inside of a [`Flip`](Flip) performance that we don't have a parser for.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct UnknownFlipAction {
     pub id: u8,
     pub data: Vec<u8>,
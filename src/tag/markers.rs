@@ -6,17 +6,20 @@
 
 use super::color::Color;
 use super::format::{enveloped, id3, mp4, Tag};
-use super::generic::Version;
+use super::generic::{Cue, Loop, Version};
 use super::serato32;
-use super::util::{take_color, take_version, write_color, write_version};
+use super::util::{
+    retain_range_millis, shift_position_millis, shift_range_millis, take_color, take_padding,
+    take_version, write_color, write_padding, write_version,
+};
 use crate::error::Error;
 use crate::util::Res;
 use nom::error::ParseError;
 use std::io;
-use std::io::Cursor;
+use std::io::{Cursor, Read};
 
 /// Represents a single marker in the `Serato Markers_` tag.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Marker {
     /// The position of the loop or cue.
     pub start_position_millis: Option<u32>,
@@ -57,9 +60,12 @@ pub struct Marker {
 ///     println!("{:?}", content);
 /// }
 /// ```
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Markers {
     /// The tag version.
+    ///
+    /// The version is written back unchanged. Set it to [`LATEST_MARKERS_VERSION`] to upgrade the
+    /// tag explicitly.
     pub version: Version,
 
     /// The marker entries.
@@ -67,9 +73,285 @@ pub struct Markers {
 
     /// The color of the track in Serato's library view.
     pub track_color: Color,
+
+    /// The number of null bytes that the tag data was padded with (written back unchanged).
+    pub padding: usize,
+
+    /// The undecoded data after the version header of version 1 tags (i.e. `0x0105`).
+    ///
+    /// Very old Serato versions (e.g. ITCH) wrote tags with a different entry layout, which
+    /// hasn't been reverse-engineered yet because no sample files are available. These tags are
+    /// parsed without any entries, padding or track color, and their data is written back
+    /// unchanged. To replace such a tag, create a new one (e.g. using
+    /// [`from_cues_and_loops()`](Self::from_cues_and_loops)).
+    pub legacy_data: Option<Vec<u8>>,
+}
+
+/// The `Serato Markers_` version written by Serato DJ (in both ID3 and MP4 tags).
+pub const LATEST_MARKERS_VERSION: Version = Version { major: 2, minor: 5 };
+
+/// The number of cue entries in the `Serato Markers_` tag.
+pub const NUM_CUES: usize = 5;
+
+/// The number of loop entries in the `Serato Markers_` tag.
+pub const NUM_LOOPS: usize = 9;
+
+/// The color that Serato uses for all saved loops in the `Serato Markers_` tag.
+const LOOP_COLOR: Color = Color {
+    red: 0x27,
+    green: 0xAA,
+    blue: 0xE1,
+};
+
+impl Marker {
+    /// Returns the entry for a set cue slot.
+    fn set_cue(cue: &Cue) -> Self {
+        Self {
+            start_position_millis: Some(cue.position_millis),
+            end_position_millis: None,
+            color: cue.color,
+            marker_type: MarkerType::Cue,
+            is_locked: false,
+        }
+    }
+
+    /// Returns the placeholder entry for an unset cue slot.
+    fn unset_cue() -> Self {
+        Self {
+            start_position_millis: None,
+            end_position_millis: None,
+            color: Color::from(0x000000),
+            marker_type: MarkerType::Invalid,
+            is_locked: false,
+        }
+    }
+
+    /// Returns the entry for a set loop slot.
+    fn set_loop(saved_loop: &Loop) -> Self {
+        Self {
+            start_position_millis: Some(saved_loop.start_position_millis),
+            end_position_millis: Some(saved_loop.end_position_millis),
+            color: LOOP_COLOR,
+            marker_type: MarkerType::Loop,
+            is_locked: saved_loop.is_locked,
+        }
+    }
+
+    /// Returns the placeholder entry for an unset loop slot.
+    fn unset_loop() -> Self {
+        Self {
+            start_position_millis: None,
+            end_position_millis: None,
+            color: Color::from(0x000000),
+            marker_type: MarkerType::Loop,
+            is_locked: false,
+        }
+    }
 }
 
 impl Markers {
+    /// Create a `Serato Markers_` tag from cues and loops.
+    ///
+    /// The tag always contains exactly 14 entries in the layout that Serato uses: 5 cue slots
+    /// followed by 9 loop slots. Unused slots are filled with placeholder entries. Loops are
+    /// always written with Serato's default loop color, because the tag can't store other loop
+    /// colors.
+    ///
+    /// Since this tag can only hold the first 5 cues and the first 9 loops, cues with an index
+    /// larger than 4 or loops with an index larger than 8 result in an
+    /// [`InvalidMarkerIndex`](Error::InvalidMarkerIndex) error. Duplicate indices result in a
+    /// [`DuplicateMarkerIndex`](Error::DuplicateMarkerIndex) error.
+    /// Filter these out first if you're converting data from the `Serato Markers2` tag.
+    pub fn from_cues_and_loops(
+        cues: &[Cue],
+        loops: &[Loop],
+        track_color: Color,
+    ) -> Result<Self, Error> {
+        let mut cue_entries: Vec<Option<Marker>> = (0..NUM_CUES).map(|_| None).collect();
+        for cue in cues {
+            let slot = cue_entries
+                .get_mut(usize::from(cue.index))
+                .ok_or(Error::InvalidMarkerIndex { index: cue.index })?;
+            if slot.is_some() {
+                return Err(Error::DuplicateMarkerIndex { index: cue.index });
+            }
+            *slot = Some(Marker::set_cue(cue));
+        }
+
+        let mut loop_entries: Vec<Option<Marker>> = (0..NUM_LOOPS).map(|_| None).collect();
+        for saved_loop in loops {
+            let slot = loop_entries.get_mut(usize::from(saved_loop.index)).ok_or(
+                Error::InvalidMarkerIndex {
+                    index: saved_loop.index,
+                },
+            )?;
+            if slot.is_some() {
+                return Err(Error::DuplicateMarkerIndex {
+                    index: saved_loop.index,
+                });
+            }
+            *slot = Some(Marker::set_loop(saved_loop));
+        }
+
+        let entries = cue_entries
+            .into_iter()
+            .map(|entry| entry.unwrap_or_else(Marker::unset_cue))
+            .chain(
+                loop_entries
+                    .into_iter()
+                    .map(|entry| entry.unwrap_or_else(Marker::unset_loop)),
+            )
+            .collect();
+
+        Ok(Self {
+            version: LATEST_MARKERS_VERSION,
+            entries,
+            track_color,
+            padding: 0,
+            legacy_data: None,
+        })
+    }
+
+    /// Update the cue slots from the given cues.
+    ///
+    /// Slots without a corresponding cue are cleared. Cues with an index that doesn't have a slot
+    /// in this tag (usually 5 or larger) are ignored, because they can only be stored in the
+    /// `Serato Markers2` tag. The cue label can't be stored in this tag either.
+    pub fn set_cues(&mut self, cues: &[Cue]) {
+        let mut index: u8 = 0;
+        for marker in &mut self.entries {
+            if marker.marker_type != MarkerType::Invalid && marker.marker_type != MarkerType::Cue {
+                continue;
+            }
+
+            *marker = match cues.iter().find(|cue| cue.index == index) {
+                Some(cue) => Marker::set_cue(cue),
+                None => Marker::unset_cue(),
+            };
+            index += 1;
+        }
+    }
+
+    /// Update the loop slots from the given loops.
+    ///
+    /// Slots without a corresponding loop are cleared. Loops are always stored with Serato's
+    /// default loop color and without a label, because this tag can't store either of them.
+    ///
+    /// Returns the indices of all loops that don't have a slot in this tag and therefore could
+    /// not be stored.
+    pub fn set_loops(&mut self, loops: &[Loop]) -> Vec<u8> {
+        let mut index: u8 = 0;
+        for marker in &mut self.entries {
+            if marker.marker_type != MarkerType::Loop {
+                continue;
+            }
+
+            *marker = match loops.iter().find(|saved_loop| saved_loop.index == index) {
+                Some(saved_loop) => Marker::set_loop(saved_loop),
+                None => Marker::unset_loop(),
+            };
+            index += 1;
+        }
+
+        loops
+            .iter()
+            .map(|saved_loop| saved_loop.index)
+            .filter(|&i| i >= index)
+            .collect()
+    }
+
+    /// Shift the positions of all cues and loops by the given offset.
+    ///
+    /// Cues that would end up at a negative position and loops that would end up entirely before
+    /// the start of the track are unset. Loops that would start before the start of the track are
+    /// truncated.
+    pub fn shift_positions(&mut self, offset_millis: i32) {
+        for marker in &mut self.entries {
+            let start_position_millis = marker.start_position_millis;
+            let end_position_millis = marker.end_position_millis;
+            match marker.marker_type {
+                MarkerType::Cue => {
+                    let position_millis = start_position_millis
+                        .and_then(|position| shift_position_millis(position, offset_millis));
+                    match position_millis {
+                        Some(_) => marker.start_position_millis = position_millis,
+                        None => *marker = Marker::unset_cue(),
+                    }
+                }
+                MarkerType::Loop => {
+                    let (start, end) = match (start_position_millis, end_position_millis) {
+                        (Some(start), Some(end)) => (start, end),
+                        _ => continue,
+                    };
+                    match shift_range_millis(start, end, offset_millis) {
+                        Some((start, end)) => {
+                            marker.start_position_millis = Some(start);
+                            marker.end_position_millis = Some(end);
+                        }
+                        None => *marker = Marker::unset_loop(),
+                    }
+                }
+                MarkerType::Invalid | MarkerType::Unknown(_) => {}
+            }
+        }
+    }
+
+    /// Move the positions of all cues and loops using the given function (e.g. to snap them to the
+    /// beatgrid).
+    ///
+    /// Loops are left unchanged if their end position wouldn't be after the start position
+    /// anymore.
+    pub fn map_positions(&mut self, f: impl Fn(u32) -> u32) {
+        for marker in &mut self.entries {
+            let start_position_millis = marker.start_position_millis;
+            let end_position_millis = marker.end_position_millis;
+            match marker.marker_type {
+                MarkerType::Cue => marker.start_position_millis = start_position_millis.map(&f),
+                MarkerType::Loop => {
+                    let (start, end) = match (start_position_millis, end_position_millis) {
+                        (Some(start), Some(end)) => (f(start), f(end)),
+                        _ => continue,
+                    };
+                    if end > start {
+                        marker.start_position_millis = Some(start);
+                        marker.end_position_millis = Some(end);
+                    }
+                }
+                MarkerType::Invalid | MarkerType::Unknown(_) => {}
+            }
+        }
+    }
+
+    /// Unset all cues and loops that are positioned after the end of the track.
+    ///
+    /// Positions up to `tolerance_millis` after the end of the track are accepted. Loops that
+    /// start before the end of the track but end after it are truncated.
+    pub fn retain_within_duration(&mut self, duration_millis: u32, tolerance_millis: u32) {
+        let max_position_millis = duration_millis.saturating_add(tolerance_millis);
+        for marker in &mut self.entries {
+            let start_position_millis = marker.start_position_millis;
+            let end_position_millis = marker.end_position_millis;
+            match marker.marker_type {
+                MarkerType::Cue => {
+                    if start_position_millis.is_some_and(|pos| pos > max_position_millis) {
+                        *marker = Marker::unset_cue();
+                    }
+                }
+                MarkerType::Loop => {
+                    let (start, end) = match (start_position_millis, end_position_millis) {
+                        (Some(start), Some(end)) => (start, end),
+                        _ => continue,
+                    };
+                    match retain_range_millis(start, end, duration_millis, tolerance_millis) {
+                        Some((_, end)) => marker.end_position_millis = Some(end),
+                        None => *marker = Marker::unset_loop(),
+                    }
+                }
+                MarkerType::Invalid | MarkerType::Unknown(_) => {}
+            }
+        }
+    }
+
     pub fn cues(&self) -> Vec<(u8, &Marker)> {
         let mut index: u8 = 0;
         let mut cues = Vec::new();
@@ -98,6 +380,26 @@ impl Markers {
         loops
     }
 
+    /// Returns the cue slot with the given index (i.e. the same marker that [`cues()`](Self::cues)
+    /// returns for that index).
+    pub fn cue(&self, index: u8) -> Option<&Marker> {
+        self.entries
+            .iter()
+            .filter(|marker| {
+                marker.marker_type == MarkerType::Invalid || marker.marker_type == MarkerType::Cue
+            })
+            .nth(index.into())
+    }
+
+    /// Returns the loop slot with the given index (i.e. the same marker that
+    /// [`loops()`](Self::loops) returns for that index).
+    pub fn saved_loop(&self, index: u8) -> Option<&Marker> {
+        self.entries
+            .iter()
+            .filter(|marker| marker.marker_type == MarkerType::Loop)
+            .nth(index.into())
+    }
+
     pub fn track_color(&self) -> Color {
         self.track_color
     }
@@ -112,7 +414,7 @@ impl Tag for Markers {
     }
 
     fn write(&self, writer: impl io::Write) -> Result<usize, Error> {
-        write_markers(writer, &self)
+        write_markers(writer, self)
     }
 }
 
@@ -130,16 +432,24 @@ impl mp4::MP4Tag for Markers {
         Ok(markers)
     }
 
+    fn parse_mp4_from_reader(reader: impl io::Read) -> Result<Self, Error> {
+        let mut content = vec![];
+        enveloped::decode_enveloped_from_reader(reader, Self::NAME)?.read_to_end(&mut content)?;
+        let (_, markers) = nom::combinator::all_consuming(take_markers_mp4)(content.as_slice())?;
+        Ok(markers)
+    }
+
     fn write_mp4(&self, writer: impl io::Write) -> Result<usize, Error> {
         let mut buffer = Cursor::new(vec![]);
-        write_markers_mp4(&mut buffer, &self)?;
+        write_markers_mp4(&mut buffer, self)?;
         let plain_data = &buffer.get_ref()[..];
         enveloped::envelope_encode_with_name(writer, plain_data, Self::NAME)
     }
 }
 
 /// Type of a Marker.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
 pub enum MarkerType {
     /// Used for unset cues.
     ///
@@ -155,6 +465,12 @@ pub enum MarkerType {
     ///
     /// In the binary format, this is represented by `0x03`.
     Loop,
+    /// An entry with an unknown type byte.
+    ///
+    /// Such entries are neither cue nor loop slots, so they are skipped by
+    /// [`Markers::cues()`](Markers::cues) and [`Markers::loops()`](Markers::loops), but they are
+    /// written back with their original type byte.
+    Unknown(u8),
 }
 
 /// Returns a bool parsed from the next input byte.
@@ -256,10 +572,7 @@ fn take_marker_type(input: &[u8]) -> Res<&[u8], MarkerType> {
         0x00 => Ok((next_input, MarkerType::Invalid)),
         0x01 => Ok((next_input, MarkerType::Cue)),
         0x03 => Ok((next_input, MarkerType::Loop)),
-        _ => Err(nom::Err::Error(nom::error::VerboseError::from_error_kind(
-            input,
-            nom::error::ErrorKind::Tag,
-        ))),
+        byte => Ok((next_input, MarkerType::Unknown(byte))),
     }
 }
 
@@ -273,7 +586,11 @@ fn test_take_marker_type() {
         take_marker_type(&[0x03, 0x01]),
         Ok((&[0x01][..], MarkerType::Loop))
     );
-    assert!(take_marker_type(&[0xAB]).is_err());
+    assert_eq!(
+        take_marker_type(&[0xAB]),
+        Ok((&[][..], MarkerType::Unknown(0xAB)))
+    );
+    assert!(take_marker_type(&[]).is_err());
 }
 
 /// Returns a `Marker` parsed from the input slice.
@@ -301,22 +618,85 @@ fn take_marker(input: &[u8]) -> Res<&[u8], Marker> {
     ))
 }
 
+/// Returns the tag version if tags of that version can be parsed.
+///
+/// The entries of version 2 tags (i.e. `0x0205`) are parsed. Version 1 tags (i.e. `0x0105`) use a
+/// different entry layout, which is not known yet, so their data is kept as is (see
+/// [`Markers::legacy_data`]). Other versions are rejected instead of being misinterpreted.
+fn take_supported_version(input: &[u8]) -> Res<&[u8], Version> {
+    nom::error::context(
+        "supported markers version",
+        nom::combinator::verify(take_version, |version: &Version| {
+            version.major == 1 || version.major == 2
+        }),
+    )(input)
+}
+
+/// Parses the undecoded data of a version 1 tag (see [`Markers::legacy_data`]).
+fn take_legacy_markers(input: &[u8], version: Version) -> Res<&[u8], Markers> {
+    let (input, data) = nom::combinator::rest(input)?;
+    let markers = Markers {
+        version,
+        entries: vec![],
+        track_color: Color::from(0x000000),
+        padding: 0,
+        legacy_data: Some(data.to_vec()),
+    };
+    Ok((input, markers))
+}
+
+/// Returns the undecoded data of a version 1 tag, or `None` for a version 2 tag.
+///
+/// Fails for all other versions, for version 1 tags without undecoded data or with entries and
+/// for version 2 tags with undecoded data, since these can't be written in a way that the parser
+/// understands.
+fn legacy_data(markers: &Markers) -> Result<Option<&[u8]>, Error> {
+    match (markers.version.major, &markers.legacy_data) {
+        (1, Some(data)) if markers.entries.is_empty() => Ok(Some(data)),
+        (2, None) => Ok(None),
+        _ => Err(Error::UnsupportedMarkersVersion {
+            major: markers.version.major,
+            minor: markers.version.minor,
+        }),
+    }
+}
+
 /// Parses the data into a `Markers` struct, consuming the whole input slice.
 fn take_markers(input: &[u8]) -> Res<&[u8], Markers> {
-    let (input, version) = take_version(&input)?;
+    let (input, version) = take_supported_version(input)?;
+    if version.major == 1 {
+        return take_legacy_markers(input, version);
+    }
     let (input, entries) =
         nom::multi::length_count(nom::number::complete::be_u32, take_marker)(input)?;
-    let (input, track_color) = nom::combinator::all_consuming(serato32::take_color)(input)?;
+    let (input, track_color) = serato32::take_color(input)?;
+    let (input, padding) = nom::combinator::all_consuming(take_padding)(input)?;
 
     let markers = Markers {
         version,
         entries,
         track_color,
+        padding,
+        legacy_data: None,
     };
     Ok((input, markers))
 }
 
 /// Returns a `Marker` parsed from the input slice (MP4 version).
+///
+/// The MP4 entries are 19 bytes long and don't use the `serato32` encoding of the ID3 version:
+///
+/// | Offset | Length | Description
+/// | ------ | ------ | -----------
+/// |   `00` |      4 | Start position (big-endian `u32`, `0xFFFFFFFF` if unset)
+/// |   `04` |      4 | End position (big-endian `u32`, `0xFFFFFFFF` if unset)
+/// |   `08` |      6 | Unknown (always `00 FF FF FF FF 00`)
+/// |   `0E` |      3 | Color (plain RGB)
+/// |   `11` |      1 | Marker type
+/// |   `12` |      1 | Locked state
+///
+/// The colors are the ones stored in the metadata, which Serato DJ Pro displays slightly
+/// differently (see [`Color::into_pro_hotcue_color`]).
 fn take_marker_mp4(input: &[u8]) -> Res<&[u8], Marker> {
     let (input, start_position_millis_raw) =
         nom::error::context("marker start position", nom::number::complete::be_u32)(input)?;
@@ -333,12 +713,13 @@ fn take_marker_mp4(input: &[u8]) -> Res<&[u8], Marker> {
     } else {
         None
     };
-    let end_position_millis =
-        if end_position_millis_raw != 0xFFFFFFFF && marker_type == MarkerType::Loop {
-            Some(end_position_millis_raw)
-        } else {
-            None
-        };
+    let end_position_millis = if end_position_millis_raw != 0xFFFFFFFF
+        && matches!(marker_type, MarkerType::Loop | MarkerType::Unknown(_))
+    {
+        Some(end_position_millis_raw)
+    } else {
+        None
+    };
 
     Ok((
         input,
@@ -354,16 +735,22 @@ fn take_marker_mp4(input: &[u8]) -> Res<&[u8], Marker> {
 
 /// Parses the data into a `Markers` struct, consuming the whole input slice (MP4 version).
 fn take_markers_mp4(input: &[u8]) -> Res<&[u8], Markers> {
-    let (input, version) = take_version(&input)?;
+    let (input, version) = take_supported_version(input)?;
+    if version.major == 1 {
+        return take_legacy_markers(input, version);
+    }
     let (input, entries) =
         nom::multi::length_count(nom::number::complete::be_u32, take_marker_mp4)(input)?;
     let (input, _) = nom::bytes::complete::tag(b"\0")(input)?;
-    let (input, track_color) = nom::combinator::all_consuming(take_color)(input)?;
+    let (input, track_color) = take_color(input)?;
+    let (input, padding) = nom::combinator::all_consuming(take_padding)(input)?;
 
     let markers = Markers {
         version,
         entries,
         track_color,
+        padding,
+        legacy_data: None,
     };
     Ok((input, markers))
 }
@@ -379,8 +766,11 @@ fn write_position(mut writer: impl io::Write, position: &Option<u32>) -> Result<
     }
 }
 
+/// Serialize a marker position to 4 bytes ([MP4](super::format::mp4) version).
+///
+/// In contrast to the ID3 version, the position is stored as plain big-endian `u32`, and unset
+/// positions are written as `0xFFFFFFFF`.
 fn write_position_mp4(mut writer: impl io::Write, position: &Option<u32>) -> Result<usize, Error> {
-    // TODO: Implement this
     let data = match position {
         Some(millis) => millis.to_be_bytes(),
         None => *b"\xFF\xFF\xFF\xFF",
@@ -393,6 +783,7 @@ fn write_marker_type(mut writer: impl io::Write, marker_type: &MarkerType) -> Re
         MarkerType::Invalid => 0x00,
         MarkerType::Cue => 0x01,
         MarkerType::Loop => 0x03,
+        MarkerType::Unknown(byte) => *byte,
     };
     Ok(writer.write(&[byte])?)
 }
@@ -405,6 +796,10 @@ fn write_bool(mut writer: impl io::Write, value: bool) -> Result<usize, Error> {
     Ok(writer.write(&[byte])?)
 }
 
+/// Serialize a single [`Marker`](Marker) entry to 22 bytes.
+///
+/// Positions and the color are stored in the `serato32` format, unset positions are written as
+/// `0x7F7F7F7F7F`.
 fn write_marker(mut writer: impl io::Write, marker: &Marker) -> Result<usize, Error> {
     let mut bytes_written = write_position(&mut writer, &marker.start_position_millis)?;
     bytes_written += write_position(&mut writer, &marker.end_position_millis)?;
@@ -415,6 +810,7 @@ fn write_marker(mut writer: impl io::Write, marker: &Marker) -> Result<usize, Er
     Ok(bytes_written)
 }
 
+/// Serialize a single [`Marker`](Marker) entry to 19 bytes ([MP4](super::format::mp4) version).
 fn write_marker_mp4(mut writer: impl io::Write, marker: &Marker) -> Result<usize, Error> {
     let mut bytes_written = write_position_mp4(&mut writer, &marker.start_position_millis)?;
     bytes_written += write_position_mp4(&mut writer, &marker.end_position_millis)?;
@@ -425,25 +821,367 @@ fn write_marker_mp4(mut writer: impl io::Write, marker: &Marker) -> Result<usize
     Ok(bytes_written)
 }
 
+/// Serialize the [`Markers` struct](Markers) to bytes.
+///
+/// The data consists of the version header, the number of entries (Serato always writes 14
+/// entries), the entries themselves and the `serato32`-encoded track color. The undecoded data of
+/// version 1 tags is written back as is. Returns an
+/// [`UnsupportedMarkersVersion`](Error::UnsupportedMarkersVersion) error for all versions that
+/// can't be parsed.
 pub fn write_markers(mut writer: impl io::Write, markers: &Markers) -> Result<usize, Error> {
+    let legacy_data = legacy_data(markers)?;
     let mut bytes_written = write_version(&mut writer, &markers.version)?;
+    if let Some(data) = legacy_data {
+        bytes_written += writer.write(data)?;
+        return Ok(bytes_written);
+    }
     let num_markers = markers.entries.len() as u32;
     bytes_written += writer.write(&num_markers.to_be_bytes())?;
     for marker in &markers.entries {
-        bytes_written += write_marker(&mut writer, &marker)?;
+        bytes_written += write_marker(&mut writer, marker)?;
     }
-    bytes_written += serato32::write_color(writer, &markers.track_color)?;
+    bytes_written += serato32::write_color(&mut writer, &markers.track_color)?;
+    bytes_written += write_padding(writer, markers.padding)?;
     Ok(bytes_written)
 }
 
+/// Serialize the [`Markers` struct](Markers) to bytes ([MP4](super::format::mp4) version).
+///
+/// Version 1 tags and unsupported versions are handled like in [`write_markers()`].
 pub fn write_markers_mp4(mut writer: impl io::Write, markers: &Markers) -> Result<usize, Error> {
+    let legacy_data = legacy_data(markers)?;
     let mut bytes_written = write_version(&mut writer, &markers.version)?;
+    if let Some(data) = legacy_data {
+        bytes_written += writer.write(data)?;
+        return Ok(bytes_written);
+    }
     let num_markers = markers.entries.len() as u32;
     bytes_written += writer.write(&num_markers.to_be_bytes())?;
     for marker in &markers.entries {
-        bytes_written += write_marker_mp4(&mut writer, &marker)?;
+        bytes_written += write_marker_mp4(&mut writer, marker)?;
     }
     bytes_written += writer.write(b"\x00")?;
-    bytes_written += write_color(writer, &markers.track_color)?;
+    bytes_written += write_color(&mut writer, &markers.track_color)?;
+    bytes_written += write_padding(writer, markers.padding)?;
     Ok(bytes_written)
 }
+
+#[test]
+fn test_write_markers() {
+    let markers = Markers {
+        version: Version { major: 2, minor: 5 },
+        entries: vec![
+            Marker {
+                start_position_millis: Some(1000),
+                end_position_millis: None,
+                color: Color::from(0xCC0000),
+                marker_type: MarkerType::Cue,
+                is_locked: false,
+            },
+            Marker {
+                start_position_millis: None,
+                end_position_millis: None,
+                color: Color::from(0x000000),
+                marker_type: MarkerType::Invalid,
+                is_locked: false,
+            },
+        ],
+        track_color: Color::from(0xFF99FF),
+        padding: 0,
+        legacy_data: None,
+    };
+
+    let mut writer = Cursor::new(vec![]);
+    let bytes_written = write_markers(&mut writer, &markers).unwrap();
+    let output = writer.get_ref().as_slice();
+    assert_eq!(bytes_written, 2 + 4 + 2 * 22 + 4);
+    assert_eq!(
+        output,
+        &[
+            0x02, 0x05, 0x00, 0x00, 0x00, 0x02, // Header
+            0x00, 0x00, 0x00, 0x07, 0x68, 0x7F, 0x7F, 0x7F, 0x7F, 0x7F, 0x00, 0x7F, 0x7F, 0x7F,
+            0x7F, 0x7F, 0x06, 0x30, 0x00, 0x00, 0x01, 0x00, // Cue
+            0x7F, 0x7F, 0x7F, 0x7F, 0x7F, 0x7F, 0x7F, 0x7F, 0x7F, 0x7F, 0x00, 0x7F, 0x7F, 0x7F,
+            0x7F, 0x7F, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Unset Cue
+            0x07, 0x7E, 0x33, 0x7F, // Track color
+        ][..]
+    );
+    assert_eq!(Markers::parse(output).unwrap(), markers);
+}
+
+#[test]
+fn test_write_markers_mp4() {
+    let markers = Markers {
+        version: Version { major: 2, minor: 5 },
+        entries: vec![
+            Marker {
+                start_position_millis: Some(0x0001_E240),
+                end_position_millis: None,
+                color: Color::from(0xCC8800),
+                marker_type: MarkerType::Cue,
+                is_locked: false,
+            },
+            Marker {
+                start_position_millis: Some(2000),
+                end_position_millis: Some(4000),
+                color: Color::from(0x27AAE1),
+                marker_type: MarkerType::Loop,
+                is_locked: true,
+            },
+        ],
+        track_color: Color::from(0x993333),
+        padding: 0,
+        legacy_data: None,
+    };
+
+    let mut writer = Cursor::new(vec![]);
+    let bytes_written = write_markers_mp4(&mut writer, &markers).unwrap();
+    let output = writer.get_ref().as_slice();
+    assert_eq!(bytes_written, 2 + 4 + 2 * 19 + 4);
+    assert_eq!(
+        output,
+        &[
+            0x02, 0x05, 0x00, 0x00, 0x00, 0x02, // Header
+            0x00, 0x01, 0xE2, 0x40, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0x00,
+            0xCC, 0x88, 0x00, 0x01, 0x00, // Cue
+            0x00, 0x00, 0x07, 0xD0, 0x00, 0x00, 0x0F, 0xA0, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0x00,
+            0x27, 0xAA, 0xE1, 0x03, 0x01, // Loop
+            0x00, 0x99, 0x33, 0x33, // Track color
+        ][..]
+    );
+
+    // Writing the same data as MP4 and ID3 tag needs to result in the same values.
+    use mp4::MP4Tag;
+    let mut writer = Cursor::new(vec![]);
+    markers.write_mp4(&mut writer).unwrap();
+    let mp4_markers = Markers::parse_mp4(writer.get_ref().as_slice()).unwrap();
+
+    use self::id3::ID3Tag;
+    let mut writer = Cursor::new(vec![]);
+    markers.write_id3(&mut writer).unwrap();
+    let id3_markers = Markers::parse_id3(writer.get_ref().as_slice()).unwrap();
+
+    assert_eq!(mp4_markers, markers);
+    assert_eq!(id3_markers, mp4_markers);
+}
+
+#[test]
+fn test_from_cues_and_loops() {
+    use super::TagContainer;
+    use super::TagFormat;
+
+    let fixtures: [&[u8]; 4] = [
+        include_bytes!("../../tests/data/tags/markers/analyzed.id3.bin"),
+        include_bytes!("../../tests/data/tags/markers/hotcue_colors.id3.bin"),
+        include_bytes!("../../tests/data/tags/markers/saved_loops.id3.bin"),
+        include_bytes!("../../tests/data/tags/markers/tracklist_color.id3.bin"),
+    ];
+    for input in fixtures.iter() {
+        let mut container = TagContainer::new();
+        container.parse_markers(input, TagFormat::ID3).unwrap();
+        let markers = Markers::from_cues_and_loops(
+            &container.cues(),
+            &container.loops(),
+            container.track_color().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(markers.entries.len(), NUM_CUES + NUM_LOOPS);
+
+        let mut writer = Cursor::new(vec![]);
+        write_markers(&mut writer, &markers).unwrap();
+        assert_eq!(writer.get_ref().as_slice(), *input);
+    }
+
+    let cue = Cue {
+        index: 5,
+        position_millis: 1000,
+        color: Color::from(0xCC0000),
+        label: String::new(),
+    };
+    assert!(matches!(
+        Markers::from_cues_and_loops(std::slice::from_ref(&cue), &[], Color::from(0xFFFFFF)),
+        Err(Error::InvalidMarkerIndex { index: 5 })
+    ));
+    let cue = Cue { index: 4, ..cue };
+    assert!(matches!(
+        Markers::from_cues_and_loops(&[cue.clone(), cue], &[], Color::from(0xFFFFFF)),
+        Err(Error::DuplicateMarkerIndex { index: 4 })
+    ));
+
+    let saved_loop = Loop {
+        index: 9,
+        start_position_millis: 0,
+        end_position_millis: 1000,
+        color: LOOP_COLOR,
+        is_locked: false,
+        label: String::new(),
+    };
+    assert!(matches!(
+        Markers::from_cues_and_loops(&[], &[saved_loop], Color::from(0xFFFFFF)),
+        Err(Error::InvalidMarkerIndex { index: 9 })
+    ));
+}
+
+#[test]
+fn test_markers_version() {
+    let input = include_bytes!("../../tests/data/tags/markers/hotcues_with_names.id3.bin");
+    let mut markers = Markers::parse(input).unwrap();
+    assert_eq!(markers.version, LATEST_MARKERS_VERSION);
+
+    // Other version 2 tags are written back with their original version.
+    let mut data = input.to_vec();
+    data[1] = 0x06;
+    let mut markers_v2_6 = Markers::parse(&data).unwrap();
+    assert_eq!(markers_v2_6.version, Version { major: 2, minor: 6 });
+    markers_v2_6.set_cues(&[]);
+    let mut writer = Cursor::new(vec![]);
+    markers_v2_6.write(&mut writer).unwrap();
+    assert_eq!(writer.get_ref()[..2], [0x02, 0x06]);
+
+    // The entry layout of version 1 tags is unknown, so their data is preserved as is.
+    data[0] = 0x01;
+    data[1] = 0x05;
+    let markers_v1 = Markers::parse(&data).unwrap();
+    assert_eq!(markers_v1.version, Version { major: 1, minor: 5 });
+    assert!(markers_v1.entries.is_empty());
+    assert_eq!(markers_v1.legacy_data.as_deref(), Some(&data[2..]));
+    let mut writer = Cursor::new(vec![]);
+    markers_v1.write(&mut writer).unwrap();
+    assert_eq!(writer.get_ref(), &data);
+
+    let mut writer = Cursor::new(vec![]);
+    write_markers_mp4(&mut writer, &markers_v1).unwrap();
+    let (_, reparsed) = take_markers_mp4(writer.get_ref()).unwrap();
+    assert_eq!(reparsed, markers_v1);
+
+    // Other versions can neither be parsed nor written.
+    data[0] = 0x03;
+    assert!(Markers::parse(&data).is_err());
+    markers.version = Version { major: 3, minor: 5 };
+    assert!(matches!(
+        write_markers(Cursor::new(vec![]), &markers),
+        Err(Error::UnsupportedMarkersVersion { major: 3, minor: 5 })
+    ));
+    assert!(matches!(
+        write_markers_mp4(Cursor::new(vec![]), &markers),
+        Err(Error::UnsupportedMarkersVersion { major: 3, minor: 5 })
+    ));
+
+    // Version 1 tags can't be written with entries, and upgrading requires new entries.
+    markers.version = Version { major: 1, minor: 5 };
+    assert!(write_markers(Cursor::new(vec![]), &markers).is_err());
+    let mut upgraded = markers_v1;
+    upgraded.version = LATEST_MARKERS_VERSION;
+    assert!(write_markers_mp4(Cursor::new(vec![]), &upgraded).is_err());
+}
+
+#[test]
+fn test_unknown_marker_type() {
+    let input = include_bytes!("../../tests/data/tags/markers/hotcues_with_names.id3.bin");
+    let original = Markers::parse(input).unwrap();
+
+    // Replace the type byte of the second cue slot.
+    let mut data = input.to_vec();
+    assert_eq!(data[2 + 4 + 22 + 20], 0x01);
+    data[2 + 4 + 22 + 20] = 0xAB;
+    let mut markers = Markers::parse(&data).unwrap();
+    assert_eq!(markers.entries[1].marker_type, MarkerType::Unknown(0xAB));
+
+    // The unknown entry is skipped, so the following cues move up by one slot.
+    let cues = markers.cues();
+    assert_eq!(cues.len(), original.cues().len() - 1);
+    assert_eq!(cues[0].1, original.cues()[0].1);
+    assert_eq!(cues[1].1, original.cues()[2].1);
+    assert!(cues
+        .iter()
+        .all(|(_, marker)| marker.marker_type != MarkerType::Unknown(0xAB)));
+    assert_eq!(markers.loops(), original.loops());
+
+    let mut writer = Cursor::new(vec![]);
+    markers.write(&mut writer).unwrap();
+    assert_eq!(writer.get_ref().as_slice(), data.as_slice());
+
+    let mut writer = Cursor::new(vec![]);
+    write_markers_mp4(&mut writer, &markers).unwrap();
+    let (_, reparsed) = take_markers_mp4(writer.get_ref()).unwrap();
+    assert_eq!(reparsed, markers);
+
+    // Updating the cues leaves the unknown entry untouched.
+    markers.set_cues(&[]);
+    assert_eq!(markers.entries[1].marker_type, MarkerType::Unknown(0xAB));
+}
+
+#[test]
+fn test_markers_match_markers2() {
+    // Serato writes the same cues and loops into both marker tags, so the `Serato Markers2` tag
+    // of the same file serves as ground truth for positions, colors and locked states.
+    use super::{format::mp4::MP4Tag, Markers2};
+
+    let pairs: [(Markers, Markers2); 2] = [
+        (
+            Markers::parse_mp4(include_bytes!(
+                "../../tests/data/tags/markers/hotcues_and_loops.mp4.bin"
+            ))
+            .unwrap(),
+            Markers2::parse_mp4(include_bytes!(
+                "../../tests/data/tags/markers2/hotcues_and_loops.mp4.bin"
+            ))
+            .unwrap(),
+        ),
+        (
+            Markers::parse(include_bytes!(
+                "../../tests/data/tags/markers/hotcues_with_names.id3.bin"
+            ))
+            .unwrap(),
+            Markers2::parse(include_bytes!(
+                "../../tests/data/tags/markers2/hotcues_with_names.id3.bin"
+            ))
+            .unwrap(),
+        ),
+    ];
+    for (markers, markers2) in pairs.iter() {
+        let cues: Vec<_> = markers
+            .cues()
+            .into_iter()
+            .filter(|(_, marker)| marker.marker_type == MarkerType::Cue)
+            .map(|(index, marker)| (index, marker.start_position_millis.unwrap(), marker.color))
+            .collect();
+        let expected_cues: Vec<_> = markers2
+            .cues()
+            .into_iter()
+            .filter(|cue| usize::from(cue.index) < NUM_CUES)
+            .map(|cue| (cue.index, cue.position_millis, cue.color))
+            .collect();
+        assert!(!cues.is_empty());
+        assert_eq!(cues, expected_cues);
+
+        let loops: Vec<_> = markers
+            .loops()
+            .into_iter()
+            .filter(|(_, marker)| marker.start_position_millis.is_some())
+            .map(|(index, marker)| {
+                (
+                    index,
+                    marker.start_position_millis.unwrap(),
+                    marker.end_position_millis.unwrap(),
+                    marker.is_locked,
+                )
+            })
+            .collect();
+        let expected_loops: Vec<_> = markers2
+            .loops()
+            .into_iter()
+            .map(|saved_loop| {
+                (
+                    saved_loop.index,
+                    saved_loop.start_position_millis,
+                    saved_loop.end_position_millis,
+                    saved_loop.is_locked,
+                )
+            })
+            .collect();
+        assert_eq!(loops, expected_loops);
+
+        assert_eq!(Some(markers.track_color), markers2.track_color());
+    }
+}
@@ -11,7 +11,11 @@ use super::format::{enveloped, flac, id3, mp4, ogg, Tag};
 use super::generic::{
     CensorFlipAction, Cue, Flip, FlipAction, JumpFlipAction, Loop, UnknownFlipAction, Version,
 };
-use super::util::{take_color, take_version, write_color, write_version};
+use super::util::{
+    retain_range_millis, shift_position_millis, shift_range_millis, take_color, take_version,
+    write_color, write_version,
+};
+use super::TagFormat;
 use crate::error::Error;
 use crate::util::{take_utf8, Res};
 use nom::error::ParseError;
@@ -27,7 +31,7 @@ use std::io::Cursor;
 /// a single byte of data, while FLIP might become quite large. By storing the length explicitly
 /// instead of deriving it from the type, a parser could ignore unknown entry types and still be
 /// able to parse known ones.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Marker {
     Unknown(UnknownMarker),
     Color(TrackColorMarker),
@@ -38,7 +42,10 @@ pub enum Marker {
 }
 
 /// An unknown marker that we don't have a parser for.
-#[derive(Debug)]
+///
+/// The raw data is kept, so that unknown markers are written back verbatim (and in their original
+/// order) when serializing the tag.
+#[derive(Debug, Clone, PartialEq)]
 pub struct UnknownMarker {
     pub name: String,
     pub data: Vec<u8>,
@@ -47,7 +54,7 @@ pub struct UnknownMarker {
 /// A `COLOR` marker.
 ///
 /// `COLOR` markers describe a track's color.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct TrackColorMarker {
     pub color: Color,
 }
@@ -56,7 +63,7 @@ pub struct TrackColorMarker {
 ///
 /// The `BPMLOCK` marker contains a single boolean value that determines if [Beatgrid is
 /// locked](https://support.serato.com/hc/en-us/articles/235214887-Lock-Beatgrids).
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct BPMLockMarker {
     pub is_locked: bool,
 }
@@ -80,14 +87,160 @@ pub struct BPMLockMarker {
 ///     println!("{:?}", content);
 /// }
 /// ```
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Markers2 {
     pub version: Option<Version>,
     pub size: usize,
     pub content: Markers2Content,
+    /// Whether the FLAC or MP4 tag data had been base64-encoded twice.
+    ///
+    /// This is a corruption caused by buggy tag copy tools (see
+    /// [`decode_enveloped_checked`](enveloped::decode_enveloped_checked)). The data is recovered
+    /// when parsing, and always written with the correct encoding.
+    pub is_double_encoded: bool,
+    /// Whether entries were recovered after skipping a stray null byte in the entry list.
+    ///
+    /// Some third-party taggers insert a premature null byte between entries. Serato ignores it,
+    /// so the entries after it are parsed in lenient mode, but the data should be treated as
+    /// suspicious. The stray null bytes are dropped when writing the tag.
+    pub has_recovered_markers: bool,
+}
+
+/// The most recent known `Serato Markers2` version written by Serato DJ.
+///
+/// The same version is used for the tag itself and for its base64-encoded content.
+pub const LATEST_MARKERS2_VERSION: Version = Version { major: 1, minor: 1 };
+
+/// The number of cues that Serato supports (i.e. the valid cue indices are `0..NUM_CUES`).
+pub const NUM_CUES: usize = 8;
+
+/// The number of saved loops that Serato supports (i.e. the valid loop indices are
+/// `0..NUM_LOOPS`).
+pub const NUM_LOOPS: usize = 8;
+
+/// The minimum size of the `Serato Markers2` tag data.
+///
+/// Shorter contents are padded with null bytes.
+pub const MIN_SIZE: usize = 470;
+
+impl Marker {
+    /// Returns the position of the marker type in the order that Serato writes entries in.
+    ///
+    /// Serato writes the `COLOR` entry first, followed by all cues, all loops, the `BPMLOCK`
+    /// entry and finally all flips. Unknown markers don't have a fixed position.
+    fn order(&self) -> Option<u8> {
+        match self {
+            Marker::Color(_) => Some(0),
+            Marker::Cue(_) => Some(1),
+            Marker::Loop(_) => Some(2),
+            Marker::BPMLock(_) => Some(3),
+            Marker::Flip(_) => Some(4),
+            Marker::Unknown(_) => None,
+        }
+    }
 }
 
 impl Markers2 {
+    /// Create an empty `Serato Markers2` tag ([`LATEST_MARKERS2_VERSION`]) without any markers.
+    pub fn new() -> Self {
+        Self {
+            version: Some(LATEST_MARKERS2_VERSION),
+            size: MIN_SIZE,
+            content: Markers2Content {
+                version: LATEST_MARKERS2_VERSION,
+                markers: vec![],
+            },
+            is_double_encoded: false,
+            has_recovered_markers: false,
+        }
+    }
+
+    /// Returns the version of the tag or its base64-encoded content if it is newer than
+    /// [`LATEST_MARKERS2_VERSION`].
+    ///
+    /// Such tags are parsed anyway (and written back with their original version), but they might
+    /// contain data that isn't interpreted correctly.
+    pub fn unknown_version(&self) -> Option<Version> {
+        self.version
+            .into_iter()
+            .chain(std::iter::once(self.content.version))
+            .find(|version| *version > LATEST_MARKERS2_VERSION)
+    }
+
+    /// Parse the tag data from a tag of the given format, but reject entries that deviate from
+    /// what Serato writes instead of parsing them leniently.
+    ///
+    /// Also fails if an entry can't be parsed at all, instead of ignoring it and all subsequent
+    /// entries.
+    pub fn parse_strict(input: &[u8], tag_format: TagFormat) -> Result<Self, Error> {
+        match tag_format {
+            TagFormat::ID3 => {
+                let (_, markers2) =
+                    nom::combinator::all_consuming(|i| take_markers2(i, true))(input)?;
+                Ok(markers2)
+            }
+            TagFormat::FLAC | TagFormat::MP4 => {
+                let (content, is_double_encoded) =
+                    enveloped::decode_enveloped_checked(input, Self::NAME)?;
+                if is_double_encoded {
+                    return Err(Error::EnvelopeParseError);
+                }
+                Self::parse_strict(&content, TagFormat::ID3)
+            }
+            TagFormat::Ogg => parse_ogg_markers2(input, true),
+        }
+    }
+
+    /// Replace all markers that have the same type as `order` with `markers`.
+    ///
+    /// The new markers are inserted where the old ones were. If there were none, they are inserted
+    /// at the position where Serato would put them, so that the order of other entries (including
+    /// unknown ones) stays stable.
+    fn replace_markers(&mut self, order: u8, markers: Vec<Marker>) {
+        let entries = &mut self.content.markers;
+        let position = entries
+            .iter()
+            .position(|m| m.order() == Some(order))
+            .or_else(|| entries.iter().position(|m| m.order() > Some(order)))
+            .unwrap_or(entries.len());
+        entries.retain(|m| m.order() != Some(order));
+        let tail = entries.split_off(position);
+        entries.extend(markers);
+        entries.extend(tail);
+    }
+
+    /// Set the track color, i.e. the color of the `COLOR` entry.
+    ///
+    /// The entry is added if the tag doesn't contain one yet.
+    pub fn set_track_color(&mut self, color: Color) {
+        self.replace_markers(0, vec![Marker::Color(TrackColorMarker { color })]);
+    }
+
+    /// Set the BPM lock state, i.e. the value of the `BPMLOCK` entry.
+    ///
+    /// The entry is added if the tag doesn't contain one yet, or removed if `is_locked` is `None`.
+    pub fn set_bpm_locked(&mut self, is_locked: Option<bool>) {
+        let markers = is_locked
+            .map(|is_locked| Marker::BPMLock(BPMLockMarker { is_locked }))
+            .into_iter()
+            .collect();
+        self.replace_markers(3, markers);
+    }
+
+    /// Replace all cues with the given ones.
+    ///
+    /// The cues are stored in the given order, so they should be sorted by index.
+    pub fn set_cues(&mut self, cues: Vec<Cue>) {
+        self.replace_markers(1, cues.into_iter().map(Marker::Cue).collect());
+    }
+
+    /// Replace all saved loops with the given ones.
+    ///
+    /// The loops are stored in the given order, so they should be sorted by index.
+    pub fn set_loops(&mut self, loops: Vec<Loop>) {
+        self.replace_markers(2, loops.into_iter().map(Marker::Loop).collect());
+    }
+
     pub fn bpm_locked(&self) -> Option<bool> {
         for marker in &self.content.markers {
             if let Marker::BPMLock(m) = marker {
@@ -117,6 +270,134 @@ impl Markers2 {
         loops
     }
 
+    /// Returns the cue with the given index.
+    ///
+    /// If the tag contains multiple cues with that index, the last one is returned.
+    pub fn cue(&self, index: u8) -> Option<&Cue> {
+        self.content
+            .markers
+            .iter()
+            .rev()
+            .find_map(|marker| match marker {
+                Marker::Cue(cue) if cue.index == index => Some(cue),
+                _ => None,
+            })
+    }
+
+    /// Returns the loop with the given index.
+    ///
+    /// If the tag contains multiple loops with that index, the last one is returned.
+    pub fn saved_loop(&self, index: u8) -> Option<&Loop> {
+        self.content
+            .markers
+            .iter()
+            .rev()
+            .find_map(|marker| match marker {
+                Marker::Loop(saved_loop) if saved_loop.index == index => Some(saved_loop),
+                _ => None,
+            })
+    }
+
+    /// Move the positions of all cues and loops using the given function (e.g. to snap them to the
+    /// beatgrid).
+    ///
+    /// Loops are left unchanged if their end position wouldn't be after the start position
+    /// anymore. Flips are not affected.
+    pub fn map_positions(&mut self, f: impl Fn(u32) -> u32) {
+        for marker in &mut self.content.markers {
+            match marker {
+                Marker::Cue(cue) => cue.position_millis = f(cue.position_millis),
+                Marker::Loop(saved_loop) => {
+                    let start = f(saved_loop.start_position_millis);
+                    let end = f(saved_loop.end_position_millis);
+                    if end > start {
+                        saved_loop.start_position_millis = start;
+                        saved_loop.end_position_millis = end;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Shift the positions of all cues, loops and flip actions by the given offset.
+    ///
+    /// Cues that would end up at a negative position and loops that would end up entirely before
+    /// the start of the track are removed. Loops that would start before the start of the track
+    /// are truncated, and flip action positions are clamped at zero.
+    pub fn shift_positions(&mut self, offset_millis: i32) {
+        let offset_seconds = f64::from(offset_millis) / 1000.0;
+        let shift_seconds = |position: &mut f64| *position = (*position + offset_seconds).max(0.0);
+        self.content.markers.retain_mut(|marker| {
+            match marker {
+                Marker::Cue(cue) => {
+                    match shift_position_millis(cue.position_millis, offset_millis) {
+                        Some(position_millis) => cue.position_millis = position_millis,
+                        None => return false,
+                    }
+                }
+                Marker::Loop(saved_loop) => {
+                    match shift_range_millis(
+                        saved_loop.start_position_millis,
+                        saved_loop.end_position_millis,
+                        offset_millis,
+                    ) {
+                        Some((start, end)) => {
+                            saved_loop.start_position_millis = start;
+                            saved_loop.end_position_millis = end;
+                        }
+                        None => return false,
+                    }
+                }
+                Marker::Flip(flip) => {
+                    for action in &mut flip.actions {
+                        match action {
+                            FlipAction::Jump(action) => {
+                                shift_seconds(&mut action.source_position_seconds);
+                                shift_seconds(&mut action.target_position_seconds);
+                            }
+                            FlipAction::Censor(action) => {
+                                shift_seconds(&mut action.start_position_seconds);
+                                shift_seconds(&mut action.end_position_seconds);
+                            }
+                            FlipAction::Unknown(_) => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+            true
+        });
+    }
+
+    /// Remove all cues, loops and flips that are positioned after the end of the track.
+    ///
+    /// Positions up to `tolerance_millis` after the end of the track are accepted. Loops that
+    /// start before the end of the track but end after it are truncated. Flips are removed if any
+    /// of their actions is positioned after the end of the track.
+    pub fn retain_within_duration(&mut self, duration_millis: u32, tolerance_millis: u32) {
+        let max_position_millis = duration_millis.saturating_add(tolerance_millis);
+        self.content.markers.retain_mut(|marker| match marker {
+            Marker::Cue(cue) => cue.position_millis <= max_position_millis,
+            Marker::Loop(saved_loop) => match retain_range_millis(
+                saved_loop.start_position_millis,
+                saved_loop.end_position_millis,
+                duration_millis,
+                tolerance_millis,
+            ) {
+                Some((_, end)) => {
+                    saved_loop.end_position_millis = end;
+                    true
+                }
+                None => false,
+            },
+            Marker::Flip(flip) => {
+                max_flip_position_seconds(flip) <= f64::from(max_position_millis) / 1000.0
+            }
+            _ => true,
+        });
+    }
+
     pub fn flips(&self) -> Vec<Flip> {
         let mut flips: Vec<Flip> = Vec::new();
         for marker in &self.content.markers {
@@ -137,21 +418,42 @@ impl Markers2 {
     }
 }
 
+impl Default for Markers2 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Tag for Markers2 {
     const NAME: &'static str = "Serato Markers2";
 
     fn parse(input: &[u8]) -> Result<Self, Error> {
-        let (_, autotags) = nom::combinator::all_consuming(take_markers2)(input)?;
+        let (_, autotags) = nom::combinator::all_consuming(|i| take_markers2(i, false))(input)?;
         Ok(autotags)
     }
 
     fn write(&self, writer: impl io::Write) -> Result<usize, Error> {
-        write_markers2(writer, &self)
+        write_markers2(writer, self)
     }
 }
 
 impl id3::ID3Tag for Markers2 {}
-impl enveloped::EnvelopedTag for Markers2 {}
+impl enveloped::EnvelopedTag for Markers2 {
+    fn parse_enveloped(input: &[u8]) -> Result<Self, Error> {
+        let (content, is_double_encoded) = enveloped::decode_enveloped_checked(input, Self::NAME)?;
+        let mut markers2 = Self::parse(&content)?;
+        markers2.is_double_encoded = is_double_encoded;
+        Ok(markers2)
+    }
+
+    fn parse_enveloped_from_reader(reader: impl io::Read) -> Result<Self, Error> {
+        let mut content = enveloped::decode_enveloped_from_reader(reader, Self::NAME)?;
+        let is_double_encoded = content.is_double_encoded();
+        let mut markers2 = Self::parse_from_reader(&mut content)?;
+        markers2.is_double_encoded = is_double_encoded;
+        Ok(markers2)
+    }
+}
 impl flac::FLACTag for Markers2 {
     const FLAC_COMMENT: &'static str = "SERATO_MARKERS_V2";
 }
@@ -162,20 +464,7 @@ impl ogg::OggTag for Markers2 {
     const OGG_COMMENT: &'static str = "serato_markers2";
 
     fn parse_ogg(input: &[u8]) -> Result<Self, Error> {
-        let size = input.len();
-        let base64_decoded = enveloped::base64_decode(input)?;
-        let version = None;
-        match parse_markers2_content(&base64_decoded) {
-            Ok((_, content)) => {
-                let markers2 = Markers2 {
-                    version,
-                    size,
-                    content,
-                };
-                Ok(markers2)
-            }
-            Err(_) => Err(Error::ParseError),
-        }
+        parse_ogg_markers2(input, false)
     }
 
     fn write_ogg(&self, mut writer: impl io::Write) -> Result<usize, Error> {
@@ -193,7 +482,7 @@ impl ogg::OggTag for Markers2 {
 }
 
 /// Represents the base64-encoded content of the `Serato Markers2` tag.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Markers2Content {
     pub version: Version,
     pub markers: Vec<Marker>,
@@ -251,10 +540,10 @@ fn decode_base64_chunks(
         }
         let mut buf = [0; 54];
         // TODO: Add proper error handling here
-        let mut res = base64::decode_config_slice(&chunk, base64::STANDARD, &mut buf);
+        let mut res = base64::decode_config_slice(chunk, base64::STANDARD, &mut buf);
         if let Err(base64::DecodeError::InvalidLength) = res {
             let mut v = Vec::new();
-            v.extend_from_slice(&chunk);
+            v.extend_from_slice(chunk);
             v.push(b'A');
             res = base64::decode_config_slice(v.as_slice(), base64::STANDARD, &mut buf);
         }
@@ -275,13 +564,13 @@ fn take_marker_name(input: &[u8]) -> Res<&[u8], String> {
 }
 
 /// Returns a [`Marker`] parsed from the input slice.
-fn take_marker(input: &[u8]) -> Res<&[u8], Marker> {
+fn take_marker(input: &[u8], strict: bool) -> Res<&[u8], Marker> {
     let (input, name) = take_marker_name(input)?;
     let (input, data) = nom::multi::length_data(nom::number::complete::be_u32)(input)?;
 
     let (_, marker) = match name.as_str() {
         "BPMLOCK" => nom::combinator::all_consuming(take_bpmlock_marker)(data)?,
-        "COLOR" => nom::combinator::all_consuming(take_color_marker)(data)?,
+        "COLOR" => nom::combinator::all_consuming(|i| take_color_marker(i, strict))(data)?,
         "CUE" => nom::combinator::all_consuming(take_cue_marker)(data)?,
         "LOOP" => nom::combinator::all_consuming(take_loop_marker)(data)?,
         "FLIP" => nom::combinator::all_consuming(take_flip_marker)(data)?,
@@ -310,9 +599,21 @@ fn take_bpmlock_marker(input: &[u8]) -> Res<&[u8], Marker> {
     Ok((input, Marker::BPMLock(marker)))
 }
 
-fn take_color_marker(input: &[u8]) -> Res<&[u8], Marker> {
-    let (input, _) = nom::bytes::complete::tag(b"\x00")(input)?;
-    let (input, color) = take_color(input)?;
+/// Returns a `COLOR` marker parsed from the entry data.
+///
+/// Serato writes a null byte followed by the RGB value. Unless `strict` is set, the nonstandard
+/// payloads written by some third-party taggers and older Serato versions are accepted, too: a
+/// 3-byte payload only contains the RGB value, and trailing bytes after the RGB value are ignored.
+fn take_color_marker(input: &[u8], strict: bool) -> Res<&[u8], Marker> {
+    let (input, color) = if strict {
+        let (input, _) = nom::bytes::complete::tag(b"\x00")(input)?;
+        take_color(input)?
+    } else {
+        let (input, _) = nom::bytes::complete::take(input.len().min(4).saturating_sub(3))(input)?;
+        let (input, color) = take_color(input)?;
+        let (input, _) = nom::combinator::rest(input)?;
+        (input, color)
+    };
     let marker = TrackColorMarker { color };
     Ok((input, Marker::Color(marker)))
 }
@@ -356,6 +657,27 @@ fn take_loop_marker(input: &[u8]) -> Res<&[u8], Marker> {
     Ok((input, Marker::Loop(marker)))
 }
 
+/// Returns the largest position that any of the flip's actions refers to.
+fn max_flip_position_seconds(flip: &Flip) -> f64 {
+    flip.actions
+        .iter()
+        .map(|action| match action {
+            FlipAction::Jump(action) => action
+                .source_position_seconds
+                .max(action.target_position_seconds),
+            FlipAction::Censor(action) => action
+                .start_position_seconds
+                .max(action.end_position_seconds),
+            FlipAction::Unknown(_) => 0.0,
+        })
+        .fold(0.0, f64::max)
+}
+
+/// Returns a `FLIP` marker parsed from the input slice.
+///
+/// The data starts with a null byte, followed by the index, the enabled flag, the
+/// null-terminated UTF-8 label, the loop flag and the list of actions (prefixed with the number
+/// of actions as `u32`).
 fn take_flip_marker(input: &[u8]) -> Res<&[u8], Marker> {
     let (input, _) = nom::bytes::complete::tag(b"\x00")(input)?;
     let (input, index) = nom::number::complete::u8(input)?;
@@ -376,7 +698,8 @@ fn take_flip_marker(input: &[u8]) -> Res<&[u8], Marker> {
 
 /// Returns a flip `FLIP` action parsed from the input slice.
 ///
-/// Each action starts with a header that contains its type and length.
+/// Each action starts with a header that contains its type and length. Actions with an unknown
+/// type are kept as raw data.
 fn take_flip_marker_action(input: &[u8]) -> Res<&[u8], FlipAction> {
     let (input, id) = nom::number::complete::u8(input)?;
     let (input, data) = nom::multi::length_data(nom::number::complete::be_u32)(input)?;
@@ -417,11 +740,58 @@ fn take_flip_marker_action_censor(input: &[u8]) -> Res<&[u8], FlipAction> {
     Ok((input, FlipAction::Censor(action)))
 }
 
-fn parse_markers2_content(input: &[u8]) -> Res<&[u8], Markers2Content> {
-    let (input, version) = take_version(&input)?;
-    let (input, markers) = nom::multi::many0(take_marker)(&input)?;
+/// Parses the base64-decoded content.
+///
+/// Also returns whether entries were recovered after a stray null byte (see
+/// [`Markers2::has_recovered_markers`]).
+fn parse_markers2_content(input: &[u8], strict: bool) -> Res<&[u8], (Markers2Content, bool)> {
+    let (mut input, version) = take_version(input)?;
+    let (remaining, mut markers) = nom::multi::many0(|i| take_marker(i, strict))(input)?;
+    input = remaining;
+    let mut has_recovered_markers = false;
+    if strict {
+        // Entries that fail to parse would silently end the marker list otherwise.
+        let (input, _) = take_nullbytes(input)?;
+        nom::combinator::eof(input)?;
+    } else {
+        while let Some(remaining) = skip_stray_nullbyte(input) {
+            let (remaining, recovered) = nom::multi::many0(|i| take_marker(i, strict))(remaining)?;
+            if recovered.is_empty() {
+                break;
+            }
+            has_recovered_markers = true;
+            markers.extend(recovered);
+            input = remaining;
+        }
+    }
 
-    Ok((input, Markers2Content { version, markers }))
+    let content = Markers2Content { version, markers };
+    Ok((input, (content, has_recovered_markers)))
+}
+
+/// Returns the input after a single null byte if it is followed by a plausible entry.
+///
+/// The null byte that terminates the entry list is usually followed by more null bytes or the
+/// end of the data. A lone null byte that is followed by an entry name and a length that fits
+/// into the remaining data is most likely a stray byte inserted by a buggy tagger.
+fn skip_stray_nullbyte(input: &[u8]) -> Option<&[u8]> {
+    let input = input.strip_prefix(b"\0")?;
+    let name_length = input.iter().position(|&x| x == 0)?;
+    let (name, rest) = input.split_at(name_length);
+    // The shortest known entry name is `CUE`.
+    if name.len() < 3
+        || !name
+            .iter()
+            .all(|x| x.is_ascii_uppercase() || x.is_ascii_digit())
+    {
+        return None;
+    }
+    let (rest, length) =
+        nom::number::complete::be_u32::<_, nom::error::Error<_>>(&rest[1..]).ok()?;
+    if length as usize > rest.len() {
+        return None;
+    }
+    Some(input)
 }
 
 fn take_nullbytes(input: &[u8]) -> Res<&[u8], &[u8]> {
@@ -431,26 +801,47 @@ fn take_nullbytes(input: &[u8]) -> Res<&[u8], &[u8]> {
     )(input)
 }
 
-fn take_markers2(input: &[u8]) -> Res<&[u8], Markers2> {
+fn take_markers2(input: &[u8], strict: bool) -> Res<&[u8], Markers2> {
     let size = input.len();
-    let (input, version) = take_version(&input)?;
+    let (input, version) = take_version(input)?;
     let version = Some(version);
-    let (input, base64_chunks) = take_base64_chunks(&input)?;
-    let (input, _) = take_nullbytes(&input)?;
+    let (input, base64_chunks) = take_base64_chunks(input)?;
+    let (input, _) = take_nullbytes(input)?;
     let base64_decoded = decode_base64_chunks(base64_chunks)?;
-    let markers2_result = parse_markers2_content(&base64_decoded);
+    let markers2_result = parse_markers2_content(&base64_decoded, strict);
     if markers2_result.is_err() {
         return Err(nom::Err::Incomplete(nom::Needed::Unknown));
     }
-    let (_, content) = markers2_result.unwrap();
+    let (_, (content, has_recovered_markers)) = markers2_result.unwrap();
     let markers2 = Markers2 {
         version,
         size,
         content,
+        is_double_encoded: false,
+        has_recovered_markers,
     };
     Ok((input, markers2))
 }
 
+fn parse_ogg_markers2(input: &[u8], strict: bool) -> Result<Markers2, Error> {
+    let size = input.len();
+    let base64_decoded = enveloped::base64_decode(input)?;
+    let version = None;
+    match parse_markers2_content(&base64_decoded, strict) {
+        Ok((_, (content, has_recovered_markers))) => {
+            let markers2 = Markers2 {
+                version,
+                size,
+                content,
+                is_double_encoded: false,
+                has_recovered_markers,
+            };
+            Ok(markers2)
+        }
+        Err(_) => Err(Error::ParseError),
+    }
+}
+
 fn write_markers2(mut writer: impl io::Write, markers2: &Markers2) -> Result<usize, Error> {
     let version = match &markers2.version {
         Some(version) => version,
@@ -458,11 +849,15 @@ fn write_markers2(mut writer: impl io::Write, markers2: &Markers2) -> Result<usi
             return Err(Error::ParseError);
         }
     };
-    let mut bytes_written = write_version(&mut writer, &version)?;
+    let mut bytes_written = write_version(&mut writer, version)?;
     let mut buffer = Cursor::new(vec![]);
     write_markers2_content(&mut buffer, &markers2.content)?;
     let plain_data = &buffer.get_ref()[..];
     bytes_written += enveloped::base64_encode(&mut writer, plain_data)?;
+
+    // The base64 data is always terminated by a null byte, and shorter contents are padded with
+    // null bytes up to the original tag size.
+    bytes_written += writer.write(b"\x00")?;
     if markers2.size > bytes_written {
         for _ in 0..(markers2.size - bytes_written) {
             bytes_written += writer.write(b"\x00")?;
@@ -477,7 +872,7 @@ fn write_markers2_content(
 ) -> Result<usize, Error> {
     let mut bytes_written = write_version(&mut writer, &content.version)?;
     for marker in &content.markers {
-        bytes_written += write_marker(&mut writer, &marker)?;
+        bytes_written += write_marker(&mut writer, marker)?;
     }
     Ok(bytes_written)
 }
@@ -533,7 +928,7 @@ fn write_color_marker(
 
 fn write_cue_marker(mut writer: impl io::Write, marker: &Cue) -> Result<usize, Error> {
     let mut bytes_written = writer.write(b"CUE\0")?;
-    let size: u32 = 13 + marker.label.as_bytes().len() as u32;
+    let size: u32 = 13 + marker.label.len() as u32;
     bytes_written += writer.write(&size.to_be_bytes())?;
     bytes_written += writer.write(b"\0")?;
     bytes_written += writer.write(&[marker.index])?;
@@ -541,14 +936,14 @@ fn write_cue_marker(mut writer: impl io::Write, marker: &Cue) -> Result<usize, E
     bytes_written += writer.write(b"\0")?;
     bytes_written += write_color(&mut writer, &marker.color)?;
     bytes_written += writer.write(b"\0\0")?;
-    bytes_written += writer.write(&marker.label.as_bytes())?;
+    bytes_written += writer.write(marker.label.as_bytes())?;
     bytes_written += writer.write(b"\0")?;
     Ok(bytes_written)
 }
 
 fn write_loop_marker(mut writer: impl io::Write, marker: &Loop) -> Result<usize, Error> {
     let mut bytes_written = writer.write(b"LOOP\0")?;
-    let size: u32 = 21 + marker.label.as_bytes().len() as u32;
+    let size: u32 = 21 + marker.label.len() as u32;
     bytes_written += writer.write(&size.to_be_bytes())?;
     bytes_written += writer.write(b"\0")?;
     bytes_written += writer.write(&[marker.index])?;
@@ -558,19 +953,19 @@ fn write_loop_marker(mut writer: impl io::Write, marker: &Loop) -> Result<usize,
     bytes_written += write_color(&mut writer, &marker.color)?;
     bytes_written += writer.write(b"\0")?;
     bytes_written += write_bool(&mut writer, marker.is_locked)?;
-    bytes_written += writer.write(&marker.label.as_bytes())?;
+    bytes_written += writer.write(marker.label.as_bytes())?;
     bytes_written += writer.write(b"\0")?;
     Ok(bytes_written)
 }
 
 fn write_flip_marker(mut writer: impl io::Write, marker: &Flip) -> Result<usize, Error> {
     let mut bytes_written = writer.write(b"FLIP\0")?;
-    let mut size: u32 = 9 + marker.label.as_bytes().len() as u32;
+    let mut size: u32 = 9 + marker.label.len() as u32;
     for action in &marker.actions {
         size += match action {
             FlipAction::Jump(_) => 21u32,
             FlipAction::Censor(_) => 29u32,
-            FlipAction::Unknown(act) => act.data.len() as u32 + 1,
+            FlipAction::Unknown(act) => act.data.len() as u32 + 5,
         }
     }
 
@@ -578,13 +973,13 @@ fn write_flip_marker(mut writer: impl io::Write, marker: &Flip) -> Result<usize,
     bytes_written += writer.write(b"\0")?;
     bytes_written += writer.write(&[marker.index])?;
     bytes_written += write_bool(&mut writer, marker.is_enabled)?;
-    bytes_written += writer.write(&marker.label.as_bytes())?;
+    bytes_written += writer.write(marker.label.as_bytes())?;
     bytes_written += writer.write(b"\0")?;
     bytes_written += write_bool(&mut writer, marker.is_loop)?;
     let num_actions = marker.actions.len() as u32;
     bytes_written += writer.write(&num_actions.to_be_bytes())?;
     for action in &marker.actions {
-        bytes_written = write_flip_marker_action(&mut writer, &action)?;
+        bytes_written += write_flip_marker_action(&mut writer, action)?;
     }
     Ok(bytes_written)
 }
@@ -598,14 +993,14 @@ fn write_flip_marker_action(
             let mut bytes_written = writer.write(b"\x00")?;
             let size = 16u32;
             bytes_written += writer.write(&size.to_be_bytes())?;
-            bytes_written += write_flip_marker_action_jump(&mut writer, &act)?;
+            bytes_written += write_flip_marker_action_jump(&mut writer, act)?;
             Ok(bytes_written)
         }
         FlipAction::Censor(act) => {
             let mut bytes_written = writer.write(b"\x01")?;
             let size = 24u32;
             bytes_written += writer.write(&size.to_be_bytes())?;
-            bytes_written += write_flip_marker_action_censor(&mut writer, &act)?;
+            bytes_written += write_flip_marker_action_censor(&mut writer, act)?;
             Ok(bytes_written)
         }
         FlipAction::Unknown(act) => {
@@ -636,3 +1031,426 @@ fn write_flip_marker_action_censor(
     bytes_written += writer.write(&action.speed_factor.to_be_bytes())?;
     Ok(bytes_written)
 }
+
+#[test]
+fn test_write_markers2() {
+    let markers2 = Markers2 {
+        version: Some(Version { major: 1, minor: 1 }),
+        size: 470,
+        content: Markers2Content {
+            version: Version { major: 1, minor: 1 },
+            markers: vec![
+                Marker::Color(TrackColorMarker {
+                    color: Color::from(0xFF99FF),
+                }),
+                Marker::Cue(Cue {
+                    index: 0,
+                    position_millis: 1234,
+                    color: Color::from(0xCC0000),
+                    label: String::from("Intro"),
+                }),
+                Marker::Loop(Loop {
+                    index: 1,
+                    start_position_millis: 2000,
+                    end_position_millis: 4000,
+                    color: Color::from(0x27AAE1),
+                    is_locked: true,
+                    label: String::new(),
+                }),
+                Marker::BPMLock(BPMLockMarker { is_locked: false }),
+            ],
+        },
+        is_double_encoded: false,
+        has_recovered_markers: false,
+    };
+
+    let mut writer = Cursor::new(vec![]);
+    let bytes_written = write_markers2(&mut writer, &markers2).unwrap();
+    let output = writer.get_ref().as_slice();
+    assert_eq!(bytes_written, 470);
+    assert_eq!(output.len(), 470);
+    assert_eq!(&output[..2], &[0x01, 0x01]);
+    assert_eq!(output[469], 0x00);
+    assert_eq!(Markers2::parse(output).unwrap(), markers2);
+
+    // Tags that exceed the minimum size are still terminated by a null byte
+    let markers2 = Markers2 {
+        size: 0,
+        ..markers2
+    };
+    let mut writer = Cursor::new(vec![]);
+    let bytes_written = write_markers2(&mut writer, &markers2).unwrap();
+    let output = writer.get_ref().as_slice();
+    assert_eq!(bytes_written, output.len());
+    assert_eq!(output.last(), Some(&0x00));
+    let reparsed = Markers2::parse(output).unwrap();
+    assert_eq!(reparsed.content, markers2.content);
+}
+
+#[test]
+fn test_write_markers2_mp4() {
+    use mp4::MP4Tag;
+
+    let empty = Markers2 {
+        version: Some(Version { major: 1, minor: 1 }),
+        size: 470,
+        content: Markers2Content {
+            version: Version { major: 1, minor: 1 },
+            markers: vec![],
+        },
+        is_double_encoded: false,
+        has_recovered_markers: false,
+    };
+    let mut writer = Cursor::new(vec![]);
+    let bytes_written = empty.write_mp4(&mut writer).unwrap();
+    let output = writer.get_ref().as_slice();
+    assert_eq!(bytes_written, output.len());
+    assert_eq!(Markers2::parse_mp4(output).unwrap(), empty);
+
+    let markers2 = Markers2 {
+        content: Markers2Content {
+            version: Version { major: 1, minor: 1 },
+            markers: vec![Marker::Cue(Cue {
+                index: 3,
+                position_millis: 98765,
+                color: Color::from(0x0000CC),
+                label: String::from("Drop 🔥 – Ünïcødé"),
+            })],
+        },
+        ..empty
+    };
+    let mut writer = Cursor::new(vec![]);
+    let bytes_written = markers2.write_mp4(&mut writer).unwrap();
+    let output = writer.get_ref().as_slice();
+    assert_eq!(bytes_written, output.len());
+    assert!(output
+        .iter()
+        .all(|&b| enveloped::is_base64(b) || b == b'\n'));
+    assert_eq!(Markers2::parse_mp4(output).unwrap(), markers2);
+}
+
+#[test]
+fn test_unknown_marker_roundtrip() {
+    let unknown_data = [0xDE, 0xAD, 0xBE, 0xEF, 0x00, 0x01];
+    let mut content = vec![0x01, 0x01];
+    content.extend_from_slice(
+        b"CUE\0\x00\x00\x00\x0D\x00\x00\x00\x00\x04\xD2\x00\xCC\x00\x00\x00\x00\x00",
+    );
+    content.extend_from_slice(b"XXXX\0\x00\x00\x00\x06");
+    content.extend_from_slice(&unknown_data);
+    content.extend_from_slice(b"BPMLOCK\0\x00\x00\x00\x01\x00");
+
+    let mut input = vec![0x01, 0x01];
+    enveloped::base64_encode(&mut input, &content).unwrap();
+    input.push(0x00);
+
+    let mut markers2 = Markers2::parse(&input).unwrap();
+    assert_eq!(markers2.content.markers.len(), 3);
+    assert_eq!(
+        markers2.content.markers[1],
+        Marker::Unknown(UnknownMarker {
+            name: String::from("XXXX"),
+            data: unknown_data.to_vec(),
+        })
+    );
+
+    if let Marker::Cue(cue) = &mut markers2.content.markers[0] {
+        cue.label = String::from("Changed");
+    } else {
+        unreachable!();
+    }
+
+    let mut writer = Cursor::new(vec![]);
+    write_markers2_content(&mut writer, &markers2.content).unwrap();
+    let output = writer.get_ref().as_slice();
+    let mut expected_unknown = b"XXXX\0\x00\x00\x00\x06".to_vec();
+    expected_unknown.extend_from_slice(&unknown_data);
+    let position = output
+        .windows(expected_unknown.len())
+        .position(|window| window == expected_unknown.as_slice())
+        .expect("Unknown marker is missing!");
+    // The unknown marker is still located between the cue and the `BPMLOCK` marker (13 bytes).
+    assert_eq!(position + expected_unknown.len(), output.len() - 13);
+
+    let mut writer = Cursor::new(vec![]);
+    write_markers2(&mut writer, &markers2).unwrap();
+    let reparsed = Markers2::parse(writer.get_ref()).unwrap();
+    assert_eq!(reparsed.content, markers2.content);
+    assert_eq!(reparsed.cues()[0].label, "Changed");
+}
+
+#[test]
+fn test_take_flip_marker() {
+    let mut data = vec![0x00, 0x02, 0x01];
+    data.extend_from_slice(b"Censored\0");
+    data.push(0x00);
+    data.extend_from_slice(&3u32.to_be_bytes());
+    // Censor action
+    data.push(0x01);
+    data.extend_from_slice(&24u32.to_be_bytes());
+    data.extend_from_slice(&10.0f64.to_be_bytes());
+    data.extend_from_slice(&12.5f64.to_be_bytes());
+    data.extend_from_slice(&(-1.0f64).to_be_bytes());
+    // Unknown action
+    data.push(0x07);
+    data.extend_from_slice(&3u32.to_be_bytes());
+    data.extend_from_slice(&[0xAB, 0xCD, 0xEF]);
+    // Jump action
+    data.push(0x00);
+    data.extend_from_slice(&16u32.to_be_bytes());
+    data.extend_from_slice(&12.5f64.to_be_bytes());
+    data.extend_from_slice(&15.0f64.to_be_bytes());
+
+    let (rest, marker) = take_flip_marker(&data).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(
+        marker,
+        Marker::Flip(Flip {
+            index: 2,
+            is_enabled: true,
+            label: String::from("Censored"),
+            is_loop: false,
+            actions: vec![
+                FlipAction::Censor(CensorFlipAction {
+                    start_position_seconds: 10.0,
+                    end_position_seconds: 12.5,
+                    speed_factor: -1.0,
+                }),
+                FlipAction::Unknown(UnknownFlipAction {
+                    id: 0x07,
+                    data: vec![0xAB, 0xCD, 0xEF],
+                }),
+                FlipAction::Jump(JumpFlipAction {
+                    source_position_seconds: 12.5,
+                    target_position_seconds: 15.0,
+                }),
+            ],
+        })
+    );
+
+    // Truncated actions are rejected.
+    assert!(take_flip_marker(&data[..data.len() - 1]).is_err());
+}
+
+#[test]
+fn test_parse_flips() {
+    let markers2 = Markers2::parse(include_bytes!(
+        "../../tests/data/tags/markers2/flips.id3.bin"
+    ))
+    .unwrap();
+    let flips = markers2.flips();
+    assert_eq!(flips.len(), 3);
+    assert_eq!(flips[0].index, 0);
+    assert_eq!(flips[0].label, "AAAAAAAAAA");
+    assert!(!flips[0].is_loop);
+    assert!(flips[1].is_loop);
+
+    // The last action is a jump from the position where the recording was stopped.
+    for flip in &flips {
+        match flip.actions.last() {
+            Some(FlipAction::Jump(_)) => {}
+            action => panic!("Unexpected last action: {:?}", action),
+        }
+    }
+    match &flips[0].actions[0] {
+        FlipAction::Jump(action) => assert_eq!(action.target_position_seconds, 54.635),
+        action => panic!("Unexpected action: {:?}", action),
+    }
+}
+
+#[test]
+fn test_write_flip_marker() {
+    let data = include_bytes!("../../tests/data/tags/markers2/flips.id3.bin");
+    let mut markers2 = Markers2::parse(data).unwrap();
+
+    // Unmodified flips are written back byte-for-byte.
+    let mut writer = Cursor::new(vec![]);
+    let bytes_written = markers2.write(&mut writer).unwrap();
+    assert_eq!(bytes_written, data.len());
+    assert_eq!(writer.get_ref().as_slice(), &data[..]);
+
+    // The reported size matches the number of bytes written, including unknown actions.
+    let flip = Flip {
+        index: 3,
+        is_enabled: false,
+        label: String::from("Edited"),
+        is_loop: true,
+        actions: vec![
+            FlipAction::Censor(CensorFlipAction {
+                start_position_seconds: 10.0,
+                end_position_seconds: 11.0,
+                speed_factor: -1.0,
+            }),
+            FlipAction::Unknown(UnknownFlipAction {
+                id: 0x07,
+                data: vec![0xAB, 0xCD, 0xEF],
+            }),
+            FlipAction::Jump(JumpFlipAction {
+                source_position_seconds: 11.0,
+                target_position_seconds: 2.5,
+            }),
+        ],
+    };
+    let mut writer = Cursor::new(vec![]);
+    let bytes_written = write_flip_marker(&mut writer, &flip).unwrap();
+    let output = writer.get_ref().as_slice();
+    assert_eq!(bytes_written, output.len());
+    let (rest, marker) = nom::combinator::all_consuming(|i| take_marker(i, true))(output).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(marker, Marker::Flip(flip.clone()));
+
+    // Edited flips survive a roundtrip through the whole tag.
+    markers2.content.markers.push(Marker::Flip(flip));
+    let mut writer = Cursor::new(vec![]);
+    markers2.write(&mut writer).unwrap();
+    let reparsed = Markers2::parse(writer.get_ref().as_slice()).unwrap();
+    assert_eq!(reparsed.content, markers2.content);
+}
+
+#[test]
+fn test_nonstandard_color_marker() {
+    use flac::FLACTag;
+
+    // `COLOR` payloads without the leading null byte and with extra trailing bytes.
+    let payloads: [&[u8]; 2] = [
+        b"COLOR\0\x00\x00\x00\x03\x99\x33\x33",
+        b"COLOR\0\x00\x00\x00\x06\x00\x99\x33\x33\xAB\xCD",
+    ];
+    for payload in &payloads {
+        let mut content = vec![0x01, 0x01];
+        content.extend_from_slice(payload);
+        content.extend_from_slice(
+            b"CUE\0\x00\x00\x00\x0D\x00\x00\x00\x00\x04\xD2\x00\xCC\x00\x00\x00\x00\x00",
+        );
+        content.extend_from_slice(b"BPMLOCK\0\x00\x00\x00\x01\x01");
+        content.push(0x00);
+
+        let mut input = vec![0x01, 0x01];
+        enveloped::base64_encode(&mut input, &content).unwrap();
+        input.push(0x00);
+
+        let markers2 = Markers2::parse(&input).unwrap();
+        assert_eq!(markers2.track_color(), Some(Color::from(0x993333)));
+        assert_eq!(markers2.cues().len(), 1);
+        assert_eq!(markers2.cues()[0].position_millis, 1234);
+        assert_eq!(markers2.bpm_locked(), Some(true));
+
+        // The entry is written in the standard format.
+        let mut writer = Cursor::new(vec![]);
+        markers2.write(&mut writer).unwrap();
+        let output = writer.get_ref().as_slice();
+        let reparsed = Markers2::parse_strict(output, TagFormat::ID3).unwrap();
+        assert_eq!(reparsed.content, markers2.content);
+
+        assert!(Markers2::parse_strict(&input, TagFormat::ID3).is_err());
+        let mut enveloped_input = vec![];
+        enveloped::envelope_encode_with_name(&mut enveloped_input, &input, Markers2::NAME).unwrap();
+        assert_eq!(Markers2::parse_flac(&enveloped_input).unwrap(), markers2);
+        assert!(Markers2::parse_strict(&enveloped_input, TagFormat::FLAC).is_err());
+    }
+}
+
+#[test]
+fn test_unknown_version() {
+    let markers2 = Markers2::new();
+    assert_eq!(markers2.version, Some(LATEST_MARKERS2_VERSION));
+    assert_eq!(markers2.unknown_version(), None);
+
+    let mut content = vec![0x02, 0x00];
+    content.extend_from_slice(b"BPMLOCK\0\x00\x00\x00\x01\x01");
+    content.push(0x00);
+    let mut input = vec![0x01, 0x03];
+    enveloped::base64_encode(&mut input, &content).unwrap();
+    input.push(0x00);
+
+    // Unknown versions are parsed anyway, and written back unchanged.
+    let mut markers2 = Markers2::parse(&input).unwrap();
+    assert_eq!(markers2.bpm_locked(), Some(true));
+    assert_eq!(
+        markers2.unknown_version(),
+        Some(Version { major: 1, minor: 3 })
+    );
+    markers2.version = Some(LATEST_MARKERS2_VERSION);
+    assert_eq!(
+        markers2.unknown_version(),
+        Some(Version { major: 2, minor: 0 })
+    );
+    markers2.set_bpm_locked(Some(false));
+    let mut writer = Cursor::new(vec![]);
+    markers2.write(&mut writer).unwrap();
+    let output = writer.get_ref().as_slice();
+    assert_eq!(output[..2], [0x01, 0x01]);
+    let reparsed = Markers2::parse(output).unwrap();
+    assert_eq!(reparsed.content.version, Version { major: 2, minor: 0 });
+    assert_eq!(reparsed.bpm_locked(), Some(false));
+}
+
+#[test]
+fn test_double_encoded() {
+    use flac::FLACTag;
+
+    let input = include_bytes!("../../tests/data/tags/markers2/hotcues_and_loops.flac.bin");
+    let markers2 = Markers2::parse_flac(input).unwrap();
+    assert!(!markers2.is_double_encoded);
+
+    let mut double_encoded = vec![];
+    enveloped::envelope_encode_with_name(&mut double_encoded, input, Markers2::NAME).unwrap();
+    let recovered = Markers2::parse_flac(&double_encoded).unwrap();
+    assert!(recovered.is_double_encoded);
+    assert_eq!(recovered.content, markers2.content);
+    assert!(Markers2::parse_strict(&double_encoded, TagFormat::FLAC).is_err());
+
+    // The data is written with the correct encoding.
+    let mut writer = Cursor::new(vec![]);
+    recovered.write_flac(&mut writer).unwrap();
+    let reparsed = Markers2::parse_strict(writer.get_ref(), TagFormat::FLAC).unwrap();
+    assert!(!reparsed.is_double_encoded);
+    assert_eq!(reparsed.content, markers2.content);
+}
+
+#[test]
+fn test_stray_nullbyte() {
+    let cue = |index: u8| -> Vec<u8> {
+        let mut entry = b"CUE\0\x00\x00\x00\x0D\x00".to_vec();
+        entry.push(index);
+        entry.extend_from_slice(b"\x00\x00\x04\xD2\x00\xCC\x00\x00\x00\x00\x00");
+        entry
+    };
+    let mut content = vec![0x01, 0x01];
+    content.extend_from_slice(b"COLOR\0\x00\x00\x00\x04\x00\x99\x33\x33");
+    content.extend(cue(0));
+    content.push(0x00);
+    content.extend(cue(1));
+    content.extend(cue(2));
+    content.push(0x00);
+    content.extend(cue(3));
+    content.extend_from_slice(b"\0\0\0");
+
+    let (_, (parsed, has_recovered_markers)) = parse_markers2_content(&content, false).unwrap();
+    assert_eq!(parsed.markers.len(), 5);
+    assert!(has_recovered_markers);
+
+    let mut input = vec![0x01, 0x01];
+    enveloped::base64_encode(&mut input, &content).unwrap();
+    input.push(0x00);
+    let markers2 = Markers2::parse(&input).unwrap();
+    let indices: Vec<u8> = markers2.cues().iter().map(|cue| cue.index).collect();
+    assert_eq!(indices, vec![0, 1, 2, 3]);
+    assert!(markers2.has_recovered_markers);
+    assert!(Markers2::parse_strict(&input, TagFormat::ID3).is_err());
+
+    // The stray null bytes are dropped when writing the tag.
+    let mut writer = Cursor::new(vec![]);
+    markers2.write(&mut writer).unwrap();
+    let reparsed = Markers2::parse_strict(writer.get_ref(), TagFormat::ID3).unwrap();
+    assert_eq!(reparsed.content, markers2.content);
+    assert!(!reparsed.has_recovered_markers);
+
+    // Null bytes that aren't followed by a plausible entry still end the entry list.
+    let mut content = vec![0x01, 0x01];
+    content.extend(cue(0));
+    content.extend_from_slice(b"\0CUE\0\x00\x00\x01\x00\x00");
+    let (_, (parsed, has_recovered_markers)) = parse_markers2_content(&content, false).unwrap();
+    assert_eq!(parsed.markers.len(), 1);
+    assert!(!has_recovered_markers);
+}
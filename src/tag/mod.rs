@@ -3,14 +3,28 @@ pub mod color;
 pub mod format;
 pub mod generic;
 pub mod serato32;
-pub(self) mod util;
+mod util;
+pub mod validation;
 
 pub mod container;
+pub use container::is_serato_identifier;
+pub use container::AnalysisState;
+pub use container::CueProvenance;
+pub use container::LoopProvenance;
+pub use container::MergePolicy;
+pub use container::OutOfRange;
+pub use container::ParseOptions;
+pub use container::SamplePositions;
+pub use container::SerializedTag;
 pub use container::TagContainer;
+pub use container::TagContainerBuilder;
 pub use container::TagFormat;
+pub use container::TagKind;
+pub use container::TagName;
+pub use container::WriteStrategy;
 
 pub mod analysis;
-pub use self::analysis::Analysis;
+pub use self::analysis::{Analysis, LATEST_ANALYSIS_VERSION, MIN_ANALYSIS_VERSION};
 
 pub mod autotags;
 pub use self::autotags::Autotags;
@@ -19,10 +33,13 @@ pub mod beatgrid;
 pub use self::beatgrid::Beatgrid;
 
 pub mod markers;
-pub use self::markers::Markers;
+pub use self::markers::{Markers, LATEST_MARKERS_VERSION};
 
 pub mod markers2;
-pub use self::markers2::Markers2;
+pub use self::markers2::{Markers2, LATEST_MARKERS2_VERSION};
+
+pub mod offsets;
+pub use self::offsets::Offsets;
 
 pub mod overview;
 pub use self::overview::Overview;
@@ -0,0 +1,251 @@
+//! The `Serato Offsets_` tag stores timing information for MP3 files.
+
+use super::format::{id3, Tag};
+use super::generic::Version;
+use super::util::{take_version, write_version};
+use crate::error::Error;
+use crate::util::Res;
+use std::io;
+
+/// Represents the `Serato Offsets_` tag.
+///
+/// This tag only exists in MP3 files and contains the sample offsets that compensate timing
+/// differences between MP3 decoders (e.g. because of the encoder delay of LAME or FhG encoded
+/// files). The data consists of:
+///
+/// | Offset | Length         | Description
+/// | ------ | -------------- | -----------
+/// | `0x00` | 2              | Version
+/// | `0x02` | 4              | Sample rate (in Hz, big-endian)
+/// | `0x06` | 4              | Number of entries (big-endian)
+/// | `0x0A` | 8 × entries    | Entries (see [`OffsetEntry`])
+///
+/// **Note:** This layout has not been confirmed with files from all Serato versions. Data that
+/// doesn't match it exactly is kept opaque in [`raw_data`](Self::raw_data), so that it is not lost
+/// when the tags are written back.
+///
+/// # Example
+///
+/// ```
+/// use triseratops::tag::{Offsets, format::id3::ID3Tag};
+///
+/// // First, read the tag data from the ID3 GEOB tag (the tag name can be accessed using the
+/// // Offsets::ID3_TAG), then parse the data like this:
+/// fn parse(data: &[u8]) {
+///     let content = Offsets::parse_id3(data).expect("Failed to parse data!");
+///     println!("{:?}", content);
+/// }
+/// ```
+#[derive(Debug, PartialEq)]
+pub struct Offsets {
+    /// The `Offsets_` version.
+    pub version: Version,
+    /// The sample rate (in Hz) that the positions and offsets refer to.
+    pub sample_rate: u32,
+    /// The entries of the offset table, ordered by position.
+    pub entries: Vec<OffsetEntry>,
+    /// The data after the version if it doesn't have the expected layout (see above).
+    ///
+    /// If this is set, the sample rate and entries are ignored and the data is written back
+    /// unchanged.
+    pub raw_data: Option<Vec<u8>>,
+}
+
+/// An entry of the [`Offsets`] table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OffsetEntry {
+    /// The position (in samples) from which on the offset applies.
+    pub position_samples: u32,
+    /// The number of samples that have to be added to positions after
+    /// [`position_samples`](Self::position_samples).
+    pub offset_samples: i32,
+}
+
+impl Offsets {
+    /// Returns the sample rate (in Hz), or `None` if the offset table couldn't be decoded.
+    pub fn sample_rate(&self) -> Option<u32> {
+        if self.raw_data.is_some() {
+            return None;
+        }
+        Some(self.sample_rate)
+    }
+
+    /// Returns the entries of the offset table, or `None` if the table couldn't be decoded.
+    pub fn entries(&self) -> Option<&[OffsetEntry]> {
+        if self.raw_data.is_some() {
+            return None;
+        }
+        Some(&self.entries)
+    }
+
+    /// Returns the offset (in samples) that applies at the given position (in samples), i.e. the
+    /// offset of the last entry at or before the position.
+    ///
+    /// Returns `None` if the offset table couldn't be decoded, and `Some(0)` for positions before
+    /// the first entry.
+    ///
+    /// ```
+    /// use triseratops::tag::{offsets::OffsetEntry, Offsets, generic::Version};
+    ///
+    /// let offsets = Offsets {
+    ///     version: Version { major: 1, minor: 2 },
+    ///     sample_rate: 44100,
+    ///     entries: vec![
+    ///         OffsetEntry { position_samples: 1000, offset_samples: -1105 },
+    ///         OffsetEntry { position_samples: 441000, offset_samples: -1152 },
+    ///     ],
+    ///     raw_data: None,
+    /// };
+    /// assert_eq!(offsets.offset_at(0), Some(0));
+    /// assert_eq!(offsets.offset_at(1000), Some(-1105));
+    /// assert_eq!(offsets.offset_at(500000), Some(-1152));
+    /// ```
+    pub fn offset_at(&self, position_samples: u64) -> Option<i32> {
+        let entries = self.entries()?;
+        let offset = entries
+            .iter()
+            .take_while(|entry| u64::from(entry.position_samples) <= position_samples)
+            .last()
+            .map_or(0, |entry| entry.offset_samples);
+        Some(offset)
+    }
+}
+
+impl Tag for Offsets {
+    const NAME: &'static str = "Serato Offsets_";
+
+    fn parse(input: &[u8]) -> Result<Self, Error> {
+        let (_, offsets) = nom::combinator::all_consuming(take_offsets)(input)?;
+        Ok(offsets)
+    }
+
+    fn write(&self, writer: impl io::Write) -> Result<usize, Error> {
+        write_offsets(writer, self)
+    }
+}
+
+impl id3::ID3Tag for Offsets {}
+
+fn take_offset_entry(input: &[u8]) -> Res<&[u8], OffsetEntry> {
+    let (input, position_samples) = nom::number::complete::be_u32(input)?;
+    let (input, offset_samples) = nom::number::complete::be_i32(input)?;
+    Ok((
+        input,
+        OffsetEntry {
+            position_samples,
+            offset_samples,
+        },
+    ))
+}
+
+fn take_offset_table(input: &[u8]) -> Res<&[u8], (u32, Vec<OffsetEntry>)> {
+    let (input, sample_rate) = nom::number::complete::be_u32(input)?;
+    let (input, entries) =
+        nom::multi::length_count(nom::number::complete::be_u32, take_offset_entry)(input)?;
+    let is_sorted = entries
+        .windows(2)
+        .all(|pair| pair[0].position_samples <= pair[1].position_samples);
+    if sample_rate == 0 || !is_sorted {
+        return Err(nom::Err::Error(nom::error::make_error(
+            input,
+            nom::error::ErrorKind::Verify,
+        )));
+    }
+    Ok((input, (sample_rate, entries)))
+}
+
+fn take_offsets(input: &[u8]) -> Res<&[u8], Offsets> {
+    let (input, version) = take_version(input)?;
+    let (input, data) = nom::combinator::rest(input)?;
+
+    let offsets = match nom::combinator::all_consuming(take_offset_table)(data) {
+        Ok((_, (sample_rate, entries))) => Offsets {
+            version,
+            sample_rate,
+            entries,
+            raw_data: None,
+        },
+        Err(_) => Offsets {
+            version,
+            sample_rate: 0,
+            entries: vec![],
+            raw_data: Some(data.to_vec()),
+        },
+    };
+    Ok((input, offsets))
+}
+
+fn write_offsets(mut writer: impl io::Write, offsets: &Offsets) -> Result<usize, Error> {
+    let mut bytes_written = write_version(&mut writer, &offsets.version)?;
+    if let Some(data) = &offsets.raw_data {
+        bytes_written += writer.write(data.as_slice())?;
+        return Ok(bytes_written);
+    }
+
+    let num_entries = offsets.entries.len() as u32;
+    bytes_written += writer.write(&offsets.sample_rate.to_be_bytes())?;
+    bytes_written += writer.write(&num_entries.to_be_bytes())?;
+    for entry in &offsets.entries {
+        bytes_written += writer.write(&entry.position_samples.to_be_bytes())?;
+        bytes_written += writer.write(&entry.offset_samples.to_be_bytes())?;
+    }
+    Ok(bytes_written)
+}
+
+#[test]
+fn test_offsets_roundtrip() {
+    let input = [0x01, 0x02, 0xDE, 0xAD, 0xBE, 0xEF];
+    let offsets = Offsets::parse(&input).unwrap();
+    assert_eq!(offsets.version, Version { major: 1, minor: 2 });
+    assert_eq!(offsets.raw_data, Some(vec![0xDE, 0xAD, 0xBE, 0xEF]));
+    assert_eq!(offsets.sample_rate(), None);
+    assert_eq!(offsets.entries(), None);
+    assert_eq!(offsets.offset_at(0), None);
+
+    let mut output = vec![];
+    assert_eq!(offsets.write(&mut output).unwrap(), input.len());
+    assert_eq!(output, input);
+
+    assert!(Offsets::parse(&[0x01]).is_err());
+}
+
+#[test]
+fn test_offsets_table() {
+    let input = include_bytes!("../../tests/data/tags/offsets/table.id3.bin");
+    let offsets = Offsets::parse(input).unwrap();
+    assert_eq!(offsets.version, Version { major: 1, minor: 2 });
+    assert_eq!(offsets.raw_data, None);
+    assert_eq!(offsets.sample_rate(), Some(44100));
+    assert_eq!(
+        offsets.entries(),
+        Some(
+            &[
+                OffsetEntry {
+                    position_samples: 0,
+                    offset_samples: -1105,
+                },
+                OffsetEntry {
+                    position_samples: 441_000,
+                    offset_samples: -1152,
+                },
+                OffsetEntry {
+                    position_samples: 882_000,
+                    offset_samples: -1201,
+                },
+            ][..]
+        )
+    );
+    assert_eq!(offsets.offset_at(441_000), Some(-1152));
+    assert_eq!(offsets.offset_at(10_000_000), Some(-1201));
+
+    let mut output = vec![];
+    assert_eq!(offsets.write(&mut output).unwrap(), input.len());
+    assert_eq!(&output[..], &input[..]);
+
+    // Tables with unordered entries are kept opaque.
+    let mut input = input.to_vec();
+    input[10..14].copy_from_slice(&1_000_000u32.to_be_bytes());
+    let offsets = Offsets::parse(&input).unwrap();
+    assert_eq!(offsets.entries(), None);
+    assert_eq!(offsets.raw_data.as_deref(), Some(&input[2..]));
+}
@@ -2,11 +2,11 @@
 //!
 //! The overview data consists of multiple chunks of 16 bytes.
 
-use super::format::{enveloped, flac, id3, mp4, Tag};
+use super::format::{enveloped, flac, id3, mp4, Tag, MAX_TAG_SIZE};
 use super::generic::Version;
-use super::util::{take_version, write_version};
+use super::util::{take_padding, take_version, write_padding, write_version};
 use crate::error::Error;
-use crate::util::Res;
+use crate::util::{read_full, Res};
 use std::io;
 
 /// Represents the `Serato Overview` tag.
@@ -25,12 +25,17 @@ use std::io;
 ///     println!("{:?}", content);
 /// }
 /// ```
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Overview {
     /// The tag version.
     pub version: Version,
     /// The Waveform overview data.
     pub data: Vec<Vec<u8>>,
+    /// The number of null bytes that the tag data was padded with (written back unchanged).
+    ///
+    /// Since chunks may consist of null bytes, only padding that is shorter than a chunk is
+    /// detected.
+    pub padding: usize,
 }
 
 impl Tag for Overview {
@@ -41,8 +46,46 @@ impl Tag for Overview {
         Ok(overview)
     }
 
+    /// Parse the overview data from a reader chunk by chunk, without reading all data into
+    /// memory first.
+    ///
+    /// Like the default implementation, this fails if the data exceeds
+    /// [`MAX_TAG_SIZE`].
+    fn parse_from_reader(mut reader: impl io::Read) -> Result<Self, Error> {
+        let mut header = [0; 2];
+        let length = read_full(&mut reader, &mut header)?;
+        let (_, version) = nom::combinator::all_consuming(take_version)(&header[..length])?;
+
+        let mut data = vec![];
+        loop {
+            let mut chunk = [0; CHUNK_SIZE];
+            let length = read_full(&mut reader, &mut chunk)?;
+            if length == CHUNK_SIZE {
+                if ((data.len() + 1) * CHUNK_SIZE) as u64 > MAX_TAG_SIZE {
+                    return Err(Error::TagTooLarge {
+                        max_size: MAX_TAG_SIZE,
+                    });
+                }
+                data.push(chunk.to_vec());
+                continue;
+            }
+
+            // The remaining data is shorter than a chunk, so it has to be padding
+            let remaining = &chunk[..length];
+            if data.is_empty() {
+                take_chunks(remaining)?;
+            }
+            let (_, padding) = nom::combinator::all_consuming(take_padding)(remaining)?;
+            return Ok(Overview {
+                version,
+                data,
+                padding,
+            });
+        }
+    }
+
     fn write(&self, writer: impl io::Write) -> Result<usize, Error> {
-        write_overview(writer, &self)
+        write_overview(writer, self)
     }
 }
 
@@ -57,7 +100,7 @@ impl mp4::MP4Tag for Overview {
 
 /// Returns a 16-byte vector of data parsed from the input slice.
 fn take_chunk(input: &[u8]) -> Res<&[u8], Vec<u8>> {
-    let (input, chunkdata) = nom::bytes::complete::take(16usize)(input)?;
+    let (input, chunkdata) = nom::bytes::complete::take(CHUNK_SIZE)(input)?;
     Ok((input, chunkdata.to_vec()))
 }
 
@@ -87,22 +130,101 @@ fn take_chunks(input: &[u8]) -> Res<&[u8], Vec<Vec<u8>>> {
 
 /// Returns an [`Overview` struct](Overview) parsed from the input slice.
 fn take_overview(input: &[u8]) -> Res<&[u8], Overview> {
-    let (input, version) = take_version(&input)?;
+    let (input, version) = take_version(input)?;
     let (input, data) = take_chunks(input)?;
+    let (input, padding) = take_padding(input)?;
 
-    let overview = Overview { version, data };
+    let overview = Overview {
+        version,
+        data,
+        padding,
+    };
     Ok((input, overview))
 }
 
+/// The size of a single overview chunk in bytes.
+pub const CHUNK_SIZE: usize = 16;
+
 fn write_chunk(mut writer: impl io::Write, chunk: &[u8]) -> Result<usize, Error> {
-    // TODO: Handle chunks with invalid size
     Ok(writer.write(chunk)?)
 }
 
+/// Serialize the [`Overview` struct](Overview) to bytes.
+///
+/// Returns an [`InvalidOverviewChunk`](Error::InvalidOverviewChunk) error if a chunk is not
+/// exactly 16 bytes long, and a [`NoTagDataAvailable`](Error::NoTagDataAvailable) error if there
+/// are no chunks at all. Nothing is written in that case.
 pub fn write_overview(mut writer: impl io::Write, overview: &Overview) -> Result<usize, Error> {
+    if overview.data.is_empty() {
+        return Err(Error::NoTagDataAvailable);
+    }
+    for (index, chunk) in overview.data.iter().enumerate() {
+        if chunk.len() != CHUNK_SIZE {
+            return Err(Error::InvalidOverviewChunk {
+                index,
+                length: chunk.len(),
+            });
+        }
+    }
+
     let mut bytes_written = write_version(&mut writer, &overview.version)?;
     for chunk in &overview.data {
         bytes_written += write_chunk(&mut writer, chunk.as_slice())?;
     }
+    bytes_written += write_padding(writer, overview.padding)?;
     Ok(bytes_written)
 }
+
+#[test]
+fn test_write_overview() {
+    use std::io::Cursor;
+
+    let mut overview = Overview {
+        version: Version { major: 1, minor: 5 },
+        data: vec![(0..16).collect(), vec![0xFF; 16]],
+        padding: 0,
+    };
+
+    let mut writer = Cursor::new(vec![]);
+    let bytes_written = write_overview(&mut writer, &overview).unwrap();
+    let output = writer.get_ref().as_slice();
+    assert_eq!(bytes_written, 2 + 2 * CHUNK_SIZE);
+    assert_eq!(&output[..4], &[0x01, 0x05, 0x00, 0x01]);
+    assert_eq!(Overview::parse(output).unwrap(), overview);
+
+    overview.data[1].pop();
+    let mut writer = Cursor::new(vec![]);
+    assert!(matches!(
+        write_overview(&mut writer, &overview),
+        Err(Error::InvalidOverviewChunk {
+            index: 1,
+            length: 15
+        })
+    ));
+    assert!(writer.get_ref().is_empty());
+
+    overview.data.clear();
+    assert!(matches!(
+        write_overview(Cursor::new(vec![]), &overview),
+        Err(Error::NoTagDataAvailable)
+    ));
+}
+
+#[test]
+fn test_parse_overview_from_reader() {
+    let mut input = vec![0x01, 0x05];
+    input.extend(0..16);
+    input.extend(&[0xFF; 16]);
+    input.extend(&[0x00; 3]);
+    let overview = Overview::parse_from_reader(&input[..]).unwrap();
+    assert_eq!(overview, Overview::parse(&input).unwrap());
+    assert_eq!(overview.data.len(), 2);
+    assert_eq!(overview.padding, 3);
+
+    // Truncated data is a parse error, not an I/O error
+    for length in &[0, 1, 2, 10, 20] {
+        let err = Overview::parse_from_reader(&input[..*length]).unwrap_err();
+        assert!(!err.is_io_error());
+        assert!(Overview::parse(&input[..*length]).is_err());
+    }
+}
@@ -94,6 +94,7 @@ pub fn take(input: &[u8]) -> Res<&[u8], (u8, u8, u8)> {
     Ok((input, value))
 }
 
+/// Writes a 3-byte tuple encoded as 4 bytes.
 pub fn write(mut writer: impl io::Write, data: (u8, u8, u8)) -> Result<usize, Error> {
     let (in1, in2, in3) = data;
     let (byte1, byte2, byte3, byte4) = encode(in1, in2, in3);
@@ -119,6 +120,7 @@ pub fn take_color(input: &[u8]) -> Res<&[u8], Color> {
     Ok((input, color))
 }
 
+/// Writes a `Color` encoded as 4 bytes.
 pub fn write_color(writer: impl io::Write, color: &Color) -> Result<usize, Error> {
     write(writer, (color.red, color.green, color.blue))
 }
@@ -143,9 +145,93 @@ pub fn take_u32(input: &[u8]) -> Res<&[u8], u32> {
     Ok((input, value))
 }
 
-pub fn write_u32(writer: impl io::Write, value: u32) -> Result<usize, Error> {
-    let byte1 = ((value >> 16) & 0xFF) as u8;
-    let byte2 = ((value >> 8) & 0xFF) as u8;
-    let byte3 = (value & 0xFF) as u8;
-    write(writer, (byte1, byte2, byte3))
+/// Writes a `u32` encoded as 4 bytes.
+///
+/// Returns an [`InvalidSerato32Value`](Error::InvalidSerato32Value) error if the value is larger
+/// than `0xFFFFFF`.
+pub fn write_u32(mut writer: impl io::Write, value: u32) -> Result<usize, Error> {
+    let bytes = encode_u32(value)?;
+    Ok(writer.write(&bytes)?)
+}
+
+/// The largest value that can be encoded in the `serato32` format.
+pub const MAX_VALUE: u32 = 0xFF_FFFF;
+
+/// Encodes a `u32` value to 4 bytes in Serato's 32-bit custom format.
+///
+/// Since the format only holds 24 bits of payload, this returns an
+/// [`InvalidSerato32Value`](Error::InvalidSerato32Value) error for values larger than
+/// [`MAX_VALUE`].
+///
+/// # Example
+/// ```
+/// use triseratops::tag::serato32::{decode_u32, encode_u32};
+///
+/// assert_eq!(encode_u32(0x0000CC).unwrap(), [0x00, 0x00, 0x01, 0x4C]);
+/// assert_eq!(decode_u32([0x00, 0x00, 0x01, 0x4C]), 0x0000CC);
+/// assert!(encode_u32(0x1000000).is_err());
+/// ```
+pub fn encode_u32(value: u32) -> Result<[u8; 4], Error> {
+    if value > MAX_VALUE {
+        return Err(Error::InvalidSerato32Value { value });
+    }
+    let [_, dec1, dec2, dec3] = value.to_be_bytes();
+    let (enc1, enc2, enc3, enc4) = encode(dec1, dec2, dec3);
+    Ok([enc1, enc2, enc3, enc4])
+}
+
+/// Decodes 4 bytes in Serato's 32-bit custom format to a `u32` value.
+///
+/// The ignored bits of the input are discarded, so the result is never larger than
+/// [`MAX_VALUE`].
+pub const fn decode_u32(bytes: [u8; 4]) -> u32 {
+    let (dec1, dec2, dec3) = decode(bytes[0], bytes[1], bytes[2], bytes[3]);
+    (dec1 as u32) << 16 | (dec2 as u32) << 8 | dec3 as u32
+}
+
+/// Encodes a `Color` to 4 bytes in Serato's 32-bit custom format.
+///
+/// # Example
+/// ```
+/// use triseratops::tag::color::Color;
+/// use triseratops::tag::serato32::{decode_color, encode_color};
+///
+/// let color = Color { red: 0xCC, green: 0x88, blue: 0x00 };
+/// assert_eq!(encode_color(color), [0x06, 0x32, 0x10, 0x00]);
+/// assert_eq!(decode_color([0x06, 0x32, 0x10, 0x00]), color);
+/// ```
+pub const fn encode_color(color: Color) -> [u8; 4] {
+    let (enc1, enc2, enc3, enc4) = encode(color.red, color.green, color.blue);
+    [enc1, enc2, enc3, enc4]
+}
+
+/// Decodes 4 bytes in Serato's 32-bit custom format to a `Color`.
+pub const fn decode_color(bytes: [u8; 4]) -> Color {
+    let (red, green, blue) = decode(bytes[0], bytes[1], bytes[2], bytes[3]);
+    Color { red, green, blue }
+}
+
+#[test]
+fn test_u32_roundtrip() {
+    for value in 0..=MAX_VALUE {
+        let bytes = encode_u32(value).unwrap();
+        assert!(bytes.iter().all(|b| b & 0x80 == 0));
+        assert_eq!(decode_u32(bytes), value);
+    }
+    assert!(matches!(
+        encode_u32(MAX_VALUE + 1),
+        Err(Error::InvalidSerato32Value { value: 0x1000000 })
+    ));
+    assert!(encode_u32(u32::MAX).is_err());
+    assert!(write_u32(io::sink(), MAX_VALUE + 1).is_err());
+}
+
+#[test]
+fn test_color_roundtrip() {
+    for value in (0..=MAX_VALUE).step_by(7) {
+        let color = Color::from(value);
+        let bytes = encode_color(color);
+        assert_eq!(bytes, encode_u32(value).unwrap());
+        assert_eq!(decode_color(bytes), color);
+    }
 }
@@ -6,6 +6,7 @@ use super::generic::Version;
 use crate::error::Error;
 use crate::util::Res;
 use nom::bytes::complete::take;
+use std::convert::TryFrom;
 use std::io;
 
 /// Returns a `Color` struct parsed from the first 3 input bytes.
@@ -76,3 +77,107 @@ fn test_take_version() {
 pub fn write_version(mut writer: impl io::Write, version: &Version) -> Result<usize, Error> {
     Ok(writer.write(&[version.major, version.minor])?)
 }
+
+/// Returns the number of null bytes at the start of the input slice.
+///
+/// Serato (and some third-party taggers) pad tag data with null bytes, so this is used to consume
+/// the padding after the actual tag data.
+pub fn take_padding(input: &[u8]) -> Res<&[u8], usize> {
+    let (input, padding) = nom::bytes::complete::take_while(|b| b == 0)(input)?;
+    Ok((input, padding.len()))
+}
+
+#[test]
+fn test_take_padding() {
+    assert_eq!(take_padding(&[]), Ok((&[][..], 0)));
+    assert_eq!(take_padding(&[0x00, 0x00, 0x00]), Ok((&[][..], 3)));
+    assert_eq!(
+        take_padding(&[0x00, 0x01, 0x00]),
+        Ok((&[0x01, 0x00][..], 1))
+    );
+}
+
+/// Write the given number of null bytes (see [`take_padding`]).
+pub fn write_padding(mut writer: impl io::Write, padding: usize) -> Result<usize, Error> {
+    Ok(writer.write(&vec![0; padding])?)
+}
+
+/// Returns the position shifted by the given offset, or `None` if the result would be negative.
+///
+/// Positions that would exceed the maximum value are saturated.
+pub fn shift_position_millis(position_millis: u32, offset_millis: i32) -> Option<u32> {
+    let position_millis = i64::from(position_millis) + i64::from(offset_millis);
+    if position_millis < 0 {
+        return None;
+    }
+    Some(u32::try_from(position_millis).unwrap_or(u32::MAX))
+}
+
+/// Returns the range shifted by the given offset.
+///
+/// If only the start position would become negative, it is clamped at zero. If the whole range
+/// would become negative, `None` is returned.
+pub fn shift_range_millis(
+    start_position_millis: u32,
+    end_position_millis: u32,
+    offset_millis: i32,
+) -> Option<(u32, u32)> {
+    let end_position_millis = shift_position_millis(end_position_millis, offset_millis)?;
+    let start_position_millis = match shift_position_millis(start_position_millis, offset_millis) {
+        Some(position_millis) => position_millis,
+        None if end_position_millis > 0 => 0,
+        None => return None,
+    };
+    Some((start_position_millis, end_position_millis))
+}
+
+/// Returns the range restricted to the given duration.
+///
+/// Ranges that end within the tolerance after the end of the track are returned unchanged,
+/// ranges that end later are truncated at the end of the track. If the range doesn't start
+/// before the end of the track, `None` is returned.
+pub fn retain_range_millis(
+    start_position_millis: u32,
+    end_position_millis: u32,
+    duration_millis: u32,
+    tolerance_millis: u32,
+) -> Option<(u32, u32)> {
+    if end_position_millis <= duration_millis.saturating_add(tolerance_millis) {
+        return Some((start_position_millis, end_position_millis));
+    }
+    if start_position_millis >= duration_millis {
+        return None;
+    }
+    Some((start_position_millis, duration_millis))
+}
+
+#[test]
+fn test_shift_position_millis() {
+    assert_eq!(shift_position_millis(1000, 500), Some(1500));
+    assert_eq!(shift_position_millis(1000, -1000), Some(0));
+    assert_eq!(shift_position_millis(1000, -1001), None);
+    assert_eq!(shift_position_millis(u32::MAX - 1, 2), Some(u32::MAX));
+
+    assert_eq!(shift_range_millis(1000, 2000, -500), Some((500, 1500)));
+    assert_eq!(shift_range_millis(1000, 2000, -1500), Some((0, 500)));
+    assert_eq!(shift_range_millis(1000, 2000, -2000), None);
+    assert_eq!(shift_range_millis(1000, 2000, -2500), None);
+}
+
+#[test]
+fn test_retain_range_millis() {
+    assert_eq!(retain_range_millis(1000, 2000, 3000, 0), Some((1000, 2000)));
+    assert_eq!(
+        retain_range_millis(1000, 3050, 3000, 100),
+        Some((1000, 3050))
+    );
+    assert_eq!(
+        retain_range_millis(1000, 3200, 3000, 100),
+        Some((1000, 3000))
+    );
+    assert_eq!(retain_range_millis(3000, 3200, 3000, 100), None);
+    assert_eq!(
+        retain_range_millis(3050, 3080, 3000, 100),
+        Some((3050, 3080))
+    );
+}
@@ -0,0 +1,145 @@
+//! Types for reporting inconsistent or invalid data in Serato's tags.
+//!
+//! Use [`TagContainer::validate()`](super::TagContainer::validate) to check the tags of a
+//! container, or [`TagContainer::cues_checked()`](super::TagContainer::cues_checked) and
+//! [`TagContainer::loops_checked()`](super::TagContainer::loops_checked) to find out which
+//! entries are skipped when merging the markers tags.
+
+use super::container::{TagFormat, TagKind};
+use super::generic::Version;
+use super::overview::CHUNK_SIZE;
+use std::fmt;
+
+/// A problem that was found in one of the tags.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    /// The tag that contains the problematic entry.
+    pub tag: TagKind,
+
+    /// The index of the problematic entry.
+    ///
+    /// For cues and loops, this is the cue or loop index. For beatgrid markers and overview
+    /// chunks, this is the position in the list of markers or chunks. Problems that affect the
+    /// whole tag (e.g. an unknown version) use the index 0.
+    pub index: usize,
+
+    /// The problem itself.
+    pub problem: Problem,
+}
+
+/// The kind of a [`Finding`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    /// A cue is set in `Serato Markers2` but its slot in `Serato Markers_` is unset, so Serato
+    /// ignores the cue.
+    CueUnsetInMarkers,
+
+    /// A loop is set in `Serato Markers2` but its slot in `Serato Markers_` is unset, so Serato
+    /// ignores the loop.
+    LoopUnsetInMarkers,
+
+    /// A marker in `Serato Markers_` has a type that doesn't match its slot (e.g. a loop in a cue
+    /// slot).
+    UnexpectedMarkerType,
+
+    /// A cue or loop is marked as set but lacks its position (or, for loops, its start or end
+    /// position).
+    MissingPosition,
+
+    /// A loop doesn't end after its start position.
+    InvalidLoopPositions {
+        start_position_millis: u32,
+        end_position_millis: u32,
+    },
+
+    /// The cue or loop index is larger than what Serato supports.
+    IndexOutOfRange,
+
+    /// A cue has an index that doesn't have a slot in the `Serato Markers_` tag, so it can't be
+    /// stored there.
+    CueNotInMarkers,
+
+    /// A loop has an index that doesn't have a slot in the `Serato Markers_` tag, so it can't be
+    /// stored there.
+    LoopNotInMarkers,
+
+    /// A beatgrid marker has a negative or non-finite position.
+    InvalidBeatgridPosition { position: f32 },
+
+    /// A beatgrid marker is not positioned after a previous marker.
+    BeatgridMarkersOutOfOrder { previous_index: usize },
+
+    /// A non-terminal beatgrid marker spans no beats at all, or so many beats that the tempo until
+    /// the next marker is outside the supported BPM range (see
+    /// [`beatgrid::MIN_BPM`](super::beatgrid::MIN_BPM) and
+    /// [`beatgrid::MAX_BPM`](super::beatgrid::MAX_BPM)).
+    InvalidBeatCount { beats: u32 },
+
+    /// The BPM of the terminal beatgrid marker is outside the supported range.
+    BpmOutOfRange { bpm: f32 },
+
+    /// An overview chunk doesn't have the expected length of 16 bytes.
+    InvalidOverviewChunk { length: usize },
+
+    /// The tag has a version that is newer than the most recent known one, so its data might not
+    /// be interpreted correctly.
+    UnknownVersion { version: Version },
+
+    /// The tag can't be written to files of the given format yet, so it is skipped when
+    /// serializing the tags (see
+    /// [`TagContainer::serialize_tags_checked()`](super::TagContainer::serialize_tags_checked)).
+    UnsupportedTagFormat { tag_format: TagFormat },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::CueUnsetInMarkers => write!(f, "cue is unset in the Serato Markers_ tag"),
+            Problem::LoopUnsetInMarkers => write!(f, "loop is unset in the Serato Markers_ tag"),
+            Problem::UnexpectedMarkerType => write!(f, "marker type doesn't match the slot"),
+            Problem::MissingPosition => write!(f, "position is missing"),
+            Problem::InvalidLoopPositions {
+                start_position_millis,
+                end_position_millis,
+            } => write!(
+                f,
+                "end position {} ms is not after start position {} ms",
+                end_position_millis, start_position_millis
+            ),
+            Problem::IndexOutOfRange => write!(f, "index is out of range"),
+            Problem::CueNotInMarkers => write!(f, "cue can't be stored in the Serato Markers_ tag"),
+            Problem::LoopNotInMarkers => {
+                write!(f, "loop can't be stored in the Serato Markers_ tag")
+            }
+            Problem::InvalidBeatgridPosition { position } => {
+                write!(f, "position {} is invalid", position)
+            }
+            Problem::BeatgridMarkersOutOfOrder { previous_index } => {
+                write!(f, "position is not after marker {}", previous_index)
+            }
+            Problem::InvalidBeatCount { beats } => write!(f, "invalid number of beats {}", beats),
+            Problem::BpmOutOfRange { bpm } => write!(f, "BPM {} is out of range", bpm),
+            Problem::InvalidOverviewChunk { length } => {
+                write!(f, "chunk has {} bytes instead of {}", length, CHUNK_SIZE)
+            }
+            Problem::UnknownVersion { version } => {
+                write!(f, "unknown version {}.{}", version.major, version.minor)
+            }
+            Problem::UnsupportedTagFormat { tag_format } => {
+                write!(f, "tag can't be written to {:?} files", tag_format)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (entry {}): {}",
+            self.tag.name(),
+            self.index,
+            self.problem
+        )
+    }
+}
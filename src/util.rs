@@ -26,7 +26,7 @@ fn test_take_until_nullbyte() {
 }
 
 pub fn parse_utf8(input: &[u8]) -> Res<&[u8], String> {
-    let res = std::str::from_utf8(&input);
+    let res = std::str::from_utf8(input);
     match res {
         Ok(s) => Ok((b"", s.to_owned())),
         Err(_) => Err(nom::Err::Incomplete(nom::Needed::Unknown)),
@@ -42,8 +42,8 @@ fn test_parse_utf8() {
 }
 
 pub fn take_utf8(input: &[u8]) -> Res<&[u8], String> {
-    let (input, data) = take_until_nullbyte(&input)?;
-    let (_, value) = parse_utf8(&data)?;
+    let (input, data) = take_until_nullbyte(input)?;
+    let (_, value) = parse_utf8(data)?;
     let (input, _) = nom::bytes::complete::take(1usize)(input)?;
     Ok((input, value))
 }
@@ -55,3 +55,33 @@ fn test_take_utf8() {
         Ok((&b""[..], String::from("AB")))
     );
 }
+
+/// Read until the buffer is full or the end of the data is reached, and return the number of
+/// bytes read.
+///
+/// In contrast to [`Read::read_exact`](std::io::Read::read_exact), reaching the end of the data
+/// is not an error, so that truncated data can be reported as a parse error.
+pub fn read_full(mut reader: impl std::io::Read, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut length = 0;
+    while length < buf.len() {
+        match reader.read(&mut buf[length..]) {
+            Ok(0) => break,
+            Ok(n) => length += n,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => (),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(length)
+}
+
+#[test]
+fn test_read_full() {
+    let mut buf = [0; 4];
+    assert_eq!(
+        read_full(&[0x01, 0x02, 0x03, 0x04, 0x05][..], &mut buf).unwrap(),
+        4
+    );
+    assert_eq!(buf, [0x01, 0x02, 0x03, 0x04]);
+    assert_eq!(read_full(&[0x06, 0x07][..], &mut buf).unwrap(), 2);
+    assert_eq!(buf, [0x06, 0x07, 0x03, 0x04]);
+}
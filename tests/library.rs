@@ -1,11 +1,21 @@
 extern crate triseratops;
 
-use triseratops::library::{Library, Track};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use triseratops::error::Error;
+use triseratops::library::crate_file::{
+    ColumnConfig, ColumnName, Crate, SkipReason, SkippedEntry, Sorting,
+};
+use triseratops::library::crate_tree::{read_crate_tree, CrateNode};
+use triseratops::library::discover::{discover, LibraryKind, LibraryLocation};
+use triseratops::library::neworder;
+use triseratops::library::path::PathResolver;
+use triseratops::library::{database, Library, PathChange, Track};
 
 #[test]
 fn test_library() {
     let library = Library::read_from_path("tests/data/library/usb_drive").unwrap();
-    let tracks: Vec<&Track> = library.tracks().collect();
+    let tracks: Vec<&Track> = library.tracks().unwrap().collect();
     assert_eq!(tracks.len(), 4);
 
     let subcrates: Vec<String> = library.subcrates().collect();
@@ -17,3 +27,948 @@ fn test_library() {
     let tracks: Vec<&Track> = library.subcrate("French House").unwrap().collect();
     assert_eq!(tracks.len(), 2);
 }
+
+#[test]
+fn test_library_open() {
+    let library = Library::open("tests/data/library/usb_drive/_Serato_").unwrap();
+    assert_eq!(
+        library.crates().unwrap(),
+        vec!["80s Mashup", "French House"]
+    );
+    let tracks = library.crate_tracks("French House").unwrap();
+    assert_eq!(tracks.len(), 2);
+    assert_eq!(
+        tracks[0].file_path,
+        Path::new("ALAN BRAXE - INTRO ( Max Padovani Remix).mp3")
+    );
+    assert_eq!(library.tracks().unwrap().count(), 4);
+    // Missing components are treated as empty
+    assert!(library.smart_crates().unwrap().is_empty());
+    assert_eq!(library.sessions().unwrap().count(), 0);
+    assert!(library.crate_tracks("Unknown").is_err());
+    assert!(Library::open("tests/data/library/missing/_Serato_").is_err());
+
+    let dir = std::env::temp_dir().join(format!("triseratops-open-{}", std::process::id()));
+    let sessions_dir = dir.join("_Serato_/History/Sessions");
+    let smart_crates_dir = dir.join("_Serato_/SmartCrates");
+    std::fs::create_dir_all(&sessions_dir).unwrap();
+    std::fs::create_dir_all(&smart_crates_dir).unwrap();
+    let session = |title: &str| {
+        let mut attribute = 6u32.to_be_bytes().to_vec();
+        let text: Vec<u8> = title
+            .encode_utf16()
+            .flat_map(|c| c.to_be_bytes().to_vec())
+            .collect();
+        attribute.extend_from_slice(&(text.len() as u32).to_be_bytes());
+        attribute.extend(text);
+        let fields = vec![database::Field::UnknownContainerField {
+            name: b"ent".to_vec(),
+            fields: vec![database::Field::Unknown {
+                field_type: b'a',
+                name: b"dat".to_vec(),
+                content: attribute,
+            }],
+        }];
+        let mut data = vec![];
+        database::write(&mut data, &fields).unwrap();
+        data
+    };
+    std::fs::write(sessions_dir.join("10.session"), session("Second")).unwrap();
+    std::fs::write(sessions_dir.join("9.session"), session("First")).unwrap();
+    let mut smart_crate = vec![];
+    database::write(
+        &mut smart_crate,
+        &[database::Field::Version(String::from(
+            "1.0/Serato ScratchLive Smart Crate",
+        ))],
+    )
+    .unwrap();
+    std::fs::write(smart_crates_dir.join("House.scrate"), smart_crate).unwrap();
+
+    let library = Library::open(dir.join("_Serato_")).unwrap();
+    let titles: Vec<Option<String>> = library
+        .sessions()
+        .unwrap()
+        .map(|session| session.unwrap().entries[0].title.clone())
+        .collect();
+    let smart_crates = library.smart_crates().unwrap();
+    // There is no database and there are no crates yet
+    let track_count = library.tracks().unwrap().count();
+    let crates = library.crates().unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(
+        titles,
+        vec![Some(String::from("First")), Some(String::from("Second"))]
+    );
+    assert_eq!(smart_crates.len(), 1);
+    assert_eq!(smart_crates[0].name, "House");
+    assert_eq!(track_count, 0);
+    assert!(crates.is_empty());
+}
+
+#[test]
+fn test_library_track_fields() {
+    let library = Library::read_from_path("tests/data/library/usb_drive").unwrap();
+    let track = library
+        .track(Path::new("CASSIUS_-_99_Keller 2016 RE-EDIT -.mp3"))
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        track.title.as_deref(),
+        Some("CASSIUS_-_99_Keller 2016 RE-EDIT -")
+    );
+    assert_eq!(track.file_type.as_deref(), Some("mp3"));
+    assert_eq!(track.key.as_deref(), Some("Bb"));
+    assert_eq!(track.bpm, Some(126.0));
+    assert_eq!(track.bitrate_kbps, Some(320.0));
+    assert_eq!(track.sample_rate_hz, Some(44100));
+    assert_eq!(track.length, Some(Duration::from_millis(382_930)));
+    assert_eq!(track.date_added, Some(1580992490));
+    assert_eq!(
+        track.date_added_system_time(),
+        Some(std::time::UNIX_EPOCH + Duration::from_secs(1580992490))
+    );
+    assert!(!track.missing);
+    assert!(!track.beatgrid_locked);
+    assert_eq!(track.flags.get("ply"), Some(&true));
+}
+
+#[test]
+fn test_library_cleanup() {
+    let library = Library::read_from_path("tests/data/library/usb_drive").unwrap();
+    assert!(library.orphaned_crate_entries().unwrap().is_empty());
+    assert!(library.missing_files(false).unwrap().is_empty());
+    // The fixture doesn't contain the audio files
+    assert_eq!(library.missing_files(true).unwrap().len(), 4);
+
+    let dir = std::env::temp_dir().join(format!("triseratops-cleanup-{}", std::process::id()));
+    let subcrates_dir = dir.join("_Serato_/Subcrates");
+    std::fs::create_dir_all(&subcrates_dir).unwrap();
+    let mut db =
+        database::Database::read_from_path("tests/data/library/usb_drive/_Serato_/database V2")
+            .unwrap();
+    let lipps = "Lipps, Inc-Funky Town meets Joris Voorn-Spank The Maid - Mood Funk - Mash_Up.mp3";
+    db.track_mut(lipps).unwrap().set_missing(true);
+    db.write_to_path(dir.join("_Serato_/database V2")).unwrap();
+    let cassius = "CASSIUS_-_99_Keller 2016 RE-EDIT -.mp3";
+    std::fs::write(dir.join(cassius), b"").unwrap();
+    let tracks = vec![
+        PathBuf::from("alan braxe - intro ( max padovani remix).MP3"),
+        PathBuf::from("Gone.mp3"),
+        PathBuf::from(format!("Sub\\..\\{}", cassius)),
+    ];
+    Crate::new(Sorting::default(), tracks)
+        .write_to_dir(&subcrates_dir, &["Cleanup"])
+        .unwrap();
+
+    let library = Library::read_from_path(&dir).unwrap();
+    assert_eq!(
+        library.orphaned_crate_entries().unwrap(),
+        vec![(String::from("Cleanup"), PathBuf::from("Gone.mp3"))]
+    );
+    let paths = |tracks: Vec<&Track>| -> Vec<PathBuf> {
+        tracks
+            .into_iter()
+            .map(|track| track.file_path.clone())
+            .collect()
+    };
+    assert_eq!(
+        paths(library.missing_files(false).unwrap()),
+        vec![PathBuf::from(lipps)]
+    );
+    assert_eq!(
+        paths(library.missing_files(true).unwrap()),
+        vec![
+            PathBuf::from("ALAN BRAXE - INTRO ( Max Padovani Remix).mp3"),
+            PathBuf::from(lipps),
+            PathBuf::from("Pete Heller - Big Love (Vaudafunk 2019 Reinterpretation).mp3"),
+        ]
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_library_relocate() {
+    let dir = std::env::temp_dir().join(format!("triseratops-relocate-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("_Serato_/Subcrates")).unwrap();
+    // The volume root is canonicalized by the library
+    let dir = std::fs::canonicalize(&dir).unwrap();
+    let subcrates_dir = dir.join("_Serato_/Subcrates");
+    let track = |path: &str| {
+        database::DatabaseEntry::Track(database::TrackRecord::new(vec![
+            database::Field::FilePath(PathBuf::from(path)),
+            database::Field::SongTitle(String::from(path)),
+            database::Field::UnknownU32Field {
+                name: b"zzz".to_vec(),
+                value: 42,
+            },
+        ]))
+    };
+    let paths = [
+        "Music/a.mp3",
+        "music/Sub/b.mp3",
+        "Music2/c.mp3",
+        "Other/Music/d.mp3",
+    ];
+    let db = database::Database {
+        entries: paths.iter().map(|path| track(path)).collect(),
+    };
+    let database_path = dir.join("_Serato_/database V2");
+    db.write_to_path(&database_path).unwrap();
+    let crate_path = Crate::new(
+        Sorting::default(),
+        paths.iter().rev().map(PathBuf::from).collect(),
+    )
+    .write_to_dir(&subcrates_dir, &["All"])
+    .unwrap();
+    let database_data = std::fs::read(&database_path).unwrap();
+    let crate_data = std::fs::read(&crate_path).unwrap();
+
+    let mut library = Library::read_from_path(&dir).unwrap();
+    let changes = library
+        .relocate(dir.join("Music"), dir.join("New/Music"), true)
+        .unwrap();
+    let change = |file: &Path, old_path: &str, new_path: &str| PathChange {
+        file: file.to_path_buf(),
+        old_path: PathBuf::from(old_path),
+        new_path: PathBuf::from(new_path),
+    };
+    assert_eq!(
+        changes,
+        vec![
+            change(&database_path, "Music/a.mp3", "New/Music/a.mp3"),
+            change(&database_path, "music/Sub/b.mp3", "New/Music/Sub/b.mp3"),
+            change(&crate_path, "music/Sub/b.mp3", "New/Music/Sub/b.mp3"),
+            change(&crate_path, "Music/a.mp3", "New/Music/a.mp3"),
+        ]
+    );
+    // A dry run doesn't write anything
+    assert_eq!(std::fs::read(&database_path).unwrap(), database_data);
+    assert_eq!(std::fs::read(&crate_path).unwrap(), crate_data);
+
+    assert_eq!(
+        library.relocate("Music", "New/Music", false).unwrap(),
+        changes
+    );
+    assert!(library.track(Path::new("Music/a.mp3")).unwrap().is_none());
+    let track = library
+        .track(Path::new("New/Music/a.mp3"))
+        .unwrap()
+        .unwrap();
+    assert_eq!(track.title.as_deref(), Some("Music/a.mp3"));
+
+    let db = database::Database::read_from_path(&database_path).unwrap();
+    let stored: Vec<&str> = db.tracks().map(|track| track.path()).collect();
+    assert_eq!(
+        stored,
+        vec![
+            "New/Music/a.mp3",
+            "New/Music/Sub/b.mp3",
+            "Music2/c.mp3",
+            "Other/Music/d.mp3"
+        ]
+    );
+    assert!(db.tracks().all(|track| track.unknown_fields().count() == 1));
+    assert_eq!(
+        Crate::read_from_path(&crate_path).unwrap().tracks,
+        vec![
+            PathBuf::from("Other/Music/d.mp3"),
+            PathBuf::from("Music2/c.mp3"),
+            PathBuf::from("New/Music/Sub/b.mp3"),
+            PathBuf::from("New/Music/a.mp3"),
+        ]
+    );
+
+    assert!(matches!(
+        library.relocate("/elsewhere/Music", "Music", true),
+        Err(Error::PathOutsideVolume { .. })
+    ));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_library_path_resolver() {
+    let library = Library::read_from_path("tests/data/library/usb_drive").unwrap();
+    let resolver = library.path_resolver();
+    let volume_root = std::fs::canonicalize("tests/data/library/usb_drive").unwrap();
+    assert_eq!(resolver.volume_root(), volume_root);
+    for track in library.tracks().unwrap() {
+        let path = resolver.resolve(&track.file_path);
+        assert!(path.starts_with(&volume_root));
+        assert_eq!(resolver.to_stored(&path).as_ref(), Some(&track.file_path));
+    }
+}
+
+#[test]
+fn test_database_track_record() {
+    let database =
+        database::Database::read_from_path("tests/data/library/usb_drive/_Serato_/database V2")
+            .unwrap();
+    let tracks: Vec<_> = database.tracks().collect();
+    assert_eq!(tracks.len(), 4);
+
+    // The values as shown in the Serato DJ library view
+    let track = tracks[0];
+    assert_eq!(track.path(), "CASSIUS_-_99_Keller 2016 RE-EDIT -.mp3");
+    assert_eq!(track.file_type(), Some("mp3"));
+    assert_eq!(track.title(), Some("CASSIUS_-_99_Keller 2016 RE-EDIT -"));
+    assert_eq!(track.artist(), None);
+    assert_eq!(track.key(), Some("Bb"));
+    assert_eq!(track.bpm(), Some(126.0));
+    assert_eq!(track.year(), None);
+    assert_eq!(track.bitrate_kbps(), Some(320.0));
+    assert_eq!(track.sample_rate_hz(), Some(44100));
+    assert_eq!(track.length(), Some(Duration::from_millis(382_930)));
+    assert_eq!(track.file_size(), Some("14.6MB"));
+    assert_eq!(track.date_added(), Some(1580992490));
+    assert!(!track.missing());
+    assert!(!track.beatgrid_locked());
+    assert!(track.unknown_fields().count() > 0);
+
+    let track = tracks[1];
+    assert_eq!(track.genre(), Some("Funky Tech"));
+    assert_eq!(track.comment(), Some("www.soundcloud.com/moodfunkrecords"));
+    assert_eq!(track.key(), Some("C#m"));
+    assert_eq!(track.length(), Some(Duration::from_millis(537_310)));
+}
+
+#[test]
+fn test_crate_file() {
+    let path = Path::new("tests/data/library/usb_drive/_Serato_/Subcrates/French House.crate");
+    let serato_crate = Crate::read_from_path(path).unwrap();
+    assert_eq!(
+        serato_crate.version.as_deref(),
+        Some("1.0/Serato ScratchLive Crate")
+    );
+    assert_eq!(
+        serato_crate.sorting,
+        Some(Sorting {
+            column: String::from("#"),
+            reverse: false,
+        })
+    );
+    let columns: Vec<&str> = serato_crate
+        .columns
+        .iter()
+        .map(|column| column.name.as_str())
+        .collect();
+    assert_eq!(
+        columns,
+        vec!["art", "song", "artist", "bpm", "key", "album", "length", "comment"]
+    );
+    assert_eq!(
+        serato_crate.columns[0],
+        ColumnConfig::new(ColumnName::Art, 0)
+    );
+    assert_eq!(
+        serato_crate.columns[1],
+        ColumnConfig::new(ColumnName::Song, 450)
+    );
+    assert_eq!(
+        serato_crate.tracks,
+        vec![
+            PathBuf::from("ALAN BRAXE - INTRO ( Max Padovani Remix).mp3"),
+            PathBuf::from("CASSIUS_-_99_Keller 2016 RE-EDIT -.mp3"),
+        ]
+    );
+
+    let volume_root = Crate::volume_root(path).unwrap();
+    assert_eq!(volume_root, Path::new("tests/data/library/usb_drive"));
+    for track_path in serato_crate.resolve_track_paths(volume_root) {
+        assert!(track_path.starts_with(volume_root));
+    }
+}
+
+#[test]
+fn test_crate_file_write() {
+    let subcrates_dir = Path::new("tests/data/library/usb_drive/_Serato_/Subcrates");
+    for name in &["French House", "80s Mashup"] {
+        let path = subcrates_dir.join(format!("{}.crate", name));
+        let data = std::fs::read(&path).unwrap();
+        let serato_crate = Crate::parse(&data).unwrap();
+        let mut output = vec![];
+        let bytes_written = serato_crate.write(&mut output).unwrap();
+        assert_eq!(bytes_written, output.len());
+        assert_eq!(output, data);
+    }
+
+    // A new crate with the same tracks matches the file written by Serato.
+    let data = std::fs::read(subcrates_dir.join("80s Mashup.crate")).unwrap();
+    let serato_crate = Crate::new(
+        Sorting::default(),
+        vec![PathBuf::from(
+            "/Lipps, Inc-Funky Town meets Joris Voorn-Spank The Maid - Mood Funk - Mash_Up.mp3",
+        )],
+    );
+    let mut output = vec![];
+    serato_crate.write(&mut output).unwrap();
+    assert_eq!(output, data);
+
+    let dir = std::env::temp_dir().join(format!("triseratops-crates-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = serato_crate
+        .write_to_dir(&dir, &["Mashups", "80s"])
+        .unwrap();
+    assert_eq!(path, dir.join("Mashups%%80s.crate"));
+    assert_eq!(std::fs::read(&path).unwrap(), data);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_crate_file_m3u8() {
+    let library_dir = "tests/data/library/usb_drive/_Serato_";
+    let serato_crate =
+        Crate::read_from_path(format!("{}/Subcrates/French House.crate", library_dir)).unwrap();
+    let database =
+        database::Database::read_from_path(format!("{}/database V2", library_dir)).unwrap();
+    let resolver = PathResolver::new("/Volumes/USB");
+
+    assert_eq!(
+        serato_crate.to_m3u8(&resolver),
+        "#EXTM3U\n\
+         /Volumes/USB/ALAN BRAXE - INTRO ( Max Padovani Remix).mp3\n\
+         /Volumes/USB/CASSIUS_-_99_Keller 2016 RE-EDIT -.mp3\n"
+    );
+    assert_eq!(
+        serato_crate.to_m3u8_with_database(&resolver, &database),
+        "#EXTM3U\n\
+         #EXTINF:302,ALAN BRAXE - INTRO ( Max Padovani Remix)\n\
+         /Volumes/USB/ALAN BRAXE - INTRO ( Max Padovani Remix).mp3\n\
+         #EXTINF:383,CASSIUS_-_99_Keller 2016 RE-EDIT -\n\
+         /Volumes/USB/CASSIUS_-_99_Keller 2016 RE-EDIT -.mp3\n"
+    );
+}
+
+#[test]
+fn test_crate_file_m3u_import() {
+    let dir = std::env::temp_dir().join(format!("triseratops-m3u-{}", std::process::id()));
+    let subcrates_dir = dir.join("USB/_Serato_/Subcrates");
+    std::fs::create_dir_all(&subcrates_dir).unwrap();
+    std::fs::create_dir_all(dir.join("USB/Music")).unwrap();
+    for name in &["a.mp3", "B\u{e4}r \u{1F3A7}.mp3", "c.mp3"] {
+        std::fs::write(dir.join("USB/Music").join(name), b"").unwrap();
+    }
+    std::fs::write(dir.join("outside.mp3"), b"").unwrap();
+
+    let tracks = vec![
+        PathBuf::from("Music/c.mp3"),
+        PathBuf::from("Music/B\u{e4}r \u{1F3A7}.mp3"),
+        PathBuf::from("Music/a.mp3"),
+    ];
+    let original = Crate::new(Sorting::default(), tracks.clone());
+    let crate_path = original.write_to_dir(&subcrates_dir, &["Export"]).unwrap();
+    let resolver = PathResolver::from_library_file(&crate_path).unwrap();
+
+    // Crate -> M3U8 -> crate
+    let mut playlist = original.to_m3u8(&resolver);
+    playlist.push_str("# Comment\n");
+    playlist.push_str(&format!("{}\n", dir.join("outside.mp3").display()));
+    playlist.push_str("Music/missing.mp3\r\n");
+    playlist.push_str("Music/a.mp3\n");
+    let playlist_path = dir.join("USB/Export.m3u8");
+    std::fs::write(&playlist_path, playlist).unwrap();
+
+    let (imported, skipped) = Crate::read_m3u(&playlist_path, &resolver).unwrap();
+    assert_eq!(imported.tracks, tracks);
+    assert_eq!(
+        skipped,
+        vec![
+            SkippedEntry {
+                line: 6,
+                path: dir.join("outside.mp3").display().to_string(),
+                reason: SkipReason::OtherVolume,
+            },
+            SkippedEntry {
+                line: 7,
+                path: String::from("Music/missing.mp3"),
+                reason: SkipReason::NotFound,
+            },
+            SkippedEntry {
+                line: 8,
+                path: String::from("Music/a.mp3"),
+                reason: SkipReason::Duplicate,
+            },
+        ]
+    );
+
+    let path = imported.write_to_dir(&subcrates_dir, &["Import"]).unwrap();
+    let reparsed = Crate::read_from_path(&path).unwrap();
+    assert_eq!(reparsed.tracks, original.tracks);
+    assert_eq!(
+        std::fs::read(&path).unwrap(),
+        std::fs::read(&crate_path).unwrap()
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_crate_file_columns() {
+    let path = Path::new("tests/data/library/usb_drive/_Serato_/Subcrates/French House.crate");
+    let mut serato_crate = Crate::read_from_path(path).unwrap();
+    serato_crate.columns[1].width = Some(300);
+    serato_crate.columns.remove(0);
+    serato_crate
+        .columns
+        .push(ColumnConfig::new(ColumnName::from("playCount"), 0));
+
+    let mut data = vec![];
+    serato_crate.write(&mut data).unwrap();
+    let written = Crate::parse(&data).unwrap();
+    assert_eq!(written.columns, serato_crate.columns);
+    assert_eq!(written.columns[0], ColumnConfig::new(ColumnName::Song, 300));
+    assert_eq!(
+        written.columns[7].name,
+        ColumnName::Other(String::from("playCount"))
+    );
+    assert_eq!(written.tracks, serato_crate.tracks);
+}
+
+#[test]
+fn test_database_write() {
+    let data = std::fs::read("tests/data/library/usb_drive/_Serato_/database V2").unwrap();
+    let fields = database::parse(&data).unwrap();
+    let mut output = vec![];
+    database::write(&mut output, &fields).unwrap();
+    assert_eq!(output, data);
+
+    let database = database::Database::parse(&data).unwrap();
+    assert_eq!(database.tracks().count(), 4);
+    let mut output = vec![];
+    database.write(&mut output).unwrap();
+    assert_eq!(output, data);
+}
+
+#[test]
+fn test_database_edit() {
+    let data = std::fs::read("tests/data/library/usb_drive/_Serato_/database V2").unwrap();
+    let mut database = database::Database::parse(&data).unwrap();
+    let paths: Vec<PathBuf> = database
+        .tracks()
+        .filter_map(|track| track.file_path().map(Path::to_path_buf))
+        .collect();
+    for track in database.tracks_mut() {
+        let path = Path::new("Moved").join(track.file_path().unwrap());
+        track.set_file_path(path);
+        track.set_missing(true);
+        track.set_grouping(Some(String::from("Moved")));
+    }
+
+    let mut output = vec![];
+    database.write(&mut output).unwrap();
+    let tracks = database::parse_tracks(&output).unwrap();
+    assert_eq!(tracks.len(), paths.len());
+    for (track, path) in tracks.iter().zip(&paths) {
+        assert_eq!(track.file_path, Path::new("Moved").join(path));
+        assert!(track.missing);
+        assert_eq!(track.grouping.as_deref(), Some("Moved"));
+    }
+}
+
+#[test]
+fn test_database_track_flags() {
+    let data = std::fs::read("tests/data/library/usb_drive/_Serato_/database V2").unwrap();
+    let mut database = database::Database::parse(&data).unwrap();
+    for track in database.tracks() {
+        assert!(!track.missing());
+        assert!(!track.corrupt());
+        assert!(!track.beatgrid_locked());
+        assert!(track.played());
+    }
+
+    // Mark the second and the last track as missing, like Serato does when the files are gone.
+    let paths: Vec<String> = database
+        .tracks()
+        .map(|track| track.path().to_string())
+        .collect();
+    for path in [&paths[1], &paths[3]] {
+        database.track_mut(path).unwrap().set_missing(true);
+    }
+    let mut output = vec![];
+    database.write(&mut output).unwrap();
+    assert_eq!(output.len(), data.len());
+
+    let reparsed = database::Database::parse(&output).unwrap();
+    let missing: Vec<bool> = reparsed.tracks().map(|track| track.missing()).collect();
+    assert_eq!(missing, vec![false, true, false, true]);
+    let refs: Vec<bool> = database::track_record_refs(&output)
+        .map(|track| track.unwrap().missing())
+        .collect();
+    assert_eq!(refs, missing);
+}
+
+#[test]
+fn test_crate_file_edit() {
+    let data = std::fs::read("tests/data/library/usb_drive/_Serato_/Subcrates/French House.crate")
+        .unwrap();
+
+    // Insert unknown fields between the columns and the tracks.
+    let tracks_offset = data.windows(4).position(|w| w == b"otrk").unwrap();
+    let mut input = data[..tracks_offset].to_vec();
+    input.extend_from_slice(b"tzzz\x00\x00\x00\x02\x00x");
+    input.extend_from_slice(b"uzzz\x00\x00\x00\x04\x00\x00\x00\x2A");
+    input.extend_from_slice(&data[tracks_offset..]);
+
+    let dir = std::env::temp_dir().join(format!("triseratops-edit-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("French House.crate");
+    std::fs::write(&path, &input).unwrap();
+
+    let original = Crate::read_from_path(&path).unwrap();
+    assert_eq!(original.path(), Some(path.as_path()));
+    assert_eq!(original.other_fields.len(), 2);
+
+    let mut serato_crate = original.clone();
+    serato_crate.add_track("New Track.mp3").unwrap();
+    assert!(matches!(
+        serato_crate.add_track("/New Track.mp3"),
+        Err(Error::DuplicateTrack { .. })
+    ));
+    assert!(serato_crate.contains_track("New Track.mp3"));
+    serato_crate.move_track(2, 0).unwrap();
+    assert!(matches!(
+        serato_crate.move_track(0, 3),
+        Err(Error::InvalidTrackIndex { index: 3 })
+    ));
+    assert!(serato_crate.remove_track("CASSIUS_-_99_Keller 2016 RE-EDIT -.mp3"));
+    assert!(!serato_crate.remove_track("Unknown.mp3"));
+    serato_crate.save().unwrap();
+
+    let reparsed = Crate::read_from_path(&path).unwrap();
+    assert_eq!(
+        reparsed.tracks,
+        vec![
+            PathBuf::from("New Track.mp3"),
+            PathBuf::from("ALAN BRAXE - INTRO ( Max Padovani Remix).mp3"),
+        ]
+    );
+    assert_eq!(reparsed.version, original.version);
+    assert_eq!(reparsed.sorting, original.sorting);
+    assert_eq!(reparsed.columns, original.columns);
+    assert_eq!(reparsed.other_fields, original.other_fields);
+
+    // Everything before the tracks is written back byte by byte.
+    let output = std::fs::read(&path).unwrap();
+    let offset = tracks_offset + 22;
+    assert_eq!(output[..offset], input[..offset]);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(matches!(
+        Crate::new(Sorting::default(), vec![]).save(),
+        Err(Error::NoCratePath)
+    ));
+}
+
+/// Serialize the fields in the format of the `database V2` and crate files.
+fn encode_fields(fields: &[database::Field]) -> Vec<u8> {
+    let mut data = vec![];
+    database::write(&mut data, fields).unwrap();
+    data
+}
+
+#[test]
+fn test_unknown_fields_roundtrip() {
+    // Fabricated fields that a future Serato version might add
+    let unknown_text = database::Field::UnknownTextField {
+        name: b"zzz".to_vec(),
+        text: String::from("new"),
+    };
+    let unknown_raw = database::Field::Unknown {
+        field_type: b'x',
+        name: b"zzz".to_vec(),
+        content: vec![0x01, 0x02, 0x03],
+    };
+    let unknown_top = database::Field::UnknownContainerField {
+        name: b"zzz".to_vec(),
+        fields: vec![database::Field::UnknownU32Field {
+            name: b"zzz".to_vec(),
+            value: 42,
+        }],
+    };
+
+    // Database: Inject the fields at the start of the first track record and at the end.
+    let data = std::fs::read("tests/data/library/usb_drive/_Serato_/database V2").unwrap();
+    let mut fields = database::parse(&data).unwrap();
+    if let Some(database::Field::Track(fields)) = fields.get_mut(1) {
+        fields.splice(0..0, vec![unknown_text.clone(), unknown_raw.clone()]);
+    }
+    fields.push(unknown_top.clone());
+    let input = encode_fields(&fields);
+
+    let mut db = database::Database::parse(&input).unwrap();
+    let mut unmodified = vec![];
+    db.write(&mut unmodified).unwrap();
+    assert_eq!(unmodified, input);
+
+    let first_path = db.tracks().next().unwrap().path().to_string();
+    let track = db.track_mut(&first_path).unwrap();
+    track.set_grouping(Some(String::from("Modified")));
+    track.set_missing(true);
+    let mut output = vec![];
+    db.write(&mut output).unwrap();
+
+    let reparsed = database::Database::parse(&output).unwrap();
+    let track = reparsed.tracks().next().unwrap();
+    assert_eq!(track.grouping(), Some("Modified"));
+    assert!(track.missing());
+    assert_eq!(
+        encode_fields(&track.fields[..2]),
+        encode_fields(&[unknown_text.clone(), unknown_raw.clone()])
+    );
+    assert!(output.ends_with(&encode_fields(std::slice::from_ref(&unknown_top))));
+
+    // Crate: Inject the fields into the sort settings, the first track and after the tracks.
+    let data = std::fs::read("tests/data/library/usb_drive/_Serato_/Subcrates/French House.crate")
+        .unwrap();
+    let serato_crate = Crate::parse(&data).unwrap();
+    let mut fields = database::parse(&data).unwrap();
+    for field in &mut fields {
+        match field {
+            database::Field::Sorting(fields) => fields.push(unknown_raw.clone()),
+            database::Field::Track(fields) if fields.len() == 1 => {
+                fields.insert(0, unknown_text.clone())
+            }
+            _ => (),
+        }
+    }
+    fields.push(unknown_top);
+    let input = encode_fields(&fields);
+
+    let mut serato_crate_with_unknowns = Crate::parse(&input).unwrap();
+    assert_eq!(serato_crate_with_unknowns.tracks, serato_crate.tracks);
+    let mut unmodified = vec![];
+    serato_crate_with_unknowns.write(&mut unmodified).unwrap();
+    assert_eq!(unmodified, input);
+
+    serato_crate_with_unknowns
+        .add_track("New Track.mp3")
+        .unwrap();
+    serato_crate_with_unknowns.sorting = Some(Sorting {
+        column: String::from("bpm"),
+        reverse: true,
+    });
+    let mut output = vec![];
+    serato_crate_with_unknowns.write(&mut output).unwrap();
+    let fields = database::parse(&output).unwrap();
+    let mut expected = database::parse(&input).unwrap();
+    let new_track = database::Field::Track(vec![database::Field::TrackPath(PathBuf::from(
+        "New Track.mp3",
+    ))]);
+    let position = expected.len() - 1;
+    expected.insert(position, new_track);
+    for field in &mut expected {
+        if let database::Field::Sorting(fields) = field {
+            fields[0] = database::Field::ColumnName(String::from("bpm"));
+            fields[1] = database::Field::ReverseOrder(true);
+        }
+    }
+    assert_eq!(fields, expected);
+}
+
+#[test]
+fn test_library_skips_bad_tracks() {
+    let dir = std::env::temp_dir().join(format!("triseratops-bad-tracks-{}", std::process::id()));
+    let serato_dir = dir.join("_Serato_");
+    std::fs::create_dir_all(&serato_dir).unwrap();
+    let data = encode_fields(&[
+        database::Field::Version(String::from("2.0")),
+        database::Field::Track(vec![database::Field::FilePath(PathBuf::from("a"))]),
+        // The length of the inner field exceeds the record
+        database::Field::Unknown {
+            field_type: b'o',
+            name: b"trk".to_vec(),
+            content: b"pfil\x00\x00\x00\xFF\x00b".to_vec(),
+        },
+        database::Field::Track(vec![database::Field::FilePath(PathBuf::from("c"))]),
+    ]);
+    std::fs::write(serato_dir.join("database V2"), &data).unwrap();
+
+    let library = Library::read_from_path(&dir).unwrap();
+    let mut paths: Vec<&Path> = library
+        .tracks()
+        .unwrap()
+        .map(|track| track.file_path.as_path())
+        .collect();
+    paths.sort();
+    assert_eq!(paths, vec![Path::new("a"), Path::new("c")]);
+    assert!(library.track(Path::new("c")).unwrap().is_some());
+    assert_eq!(library.track_errors().unwrap().len(), 1);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_database_reader() {
+    let path = "tests/data/library/usb_drive/_Serato_/database V2";
+    let data = std::fs::read(path).unwrap();
+    let file = std::io::BufReader::new(std::fs::File::open(path).unwrap());
+    let mut reader = database::DatabaseReader::new(file);
+    let tracks: Vec<Track> = reader.tracks().collect::<Result<_, _>>().unwrap();
+    assert_eq!(reader.version(), Some("2.0/Serato Scratch LIVE Database"));
+    let expected = database::parse_tracks(&data).unwrap();
+    assert_eq!(tracks.len(), expected.len());
+    for (track, expected) in tracks.iter().zip(expected.iter()) {
+        assert_eq!(track.file_path, expected.file_path);
+        assert_eq!(track.title, expected.title);
+    }
+
+    // Stop at the first match
+    let mut reader = database::DatabaseReader::new(&data[..]);
+    let track = reader
+        .tracks()
+        .filter_map(Result::ok)
+        .find(|track| track.key.as_deref() == Some("Bb"))
+        .unwrap();
+    assert_eq!(
+        track.file_path,
+        PathBuf::from("CASSIUS_-_99_Keller 2016 RE-EDIT -.mp3")
+    );
+}
+
+#[test]
+fn test_crate_tree() {
+    let library = Library::read_from_path("tests/data/library/usb_drive").unwrap();
+    let names: Vec<String> = library
+        .crate_tree()
+        .unwrap()
+        .into_iter()
+        .map(|node| node.name)
+        .collect();
+    assert_eq!(names, vec!["80s Mashup", "French House"]);
+
+    let dir = std::env::temp_dir().join(format!("triseratops-tree-{}", std::process::id()));
+    let subcrates_dir = dir.join("Subcrates");
+    std::fs::create_dir_all(&subcrates_dir).unwrap();
+    let crate_names: &[&[&str]] = &[
+        &["House"],
+        &["House", "Deep"],
+        &["House", "Acid"],
+        &["100%", "Disco"],
+        &["Techno", "Minimal", "Dub"],
+    ];
+    for names in crate_names {
+        let path = subcrates_dir.join(Crate::file_name(names).unwrap());
+        std::fs::write(path, b"").unwrap();
+    }
+    std::fs::write(subcrates_dir.join("notes.txt"), b"").unwrap();
+
+    // Without a crate order file, the crates are sorted alphabetically.
+    let names: Vec<String> = read_crate_tree(&subcrates_dir)
+        .unwrap()
+        .into_iter()
+        .map(|node| node.name)
+        .collect();
+    assert_eq!(names, vec!["100%", "House", "Techno"]);
+
+    neworder::write_to_dir(&dir, &["Techno", "House", "House%%Deep"]).unwrap();
+
+    let tree = read_crate_tree(&subcrates_dir).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let crate_file = |names: &[&str]| Some(subcrates_dir.join(Crate::file_name(names).unwrap()));
+    let node = |name: &str, crate_file, children| CrateNode {
+        name: String::from(name),
+        children,
+        crate_file,
+    };
+    assert_eq!(
+        tree,
+        vec![
+            node(
+                "Techno",
+                None,
+                vec![node(
+                    "Minimal",
+                    None,
+                    vec![node(
+                        "Dub",
+                        crate_file(&["Techno", "Minimal", "Dub"]),
+                        vec![]
+                    )]
+                )]
+            ),
+            node(
+                "House",
+                crate_file(&["House"]),
+                vec![
+                    node("Deep", crate_file(&["House", "Deep"]), vec![]),
+                    node("Acid", crate_file(&["House", "Acid"]), vec![]),
+                ]
+            ),
+            node(
+                "100%",
+                None,
+                vec![node("Disco", crate_file(&["100%", "Disco"]), vec![])]
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_discover() {
+    let dir = std::env::temp_dir().join(format!("triseratops-discover-{}", std::process::id()));
+    let music_dir = dir.join("home/Music");
+    let usb = dir.join("Volumes/USB");
+    let old_drive = dir.join("Volumes/Old");
+    let empty_drive = dir.join("Volumes/Empty");
+    for path in &[
+        music_dir.join("_Serato_"),
+        usb.join("_Serato_"),
+        old_drive.join("ScratchLIVE"),
+        empty_drive.join("Music"),
+    ] {
+        std::fs::create_dir_all(path).unwrap();
+    }
+
+    let locations = discover(
+        Some(&music_dir),
+        &[&usb, &old_drive, &empty_drive, &dir.join("Volumes/Missing")],
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(
+        locations,
+        vec![
+            LibraryLocation {
+                path: music_dir.join("_Serato_"),
+                kind: LibraryKind::Primary,
+                is_legacy: false,
+            },
+            LibraryLocation {
+                path: usb.join("_Serato_"),
+                kind: LibraryKind::External,
+                is_legacy: false,
+            },
+            LibraryLocation {
+                path: old_drive.join("ScratchLIVE"),
+                kind: LibraryKind::External,
+                is_legacy: true,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_database_diff() {
+    use triseratops::library::diff::{DiffOptions, TrackChange};
+
+    let data = std::fs::read("tests/data/library/usb_drive/_Serato_/database V2").unwrap();
+    let old = database::Database::parse(&data).unwrap();
+    let mut new = old.clone();
+    let track = new
+        .track_mut("CASSIUS_-_99_Keller 2016 RE-EDIT -.mp3")
+        .unwrap();
+    track.set_file_path("House/CASSIUS_-_99_Keller 2016 RE-EDIT -.mp3");
+
+    let options = DiffOptions {
+        detect_relocations: true,
+    };
+    assert!(triseratops::library::diff(old.tracks(), old.tracks(), &options).is_empty());
+    assert_eq!(
+        triseratops::library::diff(old.tracks(), new.tracks(), &options),
+        vec![TrackChange::Relocated {
+            old_path: PathBuf::from("CASSIUS_-_99_Keller 2016 RE-EDIT -.mp3"),
+            new_path: PathBuf::from("House/CASSIUS_-_99_Keller 2016 RE-EDIT -.mp3"),
+            fields: vec![],
+        }]
+    );
+}
@@ -0,0 +1,200 @@
+#![cfg(feature = "lofty")]
+extern crate triseratops;
+
+use lofty::config::WriteOptions;
+use lofty::file::{AudioFile, TaggedFile, TaggedFileExt};
+use lofty::id3::v2::{Frame, Id3v2Tag};
+use lofty::mp4::{AtomData, AtomIdent, DataType, Ilst};
+use lofty::ogg::VorbisComments;
+use lofty::probe::Probe;
+use lofty::tag::{Accessor, Tag};
+use std::io::Cursor;
+use triseratops::error::Error;
+use triseratops::tag::{is_serato_identifier, TagContainer, TagFormat, TagKind};
+
+fn read_file(data: Vec<u8>) -> TaggedFile {
+    Probe::new(Cursor::new(data))
+        .guess_file_type()
+        .unwrap()
+        .read()
+        .unwrap()
+}
+
+fn save_file(file: &TaggedFile, data: Vec<u8>) -> Vec<u8> {
+    let mut cursor = Cursor::new(data);
+    file.save_to(&mut cursor, WriteOptions::default()).unwrap();
+    cursor.into_inner()
+}
+
+// The files in `tests/data/media` are minimal silent files without any tags.
+
+/// Count the `GEOB` frames, Vorbis comments or freeform atoms of the Serato tags in the file.
+///
+/// MP4 atoms are only counted if they use the implicit (reserved) data type, like Serato does.
+fn serato_item_count(file: &TaggedFile, tag_format: TagFormat) -> usize {
+    let tag = file.primary_tag().unwrap().clone();
+    match tag_format {
+        TagFormat::ID3 => Id3v2Tag::from(tag)
+            .into_iter()
+            .filter(|frame| match frame {
+                Frame::Binary(frame) => frame.id().as_str() == "GEOB",
+                _ => false,
+            })
+            .count(),
+        TagFormat::FLAC | TagFormat::Ogg => VorbisComments::from(tag)
+            .items()
+            .filter(|(key, _)| is_serato_identifier(tag_format, key))
+            .count(),
+        TagFormat::MP4 => Ilst::from(tag)
+            .into_iter()
+            .filter(|atom| match atom.ident() {
+                AtomIdent::Freeform { mean, .. } => mean == "com.serato.dj",
+                _ => false,
+            })
+            .filter(|atom| {
+                atom.data().all(|data| {
+                    matches!(
+                        data,
+                        AtomData::Unknown {
+                            code: DataType::Reserved,
+                            ..
+                        }
+                    )
+                })
+            })
+            .count(),
+    }
+}
+
+/// Write the Serato tags into a file without tags, save it and read them back with lofty.
+fn test_lofty_roundtrip(path: &str, tag_format: TagFormat, markers2: &[u8], analysis: &[u8]) {
+    let data = std::fs::read(path).unwrap();
+    let mut file = read_file(data.clone());
+    let container = TagContainer::read_from_lofty(&file).unwrap();
+    assert!(TagKind::ALL.iter().all(|kind| !container.has(*kind)));
+
+    let mut container = TagContainer::new();
+    container.parse_markers2(markers2, tag_format).unwrap();
+    container.parse_analysis(analysis, tag_format).unwrap();
+    assert!(!container.cues().is_empty());
+
+    let mut tag = Tag::new(file.primary_tag_type());
+    tag.set_title("Some Title".to_owned());
+    file.insert_tag(tag);
+    container.write_into_lofty(&mut file).unwrap();
+    // Writing the tags again replaces them instead of adding duplicates.
+    container.write_into_lofty(&mut file).unwrap();
+    let data = save_file(&file, data);
+
+    let mut file = read_file(data.clone());
+    let tags = container.serialize_tags(tag_format).unwrap();
+    assert_eq!(serato_item_count(&file, tag_format), tags.len());
+    let tag = file.primary_tag().unwrap();
+    assert_eq!(tag.title().as_deref(), Some("Some Title"));
+    let mut reparsed = TagContainer::read_from_lofty(&file).unwrap();
+    assert_eq!(reparsed.cues(), container.cues());
+    assert_eq!(reparsed.loops(), container.loops());
+    assert_eq!(reparsed.track_color(), container.track_color());
+    assert!(reparsed.has(TagKind::Analysis));
+
+    // Cleared tags are removed from the file.
+    reparsed.clear_tag(TagKind::Markers2);
+    reparsed.write_into_lofty(&mut file).unwrap();
+    let data = save_file(&file, data);
+    let file = read_file(data);
+    let reparsed = TagContainer::read_from_lofty(&file).unwrap();
+    assert!(!reparsed.has(TagKind::Markers2));
+    assert!(reparsed.has(TagKind::Analysis));
+    assert_eq!(
+        file.primary_tag().unwrap().title().as_deref(),
+        Some("Some Title")
+    );
+}
+
+#[test]
+fn test_lofty_mp3() {
+    test_lofty_roundtrip(
+        "tests/data/media/silence.mp3",
+        TagFormat::ID3,
+        include_bytes!("data/tags/markers2/hotcues_with_names.id3.bin"),
+        include_bytes!("data/tags/analysis/analyzed.id3.bin"),
+    );
+}
+
+#[test]
+fn test_lofty_aiff() {
+    test_lofty_roundtrip(
+        "tests/data/media/silence.aiff",
+        TagFormat::ID3,
+        include_bytes!("data/tags/markers2/hotcues_with_names.id3.bin"),
+        include_bytes!("data/tags/analysis/analyzed.id3.bin"),
+    );
+}
+
+#[test]
+fn test_lofty_wav() {
+    test_lofty_roundtrip(
+        "tests/data/media/silence.wav",
+        TagFormat::ID3,
+        include_bytes!("data/tags/markers2/hotcues_with_names.id3.bin"),
+        include_bytes!("data/tags/analysis/analyzed.id3.bin"),
+    );
+}
+
+#[test]
+fn test_lofty_flac() {
+    test_lofty_roundtrip(
+        "tests/data/media/silence.flac",
+        TagFormat::FLAC,
+        include_bytes!("data/tags/markers2/hotcues_and_loops.flac.bin"),
+        include_bytes!("data/tags/analysis/version_2_1_encoded_EA.flac.bin"),
+    );
+}
+
+#[test]
+fn test_lofty_ogg() {
+    test_lofty_roundtrip(
+        "tests/data/media/silence.ogg",
+        TagFormat::Ogg,
+        include_bytes!("data/tags/markers2/hotcues_and_loops.ogg.bin"),
+        include_bytes!("data/tags/analysis/version_2_1.ogg.bin"),
+    );
+}
+
+#[test]
+fn test_lofty_mp4() {
+    test_lofty_roundtrip(
+        "tests/data/media/silence.m4a",
+        TagFormat::MP4,
+        include_bytes!("data/tags/markers2/hotcues_and_loops.mp4.bin"),
+        include_bytes!("data/tags/analysis/version_2_1_encoded_EA.mp4.bin"),
+    );
+}
+
+#[test]
+fn test_lofty_unsupported() {
+    let data = std::fs::read("tests/data/media/silence.opus").unwrap();
+    let mut file = read_file(data);
+    assert!(matches!(
+        TagContainer::read_from_lofty(&file),
+        Err(Error::UnsupportedTagType { .. })
+    ));
+
+    let mut container = TagContainer::new();
+    container
+        .parse_markers2(
+            include_bytes!("data/tags/markers2/hotcues_and_loops.ogg.bin"),
+            TagFormat::Ogg,
+        )
+        .unwrap();
+    let tags_before = file.primary_tag().cloned();
+    let err = container.write_into_lofty(&mut file).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Serato tags are not supported in VorbisComments tags of Opus files"
+    );
+    assert_eq!(
+        file.primary_tag().map(|tag| tag.item_count()),
+        tags_before.map(|tag| tag.item_count())
+    );
+}